@@ -6,6 +6,19 @@ pub struct ListenerId(u32);
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct ConnectionId(ListenerId, u32);
 
+impl std::fmt::Display for ListenerId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl ConnectionId {
+    /// The listener which accepted this connection
+    pub fn listener(&self) -> ListenerId {
+        self.0
+    }
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct ListenerIdGenerator {
     last: AtomicU32,
@@ -2,7 +2,7 @@ use crate::internal::*;
 use crate::*;
 
 use sha1::{Digest, Sha1};
-use std::net::IpAddr;
+use std::{net::IpAddr, sync::Arc};
 use tokio::{
     io::AsyncWriteExt,
     net::TcpStream,
@@ -33,6 +33,7 @@ impl InternalConnection {
 
         match connection_type {
             InternalConnectionType::Tls(tls_config) => {
+                let tls_config = Arc::clone(&tls_config.read().unwrap());
                 let tls_acceptor: tokio_rustls::TlsAcceptor = tls_config.into();
                 match tls_acceptor.accept(stream).await {
                     Ok(mut tls_stream) => {
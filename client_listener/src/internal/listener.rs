@@ -79,6 +79,23 @@ impl Listener {
         listener_id: ListenerId,
     ) -> Result<(), std::io::Error> {
         let listener = TcpListener::bind(address).await?;
+
+        let conn_type = match connection_type {
+            InternalConnectionType::Clear => ConnectionType::Clear,
+            InternalConnectionType::Tls(_) => ConnectionType::Tls,
+        };
+        let bound = InternalConnectionEvent::NewListener(ListenerData {
+            id: listener_id,
+            addr: address,
+            conn_type,
+        });
+        if let Err(e) = event_channel
+            .send(InternalConnectionEventType::Event(bound))
+            .await
+        {
+            tracing::error!("Error reporting new listener: {}", e);
+        }
+
         let id_gen = ConnectionIdGenerator::new(listener_id, 1);
 
         loop {
@@ -5,12 +5,16 @@ use crate::protocols::*;
 use rustls::ServerConfig;
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+
+/// TLS settings shared between all TLS listeners, so that reloaded certificates
+/// are used for every new connection
+pub type SharedTlsConfig = Arc<RwLock<Arc<ServerConfig>>>;
 
 #[derive(Clone)]
 pub enum InternalConnectionType {
     Clear,
-    Tls(Arc<ServerConfig>),
+    Tls(SharedTlsConfig),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
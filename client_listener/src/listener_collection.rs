@@ -2,12 +2,13 @@ use crate::internal::*;
 use crate::*;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     convert::TryInto,
     env::current_exe,
     io,
     net::SocketAddr,
     path::{Path, PathBuf},
+    sync::{Arc, RwLock},
 };
 
 use sable_ipc::{channel as ipc_channel, Receiver as IpcReceiver, Sender as IpcSender};
@@ -34,6 +35,10 @@ pub struct SavedListenerCollection {
     control_sender: RawFd,
     event_receiver: RawFd,
     id_gen: ListenerIdGenerator,
+    #[serde(default)]
+    listener_addresses: HashMap<ListenerId, SocketAddr>,
+    #[serde(default)]
+    listener_types: HashMap<ListenerId, ConnectionType>,
     connection_data: HashMap<ConnectionId, ConnectionData>,
     child_pid: i32,
 }
@@ -43,6 +48,25 @@ type CommResult = std::io::Result<(
     IpcReceiver<InternalConnectionEvent>,
 )>;
 
+/// The listeners which have been asked for and not removed. This is shared with
+/// the communication task, which learns from the worker process whether each one
+/// could be created.
+#[derive(Default)]
+struct ListenerTable {
+    addresses: HashMap<ListenerId, SocketAddr>,
+    types: HashMap<ListenerId, ConnectionType>,
+    /// Listeners which the worker process hasn't yet confirmed are listening
+    pending: HashSet<ListenerId>,
+}
+
+impl ListenerTable {
+    fn remove(&mut self, id: ListenerId) {
+        self.addresses.remove(&id);
+        self.types.remove(&id);
+        self.pending.remove(&id);
+    }
+}
+
 /// Errors that could happen when initializing a listener collection
 #[derive(Debug, Error)]
 pub enum ListenerCollectionError {
@@ -68,6 +92,7 @@ pub enum ListenerCollectionError {
 /// open file descriptors are inherited.
 pub struct ListenerCollection {
     listener_id_generator: ListenerIdGenerator,
+    listeners: Arc<RwLock<ListenerTable>>,
     control_sender: UnboundedSender<ControlMessage>,
     comm_task: JoinHandle<CommResult>,
     connection_data: HashMap<ConnectionId, ConnectionData>,
@@ -141,6 +166,7 @@ impl ListenerCollection {
         };
 
         let child_pid = Pid::from_raw(child.id().try_into().unwrap());
+        let listeners = Arc::new(RwLock::new(ListenerTable::default()));
 
         let comm_task = task::spawn(run_communication_task(
             control_send,
@@ -148,11 +174,13 @@ impl ListenerCollection {
             local_control_recv,
             event_recv,
             event_channel,
+            Arc::clone(&listeners),
             child_pid,
         ));
 
         let ret = Self {
             listener_id_generator: ListenerIdGenerator::new(0),
+            listeners,
             control_sender: local_control_send,
             comm_task,
             connection_data: HashMap::new(),
@@ -194,10 +222,18 @@ impl ListenerCollection {
 
         tracing::debug!("unwrapped fds");
 
+        // The communication task has finished, so this is the only reference left
+        let listeners = Arc::try_unwrap(self.listeners)
+            .unwrap_or_else(|_| panic!("Couldn't unwrap listener table"))
+            .into_inner()
+            .unwrap();
+
         Ok(SavedListenerCollection {
             control_sender: ctl_fd,
             event_receiver: evt_fd,
             id_gen: self.listener_id_generator,
+            listener_addresses: listeners.addresses,
+            listener_types: listeners.types,
             connection_data: self.connection_data,
             child_pid: self.child_pid.as_raw(),
         })
@@ -234,6 +270,14 @@ impl ListenerCollection {
         let (local_control_send, local_control_recv) = unbounded_channel();
 
         let child_pid = Pid::from_raw(state.child_pid);
+        // Listeners which were still pending when saved will report back to the
+        // new communication task, but those that were already listening won't,
+        // so they're all treated as confirmed
+        let listeners = Arc::new(RwLock::new(ListenerTable {
+            addresses: state.listener_addresses,
+            types: state.listener_types,
+            pending: HashSet::new(),
+        }));
 
         let handle = tokio::spawn(run_communication_task(
             control_sender,
@@ -241,6 +285,7 @@ impl ListenerCollection {
             local_control_recv,
             event_receiver,
             event_channel,
+            Arc::clone(&listeners),
             child_pid,
         ));
 
@@ -248,6 +293,7 @@ impl ListenerCollection {
             control_sender: local_control_send,
             comm_task: handle,
             listener_id_generator: state.id_gen,
+            listeners,
             connection_data: state.connection_data,
             child_process: None,
             child_pid,
@@ -257,9 +303,11 @@ impl ListenerCollection {
     /// Create a new listener with the given socket address and type.
     ///
     /// Note that this method will only return an `Err(_)` variant if sending the
-    /// control message to the child process fails. If the worker process is unable
-    /// to create the listener, a separate error event will later be emitted on the
-    /// event channel.
+    /// control message to the child process fails. The listener is
+    /// [pending](Self::listener_is_pending) until the worker process confirms that
+    /// it is listening. If the worker process is unable to create the listener,
+    /// the error is logged and the listener is forgotten, so that it no longer
+    /// appears in [`listener_ids`](Self::listener_ids).
     pub fn add_listener(
         &self,
         address: SocketAddr,
@@ -267,11 +315,64 @@ impl ListenerCollection {
     ) -> Result<ListenerId, ListenerError> {
         let id = self.listener_id_generator.next();
 
-        let message = ControlMessage::Listener(id, ListenerControlDetail::Add(address, conn_type));
-        self.control_sender.send(message)?;
+        let message =
+            ControlMessage::Listener(id, ListenerControlDetail::Add(address, conn_type.clone()));
+        // Recorded before sending, so that the worker's reply can't arrive first
+        let mut listeners = self.listeners.write().unwrap();
+        listeners.addresses.insert(id, address);
+        listeners.types.insert(id, conn_type);
+        listeners.pending.insert(id);
+
+        if let Err(e) = self.control_sender.send(message) {
+            listeners.remove(id);
+            return Err(e.into());
+        }
         Ok(id)
     }
 
+    /// Close the given listener. Connections it has already accepted are not
+    /// affected.
+    pub fn remove_listener(&self, id: ListenerId) -> Result<(), ListenerError> {
+        self.control_sender
+            .send(ControlMessage::Listener(id, ListenerControlDetail::Close))?;
+        self.listeners.write().unwrap().remove(id);
+        Ok(())
+    }
+
+    /// The address on which the given listener was asked to listen
+    pub fn listener_address(&self, id: ListenerId) -> Option<SocketAddr> {
+        self.listeners.read().unwrap().addresses.get(&id).copied()
+    }
+
+    /// The type of the given listener. This may not be known for listeners
+    /// created before an upgrade from a version which didn't record it.
+    pub fn listener_type(&self, id: ListenerId) -> Option<ConnectionType> {
+        self.listeners.read().unwrap().types.get(&id).cloned()
+    }
+
+    /// Whether the given listener has yet to be confirmed as listening by the
+    /// worker process
+    pub fn listener_is_pending(&self, id: ListenerId) -> bool {
+        self.listeners.read().unwrap().pending.contains(&id)
+    }
+
+    /// Whether the worker process can still be sent control messages
+    pub fn is_running(&self) -> bool {
+        !self.control_sender.is_closed()
+    }
+
+    /// The IDs of all listeners which have been created and not removed,
+    /// including those which are still pending
+    pub fn listener_ids(&self) -> Vec<ListenerId> {
+        self.listeners
+            .read()
+            .unwrap()
+            .addresses
+            .keys()
+            .copied()
+            .collect()
+    }
+
     /// Load the provided TLS settings. This must be done before a TLS listener can be
     /// created.
     pub fn load_tls_certificates(
@@ -312,6 +413,7 @@ async fn run_communication_task<'a>(
     mut local_control_recv: UnboundedReceiver<ControlMessage>,
     event_receiver: IpcReceiver<InternalConnectionEvent>,
     event_sender: UnboundedSender<ConnectionEvent>,
+    listeners: Arc<RwLock<ListenerTable>>,
     child_pid: Pid,
 ) -> CommResult {
     loop {
@@ -348,6 +450,25 @@ async fn run_communication_task<'a>(
                             tracing::trace!(connection=?id, ?msg, "Got message");
                             ConnectionEvent::message(id, msg)
                         },
+                        NewListener(data) =>
+                        {
+                            tracing::info!(listener=?data.id, address=%data.addr, "Listening");
+                            listeners.write().unwrap().pending.remove(&data.id);
+                            continue
+                        },
+                        ListenerError(id, err) =>
+                        {
+                            // Forget the listener, so that it's created again if it's
+                            // still configured at the next reload
+                            tracing::error!(listener=?id, error=?err, "Listener error");
+                            listeners.write().unwrap().remove(id);
+                            continue
+                        },
+                        BadTlsConfig =>
+                        {
+                            tracing::error!("Listener process couldn't load TLS settings");
+                            continue
+                        },
                         _ => continue
                     };
                     if let Err(e) = event_sender.send(translated_event) {
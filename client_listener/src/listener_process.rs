@@ -4,7 +4,7 @@ use internal::*;
 use std::collections::HashMap;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
-    Arc,
+    Arc, RwLock,
 };
 
 use sable_ipc::{Receiver as IpcReceiver, Sender as IpcSender};
@@ -17,7 +17,7 @@ use tokio::sync::mpsc::{channel, unbounded_channel, UnboundedReceiver};
 pub struct ListenerProcess {
     control_receiver: IpcReceiver<ControlMessage>,
     event_sender: Arc<IpcSender<InternalConnectionEvent>>,
-    tls_config: Option<Arc<rustls::ServerConfig>>,
+    tls_config: Option<SharedTlsConfig>,
 
     listeners: HashMap<ListenerId, Listener>,
     connections: HashMap<ConnectionId, InternalConnection>,
@@ -41,7 +41,7 @@ impl ListenerProcess {
     }
 
     fn translate_connection_type(
-        tls_config: &Option<Arc<rustls::ServerConfig>>,
+        tls_config: &Option<SharedTlsConfig>,
         ct: ConnectionType,
     ) -> Result<InternalConnectionType, ListenerError> {
         match ct {
@@ -149,13 +149,8 @@ impl ListenerProcess {
                                 }
                                 ListenerControlDetail::Close =>
                                 {
-                                    if let Some(listener) = self.listeners.get(&id)
-                                    {
-                                        if let Err(e) = listener.control_channel.try_send(msg)
-                                        {
-                                            ipc_event_send.send(InternalConnectionEvent::ListenerError(id, e.into())).unwrap();
-                                        }
-                                    }
+                                    // Dropping the listener tells its task to stop
+                                    self.listeners.remove(&id);
                                 }
                             }
                         }
@@ -163,7 +158,13 @@ impl ListenerProcess {
                         {
                             if let Ok(config) = Self::build_tls_config(settings)
                             {
-                                self.tls_config = Some(config);
+                                // Existing TLS listeners share the config, so they
+                                // pick up the new certificates for new connections
+                                match &self.tls_config
+                                {
+                                    Some(shared) => *shared.write().unwrap() = config,
+                                    None => self.tls_config = Some(Arc::new(RwLock::new(config))),
+                                }
                             }
                             else
                             {
@@ -11,7 +11,7 @@ pub struct TlsInfo {
     pub fingerprint: Option<String>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ConnectionType {
     Clear,
     Tls,
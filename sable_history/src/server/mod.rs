@@ -211,6 +211,11 @@ impl ServerType for HistoryServer {
                 tracing::warn!(?req, "Got unsupported request (ping)");
                 RemoteServerResponse::NotSupported
             }
+            // Answered by the network node before reaching here
+            StateDigest | ObjectDigests(_) => {
+                tracing::warn!(?req, "Got unsupported request (state digest)");
+                RemoteServerResponse::NotSupported
+            }
         }
     }
 }
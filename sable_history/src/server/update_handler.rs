@@ -35,10 +35,12 @@ impl HistoryServer {
             | NetworkStateChange::ChannelRename(_)
             | NetworkStateChange::NewServer(_)
             | NetworkStateChange::ServerQuit(_)
+            | NetworkStateChange::SyncPeerUpdate(_)
             | NetworkStateChange::NewAuditLogEntry(_)
             | NetworkStateChange::UserLoginChange(_)
             | NetworkStateChange::HistoryServerUpdate(_)
             | NetworkStateChange::ServicesUpdate(_)
+            | NetworkStateChange::ServicesNotice(_)
             | NetworkStateChange::EventComplete(_) => Ok(()),
         }
     }
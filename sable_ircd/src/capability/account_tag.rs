@@ -26,9 +26,11 @@ fn account_for_tag(update: &NetworkStateChange, net: &Network) -> Option<String>
         NetworkStateChange::UserModeChange(_) => None,
         NetworkStateChange::NewServer(_) => None,
         NetworkStateChange::ServerQuit(_) => None,
+        NetworkStateChange::SyncPeerUpdate(_) => None,
         NetworkStateChange::NewAuditLogEntry(_) => None,
         NetworkStateChange::HistoryServerUpdate(_) => None,
         NetworkStateChange::ServicesUpdate(_) => None,
+        NetworkStateChange::ServicesNotice(_) => None,
         NetworkStateChange::EventComplete(_) => None,
     }?;
     Some(net.historic_user(*id).ok()?.account?.to_string())
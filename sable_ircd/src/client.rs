@@ -193,6 +193,11 @@ impl ClientConnection {
         self.receive_queue.add(message)
     }
 
+    /// The number of received messages waiting to be processed
+    pub fn pending_messages(&self) -> usize {
+        self.receive_queue.len()
+    }
+
     /// Poll for messages that the throttle permits to be processed
     pub fn poll_messages(&self) -> impl Iterator<Item = String> + '_ {
         self.receive_queue.iter()
@@ -274,6 +274,12 @@ impl ClientCommand {
                             NotOnChannel => Some(make_numeric!(NotOnChannel, &channel_name)),
                             UserNotOp => Some(make_numeric!(ChanOpPrivsNeeded, &channel_name)),
                             UserIsBanned => Some(make_numeric!(BannedOnChannel, &channel_name)),
+                            AutoKicked(reason) => {
+                                self.notice(format_args!(
+                                    "You are banned from {channel_name}: {reason}"
+                                ));
+                                Some(make_numeric!(BannedOnChannel, &channel_name))
+                            }
                             CannotSendToChannel => {
                                 Some(make_numeric!(CannotSendToChannel, &channel_name))
                             }
@@ -288,6 +294,9 @@ impl ClientCommand {
                     PermissionError::User(UserPermissionError::NotOper) => {
                         Some(make_numeric!(NotOper))
                     }
+                    PermissionError::User(UserPermissionError::MissingPrivilege(privilege)) => {
+                        Some(make_numeric!(NoPrivs, &privilege.to_string()))
+                    }
                     // These have no corresponding numerics
                     PermissionError::User(_) => None,
                     PermissionError::Registration(_) => None,
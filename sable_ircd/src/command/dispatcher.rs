@@ -1,4 +1,9 @@
 use super::{plumbing::Command, *};
+use sable_network::metrics::{Histogram, MetricType, MetricsWriter, LATENCY_BUCKETS};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
 
 /// Type alias for a boxed command context
 pub type BoxCommand<'cmd> = Box<dyn Command + 'cmd>;
@@ -14,10 +19,17 @@ pub struct CommandRegistration {
     pub(super) handler: CommandHandlerWrapper,
 }
 
+/// A registered handler, and the time taken by each call to it
+struct DispatchEntry {
+    handler: CommandHandlerWrapper,
+    timing: Arc<Histogram>,
+}
+
 /// A command dispatcher. Collects registered command handlers and allows lookup by
 /// command name.
 pub struct CommandDispatcher {
-    handlers: HashMap<String, CommandHandlerWrapper>,
+    handlers: HashMap<String, DispatchEntry>,
+    unknown_commands: AtomicU64,
 }
 
 inventory::collect!(CommandRegistration);
@@ -42,11 +54,20 @@ impl CommandDispatcher {
 
         for reg in inventory::iter::<CommandRegistration> {
             if reg.dispatcher == category_name {
-                map.insert(reg.command.to_ascii_uppercase(), reg.handler);
+                map.insert(
+                    reg.command.to_ascii_uppercase(),
+                    DispatchEntry {
+                        handler: reg.handler,
+                        timing: Arc::new(Histogram::new(LATENCY_BUCKETS)),
+                    },
+                );
             }
         }
 
-        Self { handlers: map }
+        Self {
+            handlers: map,
+            unknown_commands: AtomicU64::new(0),
+        }
     }
 
     /// Look up and execute the handler function for to a given command.
@@ -60,11 +81,58 @@ impl CommandDispatcher {
         let command: BoxCommand<'cmd> = Box::new(command);
 
         match self.handlers.get(&command.command().to_ascii_uppercase()) {
-            Some(handler) => handler(command),
+            Some(entry) => {
+                let started = Instant::now();
+                match (entry.handler)(command) {
+                    Some(handler) => {
+                        let timing = Arc::clone(&entry.timing);
+                        Some(Box::pin(async move {
+                            handler.await;
+                            timing.observe_duration(started.elapsed());
+                        }))
+                    }
+                    None => {
+                        entry.timing.observe_duration(started.elapsed());
+                        None
+                    }
+                }
+            }
             None => {
+                self.unknown_commands.fetch_add(1, Ordering::Relaxed);
                 command.notify_error(CommandError::CommandNotFound(command.command().to_owned()));
                 None
             }
         }
     }
+
+    /// Write the number of calls to each command, and the time taken to handle them
+    pub fn export_metrics(&self, out: &mut MetricsWriter) {
+        let mut commands: Vec<_> = self.handlers.iter().collect();
+        commands.sort_by_key(|(name, _)| *name);
+
+        let mut family = out.family(
+            "sable_commands_total",
+            MetricType::Counter,
+            "Commands handled, by command name",
+        );
+        for (name, entry) in &commands {
+            family.sample(&[("command", name.as_str())], entry.timing.count() as f64);
+        }
+
+        out.single(
+            "sable_unknown_commands_total",
+            MetricType::Counter,
+            "Commands received with no registered handler",
+            self.unknown_commands.load(Ordering::Relaxed) as f64,
+        );
+
+        let mut family = out.family(
+            "sable_command_duration_seconds",
+            MetricType::Histogram,
+            "Time taken to handle each command, by command name",
+        );
+        for (name, entry) in &commands {
+            family.histogram(&[("command", name.as_str())], &entry.timing);
+        }
+    }
 }
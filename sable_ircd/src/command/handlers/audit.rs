@@ -0,0 +1,89 @@
+use super::*;
+use crate::utils::format_timestamp;
+
+const DEFAULT_AUDIT_LIMIT: usize = 20;
+
+const SYNTAX: &str = "Syntax: AUDIT [category=<category>] [source=<mask>] [target=<mask>] \
+                      [since=<minutes ago>] [until=<minutes ago>] [limit=<count>]";
+
+/// The timestamp `minutes` minutes before `now`, if it can be represented
+fn minutes_ago(now: i64, minutes: &str) -> Option<i64> {
+    minutes
+        .parse::<i64>()
+        .ok()
+        .and_then(|minutes| minutes.checked_mul(60))
+        .and_then(|seconds| now.checked_sub(seconds))
+}
+
+#[command_handler("AUDIT")]
+fn handle_audit(
+    server: &ClientServer,
+    net: &Network,
+    source: UserSource,
+    cmd: &dyn Command,
+    args: ArgList<'_>,
+) -> CommandResult {
+    server.policy().require_oper(&source)?;
+
+    let now = sable_network::utils::now();
+    let mut filter = AuditLogFilter::default();
+    let mut limit = DEFAULT_AUDIT_LIMIT;
+
+    for arg in args.iter() {
+        let parsed = match arg.split_once('=') {
+            Some(("category", value)) => value.parse().map(|c| filter.category = Some(c)).ok(),
+            Some(("source", value)) => {
+                filter.source = Some(Pattern::new(value.to_owned()));
+                Some(())
+            }
+            Some(("target", value)) => {
+                filter.target = Some(Pattern::new(value.to_owned()));
+                Some(())
+            }
+            Some(("since", value)) => minutes_ago(now, value).map(|t| filter.since = Some(t)),
+            Some(("until", value)) => minutes_ago(now, value).map(|t| filter.until = Some(t)),
+            Some(("limit", value)) => value.parse().map(|l| limit = l).ok(),
+            _ => None,
+        };
+
+        if parsed.is_none() {
+            cmd.notice(SYNTAX);
+            return Ok(());
+        }
+    }
+
+    let entries = net.audit_entries(&filter);
+    let shown = &entries[entries.len().saturating_sub(limit)..];
+
+    cmd.notice(format_args!(
+        "Showing {} of {} matching audit log entries",
+        shown.len(),
+        entries.len()
+    ));
+
+    for entry in shown {
+        let mut line = format!(
+            "{} [{:?}] {} {}",
+            format_timestamp(entry.timestamp),
+            entry.category,
+            entry.source_str,
+            entry.action
+        );
+        if let Some(target) = &entry.target_str {
+            line.push(' ');
+            line.push_str(target);
+        }
+        if let Some(duration) = entry.target_duration {
+            line.push_str(&format!(" ({duration} minutes)"));
+        }
+        if let Some(reason) = &entry.reason {
+            line.push_str(" : ");
+            line.push_str(reason);
+        }
+        cmd.notice(line);
+    }
+
+    cmd.notice("End of audit log");
+
+    Ok(())
+}
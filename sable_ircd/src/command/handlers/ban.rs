@@ -28,7 +28,7 @@ fn handle_ban(
     response: &dyn CommandResponse,
     new_ban_str: &str,
 ) -> CommandResult {
-    server.policy().require_oper(&source)?;
+    server.policy().can_set_network_ban(&source)?;
 
     let new_ban_details: NewBanArguments = match serde_json::from_str(new_ban_str) {
         Ok(ban) => ban,
@@ -7,7 +7,7 @@ fn info_handler(response: &dyn CommandResponse, server: &ClientServer) -> Comman
     let version = node.version();
     let commit_date = node.commit_date();
 
-    for line in &server.info_strings.info {
+    for line in &server.info_strings.read().info {
         response.numeric(make_numeric!(Info, line));
     }
     response.numeric(make_numeric!(Info, &format!("Version: {version}")));
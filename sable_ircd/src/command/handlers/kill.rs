@@ -9,8 +9,6 @@ fn handle_kill(
     target: wrapper::User,
     message: &str,
 ) -> CommandResult {
-    server.policy().require_oper(&source)?;
-
     server.policy().can_kill(&source, &target)?;
 
     audit
@@ -25,6 +25,13 @@ fn handle_kline(
     let mask_parts: Vec<_> = mask.split('@').collect();
 
     if let [user, host] = mask_parts[..] {
+        server.policy().can_set_kline(
+            &source,
+            &Pattern::new(user.to_owned()),
+            &Pattern::new(host.to_owned()),
+            duration,
+        )?;
+
         let user_condition = if user == "*" {
             None
         } else {
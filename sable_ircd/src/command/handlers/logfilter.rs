@@ -0,0 +1,131 @@
+use super::*;
+use sable_server::{config::LogLevel, LogFilterChange, LogFilterSettings, TargetFilterChange};
+
+const SYNTAX: &str = "Syntax: LOGFILTER [LIST | DEFAULT <level> [minutes] | \
+                      MODULE <module> <level> [minutes] | TARGET <index|*> <level> [minutes] | RESET]";
+
+#[command_handler("LOGFILTER")]
+fn handle_logfilter(
+    server: &ClientServer,
+    source: UserSource,
+    cmd: &dyn Command,
+    audit: AuditLogger,
+    subcommand: Option<&str>,
+    mut args: ArgList<'_>,
+) -> CommandResult {
+    server.policy().can_administer(&source)?;
+
+    let Some(filters) = sable_server::log_filters() else {
+        cmd.notice("Log filters can't be changed on this server");
+        return Ok(());
+    };
+
+    let mut change = LogFilterChange::default();
+
+    let result = match subcommand.map(|s| s.to_ascii_uppercase()).as_deref() {
+        None | Some("LIST") => {
+            list_filters(cmd, &filters.current());
+            return Ok(());
+        }
+        Some("RESET") => filters.reset(),
+        Some("DEFAULT") => {
+            change.default_level = Some(parse_level(args.next()?)?);
+            change.revert_after = parse_minutes(&mut args)?;
+            filters.change(change)
+        }
+        Some("MODULE") => {
+            let module: &str = args.next()?;
+            let level = parse_level(args.next()?)?;
+            change.module_levels.insert(module.to_owned(), level);
+            change.revert_after = parse_minutes(&mut args)?;
+            filters.change(change)
+        }
+        Some("TARGET") => {
+            let target: &str = args.next()?;
+            let level = parse_level(args.next()?)?;
+            let indexes = if target == "*" {
+                (0..filters.current().targets.len()).collect()
+            } else {
+                match target.parse() {
+                    Ok(index) => vec![index],
+                    Err(_) => {
+                        return Err(CommandError::InvalidArgument(
+                            target.to_owned(),
+                            "log target index".to_owned(),
+                        ))
+                    }
+                }
+            };
+            change.targets = indexes
+                .into_iter()
+                .map(|target| TargetFilterChange {
+                    target,
+                    level: Some(level),
+                    modules: None,
+                    category: None,
+                })
+                .collect();
+            change.revert_after = parse_minutes(&mut args)?;
+            filters.change(change)
+        }
+        _ => {
+            cmd.notice(SYNTAX);
+            return Ok(());
+        }
+    };
+
+    match result {
+        Ok(settings) => {
+            audit
+                .general()
+                .target_str(subcommand.unwrap_or_default().to_ascii_uppercase())
+                .log();
+            cmd.notice("Log filters updated");
+            list_filters(cmd, &settings);
+        }
+        Err(e) => cmd.notice(format_args!("Couldn't update log filters: {e}")),
+    }
+
+    Ok(())
+}
+
+fn parse_level(level: &str) -> Result<LogLevel, CommandError> {
+    level
+        .parse()
+        .map_err(|_| CommandError::InvalidArgument(level.to_owned(), "log level".to_owned()))
+}
+
+/// Parse an optional number of minutes after which to revert, returning it in seconds
+fn parse_minutes(args: &mut ArgList<'_>) -> Result<Option<u64>, CommandError> {
+    if args.is_empty() {
+        return Ok(None);
+    }
+    let minutes: u32 = args.next()?;
+    Ok(Some(u64::from(minutes) * 60))
+}
+
+fn list_filters(cmd: &dyn Command, settings: &LogFilterSettings) {
+    cmd.notice(format_args!("Default level: {:?}", settings.default_level));
+
+    let mut modules: Vec<_> = settings.module_levels.iter().collect();
+    modules.sort_by_key(|(module, _)| *module);
+    for (module, level) in modules {
+        cmd.notice(format_args!("Module {module}: {level:?}"));
+    }
+
+    for (index, target) in settings.targets.iter().enumerate() {
+        cmd.notice(format_args!(
+            "Target {}: {:?}, modules: {}, category: {}",
+            index,
+            target.level,
+            if target.modules.is_empty() {
+                "all".to_owned()
+            } else {
+                target.modules.join(", ")
+            },
+            target.category.as_deref().unwrap_or("all")
+        ));
+    }
+
+    cmd.notice("End of log filters");
+}
@@ -6,13 +6,14 @@ async fn handle_mode(
     source: UserSource<'_>,
     cmd: &dyn Command,
     response: &dyn CommandResponse,
+    audit: AuditLogger<'_>,
     target: TargetParameter<'_>,
     mode_str: Option<&str>,
     args: ArgList<'_>,
 ) -> CommandResult {
     match target {
         TargetParameter::Channel(chan) => {
-            handle_channel_mode(server, &source, cmd, response, chan, mode_str, args).await
+            handle_channel_mode(server, &source, cmd, response, &audit, chan, mode_str, args).await
         }
         TargetParameter::User(user) => {
             handle_user_mode(server, &source, cmd, response, user, mode_str, args).await
@@ -100,11 +101,13 @@ async fn handle_user_mode(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_channel_mode(
     server: &ClientServer,
     source: &wrapper::User<'_>,
     cmd: &dyn Command,
     response: &dyn CommandResponse,
+    audit: &AuditLogger<'_>,
     chan: wrapper::Channel<'_>,
     mode_str: Option<&str>,
     mut args: ArgList<'_>,
@@ -116,6 +119,29 @@ async fn handle_channel_mode(
         return Ok(());
     };
 
+    // Opers with the override privilege can make any change that channel access
+    // would otherwise forbid
+    let can_override = server
+        .policy()
+        .can_override_channel_mode(source, &chan)
+        .is_ok();
+    let mut overridden = false;
+    let mut check = |result: policy::PermissionResult| match result {
+        Err(_) if can_override => {
+            // Record the first change that needed the override, before it's made
+            if !overridden {
+                overridden = true;
+                audit
+                    .general()
+                    .target_str(format!("{} {}", chan.name(), mode_str))
+                    .reason("Channel mode override".to_owned())
+                    .log();
+            }
+            Ok(())
+        }
+        result => result,
+    };
+
     let mut sent_unknown = false;
     let mut added = ChannelModeSet::new();
     let mut removed = ChannelModeSet::new();
@@ -126,7 +152,7 @@ async fn handle_channel_mode(
         if let Ok(d) = Direction::try_from(c) {
             dir = d;
         } else if let Some(flag) = ChannelModeFlag::from_mode_char(c) {
-            server.policy().can_change_mode(source, &chan, flag)?;
+            check(server.policy().can_change_mode(source, &chan, flag))?;
             match dir {
                 Direction::Add => {
                     added |= flag;
@@ -146,15 +172,19 @@ async fn handle_channel_mode(
 
             match dir {
                 Direction::Add => {
-                    server
-                        .policy()
-                        .can_grant_permission(source, &chan, &target, flag)?;
+                    check(
+                        server
+                            .policy()
+                            .can_grant_permission(source, &chan, &target, flag),
+                    )?;
                     perm_added |= flag;
                 }
                 Direction::Rem => {
-                    server
-                        .policy()
-                        .can_remove_permission(source, &chan, &target, flag)?;
+                    check(
+                        server
+                            .policy()
+                            .can_remove_permission(source, &chan, &target, flag),
+                    )?;
                     perm_removed |= flag;
                 }
                 _ => {}
@@ -176,9 +206,7 @@ async fn handle_channel_mode(
                 let mask = args.next::<&str>()?;
 
                 if dir == Direction::Add {
-                    server
-                        .policy()
-                        .can_set_ban(source, &chan, list_type, mask)?;
+                    check(server.policy().can_set_ban(source, &chan, list_type, mask))?;
                     server.policy().validate_ban_mask(mask, list_type, &chan)?;
 
                     let detail = event::NewListModeEntry {
@@ -191,9 +219,11 @@ async fn handle_channel_mode(
                 } else {
                     // We've already tested for Direction::Query above, so this is definitely Remove
                     if let Some(entry) = list.entries().find(|e| e.pattern() == mask) {
-                        server
-                            .policy()
-                            .can_unset_ban(source, &chan, list_type, mask)?;
+                        check(
+                            server
+                                .policy()
+                                .can_unset_ban(source, &chan, list_type, mask),
+                        )?;
 
                         let detail = event::DelListModeEntry {
                             removed_by: source.id(),
@@ -211,11 +241,11 @@ async fn handle_channel_mode(
                         Ok(key) => key,
                         Err(_) => return numeric_error!(InvalidKey, &chan.name()),
                     };
-                    server.policy().can_set_key(source, &chan, Some(&new_key))?;
+                    check(server.policy().can_set_key(source, &chan, Some(&new_key)))?;
                     key_change = OptionChange::Set(new_key);
                 }
                 Direction::Rem => {
-                    server.policy().can_set_key(source, &chan, None)?;
+                    check(server.policy().can_set_key(source, &chan, None))?;
                     key_change = OptionChange::Unset;
                 }
             }
@@ -6,6 +6,7 @@ fn handle_oper(
     response: &dyn CommandResponse,
     server: &ClientServer,
     net: &Network,
+    cmd: &dyn Command,
     source: UserSource,
     audit: AuditLogger,
     oper_name: &str,
@@ -13,8 +14,20 @@ fn handle_oper(
 ) -> CommandResult {
     server.policy().user_can_oper(&source)?;
 
+    let credentials = policy::OperCredentials {
+        password: Some(password),
+        fingerprint: cmd
+            .connection()
+            .tls_info()
+            .and_then(|ti| ti.fingerprint.as_deref()),
+        account: source.user.account_name(),
+        username: source.user.user(),
+        hostname: source.user_connection.hostname(),
+        ip: *source.user_connection.ip(),
+    };
+
     if let Some(conf) = find_oper_block(net, &source, oper_name) {
-        if server.policy().authenticate(conf, oper_name, password) {
+        if server.policy().authenticate(conf, oper_name, &credentials) {
             audit.general().log();
 
             response.numeric(make_numeric!(YoureOper));
@@ -32,7 +32,7 @@ async fn do_register_user(
     response_to: &dyn CommandResponse,
     server: &ClientServer,
     account: &str,
-    _email: &str,
+    email: &str,
     password: &str,
 ) -> CommandResult {
     let Some(services_name) = network.current_services_server_name() else {
@@ -72,9 +72,29 @@ async fn do_register_user(
         return Ok(());
     }
 
-    let message =
-        rpc::RemoteServicesServerRequestType::RegisterUser(requested_account, password.to_owned())
-            .into();
+    // Addresses are written into mail headers, so mustn't contain line breaks
+    let valid_email =
+        email.contains('@') && !email.chars().any(|c| c.is_whitespace() || c.is_control());
+    let email = if email == "*" {
+        None
+    } else if valid_email {
+        Some(email.to_owned())
+    } else {
+        response_to.send(message::Fail::new(
+            "REGISTER",
+            "INVALID_EMAIL",
+            email,
+            "Invalid email address",
+        ));
+        return Ok(());
+    };
+
+    let message = rpc::RemoteServicesServerRequestType::RegisterUser(
+        requested_account,
+        password.to_owned(),
+        email,
+    )
+    .into();
 
     match server
         .node()
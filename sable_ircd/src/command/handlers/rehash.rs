@@ -0,0 +1,44 @@
+use super::*;
+
+#[command_handler("REHASH")]
+async fn handle_rehash(
+    server: &ClientServer,
+    source: UserSource<'_>,
+    cmd: &dyn Command,
+    audit: AuditLogger,
+) -> CommandResult {
+    server.policy().can_administer(&source)?;
+
+    let report = match sable_server::reload_config().await {
+        None => {
+            cmd.notice("Configuration can't be reloaded on this server");
+            return Ok(());
+        }
+        Some(Err(e)) => {
+            cmd.notice(format_args!("Couldn't reload configuration: {e}"));
+            return Ok(());
+        }
+        Some(Ok(report)) => report,
+    };
+
+    audit.general().log();
+
+    if report.applied.is_empty() && report.pending.is_empty() && report.restart_required.is_empty()
+    {
+        cmd.notice("Configuration reloaded with no changes");
+        return Ok(());
+    }
+
+    for change in &report.applied {
+        cmd.notice(format_args!("Applied: {change}"));
+    }
+    for change in &report.pending {
+        cmd.notice(format_args!("In progress: {change}"));
+    }
+    for change in &report.restart_required {
+        cmd.notice(format_args!("Needs a restart to apply: {change}"));
+    }
+    cmd.notice("Configuration reloaded");
+
+    Ok(())
+}
@@ -15,7 +15,13 @@ async fn handle_rename(
     let new_name = ChannelName::from_str(new_name)?;
     server
         .policy()
-        .can_rename(source.as_ref(), &channel, &new_name, message)?;
+        .can_rename(source.as_ref(), &channel, &new_name, message)
+        .or_else(|err| {
+            server
+                .policy()
+                .can_override_rename(source.as_ref(), &channel)
+                .map_err(|_| err)
+        })?;
 
     if net.channel_by_name(&new_name).is_ok() {
         response.send(message::Fail::new(
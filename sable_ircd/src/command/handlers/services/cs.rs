@@ -18,5 +18,8 @@ async fn handle_cs<'a>(
 }
 
 mod access;
+mod akick;
+mod hold;
 mod register;
 mod role;
+mod set;
@@ -0,0 +1,190 @@
+use sable_network::{
+    network::state::ChannelAutoKickTarget,
+    policy::RegistrationPolicyService,
+    rpc::{RemoteServerResponse, RemoteServicesServerRequestType, RemoteServicesServerResponse},
+};
+
+use crate::utils::format_timestamp;
+
+use super::*;
+
+const SYNTAX: &str =
+    "Syntax: CS AKICK <#channel> [ADD <mask|account> [minutes] [reason] | DEL <mask|account> | LIST]";
+
+#[command_handler("AKICK", in("CS"))]
+async fn handle_akick(
+    source: LoggedInUserSource<'_>,
+    cmd: &dyn Command,
+    services_target: ServicesTarget<'_>,
+    channel: wrapper::ChannelRegistration<'_>,
+    subcommand: Option<&str>,
+    mut args: ArgList<'_>,
+) -> CommandResult {
+    match subcommand.map(|s| s.to_ascii_uppercase()).as_deref() {
+        None | Some("LIST") => akick_list(source, cmd, channel),
+        Some("ADD") => {
+            let target = parse_target(args.next()?)?;
+            akick_add(source, cmd, services_target, channel, target, args).await
+        }
+        Some("DEL") => {
+            let target = parse_target(args.next()?)?;
+            akick_del(source, cmd, services_target, channel, target).await
+        }
+        _ => {
+            cmd.notice(SYNTAX);
+            Ok(())
+        }
+    }
+}
+
+/// Anything that looks like a hostmask is a mask entry; anything else must be an account name
+fn parse_target(arg: &str) -> Result<ChannelAutoKickTarget, CommandError> {
+    if arg.contains(['!', '@', '*', '?']) {
+        Ok(ChannelAutoKickTarget::Mask(Pattern::new(arg.to_owned())))
+    } else {
+        Ok(ChannelAutoKickTarget::Account(Nickname::from_str(arg)?))
+    }
+}
+
+fn akick_list(
+    source: LoggedInUserSource<'_>,
+    cmd: &dyn Command,
+    chan: wrapper::ChannelRegistration<'_>,
+) -> CommandResult {
+    cmd.server()
+        .node()
+        .policy()
+        .can_view_auto_kicks(&source.user, &chan)?;
+
+    let now = sable_network::utils::now();
+
+    cmd.notice(format_args!("Auto-kick list for {}", chan.name()));
+    cmd.notice(" ");
+
+    for akick in chan.auto_kicks().filter(|a| !a.is_expired(now)) {
+        let expiry = match akick.expires {
+            Some(expires) => format!("expires {}", format_timestamp(expires)),
+            None => "permanent".to_owned(),
+        };
+
+        cmd.notice(format_args!(
+            "{} ({}) set by {} on {}, {}",
+            akick.target,
+            akick.reason,
+            akick.setter,
+            format_timestamp(akick.timestamp),
+            expiry
+        ));
+    }
+
+    cmd.notice(format_args!("End of auto-kick list for {}", chan.name()));
+
+    Ok(())
+}
+
+async fn akick_add(
+    source: LoggedInUserSource<'_>,
+    cmd: &dyn Command,
+    services_target: ServicesTarget<'_>,
+    chan: wrapper::ChannelRegistration<'_>,
+    target: ChannelAutoKickTarget,
+    args: ArgList<'_>,
+) -> CommandResult {
+    cmd.server()
+        .node()
+        .policy()
+        .can_edit_auto_kicks(&source.account, &chan)?;
+
+    let mut rest = args.iter().peekable();
+
+    let expires = match rest.peek().and_then(|s| s.parse::<u32>().ok()) {
+        Some(minutes) => {
+            rest.next();
+            Some(sable_network::utils::now() + minutes as i64 * 60)
+        }
+        None => None,
+    };
+
+    let reason = rest.collect::<Vec<_>>().join(" ");
+    let reason = if reason.is_empty() {
+        "You are banned from this channel".to_owned()
+    } else {
+        reason
+    };
+
+    let request = RemoteServicesServerRequestType::AddAutoKick {
+        source: source.account.id(),
+        channel: chan.id(),
+        target: target.clone(),
+        reason,
+        expires,
+    };
+
+    send_akick_request(cmd, services_target, request, || {
+        format!("{} added to the auto-kick list for {}", target, chan.name())
+    })
+    .await
+}
+
+async fn akick_del(
+    source: LoggedInUserSource<'_>,
+    cmd: &dyn Command,
+    services_target: ServicesTarget<'_>,
+    chan: wrapper::ChannelRegistration<'_>,
+    target: ChannelAutoKickTarget,
+) -> CommandResult {
+    cmd.server()
+        .node()
+        .policy()
+        .can_edit_auto_kicks(&source.account, &chan)?;
+
+    if !chan.auto_kicks().any(|a| a.target == target) {
+        cmd.notice(format_args!(
+            "{} is not on the auto-kick list for {}",
+            target,
+            chan.name()
+        ));
+        return Ok(());
+    }
+
+    let request = RemoteServicesServerRequestType::RemoveAutoKick {
+        source: source.account.id(),
+        channel: chan.id(),
+        target: target.clone(),
+    };
+
+    send_akick_request(cmd, services_target, request, || {
+        format!(
+            "{} removed from the auto-kick list for {}",
+            target,
+            chan.name()
+        )
+    })
+    .await
+}
+
+async fn send_akick_request(
+    cmd: &dyn Command,
+    services_target: ServicesTarget<'_>,
+    request: RemoteServicesServerRequestType,
+    success_message: impl FnOnce() -> String,
+) -> CommandResult {
+    match services_target.send_remote_request(request.into()).await {
+        Ok(RemoteServerResponse::Success) => {
+            cmd.notice(success_message());
+        }
+        Ok(RemoteServerResponse::Services(RemoteServicesServerResponse::AccessDenied)) => {
+            cmd.notice("Access denied");
+        }
+        Ok(response) => {
+            tracing::warn!(?response, "Unexpected response to auto-kick message");
+            cmd.notice("Error updating auto-kick list");
+        }
+        Err(e) => {
+            tracing::warn!(?e, "Error response updating auto-kick list");
+            cmd.notice("Error updating auto-kick list");
+        }
+    }
+
+    Ok(())
+}
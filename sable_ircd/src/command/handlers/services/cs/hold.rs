@@ -0,0 +1,59 @@
+use sable_network::rpc::{
+    RemoteServerResponse, RemoteServicesServerRequestType, RemoteServicesServerResponse,
+};
+
+use super::*;
+
+#[command_handler("HOLD", in("CS"))]
+async fn handle_hold(
+    server: &ClientServer,
+    source: UserSource<'_>,
+    cmd: &dyn Command,
+    services: ServicesTarget<'_>,
+    channel: wrapper::ChannelRegistration<'_>,
+    setting: &str,
+) -> CommandResult {
+    server.policy().can_administer(&source)?;
+
+    let no_expire = match setting.to_ascii_uppercase().as_str() {
+        "ON" => true,
+        "OFF" => false,
+        _ => {
+            cmd.notice("Syntax: CS HOLD <#channel> ON|OFF");
+            return Ok(());
+        }
+    };
+
+    let request = RemoteServicesServerRequestType::SetChannelNoExpire {
+        source: source.id(),
+        channel: channel.id(),
+        no_expire,
+    }
+    .into();
+
+    match services.send_remote_request(request).await {
+        Ok(RemoteServerResponse::Success) => {
+            if no_expire {
+                cmd.notice(format_args!(
+                    "Channel {} will no longer expire",
+                    channel.name()
+                ));
+            } else {
+                cmd.notice(format_args!("Channel {} is no longer held", channel.name()));
+            }
+        }
+        Ok(RemoteServerResponse::Services(RemoteServicesServerResponse::AccessDenied)) => {
+            cmd.notice("Access denied");
+        }
+        Ok(response) => {
+            tracing::warn!(?response, "Unexpected response to channel hold message");
+            cmd.notice("Error updating channel");
+        }
+        Err(e) => {
+            tracing::warn!(?e, "Error response updating channel hold");
+            cmd.notice("Error updating channel");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use sable_network::rpc::{
+    ChannelSetting, RemoteServerResponse, RemoteServicesServerRequestType,
+    RemoteServicesServerResponse,
+};
+
+use super::*;
+
+const SYNTAX: &str =
+    "Syntax: CS SET <#channel> MLOCK <+modes-modes|OFF> | KEEPTOPIC ON|OFF | ENTRYMSG [message] | GUARD ON|OFF";
+
+#[command_handler("SET", in("CS"))]
+async fn handle_set(
+    source: LoggedInUserSource<'_>,
+    cmd: &dyn Command,
+    services: ServicesTarget<'_>,
+    channel: wrapper::ChannelRegistration<'_>,
+    setting: &str,
+    args: ArgList<'_>,
+) -> CommandResult {
+    let rest: Vec<_> = args.iter().collect();
+
+    let setting = match (setting.to_ascii_uppercase().as_str(), rest.as_slice()) {
+        ("MLOCK", [modes]) => match parse_mode_lock(modes) {
+            Some((on, off)) => ChannelSetting::ModeLock { on, off },
+            None => {
+                cmd.notice(format_args!("Invalid mode lock {modes}"));
+                return Ok(());
+            }
+        },
+        ("KEEPTOPIC", [value]) => match parse_on_off(value) {
+            Some(value) => ChannelSetting::KeepTopic(value),
+            None => {
+                cmd.notice(SYNTAX);
+                return Ok(());
+            }
+        },
+        ("ENTRYMSG", []) => ChannelSetting::EntryMessage(None),
+        ("ENTRYMSG", words) => ChannelSetting::EntryMessage(Some(words.join(" "))),
+        ("GUARD", [value]) => match parse_on_off(value) {
+            Some(value) => ChannelSetting::Guard(value),
+            None => {
+                cmd.notice(SYNTAX);
+                return Ok(());
+            }
+        },
+        _ => {
+            cmd.notice(SYNTAX);
+            return Ok(());
+        }
+    };
+
+    let request = RemoteServicesServerRequestType::SetChannelSetting {
+        source: source.account.id(),
+        channel: channel.id(),
+        setting: setting.clone(),
+    }
+    .into();
+
+    match services.send_remote_request(request).await {
+        Ok(RemoteServerResponse::Success) => {
+            cmd.notice(describe_setting(channel.name(), &setting));
+        }
+        Ok(RemoteServerResponse::Services(RemoteServicesServerResponse::AccessDenied)) => {
+            cmd.notice("Access denied");
+        }
+        Ok(response) => {
+            tracing::warn!(?response, "Unexpected response to channel setting message");
+            cmd.notice("Error updating channel settings");
+        }
+        Err(e) => {
+            tracing::warn!(?e, "Error response updating channel settings");
+            cmd.notice("Error updating channel settings");
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_on_off(value: &str) -> Option<bool> {
+    match value.to_ascii_uppercase().as_str() {
+        "ON" => Some(true),
+        "OFF" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parse a mode lock of the form `+nt-s` into the sets of locked-on and locked-off modes
+fn parse_mode_lock(modes: &str) -> Option<(ChannelModeSet, ChannelModeSet)> {
+    let mut on = ChannelModeSet::new();
+    let mut off = ChannelModeSet::new();
+
+    if modes.eq_ignore_ascii_case("OFF") {
+        return Some((on, off));
+    }
+
+    let mut adding = true;
+    for c in modes.chars() {
+        match c {
+            '+' => adding = true,
+            '-' => adding = false,
+            _ => {
+                let flag = ChannelModeFlag::from_mode_char(c)?;
+                if adding {
+                    on |= flag;
+                    off &= !ChannelModeSet::from(flag);
+                } else {
+                    off |= flag;
+                    on &= !ChannelModeSet::from(flag);
+                }
+            }
+        }
+    }
+
+    Some((on, off))
+}
+
+fn describe_setting(channel: &ChannelName, setting: &ChannelSetting) -> String {
+    match setting {
+        ChannelSetting::ModeLock { on, off } if on.is_empty() && off.is_empty() => {
+            format!("Mode lock for {channel} has been removed")
+        }
+        ChannelSetting::ModeLock { on, off } => {
+            let mut lock = String::new();
+            if !on.is_empty() {
+                lock = format!("+{}", on.to_chars());
+            }
+            if !off.is_empty() {
+                lock += &format!("-{}", off.to_chars());
+            }
+            format!("Mode lock for {channel} is now {lock}")
+        }
+        ChannelSetting::KeepTopic(true) => format!("Topic retention is now on for {channel}"),
+        ChannelSetting::KeepTopic(false) => format!("Topic retention is now off for {channel}"),
+        ChannelSetting::EntryMessage(Some(message)) => {
+            format!("Entry message for {channel} is now: {message}")
+        }
+        ChannelSetting::EntryMessage(None) => {
+            format!("Entry message for {channel} has been cleared")
+        }
+        ChannelSetting::Guard(true) => format!("{channel} will now be kept while empty"),
+        ChannelSetting::Guard(false) => format!("{channel} will no longer be kept while empty"),
+    }
+}
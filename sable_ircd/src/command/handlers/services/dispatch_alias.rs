@@ -9,6 +9,14 @@ pub async fn dispatch_alias_command(
     let (command, args) = command_str.split_once(' ').unwrap_or((command_str, ""));
 
     let new_args = args.split(' ').map(ToOwned::to_owned).collect::<Vec<_>>();
+
+    let degraded_args: Vec<_> = std::iter::once(command).chain(args.split(' ')).collect();
+    if cmd
+        .server()
+        .refuse_if_degraded(cmd.connection(), alias, &degraded_args)
+    {
+        return Ok(());
+    }
     let new_arg_iter = ArgListIter::new(&new_args);
 
     let new_cmd = ServicesCommand::new(cmd, command, new_arg_iter, Some(through_user));
@@ -125,9 +125,10 @@ impl Command for ServicesCommand<'_> {
                     PermissionError::User(ue) =>
                     {
                         use UserPermissionError::*;
-                        #[allow(clippy::single_match)] // For consistency with other branches
                         match ue {
                             NotLoggedIn => { self.notice("You are not logged in") }
+                            NotOper => { self.notice("Access denied - you are not an operator") }
+                            MissingPrivilege(privilege) => { self.notice(format_args!("Access denied - you need the {privilege} oper privilege")) }
                             _ => {}
                         }
                     }
@@ -18,4 +18,7 @@ async fn handle_cs<'a>(
 }
 
 mod cert;
+mod hold;
+mod info;
 mod login;
+mod set;
@@ -0,0 +1,59 @@
+use sable_network::rpc::{
+    RemoteServerResponse, RemoteServicesServerRequestType, RemoteServicesServerResponse,
+};
+
+use super::*;
+
+#[command_handler("HOLD", in("NS"))]
+async fn handle_hold(
+    server: &ClientServer,
+    source: UserSource<'_>,
+    cmd: &dyn Command,
+    services: ServicesTarget<'_>,
+    account: wrapper::Account<'_>,
+    setting: &str,
+) -> CommandResult {
+    server.policy().can_administer(&source)?;
+
+    let no_expire = match setting.to_ascii_uppercase().as_str() {
+        "ON" => true,
+        "OFF" => false,
+        _ => {
+            cmd.notice("Syntax: NS HOLD <account> ON|OFF");
+            return Ok(());
+        }
+    };
+
+    let request = RemoteServicesServerRequestType::SetAccountNoExpire {
+        source: source.id(),
+        account: account.id(),
+        no_expire,
+    }
+    .into();
+
+    match services.send_remote_request(request).await {
+        Ok(RemoteServerResponse::Success) => {
+            if no_expire {
+                cmd.notice(format_args!(
+                    "Account {} will no longer expire",
+                    account.name()
+                ));
+            } else {
+                cmd.notice(format_args!("Account {} is no longer held", account.name()));
+            }
+        }
+        Ok(RemoteServerResponse::Services(RemoteServicesServerResponse::AccessDenied)) => {
+            cmd.notice("Access denied");
+        }
+        Ok(response) => {
+            tracing::warn!(?response, "Unexpected response to account hold message");
+            cmd.notice("Error updating account");
+        }
+        Err(e) => {
+            tracing::warn!(?e, "Error response updating account hold");
+            cmd.notice("Error updating account");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use crate::utils::format_timestamp;
+
+use super::*;
+
+#[command_handler("INFO", in("NS"))]
+fn handle_info(
+    server: &ClientServer,
+    net: &Network,
+    source: UserSource,
+    cmd: &dyn Command,
+    target: Option<&str>,
+) -> CommandResult {
+    let account = match target {
+        Some(name) => net.account_by_name(&Nickname::from_str(name)?)?,
+        None => source.account()?.ok_or(CommandError::NotLoggedIn)?,
+    };
+
+    let is_owner = source.account()?.is_some_and(|a| a.id() == account.id());
+    let is_oper = server.policy().require_oper(&source).is_ok();
+
+    cmd.notice(format_args!("Information on {}:", account.name()));
+
+    if let Some(registered) = account.registered() {
+        cmd.notice(format_args!("Registered: {}", format_timestamp(registered)));
+    }
+
+    if !account.hide_info() || is_owner || is_oper {
+        let nicks: Vec<_> = account
+            .nick_registrations()
+            .map(|n| n.nick().to_string())
+            .collect();
+        if !nicks.is_empty() {
+            cmd.notice(format_args!("Registered nicks: {}", nicks.join(" ")));
+        }
+
+        if account.users().next().is_some() {
+            cmd.notice("Last seen: now");
+        } else if let Some(last_seen) = account.last_seen() {
+            cmd.notice(format_args!("Last seen: {}", format_timestamp(last_seen)));
+        }
+
+        if let Some(last_quit) = account.last_quit() {
+            cmd.notice(format_args!("Last quit: {last_quit}"));
+        }
+    }
+
+    for user in account.users() {
+        if server.policy().can_see_connection_info(&source, &user) {
+            cmd.notice(format_args!("Logged in from: {}", user.nuh()));
+        }
+    }
+
+    if is_owner || is_oper {
+        let mut flags = Vec::new();
+        if account.hide_info() {
+            flags.push("HIDEINFO");
+        }
+        if account.no_expire() {
+            flags.push("HOLD");
+        }
+        if !flags.is_empty() {
+            cmd.notice(format_args!("Flags: {}", flags.join(" ")));
+        }
+    }
+
+    cmd.notice(format_args!("End of information on {}", account.name()));
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use sable_network::rpc::{RemoteServerResponse, RemoteServicesServerRequestType};
+
+use super::*;
+
+#[command_handler("SET", in("NS"))]
+async fn handle_set(
+    source: LoggedInUserSource<'_>,
+    cmd: &dyn Command,
+    services: ServicesTarget<'_>,
+    setting: &str,
+    value: &str,
+) -> CommandResult {
+    let request = match setting.to_ascii_uppercase().as_str() {
+        "HIDEINFO" => {
+            let hide_info = match value.to_ascii_uppercase().as_str() {
+                "ON" => true,
+                "OFF" => false,
+                _ => {
+                    cmd.notice("Syntax: NS SET HIDEINFO ON|OFF");
+                    return Ok(());
+                }
+            };
+            RemoteServicesServerRequestType::SetAccountHideInfo {
+                account: source.account.id(),
+                hide_info,
+            }
+        }
+        _ => {
+            cmd.notice("Syntax: NS SET HIDEINFO ON|OFF");
+            return Ok(());
+        }
+    };
+
+    match services.send_remote_request(request.into()).await {
+        Ok(RemoteServerResponse::Success) => {
+            cmd.notice(format_args!(
+                "{} has been set to {}",
+                setting.to_ascii_uppercase(),
+                value.to_ascii_uppercase()
+            ));
+        }
+        Ok(response) => {
+            tracing::warn!(?response, "Unexpected response to account setting message");
+            cmd.notice("Error updating account settings");
+        }
+        Err(e) => {
+            tracing::warn!(?e, "Error response updating account settings");
+            cmd.notice("Error updating account settings");
+        }
+    }
+
+    Ok(())
+}
@@ -8,7 +8,7 @@ fn handle_version(server: &ClientServer, response: &dyn CommandResponse) -> Comm
         server.node().version()
     ));
 
-    for v in server.isupport.data().iter() {
+    for v in server.isupport.read().data().iter() {
         response.numeric(make_numeric!(ISupport, v))
     }
 
@@ -48,6 +48,25 @@ fn whois_handler(
         response.numeric(make_numeric!(Away, &target, away_reason));
     }
 
+    if let Some(oper) = target.oper_privileges() {
+        response.numeric(make_numeric!(WhoisOperator, &target));
+
+        if server.policy().require_oper(&source).is_ok() {
+            let privileges: Vec<_> = oper.privileges.iter().map(|p| p.to_string()).collect();
+            let class = match &oper.class {
+                Some(class) => format!(" in class {class}"),
+                None => String::new(),
+            };
+            let text = format!(
+                "is opered as {}{} with privileges: {}",
+                oper.oper_name,
+                class,
+                privileges.join(" ")
+            );
+            response.numeric(make_numeric!(WhoisSpecial, &target, &text));
+        }
+    }
+
     if server.policy().can_see_connection_info(&source, &target) {
         for conn in target.connections() {
             if let Ok(server) = conn.server() {
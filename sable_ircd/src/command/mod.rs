@@ -38,6 +38,7 @@ mod handlers {
     use std::ops::Deref;
 
     mod admin;
+    mod audit;
     mod away;
     mod ban;
     mod cap;
@@ -49,6 +50,7 @@ mod handlers {
     mod kill;
     mod kline;
     mod links;
+    mod logfilter;
     mod mode;
     mod monitor;
     mod motd;
@@ -62,6 +64,7 @@ mod handlers {
     mod privmsg;
     mod quit;
     pub mod register;
+    mod rehash;
     mod rename;
     mod tagmsg;
     mod topic;
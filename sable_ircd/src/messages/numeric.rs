@@ -22,12 +22,15 @@ define_messages! {
                                                                 => "{nick} {user} {host} * :{realname}" },
     312(WhoisServer)            => { (nick: &User.nick(), server: &Server.name(), info=server.id())
                                                                 => "{nick} {server} :{info:?}"},
+    313(WhoisOperator)          => { (nick: &User.nick())       => "{nick} :is an IRC operator" },
     314(WhowasUser)             => { (nick: &HistoricUser.nick(), user=nick.user(), host=nick.visible_host(), realname=nick.realname())
                                                                 => "{nick} {user} {host} * :{realname}" },
     315(EndOfWho)               => { (arg: &str)                => "{arg} :End of /WHO list" },
     318(EndOfWhois)             => { (user: &str)               => "{user} :End of /WHOIS" },
     319(WhoisChannels)          => { (user: &User.nick(), chanlist: &str)
                                                                 => "{user} :{chanlist}" },
+    320(WhoisSpecial)           => { (nick: &User.nick(), text: &str)
+                                                                => "{nick} :{text}" },
     378(WhoisHost)              => { (user: &User.nick(), username=user.user(), host: &Hostname, ip: &std::net::IpAddr)
                                                                 => "{user} :is connecting from {username}@{host} {ip}" },
 
@@ -127,6 +130,7 @@ define_messages! {
 
     481(NotOper)            => { ()     => ":You're not an IRC operator" },
     491(NoOperConf)         => { ()     => ":No oper configuration found" },
+    723(NoPrivs)            => { (privilege: &str)  => "{privilege} :Insufficient oper privileges" },
 
     440(ServicesNotAvailable) => { () => ":Services are not available"},
 
@@ -43,11 +43,13 @@ impl SendHistoryItem<NetworkHistoryUpdate> for ClientServer {
             NetworkStateChange::ChannelInvite(detail) => self.send_item(detail, conn, item),
             NetworkStateChange::ChannelRename(detail) => self.send_item(detail, conn, item),
             NetworkStateChange::NewMessage(detail) => self.send_item(detail, conn, item),
+            NetworkStateChange::ServicesNotice(detail) => self.send_item(detail, conn, item),
             NetworkStateChange::NewUser(_)
             | NetworkStateChange::NewUserConnection(_)
             | NetworkStateChange::UserConnectionDisconnected(_)
             | NetworkStateChange::NewServer(_)
             | NetworkStateChange::ServerQuit(_)
+            | NetworkStateChange::SyncPeerUpdate(_)
             | NetworkStateChange::NewAuditLogEntry(_)
             | NetworkStateChange::UserLoginChange(_)
             | NetworkStateChange::HistoryServerUpdate(_)
@@ -80,11 +82,13 @@ impl SendHistoryItem<HistoryLogEntry> for ClientServer {
             NetworkStateChange::ChannelInvite(detail) => self.send_item(detail, conn, item),
             NetworkStateChange::ChannelRename(detail) => self.send_item(detail, conn, item),
             NetworkStateChange::NewMessage(detail) => self.send_item(detail, conn, item),
+            NetworkStateChange::ServicesNotice(detail) => self.send_item(detail, conn, item),
             NetworkStateChange::NewUser(_)
             | NetworkStateChange::NewUserConnection(_)
             | NetworkStateChange::UserConnectionDisconnected(_)
             | NetworkStateChange::NewServer(_)
             | NetworkStateChange::ServerQuit(_)
+            | NetworkStateChange::SyncPeerUpdate(_)
             | NetworkStateChange::NewAuditLogEntry(_)
             | NetworkStateChange::UserLoginChange(_)
             | NetworkStateChange::HistoryServerUpdate(_)
@@ -442,3 +446,26 @@ impl SendHistoryItem<update::NewMessage> for ClientServer {
         Ok(())
     }
 }
+
+impl SendHistoryItem<update::ServicesNotice> for ClientServer {
+    fn send_item(
+        &self,
+        item: &update::ServicesNotice,
+        conn: impl MessageSink,
+        from_entry: &impl HistoryItem,
+    ) -> HandleResult {
+        let net = self.network();
+        let user = net.historic_user(item.user)?;
+
+        // Notices appear to come from the relevant alias user if one is configured,
+        // otherwise from the local server
+        let message = match net.alias_user_for_command(&item.source_alias) {
+            Some(alias) => message::Notice::new(&alias, user, &item.text),
+            None => message::Notice::new(self, user, &item.text),
+        };
+
+        conn.send(message.with_tags_from(from_entry, &net));
+
+        Ok(())
+    }
+}
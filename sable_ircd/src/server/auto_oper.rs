@@ -0,0 +1,60 @@
+use super::*;
+use sable_network::audit::AuditLogger;
+use sable_network::policy::OperCredentials;
+
+impl ClientServer {
+    /// Grant oper access to a newly connected user whose TLS client certificate
+    /// satisfies an oper block marked for automatic oper-up
+    pub(super) fn auto_oper(&self, conn: &ClientConnection, user: &wrapper::User) {
+        if user.is_oper() || self.policy().user_can_oper(user).is_err() {
+            return;
+        }
+        let Some(fingerprint) = conn.tls_info().and_then(|ti| ti.fingerprint.as_deref()) else {
+            return;
+        };
+        let net = self.network();
+        let Some(user_connection) = conn
+            .user_connection_id()
+            .and_then(|id| net.user_connection(id).ok())
+        else {
+            return;
+        };
+
+        let credentials = OperCredentials {
+            password: None,
+            fingerprint: Some(fingerprint),
+            account: user.account_name(),
+            username: user.user(),
+            hostname: user_connection.hostname(),
+            ip: *user_connection.ip(),
+        };
+
+        let Some(block) = net
+            .config()
+            .opers
+            .iter()
+            .filter(|block| block.auto_oper)
+            .find(|block| self.policy().authenticate(block, &block.name, &credentials))
+        else {
+            return;
+        };
+
+        AuditLogger::new(
+            self.node(),
+            Some(user.id()),
+            conn.remote_addr(),
+            "AUTO-OPER".to_owned(),
+        )
+        .general()
+        .target_str(block.name.clone())
+        .log();
+
+        conn.send(make_numeric!(YoureOper).format_for(self, user));
+        self.node.submit_event(
+            user.id(),
+            event::details::OperUp {
+                oper_name: block.name.clone(),
+            },
+        );
+    }
+}
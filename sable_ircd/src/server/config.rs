@@ -63,7 +63,7 @@ impl ServerInfoStrings {
     }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 #[serde(deny_unknown_fields)] // Dont let typos into the config
 pub struct AdminInfo {
     pub server_location: Option<String>,
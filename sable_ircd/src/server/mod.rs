@@ -43,8 +43,10 @@ use crate::monitor::MonitorSet;
 
 pub mod config;
 
+mod auto_oper;
 mod command_action;
 mod message_sink_repository;
+mod quorum;
 mod server_type;
 mod update_handler;
 mod user_access;
@@ -83,14 +85,14 @@ pub struct ClientServer {
 
     auth_client: AuthClient,
     myinfo: MyInfo,
-    pub isupport: ISupportBuilder,
+    pub isupport: RwLock<ISupportBuilder>,
     client_caps: CapabilityRepository,
 
     node: Arc<NetworkNode>,
     listeners: Movable<ListenerCollection>,
 
     // Any general static info (responses for MOTD, ADMIN, and so on)
-    pub info_strings: ServerInfoStrings,
+    pub info_strings: RwLock<ServerInfoStrings>,
 
     pub monitors: RwLock<MonitorSet>,
 }
@@ -354,6 +356,9 @@ impl ClientServer {
             match ClientMessage::parse(conn_id, &message) {
                 Ok(parsed) => {
                     if let Ok(connection) = connections.get(conn_id) {
+                        if self.refuse_if_degraded(&connection, &parsed.command, &parsed.args) {
+                            continue;
+                        }
                         if let Ok(command) =
                             ClientCommand::new(Arc::clone(self), connection, parsed)
                         {
@@ -421,6 +426,9 @@ impl ClientServer {
 
         let mut reap_preclients_timer = time::interval(Duration::from_secs(60));
 
+        let mut quorum = self.node.subscribe_quorum();
+        let mut degraded = quorum.borrow_and_update().degraded;
+
         loop {
             // tracing::trace!("ClientServer run loop");
             // Before looking for an I/O event, do our internal bookkeeping.
@@ -450,6 +458,15 @@ impl ClientServer {
                     tracing::trace!("...from reap_preclients_timer");
                     tokio::spawn(self.clone().reap_preclients());
                 },
+                Ok(()) = quorum.changed() =>
+                {
+                    tracing::trace!("...from quorum");
+                    let now_degraded = quorum.borrow_and_update().degraded;
+                    if now_degraded != degraded {
+                        degraded = now_degraded;
+                        self.announce_quorum_change(degraded);
+                    }
+                },
                 _ = async_handlers.poll(), if !async_handlers.is_empty() =>
                 {
                     tracing::trace!("...from async_handlers");
@@ -0,0 +1,186 @@
+use super::*;
+use std::str::FromStr;
+
+const DEGRADED_NOTICE: &str = "This server has lost contact with most of the network. \
+    Channel, account and ban changes are disabled until it reconnects.";
+const RECOVERED_NOTICE: &str = "This server has reconnected to the network.";
+
+/// Commands which always make lasting changes to network state. While this server
+/// is degraded, those changes would be thrown away when it rejoins the majority, so
+/// these are refused rather than appearing to succeed.
+const REFUSED_WHEN_DEGRADED: &[&str] = &[
+    "REGISTER", "RENAME", "KLINE", "BAN", "KICK", "INVITE", "KILL",
+];
+
+impl ClientServer {
+    /// Tell local users that this server has entered or left degraded mode
+    pub(super) fn announce_quorum_change(&self, degraded: bool) {
+        let text = if degraded {
+            DEGRADED_NOTICE
+        } else {
+            RECOVERED_NOTICE
+        };
+
+        let net = self.network();
+        for conn in self.connections.read().iter() {
+            match conn.user_id().and_then(|id| net.user(id).ok()) {
+                Some(user) => conn.send(message::Notice::new(self, &user, text)),
+                None => conn.send(message::Notice::new(self, &UnknownTarget, text)),
+            }
+        }
+    }
+
+    /// Send the degraded mode warning to a newly connected user, if applicable
+    pub(super) fn notify_degraded(&self, conn: &ClientConnection, user: &wrapper::User) {
+        if self.node.is_degraded() {
+            conn.send(message::Notice::new(self, user, DEGRADED_NOTICE));
+        }
+    }
+
+    /// If the given command should be refused because this server is degraded, tell the
+    /// client so and return `true`
+    pub(crate) fn refuse_if_degraded(
+        &self,
+        conn: &ClientConnection,
+        command: &str,
+        args: &[impl AsRef<str>],
+    ) -> bool {
+        if !self.node.is_degraded() {
+            return false;
+        }
+
+        let net = self.network();
+        if !command_changes_state(command, args, |name| net.channel_by_name(name).is_ok()) {
+            return false;
+        }
+
+        conn.send(message::Fail::new(
+            &command.to_ascii_uppercase(),
+            "NETWORK_DEGRADED",
+            "*",
+            DEGRADED_NOTICE,
+        ));
+        true
+    }
+}
+
+/// Whether a command would make lasting changes to network state. Commands which
+/// only sometimes do, such as `MODE` or `TOPIC`, are judged by their arguments.
+fn command_changes_state(
+    command: &str,
+    args: &[impl AsRef<str>],
+    channel_exists: impl Fn(&ChannelName) -> bool,
+) -> bool {
+    let arg = |i: usize| args.get(i).map(AsRef::as_ref);
+
+    match command.to_ascii_uppercase().as_str() {
+        // Joining an existing channel is fine, but creating one isn't
+        "JOIN" => arg(0).is_some_and(|targets| {
+            targets.split(',').any(|target| {
+                ChannelName::from_str(target).is_ok_and(|name| !channel_exists(&name))
+            })
+        }),
+        "TOPIC" => arg(1).is_some(),
+        "MODE" => {
+            arg(0).is_some_and(|target| ChannelName::from_str(target).is_ok())
+                && arg(1).is_some_and(|modes| !is_list_mode_query(modes, args.len()))
+        }
+        "NS" | "CS" => services_command_changes_state(command, args),
+        other => REFUSED_WHEN_DEGRADED
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(other)),
+    }
+}
+
+/// Whether a channel `MODE` is only asking for the contents of a list mode, such
+/// as `MODE #channel +b`
+fn is_list_mode_query(modes: &str, arg_count: usize) -> bool {
+    arg_count == 2
+        && modes
+            .trim_start_matches('+')
+            .chars()
+            .all(|c| ListModeType::from_mode_char(c).is_some())
+}
+
+/// Whether a services command would change anything, rather than only displaying
+/// information. Anything not known to be read-only is assumed to make changes.
+fn services_command_changes_state(service: &str, args: &[impl AsRef<str>]) -> bool {
+    let arg = |i: usize| args.get(i).map(|a| a.as_ref().to_ascii_uppercase());
+
+    let read_only = match (service.to_ascii_uppercase().as_str(), arg(0).as_deref()) {
+        ("NS", Some("INFO")) => true,
+        ("NS", Some("CERT")) => arg(1).as_deref() == Some("LIST"),
+        // These take the channel name before their own subcommand
+        ("CS", Some("ACCESS" | "ROLE" | "AKICK")) => {
+            matches!(arg(2).as_deref(), None | Some("LIST"))
+        }
+        _ => false,
+    };
+
+    !read_only
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes_state(line: &str) -> bool {
+        let mut words = line.split(' ');
+        let command = words.next().unwrap();
+        let args: Vec<_> = words.collect();
+
+        command_changes_state(command, &args, |name| name.as_ref() == "#existing")
+    }
+
+    #[test]
+    fn unconditional_commands_are_refused() {
+        for line in [
+            "REGISTER * * password",
+            "kick #existing someone",
+            "INVITE someone #existing",
+            "KILL someone reason",
+            "KLINE 60 *@host reason",
+        ] {
+            assert!(changes_state(line), "{line}");
+        }
+        assert!(!changes_state("PRIVMSG #existing hello"));
+        assert!(!changes_state("NICK newnick"));
+    }
+
+    #[test]
+    fn channel_creation_is_refused() {
+        assert!(!changes_state("JOIN #existing"));
+        assert!(changes_state("JOIN #new"));
+        assert!(changes_state("JOIN #existing,#new"));
+        assert!(!changes_state("JOIN 0"));
+    }
+
+    #[test]
+    fn only_changing_topics_and_modes_is_refused() {
+        assert!(!changes_state("TOPIC #existing"));
+        assert!(changes_state("TOPIC #existing new topic"));
+
+        assert!(!changes_state("MODE #existing"));
+        assert!(!changes_state("MODE #existing b"));
+        assert!(!changes_state("MODE #existing +b"));
+        assert!(changes_state("MODE #existing +b *!*@host"));
+        assert!(changes_state("MODE #existing +m"));
+        assert!(!changes_state("MODE someone +i"));
+    }
+
+    #[test]
+    fn read_only_services_commands_are_allowed() {
+        assert!(!changes_state("NS INFO someone"));
+        assert!(!changes_state("ns cert list"));
+        assert!(!changes_state("CS ACCESS #existing"));
+        assert!(!changes_state("CS AKICK #existing LIST"));
+        assert!(!changes_state("CS ROLE #existing"));
+
+        assert!(changes_state("NS SET hideinfo on"));
+        assert!(changes_state("NS CERT ADD"));
+        assert!(changes_state("CS REGISTER #existing"));
+        assert!(changes_state("CS ACCESS #existing SET someone op"));
+        assert!(changes_state("CS AKICK #existing ADD *!*@host"));
+        assert!(changes_state("NS"));
+    }
+}
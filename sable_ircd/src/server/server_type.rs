@@ -2,11 +2,13 @@ use anyhow::Context;
 use tracing::instrument;
 
 use client_listener::SavedListenerCollection;
-use sable_server::ServerSaveError;
+use sable_server::{ConfigReloadReport, ServerSaveError};
 
 use super::*;
 use crate::connection_collection::ConnectionCollectionState;
 use crate::monitor::MonitorSet;
+use sable_network::metrics::{MetricType, MetricsWriter};
+use std::{collections::BTreeMap, net::SocketAddr};
 
 /// Saved state of a [`ClientServer`] for later resumption
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -85,11 +87,11 @@ impl sable_server::ServerType for ClientServer {
             connections: RwLock::new(ConnectionCollection::new()),
             prereg_connections: Mutex::new(VecDeque::new()),
             myinfo: Self::build_myinfo(),
-            isupport: Self::build_basic_isupport(&config),
+            isupport: RwLock::new(Self::build_basic_isupport(&config)),
             client_caps: CapabilityRepository::new(),
             node,
             listeners: Movable::new(client_listeners),
-            info_strings: config.info_strings,
+            info_strings: RwLock::new(config.info_strings),
             monitors: MonitorSet::new(config.monitor.max_per_connection.into()).into(),
         })
     }
@@ -151,11 +153,11 @@ impl sable_server::ServerType for ClientServer {
             auth_client: AuthClient::resume(state.auth_state, auth_send)?,
             auth_events: Mutex::new(auth_recv),
             myinfo: Self::build_myinfo(),
-            isupport: Self::build_basic_isupport(config),
+            isupport: RwLock::new(Self::build_basic_isupport(config)),
             client_caps: state.client_caps,
             history_receiver: Mutex::new(history_receiver),
             listeners: Movable::new(listeners),
-            info_strings: config.info_strings.clone(),
+            info_strings: RwLock::new(config.info_strings.clone()),
             monitors: state.monitors.into(),
         })
     }
@@ -177,4 +179,162 @@ impl sable_server::ServerType for ClientServer {
             _ => RemoteServerResponse::NotSupported,
         }
     }
+
+    fn export_metrics(&self, out: &mut MetricsWriter) {
+        let mut per_listener = BTreeMap::<String, usize>::new();
+        let mut registered = 0;
+        let mut pending_messages = 0;
+
+        for conn in self.connections.read().iter() {
+            let listener = conn.id().listener();
+            let label = match self.listeners.listener_address(listener) {
+                Some(address) => address.to_string(),
+                None => listener.to_string(),
+            };
+            *per_listener.entry(label).or_default() += 1;
+
+            if conn.user_id().is_some() {
+                registered += 1;
+            }
+            pending_messages += conn.pending_messages();
+        }
+
+        let mut family = out.family(
+            "sable_client_connections",
+            MetricType::Gauge,
+            "Client connections, by the address of the listener that accepted them",
+        );
+        for (listener, count) in &per_listener {
+            family.sample(&[("listener", listener)], *count as f64);
+        }
+        out.single(
+            "sable_client_registered_connections",
+            MetricType::Gauge,
+            "Client connections which have completed registration",
+            registered as f64,
+        );
+        out.single(
+            "sable_client_pending_messages",
+            MetricType::Gauge,
+            "Messages received from clients and waiting to be processed",
+            pending_messages as f64,
+        );
+
+        self.command_dispatcher.export_metrics(out);
+    }
+
+    fn reload_config(
+        &self,
+        config: Self::ProcessedConfig,
+        _changed: bool,
+        tls_data: Option<&TlsData>,
+        report: &mut ConfigReloadReport,
+    ) -> anyhow::Result<()> {
+        // Check every listener address before changing anything
+        let mut new_listeners = Vec::new();
+        for listener in config.listeners.iter() {
+            let address: SocketAddr = listener
+                .address
+                .parse()
+                .with_context(|| format!("Invalid listener address: {}", listener.address))?;
+            let conn_type = if listener.tls {
+                ConnectionType::Tls
+            } else {
+                ConnectionType::Clear
+            };
+            new_listeners.push((address, conn_type));
+        }
+
+        let mut removed_listeners = Vec::new();
+        for id in self.listeners.listener_ids() {
+            let Some(address) = self.listeners.listener_address(id) else {
+                continue;
+            };
+            // Listeners resumed from before their type was recorded are kept as long
+            // as their address is still configured
+            let conn_type = self.listeners.listener_type(id);
+            let still_configured = new_listeners.iter().position(|(a, t)| {
+                *a == address
+                    && match &conn_type {
+                        Some(conn_type) => conn_type == t,
+                        None => true,
+                    }
+            });
+
+            match still_configured {
+                Some(index) => {
+                    // Added by an earlier reload, and not yet known to have worked
+                    if self.listeners.listener_is_pending(id) {
+                        let (address, conn_type) = &new_listeners[index];
+                        report
+                            .pending
+                            .push(format!("listeners: adding {address} ({conn_type:?})"));
+                    }
+                    new_listeners.remove(index);
+                }
+                None => removed_listeners.push((id, address)),
+            }
+        }
+
+        let listeners_changed =
+            tls_data.is_some() || !removed_listeners.is_empty() || !new_listeners.is_empty();
+        if listeners_changed && !self.listeners.is_running() {
+            anyhow::bail!("Client listener process is not running");
+        }
+
+        // Nothing below can fail, so either all of the new configuration is applied
+        // or none of it is. Sending to the listener process only fails if it has
+        // exited since the check above, in which case it has no listeners left to
+        // roll back.
+
+        // New certificates need to be in place before any new TLS listener is added
+        if let Some(tls_data) = tls_data {
+            match self
+                .listeners
+                .load_tls_certificates(tls_data.key.clone(), tls_data.cert_chain.clone())
+            {
+                Ok(()) => report
+                    .applied
+                    .push("tls_config (client listeners)".to_owned()),
+                Err(e) => tracing::error!("Could not load TLS certificates: {}", e),
+            }
+        }
+
+        for (id, address) in removed_listeners {
+            match self.listeners.remove_listener(id) {
+                Ok(()) => report.applied.push(format!("listeners: removed {address}")),
+                Err(e) => tracing::error!("Cannot remove listener {}: {}", address, e),
+            }
+        }
+
+        // The listener process reports whether it could bind each new listener
+        // later; one that fails is forgotten, so the next reload tries it again
+        for (address, conn_type) in new_listeners {
+            match self.listeners.add_listener(address, conn_type.clone()) {
+                Ok(_) => report
+                    .pending
+                    .push(format!("listeners: adding {address} ({conn_type:?})")),
+                Err(e) => tracing::error!("Cannot add listener {}: {}", address, e),
+            }
+        }
+
+        let max_monitors: usize = config.monitor.max_per_connection.into();
+        let mut monitors = self.monitors.write();
+        if monitors.max_per_connection != max_monitors {
+            monitors.max_per_connection = max_monitors;
+            *self.isupport.write() = Self::build_basic_isupport(&config);
+            report.applied.push("monitor".to_owned());
+        }
+
+        let mut info_strings = self.info_strings.write();
+        if info_strings.motd != config.info_strings.motd {
+            report.applied.push("motd".to_owned());
+        }
+        if info_strings.admin_info != config.info_strings.admin_info {
+            report.applied.push("admin".to_owned());
+        }
+        *info_strings = config.info_strings;
+
+        Ok(())
+    }
 }
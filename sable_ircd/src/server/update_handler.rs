@@ -155,7 +155,7 @@ impl ClientServer {
                 &self.myinfo.chan_modes,
                 &self.myinfo.chan_modes_with_a_parameter,
             ));
-            for line in self.isupport.data().iter() {
+            for line in self.isupport.read().data().iter() {
                 connection.send(numeric::ISupport::new_for(
                     &self.node.name().to_string(),
                     &user.nick(),
@@ -169,6 +169,9 @@ impl ClientServer {
 
             connection.send(message::Notice::new(&self.node.name().to_string(), &user,
                     "The network is currently running in debug mode. Do not send any sensitive information such as passwords."));
+
+            self.notify_degraded(&connection, &user);
+            self.auto_oper(&connection, &user);
         }
         Ok(())
     }
@@ -63,6 +63,15 @@ impl<T: std::fmt::Debug> ThrottledQueue<T> {
         }
     */
 
+    /// The number of items waiting in the queue
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
     /// Add an item to the queue, if doing so does not exceed the maximum capacity
     ///
     /// Returns `Ok(())` on success, and `Err(_)` containing the provided item if the queue is full
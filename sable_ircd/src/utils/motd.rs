@@ -9,7 +9,7 @@ pub fn send_motd(
     to: impl MessageSink,
     to_user: &wrapper::User,
 ) -> HandleResult {
-    match &server.info_strings.motd {
+    match &server.info_strings.read().motd {
         None => to.send(numeric::NoMotd::new().format_for(server, to_user)),
         Some(motd) => {
             to.send(numeric::MotdStart::new(server.name()).format_for(server, to_user));
@@ -202,6 +202,16 @@ pub fn define_validated(input: TokenStream) -> TokenStream {
 
         if def.casefolded.is_some() {
             out.extend(quote!(
+                impl #name
+                {
+                    /// The form of this value used to compare it with others, for use
+                    /// as a key where the comparison isn't done by this type
+                    pub fn casefolded(&self) -> String
+                    {
+                        self.0.to_ascii_lowercase()
+                    }
+                }
+
                 impl PartialEq for #name
                 {
                     fn eq(&self, other: &Self) -> bool
@@ -6,8 +6,11 @@ use crate::{
     id::*,
     network::{event::*, state::*, wrapper::WrappedUser},
     node::NetworkNode,
+    types::Pattern,
 };
 
+use serde::{Deserialize, Serialize};
+
 pub struct AuditLogger<'a> {
     node: &'a NetworkNode,
     user: Option<UserId>,
@@ -88,7 +91,29 @@ impl<'a> AuditLogger<'a> {
     }
 }
 
-impl AuditLoggerEntry<'_> {
+impl<'a> AuditLoggerEntry<'a> {
+    /// Begin an entry for an action taken through the management interface,
+    /// attributed to the named management user
+    pub fn management(
+        node: &'a NetworkNode,
+        category: AuditLogCategory,
+        user: &str,
+        action: &str,
+    ) -> Self {
+        Self {
+            node,
+            category,
+            source_id: None,
+            source_addr: None,
+            source_str: format!("<management:{user}>"),
+            action: action.to_string(),
+            target_id: None,
+            target_str: None,
+            target_duration: None,
+            reason: None,
+        }
+    }
+
     pub fn source(mut self, id: Option<UserId>, ip: Option<IpAddr>) -> Self {
         self.source_id = id;
         self.source_str = format_source(self.node, id, ip);
@@ -156,3 +181,138 @@ impl AuditLoggerEntry<'_> {
             .submit_event(entry.id, details::NewAuditLogEntry { entry });
     }
 }
+
+/// Criteria for selecting audit log entries. Every criterion that is set must
+/// match for an entry to be selected.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditLogFilter {
+    /// Only entries in this category
+    pub category: Option<AuditLogCategory>,
+    /// Only entries whose source description matches this pattern
+    pub source: Option<Pattern>,
+    /// Only entries with a target description matching this pattern
+    pub target: Option<Pattern>,
+    /// Only entries logged at or after this time
+    pub since: Option<i64>,
+    /// Only entries logged before this time
+    pub until: Option<i64>,
+}
+
+impl AuditLogFilter {
+    pub fn matches(&self, entry: &AuditLogEntry) -> bool {
+        if self
+            .category
+            .is_some_and(|category| category != entry.category)
+        {
+            return false;
+        }
+        if let Some(source) = &self.source {
+            if !source.matches(&entry.source_str) {
+                return false;
+            }
+        }
+        if let Some(target) = &self.target {
+            if !entry.target_str.as_ref().is_some_and(|t| target.matches(t)) {
+                return false;
+            }
+        }
+        if self.since.is_some_and(|since| entry.timestamp < since) {
+            return false;
+        }
+        if self.until.is_some_and(|until| entry.timestamp >= until) {
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(category: AuditLogCategory, target: Option<&str>, timestamp: i64) -> AuditLogEntry {
+        AuditLogEntry {
+            id: ObjectIdGenerator::new(ServerId::new(1)).next(),
+            timestamp,
+            category,
+            source_id: None,
+            source_addr: None,
+            source_str: "oper!user@host.name[oper]{127.0.0.1}".to_string(),
+            action: "KLINE".to_string(),
+            target_id: None,
+            target_str: target.map(str::to_string),
+            target_duration: None,
+            reason: None,
+        }
+    }
+
+    fn pattern(s: &str) -> Option<Pattern> {
+        Some(Pattern::new(s.to_string()))
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = AuditLogFilter::default();
+        assert!(filter.matches(&entry(AuditLogCategory::General, None, 0)));
+        assert!(filter.matches(&entry(AuditLogCategory::NetworkBan, Some("*@bad"), 100)));
+    }
+
+    #[test]
+    fn category_must_match() {
+        let filter = AuditLogFilter {
+            category: Some(AuditLogCategory::NetworkBan),
+            ..Default::default()
+        };
+        assert!(filter.matches(&entry(AuditLogCategory::NetworkBan, None, 0)));
+        assert!(!filter.matches(&entry(AuditLogCategory::ServerKill, None, 0)));
+    }
+
+    #[test]
+    fn source_and_target_patterns_must_match() {
+        let source = AuditLogFilter {
+            source: pattern("OPER!*"),
+            ..Default::default()
+        };
+        assert!(source.matches(&entry(AuditLogCategory::General, None, 0)));
+        let source = AuditLogFilter {
+            source: pattern("someone!*"),
+            ..Default::default()
+        };
+        assert!(!source.matches(&entry(AuditLogCategory::General, None, 0)));
+
+        let target = AuditLogFilter {
+            target: pattern("*@*.example"),
+            ..Default::default()
+        };
+        assert!(target.matches(&entry(
+            AuditLogCategory::NetworkBan,
+            Some("user@host.example"),
+            0
+        )));
+        assert!(!target.matches(&entry(
+            AuditLogCategory::NetworkBan,
+            Some("user@host.test"),
+            0
+        )));
+        // An entry without a target never matches a target pattern, even a wildcard
+        let any_target = AuditLogFilter {
+            target: pattern("*"),
+            ..Default::default()
+        };
+        assert!(!any_target.matches(&entry(AuditLogCategory::NetworkBan, None, 0)));
+    }
+
+    #[test]
+    fn since_is_inclusive_and_until_exclusive() {
+        let filter = AuditLogFilter {
+            since: Some(100),
+            until: Some(200),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&entry(AuditLogCategory::General, None, 99)));
+        assert!(filter.matches(&entry(AuditLogCategory::General, None, 100)));
+        assert!(filter.matches(&entry(AuditLogCategory::General, None, 199)));
+        assert!(!filter.matches(&entry(AuditLogCategory::General, None, 200)));
+    }
+}
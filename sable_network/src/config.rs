@@ -10,7 +10,7 @@ pub struct TlsConfig {
     pub cert_file: PathBuf,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TlsData {
     pub key: Vec<u8>,
     pub cert_chain: Vec<Vec<u8>>,
@@ -162,9 +162,11 @@ impl NetworkHistoryLog {
             | UserConnectionDisconnected(_)
             | NewServer(_)
             | ServerQuit(_)
+            | SyncPeerUpdate(_)
             | NewAuditLogEntry(_)
             | UserLoginChange(_)
             | ServicesUpdate(_)
+            | ServicesNotice(_)
             | HistoryServerUpdate(_)
             | EventComplete(_) => None,
 
@@ -57,6 +57,8 @@ impl<NetworkPolicy: policy::PolicyService> HistoryService
             // This request should never error
             | Ok(RemoteServerResponse::Error(_))
             | Ok(RemoteServerResponse::NotSupported)
+            | Ok(RemoteServerResponse::StateDigest(..))
+            | Ok(RemoteServerResponse::ObjectDigests(_))
             | Ok(RemoteServerResponse::Success) => {
                 tracing::error!("Got unexpected response to ListTargets request: {res:?}");
                 HashMap::new()
@@ -100,6 +102,8 @@ impl<NetworkPolicy: policy::PolicyService> HistoryService
             // Errors while processing this request would return Entries(Err(_))
             | Ok(RemoteServerResponse::Error(_))
             | Ok(RemoteServerResponse::NotSupported)
+            | Ok(RemoteServerResponse::StateDigest(..))
+            | Ok(RemoteServerResponse::ObjectDigests(_))
             | Ok(RemoteServerResponse::Success) => {
                 tracing::error!("Got unexpected response to GetEntries request: {res:?}");
                 Ok(Vec::new())
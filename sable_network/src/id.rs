@@ -64,6 +64,7 @@ object_ids!(ObjectId (ObjectIdGenerator) {
     Invite: (UserId, ChannelId);
 
     Config: (u64,);
+    SyncPeer: (ServerName,);
     AuditLogEntry: snowflake;
 
     Account: snowflake;
@@ -92,6 +93,12 @@ impl NicknameId {
     }
 }
 
+impl SyncPeerId {
+    pub fn server_name(&self) -> &ServerName {
+        &self.0
+    }
+}
+
 impl ListModeId {
     pub fn channel(&self) -> ChannelId {
         self.0
@@ -24,6 +24,8 @@ pub mod config;
 
 pub mod audit;
 
+pub mod metrics;
+
 pub mod types {
     mod matchers;
     pub use matchers::*;
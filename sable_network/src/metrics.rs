@@ -0,0 +1,266 @@
+//! Collection of metrics for export in the Prometheus text exposition format
+//!
+//! Each component that has something to report writes its current values into
+//! a [`MetricsWriter`] when metrics are requested; only values that need to be
+//! accumulated as they happen, such as [`Histogram`]s, are kept between requests.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Bucket bounds, in seconds, suitable for the time taken to handle a request
+pub const LATENCY_BUCKETS: &[f64] = &[
+    0.0001, 0.0005, 0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// The type of a metric family, as declared in its `# TYPE` line
+#[derive(Debug, Clone, Copy)]
+pub enum MetricType {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl MetricType {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Counter => "counter",
+            Self::Gauge => "gauge",
+            Self::Histogram => "histogram",
+        }
+    }
+}
+
+/// Accumulates observations into cumulative buckets. Observations can be
+/// recorded concurrently from any thread.
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    /// Sum of all observations, stored as the bits of an `f64`
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    /// Create a histogram with the given upper bucket bounds, in increasing order
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0f64.to_bits()),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        if let Some(bucket) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let _ = self
+            .sum
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sum| {
+                Some((f64::from_bits(sum) + value).to_bits())
+            });
+    }
+
+    /// Record a duration, in seconds
+    pub fn observe_duration(&self, duration: Duration) {
+        self.observe(duration.as_secs_f64());
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn sum(&self) -> f64 {
+        f64::from_bits(self.sum.load(Ordering::Relaxed))
+    }
+}
+
+/// Builds a metrics document in the Prometheus text exposition format
+#[derive(Debug, Default)]
+pub struct MetricsWriter {
+    out: String,
+}
+
+impl MetricsWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write a metric with a single, unlabelled value
+    pub fn single(&mut self, name: &str, kind: MetricType, help: &str, value: f64) {
+        self.family(name, kind, help).sample(&[], value);
+    }
+
+    /// Start a metric family, to which any number of labelled samples can then be
+    /// added. Every sample for a family must be written before starting the next.
+    pub fn family<'a>(
+        &'a mut self,
+        name: &'a str,
+        kind: MetricType,
+        help: &str,
+    ) -> MetricFamily<'a> {
+        let _ = writeln!(self.out, "# HELP {name} {}", help.replace('\n', " "));
+        let _ = writeln!(self.out, "# TYPE {name} {}", kind.as_str());
+        MetricFamily { writer: self, name }
+    }
+
+    /// The completed document
+    pub fn finish(self) -> String {
+        self.out
+    }
+
+    fn write_sample(
+        &mut self,
+        name: &str,
+        labels: &[(&str, &str)],
+        extra: Option<(&str, &str)>,
+        value: f64,
+    ) {
+        self.out.push_str(name);
+
+        let mut labels = labels.iter().copied().chain(extra).peekable();
+        if labels.peek().is_some() {
+            self.out.push('{');
+            for (i, (label, value)) in labels.enumerate() {
+                if i > 0 {
+                    self.out.push(',');
+                }
+                let _ = write!(self.out, "{label}=\"{}\"", escape_label(value));
+            }
+            self.out.push('}');
+        }
+
+        let _ = writeln!(self.out, " {}", format_value(value));
+    }
+}
+
+/// A metric family being written by a [`MetricsWriter`]
+pub struct MetricFamily<'a> {
+    writer: &'a mut MetricsWriter,
+    name: &'a str,
+}
+
+impl MetricFamily<'_> {
+    /// Add a sample with the given labels
+    pub fn sample(&mut self, labels: &[(&str, &str)], value: f64) -> &mut Self {
+        self.writer.write_sample(self.name, labels, None, value);
+        self
+    }
+
+    /// Add the buckets, sum and count of a histogram with the given labels
+    pub fn histogram(&mut self, labels: &[(&str, &str)], histogram: &Histogram) -> &mut Self {
+        let bucket_name = format!("{}_bucket", self.name);
+        let mut cumulative = 0;
+
+        for (bound, bucket) in histogram.bounds.iter().zip(&histogram.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = format_value(*bound);
+            self.writer
+                .write_sample(&bucket_name, labels, Some(("le", &le)), cumulative as f64);
+        }
+        let count = histogram.count() as f64;
+        self.writer
+            .write_sample(&bucket_name, labels, Some(("le", "+Inf")), count);
+        self.writer
+            .write_sample(&format!("{}_sum", self.name), labels, None, histogram.sum());
+        self.writer
+            .write_sample(&format!("{}_count", self.name), labels, None, count);
+        self
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counter_is_written_with_help_and_type() {
+        let mut out = MetricsWriter::new();
+        out.single(
+            "sable_test_total",
+            MetricType::Counter,
+            "Things that\nhappened",
+            3.0,
+        );
+
+        assert_eq!(
+            out.finish(),
+            "# HELP sable_test_total Things that happened\n\
+             # TYPE sable_test_total counter\n\
+             sable_test_total 3\n"
+        );
+    }
+
+    #[test]
+    fn gauge_labels_are_escaped() {
+        let mut out = MetricsWriter::new();
+        out.family("sable_test_queue", MetricType::Gauge, "Queue length")
+            .sample(&[("peer", "a.test")], 0.5)
+            .sample(&[("peer", "b\"c\\d\ne"), ("kind", "x")], 12.0);
+
+        assert_eq!(
+            out.finish(),
+            "# HELP sable_test_queue Queue length\n\
+             # TYPE sable_test_queue gauge\n\
+             sable_test_queue{peer=\"a.test\"} 0.5\n\
+             sable_test_queue{peer=\"b\\\"c\\\\d\\ne\",kind=\"x\"} 12\n"
+        );
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let histogram = Histogram::new(&[0.25, 1.0, 2.5]);
+        for value in [0.125, 0.5, 0.5, 4.0] {
+            histogram.observe(value);
+        }
+        assert_eq!(histogram.count(), 4);
+        assert_eq!(histogram.sum(), 5.125);
+
+        let mut out = MetricsWriter::new();
+        out.family("sable_test_seconds", MetricType::Histogram, "Time taken")
+            .histogram(&[("command", "PING")], &histogram);
+
+        assert_eq!(
+            out.finish(),
+            "# HELP sable_test_seconds Time taken\n\
+             # TYPE sable_test_seconds histogram\n\
+             sable_test_seconds_bucket{command=\"PING\",le=\"0.25\"} 1\n\
+             sable_test_seconds_bucket{command=\"PING\",le=\"1\"} 3\n\
+             sable_test_seconds_bucket{command=\"PING\",le=\"2.5\"} 3\n\
+             sable_test_seconds_bucket{command=\"PING\",le=\"+Inf\"} 4\n\
+             sable_test_seconds_sum{command=\"PING\"} 5.125\n\
+             sable_test_seconds_count{command=\"PING\"} 4\n"
+        );
+    }
+
+    #[test]
+    fn special_values_are_formatted() {
+        assert_eq!(format_value(f64::NAN), "NaN");
+        assert_eq!(format_value(f64::INFINITY), "+Inf");
+        assert_eq!(format_value(f64::NEG_INFINITY), "-Inf");
+        assert_eq!(format_value(0.0), "0");
+        assert_eq!(format_value(-1.5), "-1.5");
+        assert_eq!(format_value(0.0001), "0.0001");
+    }
+}
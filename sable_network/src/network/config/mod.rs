@@ -1,13 +1,17 @@
 use super::state;
+use crate::types::Pattern;
 use crate::validated::*;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 #[serde_as]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkConfig {
     pub opers: Vec<OperConfig>,
+    /// Named sets of privileges, which oper blocks can refer to
+    #[serde(default)]
+    pub oper_classes: Vec<OperClass>,
     pub debug_mode: bool,
 
     #[serde_as(as = "HashMap<_, state::HumanReadableChannelAccessSet>")]
@@ -18,6 +22,29 @@ pub struct NetworkConfig {
     pub object_expiry: i64,
     /// How long from sending a server ping before we force it to quit from the network
     pub pingout_duration: i64,
+
+    /// How long audit log entries are kept
+    #[serde(default)]
+    pub audit_log_retention: AuditLogRetention,
+}
+
+/// Rules for expiring audit log entries. Each server expires entries according to
+/// its own clock.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditLogRetention {
+    /// The number of seconds to keep entries for, unless overridden for their
+    /// category. If not set, entries are kept forever.
+    pub default: Option<i64>,
+    /// The number of seconds to keep entries in specific categories
+    #[serde(default)]
+    pub categories: HashMap<state::AuditLogCategory, i64>,
+}
+
+impl AuditLogRetention {
+    /// How long an entry in the given category should be kept, if not forever
+    pub fn max_age(&self, category: state::AuditLogCategory) -> Option<i64> {
+        self.categories.get(&category).copied().or(self.default)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,18 +60,64 @@ pub struct AliasUser {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OperConfig {
     pub name: String,
-    pub hash: String,
+    /// The password hash. If not set, this block can't be used with a password
+    /// and is only useful for automatic oper-up.
+    #[serde(default)]
+    pub hash: Option<String>,
+    /// The name of the oper class whose privileges this oper is granted. If not
+    /// set, the oper has every privilege.
+    #[serde(default)]
+    pub class: Option<String>,
+    /// If set, the oper must be using a TLS client certificate with this fingerprint
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    /// If set, the oper must be logged in to this account
+    #[serde(default)]
+    pub account: Option<Nickname>,
+    /// If not empty, the oper must be connecting from a `user@host` or `user@ip`
+    /// matching one of these
+    #[serde(default)]
+    pub host_masks: Vec<Pattern>,
+    /// Grant this oper block on connection, without a password, to users who match
+    /// its other requirements. Only takes effect if `fingerprint` is set.
+    #[serde(default)]
+    pub auto_oper: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperClass {
+    pub name: String,
+    pub privileges: BTreeSet<state::OperPrivilege>,
 }
 
 impl NetworkConfig {
     pub fn new() -> Self {
         Self {
             opers: Vec::new(),
+            oper_classes: Vec::new(),
             debug_mode: false,
             default_roles: HashMap::new(),
             alias_users: Vec::new(),
             object_expiry: 0,
             pingout_duration: 240,
+            audit_log_retention: AuditLogRetention::default(),
+        }
+    }
+
+    /// The privileges granted to the named oper block. An oper whose class isn't
+    /// defined has none.
+    pub fn oper_privileges(&self, oper_name: &str) -> BTreeSet<state::OperPrivilege> {
+        let Some(oper) = self.opers.iter().find(|o| o.name == oper_name) else {
+            return BTreeSet::new();
+        };
+        match &oper.class {
+            None => state::OperPrivilege::all(),
+            Some(class) => self
+                .oper_classes
+                .iter()
+                .find(|c| &c.name == class)
+                .map(|c| c.privileges.clone())
+                .unwrap_or_default(),
         }
     }
 }
@@ -0,0 +1,248 @@
+//! Deterministic digests of the network state, used to check that servers agree
+//!
+//! The digest has three levels. Each object in a section of the [`Network`] (a
+//! user, a channel, a membership, and so on) is hashed individually; each
+//! section's hash covers those of its objects; and the root hash covers the
+//! sections. Comparing two digests section by section, and then object by object
+//! within the sections that differ, finds exactly which objects two servers
+//! disagree about without either having to send its whole state.
+
+use super::Network;
+
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Sections which can differ between servers that have seen the same events, and
+/// so are left out of consistency checks: the event clock, and the objects that
+/// each server expires according to its own clock
+const UNCHECKED_SECTIONS: &[&str] = &[
+    "clock",
+    "messages",
+    "historic_users",
+    "historic_nick_users",
+    "audit_log",
+];
+
+/// A SHA-256 hash of some part of the network state.
+///
+/// Hashes don't depend on the order of objects within the state's maps and sets,
+/// which isn't stable between processes, so two servers holding the same state
+/// compute the same hashes.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StateHash([u8; 32]);
+
+impl StateHash {
+    /// Hash the whole of a network state
+    pub fn of(network: &Network) -> Result<Self, serde_json::Error> {
+        Ok(Self::of_sections(&network_sections(network)?))
+    }
+
+    pub(crate) fn of_sections(sections: &Map<String, Value>) -> Self {
+        StateDigest::of_sections(sections, |_| true).root()
+    }
+
+    fn of_json(value: &Value) -> Self {
+        Self(Sha256::digest(canonical_json(value)).into())
+    }
+
+    fn combine<'a>(hashes: impl IntoIterator<Item = &'a StateHash>) -> Self {
+        let mut hasher = Sha256::new();
+        for hash in hashes {
+            hasher.update(hash.0);
+        }
+        Self(hasher.finalize().into())
+    }
+}
+
+/// The hash of each section of a network state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateDigest {
+    pub sections: BTreeMap<String, StateHash>,
+}
+
+impl StateDigest {
+    fn of_sections(sections: &Map<String, Value>, include: impl Fn(&str) -> bool) -> Self {
+        let sections = sections
+            .iter()
+            .filter(|(name, _)| include(name))
+            .map(|(name, value)| (name.clone(), section_hash(value)))
+            .collect();
+        Self { sections }
+    }
+
+    /// A single hash covering every section
+    pub fn root(&self) -> StateHash {
+        let mut hasher = Sha256::new();
+        for (name, hash) in &self.sections {
+            hasher.update(name.as_bytes());
+            hasher.update([0]);
+            hasher.update(hash.0);
+        }
+        StateHash(hasher.finalize().into())
+    }
+
+    /// The names of sections whose hashes differ between `self` and `other`,
+    /// including any present in only one of them
+    pub fn differing_sections(&self, other: &StateDigest) -> Vec<String> {
+        let mut names: Vec<_> = self
+            .sections
+            .keys()
+            .chain(other.sections.keys())
+            .filter(|name| self.sections.get(*name) != other.sections.get(*name))
+            .cloned()
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+/// The objects in one section of the state which differ between two servers
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SectionDiff {
+    /// Keys of objects present only on the local server
+    pub only_local: Vec<String>,
+    /// Keys of objects present only on the remote server
+    pub only_remote: Vec<String>,
+    /// Keys of objects present on both servers, but with different contents
+    pub different: Vec<String>,
+}
+
+impl SectionDiff {
+    /// Compare the object hashes of a section from two servers
+    pub fn between(
+        local: &BTreeMap<String, StateHash>,
+        remote: &BTreeMap<String, StateHash>,
+    ) -> Self {
+        let mut diff = Self::default();
+        for (key, hash) in local {
+            match remote.get(key) {
+                None => diff.only_local.push(key.clone()),
+                Some(other) if other != hash => diff.different.push(key.clone()),
+                Some(_) => (),
+            }
+        }
+        for key in remote.keys() {
+            if !local.contains_key(key) {
+                diff.only_remote.push(key.clone());
+            }
+        }
+        diff
+    }
+}
+
+impl Network {
+    /// Compute a digest of this state for comparison with other servers.
+    ///
+    /// Sections which can legitimately differ between servers, such as the event
+    /// clock, are left out.
+    pub fn state_digest(&self) -> StateDigest {
+        let sections = network_sections(self).expect("Failed to serialise network state");
+        StateDigest::of_sections(&sections, |name| !UNCHECKED_SECTIONS.contains(&name))
+    }
+
+    /// The hash of each object in the named section, keyed by the object's ID,
+    /// or `None` if there is no such section
+    pub fn object_digests(&self, section: &str) -> Option<BTreeMap<String, StateHash>> {
+        let sections = network_sections(self).expect("Failed to serialise network state");
+        sections.get(section).map(object_hashes)
+    }
+}
+
+fn network_sections(network: &Network) -> Result<Map<String, Value>, serde_json::Error> {
+    match serde_json::to_value(network)? {
+        Value::Object(fields) => Ok(fields),
+        _ => Err(serde_json::Error::custom("network state is not an object")),
+    }
+}
+
+/// Hash each object in a section. Maps are serialised as lists of key-value
+/// pairs, which are identified here by their key; a section that isn't a list is
+/// treated as a single object.
+fn object_hashes(section: &Value) -> BTreeMap<String, StateHash> {
+    match section {
+        Value::Array(items) => items
+            .iter()
+            .map(|item| {
+                let hash = StateHash::of_json(item);
+                let key = match item {
+                    Value::Array(pair) if pair.len() == 2 => pair[0].to_string(),
+                    _ => hash.to_string(),
+                };
+                (key, hash)
+            })
+            .collect(),
+        other => BTreeMap::from([(String::new(), StateHash::of_json(other))]),
+    }
+}
+
+fn section_hash(section: &Value) -> StateHash {
+    match section {
+        Value::Array(_) => {
+            let mut hashes: Vec<_> = object_hashes(section).into_values().collect();
+            hashes.sort();
+            StateHash::combine(&hashes)
+        }
+        other => StateHash::of_json(other),
+    }
+}
+
+/// Encode a JSON value with object keys sorted and array elements in a consistent
+/// order, so that equal states encode identically regardless of hash map ordering
+fn canonical_json(value: &Value) -> Vec<u8> {
+    match value {
+        Value::Array(items) => {
+            let mut encoded: Vec<_> = items.iter().map(canonical_json).collect();
+            encoded.sort();
+            let mut out = b"[".to_vec();
+            out.extend(encoded.join(&b","[..]));
+            out.push(b']');
+            out
+        }
+        Value::Object(fields) => {
+            let mut names: Vec<_> = fields.keys().collect();
+            names.sort();
+            let mut out = b"{".to_vec();
+            for (i, name) in names.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                out.extend(serde_json::to_vec(name).expect("Failed to encode string"));
+                out.push(b':');
+                out.extend(canonical_json(&fields[name]));
+            }
+            out.push(b'}');
+            out
+        }
+        other => serde_json::to_vec(other).expect("Failed to encode JSON value"),
+    }
+}
+
+impl std::fmt::Debug for StateHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "StateHash({})", hex::encode(self.0))
+    }
+}
+
+impl std::fmt::Display for StateHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl Serialize for StateHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for StateHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut bytes = [0; 32];
+        hex::decode_to_slice(String::deserialize(deserializer)?, &mut bytes)
+            .map_err(D::Error::custom)?;
+        Ok(Self(bytes))
+    }
+}
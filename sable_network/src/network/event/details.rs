@@ -148,7 +148,8 @@ EventDetails => {
 
     #[target_type(NetworkBanId)]
     struct RemoveNetworkBan {
-        pub remover: UserId,
+        /// None if removed through the management interface
+        pub remover: Option<UserId>,
     }
 
     #[target_type(ServerId)]
@@ -158,6 +159,7 @@ EventDetails => {
         pub ts: i64,
         pub flags: state::ServerFlags,
         pub version: String,
+        pub signing_key: Option<ServerKey>,
     }
 
     #[target_type(ServerId)]
@@ -170,11 +172,25 @@ EventDetails => {
         pub epoch: EpochId,
     }
 
+    /// A server's digest of its network state, as of the given event clock, for
+    /// other servers to check against their own
+    #[target_type(ServerId)]
+    struct StateDigestReport {
+        pub clock: EventClock,
+        pub digest: StateDigest,
+    }
+
     #[target_type(ConfigId)]
     struct LoadConfig {
         pub config: config::NetworkConfig,
     }
 
+    /// Add or reconfigure a server in the sync network, or remove it if `peer` is `None`
+    #[target_type(SyncPeerId)]
+    struct SyncPeerUpdate {
+        pub peer: Option<PeerConfig>,
+    }
+
     #[target_type(AuditLogEntryId)]
     struct NewAuditLogEntry {
         pub entry: state::AuditLogEntry,
@@ -234,4 +250,11 @@ EventDetails => {
         /// None means logout
         pub account: Option<AccountId>
     }
+
+    #[target_type(UserId)]
+    struct ServicesNotice {
+        /// Command alias (e.g. "NS") of the alias user this notice should appear to come from
+        pub source_alias: String,
+        pub text: String,
+    }
 });
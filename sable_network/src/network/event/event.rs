@@ -26,4 +26,8 @@ pub struct Event {
 
     /// The actual type and content of the event.
     pub details: EventDetails,
+
+    /// The origin server's signature over the rest of the event. Events from
+    /// servers which predate event signing have none.
+    pub signature: Option<EventSignature>,
 }
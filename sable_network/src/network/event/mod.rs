@@ -2,6 +2,7 @@
 
 mod clock;
 mod event;
+mod signature;
 
 pub mod details;
 
@@ -10,6 +11,9 @@ pub use clock::EventClock;
 pub use event::DetailType;
 pub use event::Event;
 
+pub use signature::EventSignature;
+pub use signature::ServerKey;
+
 pub use details::*;
 
 #[cfg(test)]
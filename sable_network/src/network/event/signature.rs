@@ -0,0 +1,103 @@
+use super::Event;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+/// The public half of the key with which a server signs the events it creates
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ServerKey([u8; 32]);
+
+/// An Ed25519 signature over an [`Event`], made by the server which created it
+#[derive(Clone, PartialEq, Eq)]
+pub struct EventSignature([u8; 64]);
+
+impl ServerKey {
+    pub(crate) fn from_signing_key(key: &SigningKey) -> Self {
+        Self(key.verifying_key().to_bytes())
+    }
+
+    fn verifying_key(&self) -> Option<VerifyingKey> {
+        VerifyingKey::from_bytes(&self.0).ok()
+    }
+}
+
+impl Event {
+    /// The bytes covered by the event's signature.
+    ///
+    /// Events are re-encoded by every server that relays them, possibly with
+    /// different wire codecs, and some event details contain hash maps whose
+    /// iteration order isn't stable between processes. The signature therefore
+    /// covers a canonical form: JSON with object keys sorted, and without the
+    /// signature itself.
+    fn signing_payload(&self) -> Vec<u8> {
+        let mut value = serde_json::to_value(self).expect("Failed to serialise event");
+        if let Some(fields) = value.as_object_mut() {
+            fields.remove("signature");
+        }
+        serde_json::to_vec(&value).expect("Failed to serialise event")
+    }
+
+    /// Sign this event as its origin server
+    pub(crate) fn sign(&mut self, key: &SigningKey) {
+        let signature = key.sign(&self.signing_payload());
+        self.signature = Some(EventSignature(signature.to_bytes()));
+    }
+
+    /// Check that this event carries a valid signature made with the given key
+    pub fn verify(&self, key: &ServerKey) -> bool {
+        let (Some(signature), Some(key)) = (&self.signature, key.verifying_key()) else {
+            return false;
+        };
+
+        key.verify_strict(
+            &self.signing_payload(),
+            &Signature::from_bytes(&signature.0),
+        )
+        .is_ok()
+    }
+}
+
+// Keys and signatures are written as hex strings, which keeps them readable in
+// configuration files and state dumps.
+
+impl std::fmt::Debug for ServerKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ServerKey({})", hex::encode(self.0))
+    }
+}
+
+impl std::fmt::Debug for EventSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EventSignature({})", hex::encode(self.0))
+    }
+}
+
+impl Serialize for ServerKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for ServerKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut bytes = [0; 32];
+        hex::decode_to_slice(String::deserialize(deserializer)?, &mut bytes)
+            .map_err(D::Error::custom)?;
+        Ok(Self(bytes))
+    }
+}
+
+impl Serialize for EventSignature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for EventSignature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut bytes = [0; 64];
+        hex::decode_to_slice(String::deserialize(deserializer)?, &mut bytes)
+            .map_err(D::Error::custom)?;
+        Ok(Self(bytes))
+    }
+}
@@ -40,6 +40,9 @@ pub use errors::*;
 mod network;
 pub use network::*;
 
+mod digest;
+pub use digest::{SectionDiff, StateDigest, StateHash};
+
 pub mod update;
 pub use update::NetworkStateChange;
 pub use update::NetworkUpdateReceiver;
@@ -54,6 +57,8 @@ mod state_utils;
 
 #[cfg(test)]
 pub mod tests {
+    mod audit_log;
+    mod digest;
     mod event_application;
     pub mod fixtures;
     mod serialize;
@@ -107,6 +107,17 @@ impl Network {
             })
     }
 
+    /// Look up the alias user which handles the given command alias (e.g. "NS"), if any
+    pub fn alias_user_for_command(&self, command_alias: &str) -> Option<wrapper::User<'_>> {
+        let alias = self
+            .config
+            .alias_users
+            .iter()
+            .find(|a| a.command_alias.eq_ignore_ascii_case(command_alias))?;
+
+        self.get_alias_users().get(&alias.nick).wrap(self)
+    }
+
     /// Look up the user currently using the given nickname pattern
     pub fn users_by_nick_pattern<'a>(
         &'a self,
@@ -312,11 +323,33 @@ impl Network {
         &self.config
     }
 
+    /// Changes made to the sync network's peer list at runtime, with the server
+    /// on which each was made. A value of `None` means that the peer has been
+    /// removed.
+    pub fn sync_peer_changes(
+        &self,
+    ) -> impl Iterator<Item = (&ServerName, ServerId, &Option<PeerConfig>)> {
+        self.sync_peers
+            .iter()
+            .map(|(name, (origin, peer))| (name, *origin, peer))
+    }
+
     /// Retrieve an audit log entry
     pub fn audit_entry(&self, id: AuditLogEntryId) -> LookupResult<&state::AuditLogEntry> {
         self.audit_log.get(&id).ok_or(NoSuchAuditLogEntry(id))
     }
 
+    /// Retrieve the audit log entries matching a filter, oldest first
+    pub fn audit_entries(&self, filter: &AuditLogFilter) -> Vec<&state::AuditLogEntry> {
+        let mut entries: Vec<_> = self
+            .audit_log
+            .values()
+            .filter(|entry| filter.matches(entry))
+            .collect();
+        entries.sort_by_key(|entry| (entry.timestamp, entry.id));
+        entries
+    }
+
     /// Retrieve an account
     pub fn account(&self, id: AccountId) -> LookupResult<wrapper::Account<'_>> {
         self.accounts.get(&id).ok_or(NoSuchAccount(id)).wrap(self)
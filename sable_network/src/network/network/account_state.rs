@@ -19,6 +19,26 @@ impl Network {
         updates.notify(update::ServicesUpdate {}, event);
     }
 
+    pub(super) fn services_notice(
+        &mut self,
+        target: UserId,
+        event: &Event,
+        update: &ServicesNotice,
+        updates: &dyn NetworkUpdateReceiver,
+    ) {
+        let Some(user) = self.users.get(&target) else {
+            return;
+        };
+
+        let update = update::ServicesNotice {
+            user: self.translate_historic_user_id(user),
+            source_alias: update.source_alias.clone(),
+            text: update.text.clone(),
+        };
+
+        updates.notify(update, event);
+    }
+
     pub(super) fn update_account(
         &mut self,
         target: AccountId,
@@ -54,13 +74,22 @@ impl Network {
         target: ChannelRegistrationId,
         _event: &Event,
         update: &ChannelRegistrationUpdate,
-        _updates: &dyn NetworkUpdateReceiver,
+        updates: &dyn NetworkUpdateReceiver,
     ) {
-        if let Some(data) = &update.data {
-            self.channel_registrations.insert(target, data.clone());
+        let previous = if let Some(data) = &update.data {
+            self.channel_registrations.insert(target, data.clone())
         } else {
             // None here means deletion
-            self.channel_registrations.remove(&target);
+            self.channel_registrations.remove(&target)
+        };
+
+        // If the channel was only being kept alive by its registration's guard setting,
+        // it shouldn't outlive that setting
+        if let Some(previous) = previous.filter(|p| p.guard) {
+            if let Ok(channel) = self.raw_channel_by_name(&previous.channelname) {
+                let channel_id = channel.id;
+                self.remove_channel_if_unused(channel_id, updates);
+            }
         }
     }
 
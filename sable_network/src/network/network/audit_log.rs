@@ -13,4 +13,16 @@ impl Network {
         let update = update::NewAuditLogEntry { entry: target };
         updates.notify(update, event);
     }
+
+    /// Remove audit log entries older than the configured retention period for
+    /// their category
+    pub fn expire_audit_log(&mut self, now: i64) {
+        let retention = &self.config.audit_log_retention;
+
+        self.audit_log
+            .retain(|_, entry| match retention.max_age(entry.category) {
+                Some(max_age) => entry.timestamp >= now - max_age,
+                None => true,
+            });
+    }
 }
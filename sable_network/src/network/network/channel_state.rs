@@ -302,13 +302,7 @@ impl Network {
         updates: &dyn NetworkUpdateReceiver,
     ) {
         if let Some(removed_membership) = self.memberships.remove(&target) {
-            let empty = !self
-                .memberships
-                .iter()
-                .any(|(_, v)| v.channel == removed_membership.channel);
-            if empty {
-                self.remove_channel(removed_membership.channel, updates);
-            }
+            self.remove_channel_if_unused(removed_membership.channel, updates);
 
             if let Some(user) = self.users.get(&target.user()) {
                 let update = update::ChannelKick {
@@ -330,13 +324,7 @@ impl Network {
         updates: &dyn NetworkUpdateReceiver,
     ) {
         if let Some(removed_membership) = self.memberships.remove(&target) {
-            let empty = !self
-                .memberships
-                .iter()
-                .any(|(_, v)| v.channel == removed_membership.channel);
-            if empty {
-                self.remove_channel(removed_membership.channel, updates);
-            }
+            self.remove_channel_if_unused(removed_membership.channel, updates);
 
             if let Some(user) = self.users.get(&target.user()) {
                 let update = update::ChannelPart {
@@ -386,6 +374,28 @@ impl Network {
         }
     }
 
+    /// Determine whether a channel's registration requires it to be kept while empty
+    fn channel_is_guarded(&self, id: ChannelId) -> bool {
+        self.channels
+            .get(&id)
+            .and_then(|chan| self.channel_registration_by_name(chan.name).ok())
+            .is_some_and(|reg| reg.guard())
+    }
+
+    /// Remove a channel if it has no members and its registration doesn't require it
+    /// to be kept
+    pub(super) fn remove_channel_if_unused(
+        &mut self,
+        id: ChannelId,
+        updates: &dyn NetworkUpdateReceiver,
+    ) {
+        let empty = !self.memberships.values().any(|m| m.channel == id);
+
+        if empty && !self.channel_is_guarded(id) {
+            self.remove_channel(id, updates);
+        }
+    }
+
     fn remove_channel(&mut self, id: ChannelId, _updates: &dyn NetworkUpdateReceiver) {
         if let Some(chan) = self.channels.remove(&id) {
             if let Some(topic) = self.channel_topics.values().find(|t| t.channel == chan.id) {
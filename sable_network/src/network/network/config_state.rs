@@ -26,4 +26,26 @@ impl Network {
         self.rebuild_default_role_cache();
         self.rebuild_alias_users();
     }
+
+    pub(super) fn update_sync_peer(
+        &mut self,
+        target: SyncPeerId,
+        event: &Event,
+        details: &details::SyncPeerUpdate,
+        updates: &dyn NetworkUpdateReceiver,
+    ) {
+        let name = *target.server_name();
+        let origin = event.id.server();
+
+        self.sync_peers.insert(name, (origin, details.peer.clone()));
+
+        updates.notify(
+            update::SyncPeerUpdate {
+                name,
+                origin,
+                peer: details.peer.clone(),
+            },
+            event,
+        );
+    }
 }
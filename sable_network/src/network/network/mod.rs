@@ -92,6 +92,13 @@ pub struct Network {
     current_history_server_id: Option<ServerId>,
     config: config::NetworkConfig,
 
+    /// Changes made to the sync network's peer list since the network started,
+    /// overriding the peers defined in the configuration file, and the server
+    /// on which each was made
+    #[serde_as(as = "Vec<(_,_)>")]
+    #[serde(default)]
+    sync_peers: HashMap<ServerName, (ServerId, Option<PeerConfig>)>,
+
     clock: EventClock,
 
     // Cached or constructed data that doesn't need to be serialised
@@ -134,6 +141,8 @@ impl Network {
             current_history_server_id: None,
             config,
 
+            sync_peers: HashMap::new(),
+
             clock: EventClock::new(),
 
             cache_default_channel_roles: OnceLock::new(),
@@ -205,7 +214,9 @@ impl Network {
             NewServer => self.new_server,
             ServerPing => self.server_ping,
             ServerQuit => self.server_quit,
+            StateDigestReport => self.state_digest_report,
             LoadConfig => self.load_config,
+            SyncPeerUpdate => self.update_sync_peer,
             NewAuditLogEntry => self.new_audit_log,
             EnablePersistentSession => self.enable_persistent_session,
             DisablePersistentSession => self.disable_persistent_session,
@@ -218,6 +229,7 @@ impl Network {
             ChannelRoleUpdate => self.update_channel_role,
             UserAway => self.user_away,
             UserLogin => self.user_login,
+            ServicesNotice => self.services_notice,
         })?;
 
         self.clock.update_with_id(event.id);
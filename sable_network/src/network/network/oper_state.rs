@@ -9,11 +9,23 @@ impl Network {
         details: &details::OperUp,
         updates: &dyn NetworkUpdateReceiver,
     ) {
+        // Privileges are fixed when the user opers up, so later changes to the oper's
+        // class only apply the next time they do
+        let class = self
+            .config
+            .opers
+            .iter()
+            .find(|o| o.name == details.oper_name)
+            .and_then(|o| o.class.clone());
+        let privileges = self.config.oper_privileges(&details.oper_name);
+
         if let Some(user) = self.users.get_mut(&target) {
             let new_oper = user.oper_privileges.is_none();
 
             user.oper_privileges = Some(UserPrivileges {
                 oper_name: details.oper_name.clone(),
+                class,
+                privileges,
             });
 
             user.mode.modes |= UserModeFlag::Oper;
@@ -21,6 +21,7 @@ impl Network {
             last_ping: detail.ts,
             flags: detail.flags,
             version: detail.version.clone(),
+            signing_key: detail.signing_key,
         };
 
         self.servers.insert(target, server);
@@ -40,6 +41,17 @@ impl Network {
         }
     }
 
+    pub(super) fn state_digest_report(
+        &mut self,
+        _target: ServerId,
+        _event: &Event,
+        _detail: &details::StateDigestReport,
+        _updates: &dyn NetworkUpdateReceiver,
+    ) {
+        // Reports don't change the state; they're compared against it by each node
+        // before being applied
+    }
+
     pub(super) fn server_quit(
         &mut self,
         target: ServerId,
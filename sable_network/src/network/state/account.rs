@@ -8,6 +8,23 @@ pub struct Account {
     pub name: Nickname,
 
     pub authorised_fingerprints: Vec<String>,
+
+    /// When this account was registered; `None` for accounts which predate this field
+    #[serde(default)]
+    pub registered: Option<i64>,
+    /// When a user was last logged in to this account; `None` if not yet recorded
+    #[serde(default)]
+    pub last_seen: Option<i64>,
+    /// Quit message of the last user to disconnect while logged in to this account
+    #[serde(default)]
+    pub last_quit: Option<String>,
+    /// If set, registered nicks and last-seen information are only shown to the account
+    /// owner and opers
+    #[serde(default)]
+    pub hide_info: bool,
+    /// If set, this account is exempt from inactivity expiry
+    #[serde(default)]
+    pub no_expire: bool,
 }
 
 #[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +38,86 @@ pub struct NickRegistration {
 pub struct ChannelRegistration {
     pub id: ChannelRegistrationId,
     pub channelname: ChannelName,
+
+    /// When a user with access to this channel was last present in it; `None` if not
+    /// yet recorded
+    #[serde(default)]
+    pub last_used: Option<i64>,
+    /// If set, this registration is exempt from inactivity expiry
+    #[serde(default)]
+    pub no_expire: bool,
+
+    /// Modes which services will keep set on the channel
+    #[serde(default)]
+    pub mode_lock_on: ChannelModeSet,
+    /// Modes which services will keep unset on the channel
+    #[serde(default)]
+    pub mode_lock_off: ChannelModeSet,
+    /// If set, services will restore the last topic when the channel is recreated
+    #[serde(default)]
+    pub keep_topic: bool,
+    /// The most recent topic, recorded while `keep_topic` is set
+    #[serde(default)]
+    pub last_topic: Option<String>,
+    /// Notice sent to users joining the channel
+    #[serde(default)]
+    pub entry_message: Option<String>,
+    /// If set, the channel continues to exist when its last member leaves
+    #[serde(default)]
+    pub guard: bool,
+    /// Users who are prevented from joining the channel
+    #[serde(default)]
+    pub auto_kicks: Vec<ChannelAutoKick>,
+}
+
+/// What a channel auto-kick entry matches against
+///
+/// Two targets compare equal if they would match the same users; in particular,
+/// masks are compared case-insensitively, as they are when matched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ChannelAutoKickTarget {
+    /// Users whose `nick!user@host` matches a mask
+    Mask(Pattern),
+    /// Users logged in to the named account
+    Account(Nickname),
+}
+
+/// An entry in a registered channel's auto-kick list
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelAutoKick {
+    pub target: ChannelAutoKickTarget,
+    pub reason: String,
+    /// Name of the account which added the entry
+    pub setter: Nickname,
+    pub timestamp: i64,
+    /// When the entry stops applying, if it isn't permanent
+    pub expires: Option<i64>,
+}
+
+impl ChannelAutoKick {
+    /// Whether this entry has passed its expiry time
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+}
+
+impl PartialEq for ChannelAutoKickTarget {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Mask(a), Self::Mask(b)) => a.eq_ignore_ascii_case(b),
+            (Self::Account(a), Self::Account(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for ChannelAutoKickTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mask(pattern) => f.write_str(pattern.as_str()),
+            Self::Account(account) => write!(f, "${account}"),
+        }
+    }
 }
 
 #[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
@@ -3,11 +3,35 @@ use crate::prelude::*;
 use serde::{Deserialize, Serialize};
 
 /// An audit log category
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum AuditLogCategory {
     General,
     NetworkBan,
     ServerKill,
+    StateConsistency,
+    /// Requests made through the management interface
+    Management,
+}
+
+/// Error returned when parsing an unknown audit log category name
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Unknown audit log category {0}")]
+pub struct InvalidAuditLogCategory(pub String);
+
+impl std::str::FromStr for AuditLogCategory {
+    type Err = InvalidAuditLogCategory;
+
+    /// Parse a category name, ignoring case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "general" => Ok(Self::General),
+            "networkban" => Ok(Self::NetworkBan),
+            "serverkill" => Ok(Self::ServerKill),
+            "stateconsistency" => Ok(Self::StateConsistency),
+            "management" => Ok(Self::Management),
+            _ => Err(InvalidAuditLogCategory(s.to_string())),
+        }
+    }
 }
 
 /// An audit log entry
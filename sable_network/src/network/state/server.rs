@@ -1,4 +1,4 @@
-use crate::{id::*, validated::*};
+use crate::{id::*, network::event::ServerKey, validated::*};
 
 use bitflags::bitflags;
 use serde::{Deserialize, Serialize};
@@ -21,4 +21,6 @@ pub struct Server {
     pub last_ping: i64,
     pub flags: ServerFlags,
     pub version: String,
+    /// The key with which this server signs its events, if it does
+    pub signing_key: Option<ServerKey>,
 }
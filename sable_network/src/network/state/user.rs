@@ -1,7 +1,8 @@
 use crate::prelude::*;
-use std::net::IpAddr;
+use std::{collections::BTreeSet, net::IpAddr};
 
 use serde::{Deserialize, Serialize};
+use strum::{EnumIter, IntoEnumIterator};
 
 /// A nickname binding.
 ///
@@ -77,6 +78,53 @@ pub struct UserMode {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserPrivileges {
     pub oper_name: String,
+    /// The oper class named by the user's oper block, if any
+    #[serde(default)]
+    pub class: Option<String>,
+    /// Opers who predate privileges have all of them
+    #[serde(default = "OperPrivilege::all")]
+    pub privileges: BTreeSet<OperPrivilege>,
+}
+
+/// A privilege granted to opers by their oper class
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    EnumIter,
+    strum::Display,
+)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum OperPrivilege {
+    /// Disconnect users connected to the same server as the oper
+    KillLocal,
+    /// Disconnect users connected to other servers
+    KillRemote,
+    /// Add network bans and K-lines
+    NetworkBan,
+    /// See users' real hosts and IP addresses
+    SeeIp,
+    /// Rename channels without channel access
+    RenameChannel,
+    /// Change channel modes without channel access
+    ChannelOverride,
+    /// Use server administration commands such as `REHASH`
+    Admin,
+}
+
+impl OperPrivilege {
+    /// Every privilege, as granted to opers without an oper class
+    pub fn all() -> BTreeSet<Self> {
+        Self::iter().collect()
+    }
 }
 
 impl NickBinding {
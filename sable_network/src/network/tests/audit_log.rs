@@ -0,0 +1,44 @@
+use super::fixtures::*;
+use crate::prelude::*;
+use state::AuditLogCategory;
+
+#[test]
+fn category_retention_overrides_default() {
+    let mut config = config::NetworkConfig::new();
+    config.audit_log_retention.default = Some(100);
+    config
+        .audit_log_retention
+        .categories
+        .insert(AuditLogCategory::NetworkBan, 1000);
+    let mut builder = NetworkBuilder::with_config(config);
+
+    let old_general = builder.add_audit_entry(AuditLogCategory::General, 500);
+    let recent_general = builder.add_audit_entry(AuditLogCategory::General, 950);
+    let old_ban = builder.add_audit_entry(AuditLogCategory::NetworkBan, 500);
+    let expired_ban = builder.add_audit_entry(AuditLogCategory::NetworkBan, 49);
+
+    builder.net.expire_audit_log(1050);
+
+    assert!(builder.net.audit_entry(old_general).is_err());
+    assert!(builder.net.audit_entry(recent_general).is_ok());
+    assert!(builder.net.audit_entry(old_ban).is_ok());
+    assert!(builder.net.audit_entry(expired_ban).is_err());
+}
+
+#[test]
+fn entries_are_kept_without_retention() {
+    let mut config = config::NetworkConfig::new();
+    config
+        .audit_log_retention
+        .categories
+        .insert(AuditLogCategory::NetworkBan, 10);
+    let mut builder = NetworkBuilder::with_config(config);
+
+    let general = builder.add_audit_entry(AuditLogCategory::General, 0);
+    let ban = builder.add_audit_entry(AuditLogCategory::NetworkBan, 0);
+
+    builder.net.expire_audit_log(1000);
+
+    assert!(builder.net.audit_entry(general).is_ok());
+    assert!(builder.net.audit_entry(ban).is_err());
+}
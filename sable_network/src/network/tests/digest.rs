@@ -0,0 +1,45 @@
+use super::fixtures::*;
+use crate::prelude::*;
+use std::str::FromStr;
+
+#[test]
+fn digest_survives_serialization() {
+    let mut builder = NetworkBuilder::new();
+    builder.add_channel(ChannelName::from_str("#a").unwrap());
+    builder.add_user(Nickname::from_str("a").unwrap());
+
+    let copy: Network =
+        serde_json::from_str(&serde_json::to_string(&builder.net).unwrap()).unwrap();
+
+    assert_eq!(copy.state_digest(), builder.net.state_digest());
+    assert_eq!(
+        StateHash::of(&copy).unwrap(),
+        StateHash::of(&builder.net).unwrap()
+    );
+}
+
+#[test]
+fn digest_localises_differences() {
+    let mut builder = NetworkBuilder::new();
+    builder.add_channel(ChannelName::from_str("#a").unwrap());
+    builder.add_user(Nickname::from_str("a").unwrap());
+    let before = builder.net.clone();
+
+    builder.add_user(Nickname::from_str("b").unwrap());
+
+    let differing = builder
+        .net
+        .state_digest()
+        .differing_sections(&before.state_digest());
+    assert!(differing.contains(&"users".to_string()));
+    assert!(!differing.contains(&"channels".to_string()));
+    assert!(!differing.contains(&"clock".to_string()));
+
+    let diff = SectionDiff::between(
+        &builder.net.object_digests("users").unwrap(),
+        &before.object_digests("users").unwrap(),
+    );
+    assert_eq!(diff.only_local.len(), 1);
+    assert!(diff.only_remote.is_empty());
+    assert!(diff.different.is_empty());
+}
@@ -15,8 +15,12 @@ impl NetworkUpdateReceiver for NopUpdateReceiver {
 
 impl NetworkBuilder {
     pub fn new() -> Self {
+        Self::with_config(config::NetworkConfig::new())
+    }
+
+    pub fn with_config(config: config::NetworkConfig) -> Self {
         Self {
-            net: Network::new(config::NetworkConfig::new()),
+            net: Network::new(config),
             id_gen: ObjectIdGenerator::new(ServerId::new(1)),
         }
     }
@@ -34,6 +38,7 @@ impl NetworkBuilder {
             target: target.into(),
             timestamp: 0,
             details: details.into(),
+            signature: None,
         };
         self.net.apply(&evt, &NopUpdateReceiver).unwrap();
     }
@@ -49,8 +54,13 @@ impl NetworkBuilder {
     }
 
     pub fn add_user(&mut self, nick: Nickname) {
+        self.add_user_with_account(nick, None);
+    }
+
+    pub fn add_user_with_account(&mut self, nick: Nickname, account: Option<AccountId>) -> UserId {
+        let id = self.id_gen.next::<UserId>();
         self.apply(
-            self.id_gen.next::<UserId>(),
+            id,
             details::NewUser {
                 mode: state::UserMode::new(UserModeSet::default()),
                 nickname: nick,
@@ -58,10 +68,69 @@ impl NetworkBuilder {
                 realname: Realname::from_str("user").unwrap(),
                 visible_hostname: Hostname::from_str("host.name").unwrap(),
                 server: ServerId::new(1),
-                account: None,
+                account,
                 initial_connection: None,
             },
         );
+        id
+    }
+
+    pub fn add_account(&mut self, name: Nickname) -> AccountId {
+        let id = self.id_gen.next::<AccountId>();
+        self.apply(
+            id,
+            details::AccountUpdate {
+                data: Some(state::Account {
+                    id,
+                    name,
+                    authorised_fingerprints: Vec::new(),
+                    registered: None,
+                    last_seen: None,
+                    last_quit: None,
+                    hide_info: false,
+                    no_expire: false,
+                }),
+            },
+        );
+        id
+    }
+
+    pub fn add_channel_registration(&mut self, data: state::ChannelRegistration) {
+        self.apply(
+            data.id,
+            details::ChannelRegistrationUpdate { data: Some(data) },
+        );
+    }
+
+    pub fn add_audit_entry(
+        &mut self,
+        category: state::AuditLogCategory,
+        timestamp: i64,
+    ) -> AuditLogEntryId {
+        let id = self.id_gen.next::<AuditLogEntryId>();
+        self.apply(
+            id,
+            details::NewAuditLogEntry {
+                entry: state::AuditLogEntry {
+                    id,
+                    timestamp,
+                    category,
+                    source_id: None,
+                    source_addr: None,
+                    source_str: "<management:test>".to_string(),
+                    action: "test".to_string(),
+                    target_id: None,
+                    target_str: None,
+                    target_duration: None,
+                    reason: None,
+                },
+            },
+        );
+        id
+    }
+
+    pub fn next_id<T: From<Snowflake>>(&self) -> T {
+        self.id_gen.next()
     }
 
     pub fn remove_user(&mut self, id: UserId) {
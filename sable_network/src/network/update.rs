@@ -168,6 +168,14 @@ NetworkStateChange => {
         pub server: state::Server,
     }
 
+    /// A server has been added to, reconfigured in, or removed from the sync network
+    struct SyncPeerUpdate {
+        pub name: ServerName,
+        /// The server on which the change was made
+        pub origin: ServerId,
+        pub peer: Option<PeerConfig>,
+    }
+
     /// An entry has been added to the network audit log
     struct NewAuditLogEntry {
         pub entry: AuditLogEntryId,
@@ -184,6 +192,13 @@ NetworkStateChange => {
     struct ServicesUpdate {
     }
 
+    /// The services node has sent a notice to a user
+    struct ServicesNotice {
+        pub user: HistoricUserId,
+        pub source_alias: String,
+        pub text: String,
+    }
+
     /// The current history node has changed
     struct HistoryServerUpdate {
     }
@@ -41,6 +41,39 @@ impl Account<'_> {
     pub fn fingerprints(&self) -> &Vec<String> {
         &self.data.authorised_fingerprints
     }
+
+    /// When this account was registered, if known
+    pub fn registered(&self) -> Option<i64> {
+        self.data.registered
+    }
+
+    /// When a user was last logged in to this account, if known
+    pub fn last_seen(&self) -> Option<i64> {
+        self.data.last_seen
+    }
+
+    /// The quit message of the last user to disconnect while logged in to this account
+    pub fn last_quit(&self) -> Option<&str> {
+        self.data.last_quit.as_deref()
+    }
+
+    /// Whether the account owner has asked for last-seen information to be hidden
+    pub fn hide_info(&self) -> bool {
+        self.data.hide_info
+    }
+
+    /// The nicknames registered to this account
+    pub fn nick_registrations(&self) -> impl Iterator<Item = wrapper::NickRegistration<'_>> {
+        let my_id = self.data.id;
+        self.network
+            .nick_registrations()
+            .filter(move |n| n.account_id() == my_id)
+    }
+
+    /// Whether this account is exempt from inactivity expiry
+    pub fn no_expire(&self) -> bool {
+        self.data.no_expire
+    }
 }
 
 impl<'a> super::ObjectWrapper<'a> for Account<'a> {
@@ -15,6 +15,51 @@ impl ChannelRegistration<'_> {
         &self.data.channelname
     }
 
+    /// When a user with access was last present in the channel, if known
+    pub fn last_used(&self) -> Option<i64> {
+        self.data.last_used
+    }
+
+    /// Whether this registration is exempt from inactivity expiry
+    pub fn no_expire(&self) -> bool {
+        self.data.no_expire
+    }
+
+    /// Modes locked on by services
+    pub fn mode_lock_on(&self) -> ChannelModeSet {
+        self.data.mode_lock_on
+    }
+
+    /// Modes locked off by services
+    pub fn mode_lock_off(&self) -> ChannelModeSet {
+        self.data.mode_lock_off
+    }
+
+    /// Whether the last topic is restored when the channel is recreated
+    pub fn keep_topic(&self) -> bool {
+        self.data.keep_topic
+    }
+
+    /// The last topic recorded for restoration, if any
+    pub fn last_topic(&self) -> Option<&str> {
+        self.data.last_topic.as_deref()
+    }
+
+    /// The notice sent to users joining the channel, if any
+    pub fn entry_message(&self) -> Option<&str> {
+        self.data.entry_message.as_deref()
+    }
+
+    /// Whether the channel is kept in existence while empty
+    pub fn guard(&self) -> bool {
+        self.data.guard
+    }
+
+    /// The channel's auto-kick list, including any expired entries
+    pub fn auto_kicks(&self) -> impl Iterator<Item = &state::ChannelAutoKick> {
+        self.data.auto_kicks.iter()
+    }
+
     pub fn access_entries(&self) -> impl Iterator<Item = ChannelAccess<'_>> {
         let my_id = self.data.id;
         self.network
@@ -9,6 +9,14 @@ impl NickRegistration<'_> {
     pub fn id(&self) -> NickRegistrationId {
         self.data.id
     }
+
+    pub fn nick(&self) -> Nickname {
+        self.data.nick
+    }
+
+    pub fn account_id(&self) -> AccountId {
+        self.data.account
+    }
 }
 
 impl<'a> super::ObjectWrapper<'a> for NickRegistration<'a> {
@@ -27,6 +27,11 @@ impl Server<'_> {
     pub fn last_ping(&self) -> i64 {
         self.data.last_ping
     }
+
+    /// The key with which this server signs its events, if it does
+    pub fn signing_key(&self) -> Option<ServerKey> {
+        self.data.signing_key
+    }
 }
 
 impl<'a> super::ObjectWrapper<'a> for Server<'a> {
@@ -135,6 +135,14 @@ impl<'a> User<'a> {
         self.data.oper_privileges.as_ref()
     }
 
+    /// Test whether this user is a network operator with the given privilege
+    pub fn has_oper_privilege(&self, privilege: state::OperPrivilege) -> bool {
+        self.data
+            .oper_privileges
+            .as_ref()
+            .is_some_and(|p| p.privileges.contains(&privilege))
+    }
+
     /// Return the user's session key, if any
     pub fn session_key(&self) -> Option<&state::UserSessionKey> {
         self.data.session_key.as_ref()
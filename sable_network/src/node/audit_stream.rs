@@ -0,0 +1,50 @@
+use super::*;
+
+/// The number of new audit log entries held for each stream subscriber. A
+/// subscriber that falls further behind than this misses entries.
+const AUDIT_STREAM_CAPACITY: usize = 1024;
+
+pub(super) fn new_audit_stream() -> broadcast::Sender<state::AuditLogEntry> {
+    broadcast::channel(AUDIT_STREAM_CAPACITY).0
+}
+
+impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
+    /// Subscribe to audit log entries as they are added to the network state
+    pub fn subscribe_audit_log(&self) -> broadcast::Receiver<state::AuditLogEntry> {
+        self.audit_stream.subscribe()
+    }
+
+    /// Subscribe to new audit log entries. If `filter` has a `since` time, also
+    /// return the existing entries matching it, oldest first.
+    ///
+    /// Subscribing before reading the existing entries means none are missed in
+    /// between, but an entry added in that window may be both returned here and
+    /// received from the subscription.
+    pub fn stream_audit_log(
+        &self,
+        filter: &AuditLogFilter,
+    ) -> (
+        Vec<state::AuditLogEntry>,
+        broadcast::Receiver<state::AuditLogEntry>,
+    ) {
+        let receiver = self.subscribe_audit_log();
+        let existing = if filter.since.is_some() {
+            self.network()
+                .audit_entries(filter)
+                .into_iter()
+                .cloned()
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        (existing, receiver)
+    }
+
+    pub(super) fn publish_audit_entry(&self, id: AuditLogEntryId) {
+        if let Ok(entry) = self.network().audit_entry(id) {
+            // An error here just means nobody is subscribed
+            let _ = self.audit_stream.send(entry.clone());
+        }
+    }
+}
@@ -0,0 +1,339 @@
+//! Periodic comparison of network state between servers
+
+use super::*;
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// How often each server reports a digest of its network state
+pub(super) const STATE_DIGEST_INTERVAL: Duration = Duration::from_secs(300);
+
+/// How many earlier digests of our state to keep for comparison with reports
+/// made at older clocks
+const RECENT_DIGESTS: usize = 16;
+
+/// A node's record of state digest comparisons.
+///
+/// Digest reports don't change the state they describe, so a digest holds from
+/// the clock at which it was computed until the next event other than a report.
+/// Any report made at a clock within that period can be compared with it, even
+/// though we've seen other reports since.
+#[derive(Default)]
+pub(super) struct ConsistencyState {
+    /// The digest of our current state, and the event clock at which it was
+    /// computed
+    current: Option<(EventClock, StateDigest)>,
+    /// Digests of our state in earlier periods, with the clocks at which each
+    /// period began and ended
+    recent: VecDeque<(EventClock, EventClock, StateDigest)>,
+    /// Whether an event which may change the state is being applied
+    changing: bool,
+    /// For each server whose state last differed from ours, the sections which
+    /// differed
+    mismatches: HashMap<ServerId, Vec<String>>,
+}
+
+impl ConsistencyState {
+    /// Our digest as it was at `clock`, if it's known. `now` is our current clock.
+    fn digest_at(&self, clock: &EventClock, now: &EventClock) -> Option<&StateDigest> {
+        if let Some((since, digest)) = &self.current {
+            if since <= clock && clock <= now {
+                return Some(digest);
+            }
+        }
+        self.recent
+            .iter()
+            .find(|(since, until, _)| since <= clock && clock <= until)
+            .map(|(_, _, digest)| digest)
+    }
+
+    /// Note that an event which may change the state is about to be applied, at
+    /// the given clock
+    fn begin_change(&mut self, clock: &EventClock) {
+        if let Some((since, digest)) = self.current.take() {
+            if self.recent.len() == RECENT_DIGESTS {
+                self.recent.pop_front();
+            }
+            self.recent.push_back((since, clock.clone(), digest));
+        }
+        self.changing = true;
+    }
+
+    /// Note that the event passed to [`begin_change`](Self::begin_change) has been
+    /// applied. Any digest computed in the meantime may be of the old state.
+    fn end_change(&mut self) {
+        if std::mem::take(&mut self.changing) {
+            self.current = None;
+        }
+    }
+}
+
+/// The differences between this server's state and another's, as returned by the
+/// management interface
+#[derive(Debug, serde::Serialize)]
+pub struct StateDiff {
+    pub local_clock: EventClock,
+    pub remote_clock: EventClock,
+    /// Whether both servers had seen the same events when their states were
+    /// compared. If not, differences may only reflect events in transit.
+    pub same_clock: bool,
+    pub sections: BTreeMap<String, SectionDiff>,
+}
+
+impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
+    /// Our current state digest, and the clock at which it was computed
+    pub fn current_state_digest(&self) -> (EventClock, StateDigest) {
+        // Locked first, so that the state can't change between our reading it and
+        // recording its digest
+        let mut state = self.consistency.lock();
+        let net = self.network();
+
+        let digest = match &state.current {
+            Some((_, digest)) => digest.clone(),
+            None => {
+                let digest = net.state_digest();
+                state.current = Some((net.clock().clone(), digest.clone()));
+                digest
+            }
+        };
+        (net.clock().clone(), digest)
+    }
+
+    /// Our state digest as it was at the given clock, if it's known
+    fn state_digest_at(&self, clock: &EventClock) -> Option<StateDigest> {
+        if self.network().clock() == clock {
+            return Some(self.current_state_digest().1);
+        }
+        let state = self.consistency.lock();
+        state.digest_at(clock, self.network().clock()).cloned()
+    }
+
+    /// Forget our earlier digests, when the state has been replaced
+    pub(super) fn forget_state_digests(&self) {
+        let mut state = self.consistency.lock();
+        state.current = None;
+        state.recent.clear();
+    }
+
+    pub(super) fn report_state_digest(&self) {
+        let (clock, digest) = self.current_state_digest();
+        self.submit_event(self.my_id, details::StateDigestReport { clock, digest });
+    }
+
+    /// Compare another server's state digest report against our own state.
+    ///
+    /// This must be called before each event is applied, and
+    /// [`state_digest_applied`](Self::state_digest_applied) after it. Reports are
+    /// compared with our state as it was at the reporting server's clock. If we
+    /// don't know our digest at that clock the report is ignored, and the next
+    /// one will be checked instead.
+    pub(super) fn check_state_digest(&self, event: &Event) {
+        let EventDetails::StateDigestReport(report) = &event.details else {
+            self.consistency.lock().begin_change(self.network().clock());
+            return;
+        };
+        let ObjectId::Server(origin) = event.target else {
+            return;
+        };
+        if origin == self.my_id {
+            return;
+        }
+
+        let Some(digest) = self.state_digest_at(&report.clock) else {
+            tracing::debug!(?origin, clock = ?report.clock, "No state digest to compare report with");
+            return;
+        };
+        let differing = digest.differing_sections(&report.digest);
+
+        let mut state = self.consistency.lock();
+        if differing.is_empty() {
+            if state.mismatches.remove(&origin).is_some() {
+                tracing::info!(?origin, "Network state is consistent again");
+            }
+            return;
+        }
+        // Don't raise the same alert every time the server reports
+        if state.mismatches.get(&origin) == Some(&differing) {
+            return;
+        }
+        state.mismatches.insert(origin, differing.clone());
+        drop(state);
+
+        let server_name = self
+            .network()
+            .server(origin)
+            .map(|server| server.name().to_string())
+            .unwrap_or_else(|_| format!("{origin:?}"));
+        let reason = format!("Sections differing: {}", differing.join(", "));
+
+        tracing::error!(server = server_name, reason, "Network state mismatch");
+
+        let entry = state::AuditLogEntry {
+            id: self.ids().next(),
+            timestamp: utils::now(),
+            category: state::AuditLogCategory::StateConsistency,
+            source_id: None,
+            source_addr: None,
+            source_str: self.name.to_string(),
+            action: "STATE MISMATCH".to_string(),
+            target_id: None,
+            target_str: Some(server_name),
+            target_duration: None,
+            reason: Some(reason),
+        };
+        self.submit_event(entry.id, details::NewAuditLogEntry { entry });
+    }
+
+    /// Called after each event is applied; see
+    /// [`check_state_digest`](Self::check_state_digest)
+    pub(super) fn state_digest_applied(&self) {
+        self.consistency.lock().end_change();
+    }
+
+    /// Answer another server's request for our state digest, or for the object
+    /// hashes in one section of it
+    pub(super) fn handle_state_digest_request(
+        &self,
+        req: &RemoteServerRequestType,
+    ) -> Option<RemoteServerResponse> {
+        match req {
+            RemoteServerRequestType::StateDigest => {
+                let (clock, digest) = self.current_state_digest();
+                Some(RemoteServerResponse::StateDigest(clock, digest))
+            }
+            RemoteServerRequestType::ObjectDigests(section) => Some(
+                RemoteServerResponse::ObjectDigests(self.network().object_digests(section)),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Find which objects differ between our state and another server's.
+    ///
+    /// Only the sections whose hashes differ are compared object by object.
+    pub async fn diff_state_with(&self, server: ServerName) -> Result<StateDiff, String> {
+        let (local_clock, local_digest) = self.current_state_digest();
+
+        let (remote_clock, remote_digest) = match self
+            .event_log
+            .send_remote_request(server, RemoteServerRequestType::StateDigest)
+            .await
+        {
+            Ok(RemoteServerResponse::StateDigest(clock, digest)) => (clock, digest),
+            other => return Err(format!("Unexpected response from {server}: {other:?}")),
+        };
+
+        let mut sections = BTreeMap::new();
+        for section in local_digest.differing_sections(&remote_digest) {
+            let local = self.network().object_digests(&section).unwrap_or_default();
+            let remote = match self
+                .event_log
+                .send_remote_request(
+                    server,
+                    RemoteServerRequestType::ObjectDigests(section.clone()),
+                )
+                .await
+            {
+                Ok(RemoteServerResponse::ObjectDigests(objects)) => objects.unwrap_or_default(),
+                other => return Err(format!("Unexpected response from {server}: {other:?}")),
+            };
+            sections.insert(section, SectionDiff::between(&local, &remote));
+        }
+
+        Ok(StateDiff {
+            same_clock: local_clock == remote_clock,
+            local_clock,
+            remote_clock,
+            sections,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::SavedUpdateReceiver;
+    use crate::sync::simulator::{SimConfig, Simulation};
+    use std::str::FromStr;
+
+    fn mismatches(sim: &Simulation, node: usize) -> Vec<state::AuditLogEntry> {
+        let filter = AuditLogFilter {
+            category: Some(state::AuditLogCategory::StateConsistency),
+            ..Default::default()
+        };
+        sim.node(node)
+            .network()
+            .audit_entries(&filter)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Add a channel to a node's state behind its back, as a bug in event handling
+    /// might, leaving its event clock as it was
+    fn diverge(node: &NetworkNode) {
+        let mut net = node.net.write();
+        let event = Event {
+            clock: EventClock::new(),
+            id: node.ids().next(),
+            target: node.ids().next::<ChannelId>().into(),
+            timestamp: utils::now(),
+            details: details::NewChannel {
+                mode: state::ChannelMode::new(ChannelModeSet::default()),
+                name: ChannelName::from_str("#diverged").unwrap(),
+            }
+            .into(),
+            signature: None,
+        };
+        let mut changed = (**net).clone();
+        changed.apply(&event, &SavedUpdateReceiver::new()).unwrap();
+
+        let mut json = serde_json::to_value(&changed).unwrap();
+        json["clock"] = serde_json::to_value(net.clock()).unwrap();
+        *net = Arc::new(serde_json::from_value(json).unwrap());
+        drop(net);
+
+        node.forget_state_digests();
+    }
+
+    #[test]
+    fn diverged_state_is_audited() {
+        let mut sim = Simulation::new(2, 1, SimConfig::default());
+        sim.run();
+        sim.assert_converged();
+
+        // Both nodes reported their digests as they started
+        assert!(mismatches(&sim, 0).is_empty());
+        assert!(mismatches(&sim, 1).is_empty());
+
+        diverge(sim.node(1).node());
+
+        // Each node has applied its own report by the time the other's arrives, so
+        // they can only be compared at the reporting node's clock
+        sim.node(0).node().report_state_digest();
+        sim.node(1).node().report_state_digest();
+        sim.run();
+
+        for node in 0..2 {
+            let entries = mismatches(&sim, node);
+            assert_eq!(entries.len(), 2, "{entries:?}");
+
+            for (reporter, other) in [("node1.test", "node2.test"), ("node2.test", "node1.test")] {
+                let entry = entries
+                    .iter()
+                    .find(|entry| entry.source_str == reporter)
+                    .unwrap_or_else(|| panic!("No mismatch raised by {reporter}"));
+                assert_eq!(entry.action, "STATE MISMATCH");
+                assert_eq!(entry.target_str.as_deref(), Some(other));
+                assert_eq!(
+                    entry.reason.as_deref(),
+                    Some("Sections differing: channels")
+                );
+            }
+        }
+
+        // The alert isn't repeated while the same sections differ
+        sim.node(0).node().report_state_digest();
+        sim.run();
+        assert_eq!(mismatches(&sim, 0).len(), 2);
+    }
+}
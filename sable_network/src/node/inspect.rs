@@ -0,0 +1,252 @@
+//! Lookups of individual objects requested through the management interface
+
+use super::moderation::error_response;
+use super::*;
+use crate::network::wrapper::ObjectWrapper;
+
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use strum::IntoEnumIterator;
+
+/// An object returned by an inspection, with the most recent event targeting
+/// it if that is still in the event log
+#[derive(Serialize)]
+struct Inspected<T> {
+    #[serde(flatten)]
+    object: T,
+    last_event: Option<EventId>,
+}
+
+#[derive(Serialize)]
+struct UserDetails {
+    user: Inspected<state::User>,
+    nick_binding: Option<Inspected<state::NickBinding>>,
+    connections: Vec<Inspected<state::UserConnection>>,
+    memberships: Vec<Inspected<state::Membership>>,
+}
+
+#[derive(Serialize)]
+struct ChannelDetails {
+    channel: Inspected<state::Channel>,
+    topic: Option<Inspected<state::ChannelTopic>>,
+    memberships: Vec<Inspected<state::Membership>>,
+    list_modes: Vec<Inspected<state::ListModeEntry>>,
+    registration: Option<Inspected<state::ChannelRegistration>>,
+}
+
+#[derive(Serialize)]
+struct AccountDetails {
+    account: Inspected<state::Account>,
+    nick_registrations: Vec<Inspected<state::NickRegistration>>,
+    channel_accesses: Vec<Inspected<state::ChannelAccess>>,
+    /// Users currently logged in to the account
+    users: Vec<UserId>,
+}
+
+#[derive(Serialize)]
+struct ServerDetails {
+    server: Inspected<state::Server>,
+}
+
+/// The latest events for a set of objects, found in a single pass over the
+/// event log
+struct LastEvents(HashMap<ObjectId, EventId>);
+
+impl LastEvents {
+    fn find(node: &NetworkNode, targets: impl IntoIterator<Item = ObjectId>) -> Self {
+        let targets: HashSet<_> = targets.into_iter().collect();
+        Self(node.event_log().latest_events_for(&targets))
+    }
+
+    fn attach<T>(&self, id: impl Into<ObjectId>, object: T) -> Inspected<T> {
+        Inspected {
+            last_event: self.0.get(&id.into()).copied(),
+            object,
+        }
+    }
+}
+
+fn to_json(details: &impl Serialize) -> String {
+    serde_json::to_string(details).expect("Failed to serialise object details")
+}
+
+impl NetworkNode {
+    pub(super) fn inspect(&self, target: &rpc::InspectTarget) -> String {
+        use rpc::InspectTarget::*;
+        match target {
+            User(name) => self.inspect_user(name),
+            Channel(name) => self.inspect_channel(name),
+            Account(name) => self.inspect_account(name),
+            Server(name) => self.inspect_server(name),
+            Event(id) => self.inspect_event(id),
+        }
+    }
+
+    fn inspect_user(&self, name: &str) -> String {
+        let net = self.network();
+        let found = match name.parse::<u64>() {
+            Ok(id) => net.user(UserId::from(Snowflake::from(id))),
+            Err(_) => match Nickname::convert(name) {
+                Ok(nick) => net.user_by_nick(&nick),
+                Err(e) => return error_response(e),
+            },
+        };
+        let target = match found {
+            Ok(target) => target,
+            Err(e) => return error_response(e),
+        };
+
+        let user = target.raw().clone();
+        let nick_binding = target.nick_binding().ok().map(|b| b.raw().clone());
+        let connections: Vec<_> = target.connections().map(|c| c.raw().clone()).collect();
+        let memberships: Vec<_> = target.channels().map(|m| m.raw().clone()).collect();
+
+        let last = LastEvents::find(
+            self,
+            std::iter::once(user.id.into())
+                .chain(nick_binding.iter().map(|b| NicknameId::new(b.nick).into()))
+                .chain(connections.iter().map(|c| c.id.into()))
+                .chain(memberships.iter().map(|m| m.id.into())),
+        );
+
+        to_json(&UserDetails {
+            user: last.attach(user.id, user),
+            nick_binding: nick_binding.map(|b| last.attach(NicknameId::new(b.nick), b)),
+            connections: connections
+                .into_iter()
+                .map(|c| last.attach(c.id, c))
+                .collect(),
+            memberships: memberships
+                .into_iter()
+                .map(|m| last.attach(m.id, m))
+                .collect(),
+        })
+    }
+
+    fn inspect_channel(&self, name: &str) -> String {
+        let net = self.network();
+        let found = match name.parse::<u64>() {
+            Ok(id) => net.channel(ChannelId::from(Snowflake::from(id))),
+            Err(_) => match ChannelName::convert(name) {
+                Ok(name) => net.channel_by_name(&name),
+                Err(e) => return error_response(e),
+            },
+        };
+        let target = match found {
+            Ok(target) => target,
+            Err(e) => return error_response(e),
+        };
+
+        let channel = target.raw().clone();
+        let topic = target.topic().map(|t| t.raw().clone());
+        let memberships: Vec<_> = target.members().map(|m| m.raw().clone()).collect();
+        let list_modes: Vec<_> = ListModeType::iter()
+            .flat_map(|list_type| {
+                target
+                    .list(list_type)
+                    .entries()
+                    .map(|e| e.raw().clone())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let registration = target.is_registered().map(|r| r.raw().clone());
+
+        let last = LastEvents::find(
+            self,
+            std::iter::once(channel.id.into())
+                .chain(topic.iter().map(|t| t.id.into()))
+                .chain(memberships.iter().map(|m| m.id.into()))
+                .chain(list_modes.iter().map(|e| e.id.into()))
+                .chain(registration.iter().map(|r| r.id.into())),
+        );
+
+        to_json(&ChannelDetails {
+            channel: last.attach(channel.id, channel),
+            topic: topic.map(|t| last.attach(t.id, t)),
+            memberships: memberships
+                .into_iter()
+                .map(|m| last.attach(m.id, m))
+                .collect(),
+            list_modes: list_modes
+                .into_iter()
+                .map(|e| last.attach(e.id, e))
+                .collect(),
+            registration: registration.map(|r| last.attach(r.id, r)),
+        })
+    }
+
+    fn inspect_account(&self, name: &str) -> String {
+        let net = self.network();
+        let found = match name.parse::<u64>() {
+            Ok(id) => net.account(AccountId::from(Snowflake::from(id))),
+            Err(_) => match Nickname::convert(name) {
+                Ok(name) => net.account_by_name(&name),
+                Err(e) => return error_response(e),
+            },
+        };
+        let target = match found {
+            Ok(target) => target,
+            Err(e) => return error_response(e),
+        };
+
+        let account = target.raw().clone();
+        let nick_registrations: Vec<_> = target
+            .nick_registrations()
+            .map(|r| r.raw().clone())
+            .collect();
+        let channel_accesses: Vec<_> = target.channel_accesses().map(|a| a.raw().clone()).collect();
+        let users = target.users().map(|u| u.id()).collect();
+
+        let last = LastEvents::find(
+            self,
+            std::iter::once(account.id.into())
+                .chain(nick_registrations.iter().map(|r| r.id.into()))
+                .chain(channel_accesses.iter().map(|a| a.id.into())),
+        );
+
+        to_json(&AccountDetails {
+            account: last.attach(account.id, account),
+            nick_registrations: nick_registrations
+                .into_iter()
+                .map(|r| last.attach(r.id, r))
+                .collect(),
+            channel_accesses: channel_accesses
+                .into_iter()
+                .map(|a| last.attach(a.id, a))
+                .collect(),
+            users,
+        })
+    }
+
+    fn inspect_server(&self, name: &str) -> String {
+        let net = self.network();
+        let found = match name.parse::<u16>() {
+            Ok(id) => net.server(ServerId::new(id)).ok(),
+            Err(_) => match ServerName::convert(name) {
+                Ok(name) => net.servers().find(|s| s.name() == &name),
+                Err(e) => return error_response(e),
+            },
+        };
+        let Some(target) = found else {
+            return error_response("No such server");
+        };
+
+        let server = target.raw().clone();
+        let last = LastEvents::find(self, [server.id.into()]);
+
+        to_json(&ServerDetails {
+            server: last.attach(server.id, server),
+        })
+    }
+
+    fn inspect_event(&self, id: &str) -> String {
+        let Ok(id) = id.parse::<u64>() else {
+            return error_response("Invalid event ID");
+        };
+
+        match self.event_log().get(&EventId::from(Snowflake::from(id))) {
+            Some(event) => to_json(event),
+            None => error_response("No such event in the log"),
+        }
+    }
+}
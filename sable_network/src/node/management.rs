@@ -1,27 +1,60 @@
 use super::*;
 
+/// Statistics returned by the `/statistics` management endpoint
+#[derive(serde::Serialize)]
+struct ServerStatistics {
+    event_stats: EventLogStats,
+    propagation: PropagationStats,
+}
+
 impl NetworkNode {
     pub async fn handle_management_command(&self, cmd: ServerManagementCommand) {
         use ServerManagementCommandType::*;
-        let resp = match cmd.cmd {
+        let resp = match &cmd.cmd {
             ServerStatistics => self.export_server_statistics(),
             DumpNetwork => self.dump_network_state(),
             DumpEvents => self.dump_events(),
+            AddPeer(peer) => {
+                self.add_sync_peer(peer.clone());
+                String::new()
+            }
+            RemovePeer(name) => {
+                self.remove_sync_peer(*name);
+                String::new()
+            }
+            StateDiff(server) => match self.diff_state_with(*server).await {
+                Ok(diff) => serde_json::to_string(&diff).expect("Failed to serialise state diff"),
+                Err(error) => serde_json::json!({ "error": error }).to_string(),
+            },
+            ListBans => self.list_network_bans(),
+            AddBan(request) => self.add_network_ban(&cmd.user, request),
+            RemoveBan(id) => self.remove_network_ban(&cmd.user, *id),
+            KillUser(request) => self.kill_user(&cmd.user, request),
+            FindUsers(request) => self.find_users(&cmd.user, request),
+            QueryAuditLog(query) => self.query_audit_log(query),
+            Inspect(target) => self.inspect(target),
         };
         tracing::debug!(?cmd.cmd, ?resp, "Handled management command");
         let _ = cmd.response.send(resp);
     }
 
     fn export_server_statistics(&self) -> String {
-        unimplemented!("server stats");
-        /*
-                let stats = ServerStatistics {
-                    connected_clients: self.connections.len(),
-                    event_stats: self.server.event_log().get_stats(),
-                };
-
-                serde_json::to_string(&stats).expect("Failed to serialise statistics")
-        */
+        let stats = ServerStatistics {
+            event_stats: self.event_log().get_stats(),
+            propagation: self.event_log.propagation_stats(),
+        };
+
+        serde_json::to_string(&stats).expect("Failed to serialise statistics")
+    }
+
+    fn query_audit_log(&self, query: &AuditLogQuery) -> String {
+        let net = self.network();
+        let entries = net.audit_entries(&query.filter);
+        let skip = query
+            .limit
+            .map_or(0, |limit| entries.len().saturating_sub(limit));
+
+        serde_json::to_string(&entries[skip..]).expect("Failed to serialise audit log")
     }
 }
 
@@ -0,0 +1,97 @@
+//! Export of network and synchronisation metrics
+
+use super::*;
+use crate::metrics::{MetricType, MetricsWriter};
+
+impl NetworkNode {
+    /// Write metrics describing the network state, event log and sync network to `out`
+    pub fn export_metrics(&self, out: &mut MetricsWriter) {
+        let net = self.network();
+        out.single(
+            "sable_network_users",
+            MetricType::Gauge,
+            "Users connected to the network",
+            net.users().count() as f64,
+        );
+        out.single(
+            "sable_network_accounts",
+            MetricType::Gauge,
+            "Registered accounts",
+            net.accounts().count() as f64,
+        );
+        out.single(
+            "sable_network_channels",
+            MetricType::Gauge,
+            "Channels on the network",
+            net.channels().count() as f64,
+        );
+
+        let log_stats = self.event_log().get_stats();
+        out.single(
+            "sable_event_log_events",
+            MetricType::Gauge,
+            "Events held in the event log",
+            log_stats.stored_events as f64,
+        );
+        out.single(
+            "sable_event_log_pending_events",
+            MetricType::Gauge,
+            "Events waiting for their dependencies to arrive",
+            log_stats.pending_events as f64,
+        );
+
+        let propagation = self.event_log.propagation_stats();
+        out.single(
+            "sable_sync_fanout",
+            MetricType::Gauge,
+            "Peers to which each new event is sent",
+            propagation.fanout as f64,
+        );
+
+        let peer_metrics: [(&str, MetricType, &str, fn(&PeerPropagationStats) -> f64); 6] = [
+            (
+                "sable_sync_events_sent_total",
+                MetricType::Counter,
+                "New events sent to each peer",
+                |s| s.events_sent as f64,
+            ),
+            (
+                "sable_sync_send_failures_total",
+                MetricType::Counter,
+                "New events which couldn't be delivered to each peer",
+                |s| s.send_failures as f64,
+            ),
+            (
+                "sable_sync_events_received_total",
+                MetricType::Counter,
+                "New events received from each peer",
+                |s| s.events_received as f64,
+            ),
+            (
+                "sable_sync_duplicate_events_total",
+                MetricType::Counter,
+                "New events received from each peer which had already arrived from another",
+                |s| s.duplicates_received as f64,
+            ),
+            (
+                "sable_sync_send_latency_seconds",
+                MetricType::Gauge,
+                "Moving average of the time taken for each peer to accept a new event",
+                |s| s.latency_ms.map_or(f64::NAN, |ms| ms / 1000.0),
+            ),
+            (
+                "sable_sync_send_queue",
+                MetricType::Gauge,
+                "Messages waiting to be written to each peer's connection",
+                |s| s.send_queue as f64,
+            ),
+        ];
+
+        for (name, kind, help, value) in peer_metrics {
+            let mut family = out.family(name, kind, help);
+            for (peer, stats) in &propagation.peers {
+                family.sample(&[("peer", &peer.to_string())], value(stats));
+            }
+        }
+    }
+}
@@ -27,10 +27,25 @@ use parking_lot::RwLock;
 mod pings;
 mod update_receiver;
 
+mod quorum;
+pub use quorum::QuorumStatus;
+
+mod sync_peers;
+
+mod storage;
+pub use storage::{DurableStorage, RecoveredState, StorageConfig};
+
+mod consistency;
+pub use consistency::StateDiff;
+
 mod upgrade;
 pub use upgrade::NetworkNodeState;
 
+mod audit_stream;
+mod inspect;
 mod management;
+mod metrics;
+mod moderation;
 
 /// A network server.
 pub struct NetworkNode<Policy = crate::policy::StandardPolicyService>
@@ -51,6 +66,10 @@ where
     subscriber: UnboundedSender<NetworkHistoryUpdate>,
     remote_server_commands: Option<UnboundedSender<RemoteServerRequest>>,
     policy_service: Policy,
+    quorum: tokio::sync::watch::Sender<QuorumStatus>,
+    storage: Option<DurableStorage>,
+    consistency: parking_lot::Mutex<consistency::ConsistencyState>,
+    audit_stream: broadcast::Sender<state::AuditLogEntry>,
 }
 
 impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
@@ -70,6 +89,7 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
     ///   Should be shared with the `ReplicatedEventLog`.
     /// - `subscriber`: channel to send out network state changes for consumption
     /// - `policy_service`: a policy service
+    /// - `storage`: if present, where to keep a durable copy of the network state
     ///
     pub fn new(
         id: ServerId,
@@ -81,11 +101,16 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
         subscriber: UnboundedSender<NetworkHistoryUpdate>,
         remote_server_commands: Option<UnboundedSender<RemoteServerRequest>>,
         policy_service: Policy,
+        storage: Option<DurableStorage>,
     ) -> Self {
         if cfg!(feature = "debug") && !net.config().debug_mode {
             panic!("Server is built with debug code but network has debug disabled")
         }
 
+        Self::apply_sync_peer_changes(&event_log, id, &net);
+
+        let (quorum, _) = tokio::sync::watch::channel(Self::initial_quorum_status(&event_log));
+
         Self {
             my_id: id,
             name,
@@ -100,6 +125,10 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
             subscriber,
             remote_server_commands,
             policy_service,
+            quorum,
+            storage,
+            consistency: Default::default(),
+            audit_stream: audit_stream::new_audit_stream(),
         }
     }
 
@@ -124,6 +153,11 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
         self.event_log.create_event_and(id, detail, f).await
     }
 
+    /// Replace the network-wide configuration on every node in the network
+    pub fn load_network_config(&self, config: crate::network::config::NetworkConfig) {
+        self.submit_event(ConfigId::new(0), details::LoadConfig { config });
+    }
+
     /// Retrieve the [`ObjectIdGenerator`] used to generate object identifiers
     pub fn ids(&self) -> &ObjectIdGenerator {
         &self.id_generator
@@ -236,6 +270,7 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
         let min_ts = now - max_age;
 
         network.expire_objects(min_ts);
+        network.expire_audit_log(now);
         self.history_log.write().expire_entries(min_ts);
     }
 
@@ -247,9 +282,20 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
         // the write lock on `net`. The handlers for various network updates require read access to `net`.
         let mut update_queue = crate::network::SavedUpdateReceiver::new();
 
+        self.check_state_digest(&event);
+
+        // Logged before it's applied, so that a crash in between can't leave the
+        // state on disk behind what we've acted on
+        if let Some(storage) = &self.storage {
+            if let Err(e) = storage.append(&event) {
+                tracing::error!("Couldn't write event to disk: {}", e);
+            }
+        }
+
         Arc::make_mut(&mut *self.net.write())
             .apply(&event, &update_queue)
             .unwrap_or_else(|_| panic!("Event {event:?} failed to apply"));
+        self.state_digest_applied();
 
         update_queue.playback(self);
     }
@@ -267,11 +313,15 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
                 ts: crate::utils::now(),
                 flags: self.server_flags(),
                 version: self.version().to_string(),
+                signing_key: self.event_log.public_key(),
             },
         );
 
         let mut check_ping_timer = time::interval(Duration::from_secs(60));
         let mut expire_objects_timer = time::interval(Duration::from_secs(60));
+        let mut check_quorum_timer = time::interval(Duration::from_secs(10));
+        let mut snapshot_timer = time::interval(Duration::from_secs(10));
+        let mut state_digest_timer = time::interval(consistency::STATE_DIGEST_INTERVAL);
 
         let mut rpc_receiver = self.rpc_receiver.lock().await;
 
@@ -290,9 +340,13 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
                         Some(NetworkMessage::ImportNetworkState(new_net)) =>
                         {
                             tracing::debug!("Server got state import");
+                            Self::apply_sync_peer_changes(&self.event_log, self.my_id, &new_net);
                             // Using replace() here because it works on a mut borrow of the destination;
                             // we can't assign directly to something held by RwLock
                             let _ = std::mem::replace(&mut *self.net.write(), Arc::new(*new_net));
+                            self.forget_state_digests();
+                            // Logged events from before the import no longer apply
+                            self.write_snapshot();
                         },
                         Some(NetworkMessage::ExportNetworkState(channel)) =>
                         {
@@ -304,7 +358,12 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
                         },
                         Some(NetworkMessage::RemoteServerRequest(request)) =>
                         {
-                            if let Some(remote_server_commands) = self.remote_server_commands.as_ref()
+                            // State digests are answered by the node itself, whatever the server type
+                            if let Some(response) = self.handle_state_digest_request(&request.req)
+                            {
+                                let _ = request.response.send(response);
+                            }
+                            else if let Some(remote_server_commands) = self.remote_server_commands.as_ref()
                             {
                                 if let Err(_e) = remote_server_commands.send(request)
                                 {
@@ -327,6 +386,21 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
                     tracing::trace!("...from expire_objects_timer");
                     self.expire_old_entries();
                 }
+                _ = check_quorum_timer.tick() =>
+                {
+                    tracing::trace!("...from check_quorum_timer");
+                    self.check_quorum();
+                }
+                _ = snapshot_timer.tick() =>
+                {
+                    tracing::trace!("...from snapshot_timer");
+                    self.check_snapshot();
+                }
+                _ = state_digest_timer.tick() =>
+                {
+                    tracing::trace!("...from state_digest_timer");
+                    self.report_state_digest();
+                }
                 shutdown = shutdown_channel.recv() =>
                 {
                     match shutdown
@@ -355,6 +429,8 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
             }
         };
 
+        self.write_snapshot();
+
         let net = self.net.read();
         let me = net
             .server(self.my_id)
@@ -0,0 +1,384 @@
+//! Network bans and kills requested through the management interface
+
+use super::*;
+use crate::network::ban::*;
+use crate::network::wrapper::ObjectWrapper;
+
+use serde::Serialize;
+use serde_json::json;
+
+/// A user returned by a management user search
+#[derive(Serialize)]
+struct FoundUser {
+    id: UserId,
+    nick: Nickname,
+    user: Username,
+    visible_host: Hostname,
+    realname: Realname,
+    account: Option<Nickname>,
+    connections: Vec<state::UserConnection>,
+}
+
+pub(super) fn error_response(error: impl std::fmt::Display) -> String {
+    json!({ "error": error.to_string() }).to_string()
+}
+
+impl NetworkNode {
+    pub(super) fn list_network_bans(&self) -> String {
+        let net = self.network();
+        serde_json::to_string(net.network_bans()).expect("Failed to serialise network bans")
+    }
+
+    pub(super) fn add_network_ban(&self, user: &str, request: &rpc::NewBanRequest) -> String {
+        if request.duration <= 0 {
+            return error_response("Ban duration must be positive");
+        }
+        let timestamp = utils::now();
+        let Some(expires) = request
+            .duration
+            .checked_mul(60)
+            .and_then(|seconds| timestamp.checked_add(seconds))
+        else {
+            return error_response("Ban duration is too long");
+        };
+
+        let match_type = request.match_type.unwrap_or(BanMatchType::PreRegistration);
+        let apply_existing = request.apply_existing.unwrap_or(true);
+
+        let action = match (match_type, request.action) {
+            (BanMatchType::PreSasl, _) => NetworkBanAction::DenySasl,
+            (_, Some(rpc::NewBanAction::RequireSasl)) => {
+                NetworkBanAction::RequireSasl(apply_existing)
+            }
+            (_, Some(rpc::NewBanAction::RefuseConnection) | None) => {
+                NetworkBanAction::RefuseConnection(apply_existing)
+            }
+        };
+
+        let pattern = match match_type {
+            BanMatchType::PreRegistration => {
+                chert::parse::<PreRegistrationBanSettings>(&request.pattern)
+                    .map(|ast| ast.into_root())
+            }
+            BanMatchType::NewConnection => {
+                chert::parse::<NewConnectionBanSettings>(&request.pattern)
+                    .map(|ast| ast.into_root())
+            }
+            BanMatchType::PreSasl => {
+                chert::parse::<PreSaslBanSettings>(&request.pattern).map(|ast| ast.into_root())
+            }
+        };
+        let pattern = match pattern {
+            Ok(pattern) => pattern,
+            Err(e) => return error_response(format_args!("Invalid ban pattern: {e:?}")),
+        };
+
+        AuditLoggerEntry::management(self, AuditLogCategory::NetworkBan, user, "BAN")
+            .target_str(request.pattern.clone())
+            .target_duration(request.duration)
+            .reason(request.reason.clone())
+            .log();
+
+        let id: NetworkBanId = self.ids().next();
+
+        self.submit_event(
+            id,
+            details::NewNetworkBan {
+                match_type,
+                pattern,
+                action,
+                timestamp,
+                expires,
+                reason: request.reason.clone(),
+                oper_reason: request.oper_reason.clone(),
+                setter_info: format!("<management:{user}>"),
+            },
+        );
+
+        json!({ "id": id }).to_string()
+    }
+
+    pub(super) fn remove_network_ban(&self, user: &str, id: NetworkBanId) -> String {
+        let Some(ban) = self.network().network_bans().get(&id).cloned() else {
+            return error_response("No such network ban");
+        };
+
+        AuditLoggerEntry::management(self, AuditLogCategory::NetworkBan, user, "UNBAN")
+            .target_str(ban.id.as_u64().to_string())
+            .reason(ban.reason)
+            .log();
+
+        self.submit_event(id, details::RemoveNetworkBan { remover: None });
+
+        json!({}).to_string()
+    }
+
+    pub(super) fn kill_user(&self, user: &str, request: &rpc::KillRequest) -> String {
+        let target = {
+            let net = self.network();
+            let found = match request.user.parse::<u64>() {
+                Ok(id) => net.user(UserId::from(Snowflake::from(id))),
+                Err(_) => match Nickname::convert(request.user.as_str()) {
+                    Ok(nick) => net.user_by_nick(&nick),
+                    Err(e) => return error_response(e),
+                },
+            };
+            match found {
+                Ok(target) => target.id(),
+                Err(e) => return error_response(e),
+            }
+        };
+
+        AuditLoggerEntry::management(self, AuditLogCategory::ServerKill, user, "KILL")
+            .target_user(target)
+            .reason(request.reason.clone())
+            .log();
+
+        self.submit_event(
+            target,
+            details::UserQuit {
+                message: format!("Killed by {} ({})", user, request.reason),
+            },
+        );
+
+        json!({ "id": target }).to_string()
+    }
+
+    pub(super) fn find_users(&self, user: &str, request: &rpc::UserSearchRequest) -> String {
+        let pattern = match chert::parse::<PreRegistrationBanSettings>(&request.pattern) {
+            Ok(ast) => ast.into_root(),
+            Err(e) => return error_response(format_args!("Invalid pattern: {e:?}")),
+        };
+        let engine: chert::Engine<PreRegistrationBanSettings, ()> =
+            chert::compile::compile_unsafe(std::iter::once(((), &pattern)));
+
+        AuditLoggerEntry::management(self, AuditLogCategory::General, user, "USER SEARCH")
+            .target_str(request.pattern.clone())
+            .log();
+
+        let net = self.network();
+        let mut found = Vec::new();
+
+        for target in net.users() {
+            // Users have no connections of their own if they are pseudo-clients
+            // provided by services or configured as aliases
+            let connections: Vec<_> = target.connections().collect();
+
+            let matches = connections.iter().any(|conn| {
+                let details = PreRegistrationBanSettings {
+                    nick: target.nick(),
+                    user: target.user().clone(),
+                    host: conn.hostname().clone(),
+                    realname: target.realname().clone(),
+                    ip: *conn.ip(),
+                    // These aren't kept once the user has registered
+                    user_param_1: String::new(),
+                    user_param_2: String::new(),
+                    tls: target.mode().has_mode(UserModeFlag::TlsConnection),
+                };
+                engine.eval(&details).into_iter().next().is_some()
+            });
+
+            if matches {
+                found.push(FoundUser {
+                    id: target.id(),
+                    nick: target.nick(),
+                    user: target.user().clone(),
+                    visible_host: target.visible_host().clone(),
+                    realname: target.realname().clone(),
+                    account: target.account_name(),
+                    connections: connections.iter().map(|conn| conn.raw().clone()).collect(),
+                });
+            }
+        }
+
+        serde_json::to_string(&found).expect("Failed to serialise users")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::simulator::{SimConfig, Simulation};
+    use serde_json::Value;
+    use std::str::FromStr;
+
+    fn new_simulation() -> Simulation {
+        let mut sim = Simulation::new(1, 1, SimConfig::default());
+        sim.run();
+        sim
+    }
+
+    fn node(sim: &Simulation) -> &NetworkNode {
+        sim.node(0).node()
+    }
+
+    fn audit_entries(sim: &Simulation, category: AuditLogCategory) -> Vec<state::AuditLogEntry> {
+        let filter = AuditLogFilter {
+            category: Some(category),
+            ..Default::default()
+        };
+        sim.node(0)
+            .network()
+            .audit_entries(&filter)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Connect a user to the node, as its client server would
+    fn add_user(sim: &mut Simulation, nick: &str, ip: &str) -> UserId {
+        let id: UserId = sim.node(0).ids().next();
+        let connection: UserConnectionId = sim.node(0).ids().next();
+        let server = sim.node(0).id();
+
+        sim.create_event(
+            0,
+            id,
+            details::NewUser {
+                nickname: Nickname::from_str(nick).unwrap(),
+                username: Username::from_str("user").unwrap(),
+                visible_hostname: Hostname::from_str("visible.host").unwrap(),
+                realname: Realname::from_str("A user").unwrap(),
+                mode: state::UserMode::new(UserModeSet::default()),
+                server,
+                account: None,
+                initial_connection: Some((
+                    connection,
+                    details::NewUserConnection {
+                        user: id,
+                        hostname: Hostname::from_str("real.host").unwrap(),
+                        ip: ip.parse().unwrap(),
+                        connection_time: utils::now(),
+                    },
+                )),
+            },
+        );
+        sim.run();
+        id
+    }
+
+    fn ban_request(duration: i64) -> rpc::NewBanRequest {
+        serde_json::from_value(json!({
+            "pattern": "ip == 192.0.2.1",
+            "duration": duration,
+            "reason": "spam",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn network_ban_is_added_and_audited() {
+        let mut sim = new_simulation();
+
+        let response: Value =
+            serde_json::from_str(&node(&sim).add_network_ban("admin", &ban_request(10))).unwrap();
+        let id: NetworkBanId = serde_json::from_value(response["id"].clone()).unwrap();
+        sim.run();
+
+        let net = sim.node(0).network();
+        let ban = net.network_bans().get(&id).expect("Ban wasn't added");
+        assert!(matches!(ban.match_type, BanMatchType::PreRegistration));
+        assert!(matches!(
+            ban.action,
+            NetworkBanAction::RefuseConnection(true)
+        ));
+        assert_eq!(ban.expires - ban.timestamp, 600);
+        assert_eq!(ban.reason, "spam");
+        assert_eq!(ban.setter_info, "<management:admin>");
+
+        let entries = audit_entries(&sim, AuditLogCategory::NetworkBan);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].action, "BAN");
+        assert_eq!(entries[0].source_str, "<management:admin>");
+        assert_eq!(entries[0].target_str.as_deref(), Some("ip == 192.0.2.1"));
+        assert_eq!(entries[0].target_duration, Some(10));
+        assert_eq!(entries[0].reason.as_deref(), Some("spam"));
+    }
+
+    #[test]
+    fn invalid_ban_durations_are_rejected() {
+        let mut sim = new_simulation();
+        let bans_before = node(&sim).list_network_bans();
+
+        for duration in [0, -5, i64::MAX] {
+            let response: Value =
+                serde_json::from_str(&node(&sim).add_network_ban("admin", &ban_request(duration)))
+                    .unwrap();
+            assert!(response["error"].is_string(), "{duration}: {response}");
+        }
+        sim.run();
+
+        assert_eq!(node(&sim).list_network_bans(), bans_before);
+        assert!(audit_entries(&sim, AuditLogCategory::NetworkBan).is_empty());
+    }
+
+    #[test]
+    fn kill_user_quits_and_audits() {
+        let mut sim = new_simulation();
+        let id = add_user(&mut sim, "victim", "192.0.2.1");
+
+        let request = rpc::KillRequest {
+            user: "victim".to_string(),
+            reason: "bye".to_string(),
+        };
+        let response: Value =
+            serde_json::from_str(&node(&sim).kill_user("admin", &request)).unwrap();
+        assert_eq!(response["id"], serde_json::to_value(id).unwrap());
+        sim.run();
+
+        assert!(sim.node(0).network().user(id).is_err());
+
+        let entries = audit_entries(&sim, AuditLogCategory::ServerKill);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].action, "KILL");
+        assert_eq!(entries[0].source_str, "<management:admin>");
+        assert_eq!(entries[0].target_id, Some(id));
+        assert_eq!(
+            entries[0].target_str.as_deref(),
+            Some("victim!user@visible.host[]")
+        );
+        assert_eq!(entries[0].reason.as_deref(), Some("bye"));
+    }
+
+    #[test]
+    fn kill_unknown_user_is_rejected() {
+        let mut sim = new_simulation();
+
+        let request = rpc::KillRequest {
+            user: "nobody".to_string(),
+            reason: "bye".to_string(),
+        };
+        let response: Value =
+            serde_json::from_str(&node(&sim).kill_user("admin", &request)).unwrap();
+        assert!(response["error"].is_string());
+        sim.run();
+
+        assert!(audit_entries(&sim, AuditLogCategory::ServerKill).is_empty());
+    }
+
+    #[test]
+    fn find_users_matches_connections() {
+        let mut sim = new_simulation();
+        let found_id = add_user(&mut sim, "found", "192.0.2.1");
+        add_user(&mut sim, "other", "198.51.100.1");
+
+        let request = rpc::UserSearchRequest {
+            pattern: "ip == 192.0.2.1".to_string(),
+        };
+        let response: Value =
+            serde_json::from_str(&node(&sim).find_users("admin", &request)).unwrap();
+        sim.run();
+
+        let found = response.as_array().unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0]["id"], serde_json::to_value(found_id).unwrap());
+        assert_eq!(found[0]["nick"], "found");
+        assert_eq!(found[0]["connections"][0]["hostname"], "real.host");
+
+        let entries = audit_entries(&sim, AuditLogCategory::General);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].action, "USER SEARCH");
+        assert_eq!(entries[0].target_str.as_deref(), Some("ip == 192.0.2.1"));
+    }
+}
@@ -0,0 +1,240 @@
+use super::*;
+
+/// This server's view of whether it can see enough of the network for the changes it
+/// makes to be kept.
+///
+/// If a netsplit leaves this server on the minority side, the majority will eventually
+/// ping it out and reject its events, so anything done here in the meantime will be
+/// lost. Tracking that lets us warn users and refuse changes that would be discarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct QuorumStatus {
+    /// Configured servers, including this one, that we've heard from recently
+    pub reachable: usize,
+    /// Total number of configured servers in the sync network
+    pub total: usize,
+    /// Whether this server has lost sight of the majority of the network
+    pub degraded: bool,
+    /// Whether this server should restart to rejoin the network
+    pub restart_requested: bool,
+}
+
+impl QuorumStatus {
+    fn quorate(total: usize) -> Self {
+        Self {
+            reachable: total,
+            total,
+            degraded: false,
+            restart_requested: false,
+        }
+    }
+
+    /// The number of reachable servers required to avoid degraded mode
+    pub fn required(&self) -> usize {
+        self.total / 2 + 1
+    }
+}
+
+impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
+    pub(super) fn initial_quorum_status(event_log: &ReplicatedEventLog) -> QuorumStatus {
+        QuorumStatus::quorate(event_log.configured_servers().len())
+    }
+
+    /// The current quorum status of this server
+    pub fn quorum_status(&self) -> QuorumStatus {
+        *self.quorum.borrow()
+    }
+
+    /// Whether this server is currently cut off from the majority of the network
+    pub fn is_degraded(&self) -> bool {
+        self.quorum.borrow().degraded
+    }
+
+    /// Subscribe to changes in this server's quorum status
+    pub fn subscribe_quorum(&self) -> tokio::sync::watch::Receiver<QuorumStatus> {
+        self.quorum.subscribe()
+    }
+
+    pub(super) fn check_quorum(&self) {
+        let config = self.event_log.quorum_config();
+        if !config.enabled {
+            return;
+        }
+
+        let now = utils::now();
+        let net = self.network();
+
+        let mut total = 0;
+        let mut reachable = 0;
+
+        for name in self.event_log.configured_servers() {
+            total += 1;
+
+            if name == self.name {
+                reachable += 1;
+                continue;
+            }
+
+            // Pings are relayed through the rest of the network, so this also counts
+            // peers that we can only reach indirectly
+            if net
+                .servers()
+                .any(|s| s.name() == &name && now - s.last_ping() <= config.peer_timeout)
+            {
+                reachable += 1;
+            }
+        }
+
+        let previous = self.quorum_status();
+        let mut status = QuorumStatus {
+            reachable,
+            total,
+            degraded: false,
+            restart_requested: previous.restart_requested,
+        };
+        status.degraded = reachable < status.required();
+
+        // Once any peer has rejected our events, the rest of the network has seen us
+        // quit and we can only rejoin with a new epoch
+        if status.degraded && config.auto_restart && !status.restart_requested {
+            let rejected_by = self.event_log.rejected_by();
+            if !rejected_by.is_empty() {
+                tracing::warn!(
+                    ?rejected_by,
+                    "Network has moved on without us; restarting to rejoin"
+                );
+                status.restart_requested = true;
+            }
+        }
+
+        if status.degraded != previous.degraded {
+            if status.degraded {
+                tracing::warn!(
+                    reachable,
+                    total,
+                    "Lost sight of the majority of the network; entering degraded mode"
+                );
+            } else {
+                tracing::info!(
+                    reachable,
+                    total,
+                    "Majority of the network reachable again; leaving degraded mode"
+                );
+            }
+        }
+
+        self.quorum.send_if_modified(|current| {
+            let changed = *current != status;
+            *current = status;
+            changed
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::simulator::{SimConfig, Simulation};
+    use crate::sync::QuorumConfig;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    fn new_simulation(auto_restart: bool) -> Simulation {
+        let config = SimConfig {
+            quorum: QuorumConfig {
+                enabled: true,
+                auto_restart,
+                ..QuorumConfig::default()
+            },
+            ..SimConfig::default()
+        };
+        let mut sim = Simulation::new(3, 6, config);
+        sim.run();
+        sim
+    }
+
+    #[test]
+    fn majority_is_more_than_half_of_configured_servers() {
+        for (total, required) in [(1, 1), (2, 2), (3, 2), (4, 3), (5, 3), (6, 4)] {
+            let status = QuorumStatus::quorate(total);
+            assert_eq!(status.required(), required, "for {total} servers");
+            assert!(!status.degraded);
+        }
+    }
+
+    #[test]
+    fn isolated_server_enters_and_leaves_degraded_mode() {
+        let mut sim = new_simulation(false);
+        for node in sim.nodes() {
+            assert!(!node.node().is_degraded());
+        }
+
+        sim.partition(&[0]);
+        // Longer than the quorum timeout, but not long enough to be pinged out
+        sim.run_for(Duration::from_secs(200));
+
+        let isolated = sim.node(0).node().quorum_status();
+        assert_eq!(isolated.reachable, 1);
+        assert_eq!(isolated.total, 3);
+        assert!(isolated.degraded);
+        assert!(!isolated.restart_requested);
+        for majority in 1..3 {
+            let status = sim.node(majority).node().quorum_status();
+            assert_eq!(status.reachable, 2);
+            assert!(!status.degraded, "node {majority} is degraded");
+        }
+
+        sim.heal();
+        sim.sync_all();
+        // Long enough for another quorum check
+        sim.run_for(Duration::from_secs(20));
+
+        for node in sim.nodes() {
+            let status = node.node().quorum_status();
+            assert_eq!(status.reachable, 3);
+            assert!(!status.degraded);
+        }
+    }
+
+    #[test]
+    fn rejected_degraded_server_requests_restart() {
+        let mut sim = new_simulation(true);
+
+        sim.partition(&[0]);
+        sim.run_for(Duration::from_secs(200));
+        assert!(sim.node(0).node().is_degraded());
+        assert!(!sim.node(0).node().quorum_status().restart_requested);
+
+        // As the majority would once it pings the isolated server out
+        let isolated = sim.node(0).node();
+        let (name, id, epoch) = (*isolated.name(), isolated.id(), isolated.epoch());
+        for majority in 1..3 {
+            sim.node(majority)
+                .node()
+                .sync_log()
+                .disable_server(name, id, epoch);
+        }
+
+        sim.heal();
+        let channel = sim.node(0).ids().next();
+        sim.create_event(
+            0,
+            channel,
+            details::NewChannel {
+                mode: state::ChannelMode::new(ChannelModeSet::default()),
+                name: ChannelName::from_str("#isolated").unwrap(),
+            },
+        );
+        sim.run();
+        assert!(!sim.node(0).node().sync_log().rejected_by().is_empty());
+
+        // Long enough for another quorum check
+        sim.run_for(Duration::from_secs(10));
+
+        let status = sim.node(0).node().quorum_status();
+        assert!(status.degraded);
+        assert!(status.restart_requested);
+        for majority in 1..3 {
+            assert!(!sim.node(majority).node().quorum_status().restart_requested);
+        }
+    }
+}
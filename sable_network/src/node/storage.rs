@@ -0,0 +1,393 @@
+//! Durable on-disk copy of the network state, for recovery after a crash
+
+use super::*;
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const SNAPSHOT_TEMP_FILE: &str = "snapshot.json.tmp";
+const WAL_FILE: &str = "events.wal";
+
+/// Configuration for a node's durable storage
+#[derive(Debug, Clone, Deserialize)]
+pub struct StorageConfig {
+    /// Directory in which to keep the snapshot and event log
+    pub dir: PathBuf,
+    /// Seconds between snapshots of the network state
+    #[serde(default = "StorageConfig::default_snapshot_interval")]
+    pub snapshot_interval: i64,
+    /// Whether to flush each event to disk before applying it. Slower, but
+    /// survives power loss as well as process crashes.
+    #[serde(default)]
+    pub sync_writes: bool,
+}
+
+impl StorageConfig {
+    fn default_snapshot_interval() -> i64 {
+        300
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot<N> {
+    saved_at: i64,
+    network: N,
+}
+
+/// A periodic snapshot of the [`Network`] state, plus a write-ahead log of the
+/// events applied since that snapshot.
+///
+/// Together these let a server that has crashed, or a whole network that has gone
+/// down, come back with the state it had rather than needing a copy from a peer.
+pub struct DurableStorage {
+    config: StorageConfig,
+    wal: parking_lot::Mutex<BufWriter<File>>,
+    last_snapshot: parking_lot::Mutex<i64>,
+}
+
+/// Network state recovered from disk
+pub struct RecoveredState {
+    /// The network state as of the most recent event written to disk
+    pub network: Network,
+    /// The timestamp of the most recent snapshot or event written to disk
+    pub last_written: i64,
+}
+
+impl DurableStorage {
+    /// Open the storage directory, creating it if needed
+    pub fn open(config: StorageConfig) -> io::Result<Self> {
+        fs::create_dir_all(&config.dir)?;
+        let wal = Self::open_wal(&config, false)?;
+
+        Ok(Self {
+            config,
+            wal: parking_lot::Mutex::new(wal),
+            last_snapshot: parking_lot::Mutex::new(utils::now()),
+        })
+    }
+
+    fn open_wal(config: &StorageConfig, truncate: bool) -> io::Result<BufWriter<File>> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(!truncate)
+            .write(true)
+            .truncate(truncate)
+            .open(config.dir.join(WAL_FILE))?;
+        Ok(BufWriter::new(file))
+    }
+
+    /// Read the most recent snapshot and replay the logged events on top of it.
+    ///
+    /// A partially written event at the end of the log, as left by a crash, is
+    /// ignored, and a missing log is treated as empty.
+    pub fn load(&self) -> io::Result<Option<RecoveredState>> {
+        let snapshot_file = match File::open(self.config.dir.join(SNAPSHOT_FILE)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let snapshot: Snapshot<Network> = serde_json::from_reader(BufReader::new(snapshot_file))?;
+
+        let mut network = snapshot.network;
+        let mut last_written = snapshot.saved_at;
+        let mut replayed = 0;
+
+        let wal = match File::open(self.config.dir.join(WAL_FILE)) {
+            Ok(file) => Some(BufReader::new(file)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                tracing::warn!("No write-ahead log found; recovering from the snapshot alone");
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        for line in wal.into_iter().flat_map(|wal| wal.lines()) {
+            let Ok(event) = serde_json::from_str::<Event>(&line?) else {
+                tracing::warn!("Ignoring incomplete event at end of write-ahead log");
+                break;
+            };
+
+            // Nothing is listening for updates yet; they'll be reflected in the state
+            let updates = crate::network::SavedUpdateReceiver::new();
+            if network.apply(&event, &updates).is_err() {
+                tracing::warn!(?event, "Couldn't replay logged event");
+            }
+            last_written = last_written.max(event.timestamp);
+            replayed += 1;
+        }
+
+        tracing::info!(replayed, "Recovered network state from disk");
+
+        Ok(Some(RecoveredState {
+            network,
+            last_written,
+        }))
+    }
+
+    /// Record an event that is about to be applied to the network state
+    pub fn append(&self, event: &Event) -> io::Result<()> {
+        let mut wal = self.wal.lock();
+        serde_json::to_writer(&mut *wal, event)?;
+        wal.write_all(b"\n")?;
+        wal.flush()?;
+        if self.config.sync_writes {
+            wal.get_ref().sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Whether it's time for a new snapshot
+    pub fn snapshot_due(&self) -> bool {
+        utils::now() - *self.last_snapshot.lock() >= self.config.snapshot_interval
+    }
+
+    /// Write a snapshot of the given network state, and discard the logged events
+    /// that it includes.
+    ///
+    /// This must not run concurrently with [`append`](Self::append), or events
+    /// applied after `network` was captured could be lost.
+    pub fn snapshot(&self, network: &Network) -> io::Result<()> {
+        let now = utils::now();
+        let temp_path = self.config.dir.join(SNAPSHOT_TEMP_FILE);
+
+        {
+            let mut file = BufWriter::new(File::create(&temp_path)?);
+            serde_json::to_writer(
+                &mut file,
+                &Snapshot {
+                    saved_at: now,
+                    network,
+                },
+            )?;
+            file.flush()?;
+            file.get_ref().sync_all()?;
+        }
+        fs::rename(&temp_path, self.config.dir.join(SNAPSHOT_FILE))?;
+
+        *self.wal.lock() = Self::open_wal(&self.config, true)?;
+        *self.last_snapshot.lock() = now;
+
+        tracing::debug!("Wrote network state snapshot");
+        Ok(())
+    }
+}
+
+impl NetworkNode {
+    /// Restore the network state from durable storage, if there is any recent enough
+    /// to be brought up to date, and ask a peer for the events we've missed since.
+    ///
+    /// Returns `None` if a full copy of the network state needs to be fetched
+    /// instead.
+    pub async fn recover_network(
+        storage: &DurableStorage,
+        event_log: &ReplicatedEventLog,
+    ) -> Option<Network> {
+        let recovered = match storage.load() {
+            Ok(Some(recovered)) => recovered,
+            Ok(None) => return None,
+            Err(e) => {
+                tracing::error!("Couldn't read network state from disk: {}", e);
+                return None;
+            }
+        };
+
+        // Peers only keep events for so long; beyond that we can't catch up
+        let age = utils::now() - recovered.last_written;
+        if age > event_log.event_expiry() {
+            tracing::warn!(age, "Saved network state is too old to resume from");
+            return None;
+        }
+
+        event_log.resume_from(&recovered.network);
+        event_log.request_missing_events().await;
+
+        Some(recovered.network)
+    }
+}
+
+impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
+    /// Write a snapshot if one is due
+    pub(super) fn check_snapshot(&self) {
+        if self
+            .storage
+            .as_ref()
+            .is_some_and(DurableStorage::snapshot_due)
+        {
+            self.write_snapshot();
+        }
+    }
+
+    pub(super) fn write_snapshot(&self) {
+        if let Some(storage) = &self.storage {
+            if let Err(e) = storage.snapshot(&self.network()) {
+                tracing::error!("Couldn't write network state snapshot: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::config::NetworkConfig;
+    use event::*;
+    use std::str::FromStr;
+
+    /// A storage directory which is removed again when the test finishes
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("sable-storage-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            Self(path)
+        }
+
+        fn config(&self) -> StorageConfig {
+            StorageConfig {
+                dir: self.0.clone(),
+                snapshot_interval: 300,
+                sync_writes: false,
+            }
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn new_channel(ids: &ObjectIdGenerator, name: &str, timestamp: i64) -> Event {
+        Event {
+            clock: EventClock::new(),
+            id: ids.next(),
+            target: ids.next::<ChannelId>().into(),
+            timestamp,
+            details: details::NewChannel {
+                mode: state::ChannelMode::new(ChannelModeSet::default()),
+                name: ChannelName::from_str(name).unwrap(),
+            }
+            .into(),
+            signature: None,
+        }
+    }
+
+    fn has_channel(net: &Network, name: &str) -> bool {
+        net.channel_by_name(&ChannelName::from_str(name).unwrap())
+            .is_ok()
+    }
+
+    #[test]
+    fn nothing_to_load_without_snapshot() {
+        let dir = TempDir::new("empty");
+        let storage = DurableStorage::open(dir.config()).unwrap();
+
+        assert!(storage.load().unwrap().is_none());
+    }
+
+    #[test]
+    fn logged_events_are_replayed_over_snapshot() {
+        let dir = TempDir::new("replay");
+        let ids = ObjectIdGenerator::new(ServerId::new(1));
+        let storage = DurableStorage::open(dir.config()).unwrap();
+
+        storage
+            .snapshot(&Network::new(NetworkConfig::new()))
+            .unwrap();
+        storage.append(&new_channel(&ids, "#a", 100)).unwrap();
+        storage.append(&new_channel(&ids, "#b", 200)).unwrap();
+
+        // A fresh instance, as after a restart
+        let recovered = DurableStorage::open(dir.config())
+            .unwrap()
+            .load()
+            .unwrap()
+            .unwrap();
+
+        assert!(has_channel(&recovered.network, "#a"));
+        assert!(has_channel(&recovered.network, "#b"));
+        assert!(recovered.last_written >= 200);
+    }
+
+    #[test]
+    fn truncated_event_is_ignored() {
+        let dir = TempDir::new("truncated");
+        let ids = ObjectIdGenerator::new(ServerId::new(1));
+        let storage = DurableStorage::open(dir.config()).unwrap();
+
+        storage
+            .snapshot(&Network::new(NetworkConfig::new()))
+            .unwrap();
+        storage.append(&new_channel(&ids, "#a", 100)).unwrap();
+
+        // Half of a second event, as left by a crash part way through writing it
+        let partial = serde_json::to_string(&new_channel(&ids, "#b", 200)).unwrap();
+        let mut wal = OpenOptions::new()
+            .append(true)
+            .open(dir.0.join(WAL_FILE))
+            .unwrap();
+        wal.write_all(&partial.as_bytes()[..partial.len() / 2])
+            .unwrap();
+        drop(wal);
+
+        let recovered = storage.load().unwrap().unwrap();
+
+        assert!(has_channel(&recovered.network, "#a"));
+        assert!(!has_channel(&recovered.network, "#b"));
+    }
+
+    #[test]
+    fn missing_log_is_treated_as_empty() {
+        let dir = TempDir::new("missing-log");
+        let ids = ObjectIdGenerator::new(ServerId::new(1));
+        let storage = DurableStorage::open(dir.config()).unwrap();
+
+        let mut network = Network::new(NetworkConfig::new());
+        network
+            .apply(
+                &new_channel(&ids, "#a", 100),
+                &crate::network::SavedUpdateReceiver::new(),
+            )
+            .unwrap();
+        storage.snapshot(&network).unwrap();
+
+        // Lost, for example, when only the snapshot was copied from a backup
+        fs::remove_file(dir.0.join(WAL_FILE)).unwrap();
+
+        let recovered = storage.load().unwrap().unwrap();
+
+        assert!(has_channel(&recovered.network, "#a"));
+    }
+
+    #[test]
+    fn snapshot_truncates_log() {
+        let dir = TempDir::new("snapshot");
+        let ids = ObjectIdGenerator::new(ServerId::new(1));
+        let storage = DurableStorage::open(dir.config()).unwrap();
+
+        let mut network = Network::new(NetworkConfig::new());
+        storage.snapshot(&network).unwrap();
+
+        let event = new_channel(&ids, "#a", 100);
+        storage.append(&event).unwrap();
+        network
+            .apply(&event, &crate::network::SavedUpdateReceiver::new())
+            .unwrap();
+
+        storage.snapshot(&network).unwrap();
+        assert_eq!(fs::metadata(dir.0.join(WAL_FILE)).unwrap().len(), 0);
+
+        // Events after the snapshot are still logged and replayed
+        storage.append(&new_channel(&ids, "#b", 200)).unwrap();
+        let recovered = storage.load().unwrap().unwrap();
+
+        assert!(has_channel(&recovered.network, "#a"));
+        assert!(has_channel(&recovered.network, "#b"));
+    }
+}
@@ -0,0 +1,67 @@
+use super::*;
+
+impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
+    /// Add a server to the sync network, or change the address or certificate
+    /// fingerprint of an existing one, on every node in the network
+    pub fn add_sync_peer(&self, peer: PeerConfig) {
+        self.submit_event(
+            SyncPeerId::new(peer.name),
+            details::SyncPeerUpdate { peer: Some(peer) },
+        );
+    }
+
+    /// Remove a server from the sync network on every node in the network
+    pub fn remove_sync_peer(&self, name: ServerName) {
+        self.submit_event(
+            SyncPeerId::new(name),
+            details::SyncPeerUpdate { peer: None },
+        );
+    }
+
+    pub(super) fn apply_sync_peer_change(
+        &self,
+        name: &ServerName,
+        origin: ServerId,
+        peer: &Option<PeerConfig>,
+    ) {
+        Self::apply_sync_peer_change_to(&self.event_log, self.my_id, name, origin, peer);
+    }
+
+    /// Bring the sync peer list in line with the changes recorded in a network state,
+    /// which may be newer than our configuration file
+    pub(super) fn apply_sync_peer_changes(
+        event_log: &ReplicatedEventLog,
+        my_id: ServerId,
+        net: &Network,
+    ) {
+        for (name, origin, peer) in net.sync_peer_changes() {
+            Self::apply_sync_peer_change_to(event_log, my_id, name, origin, peer);
+        }
+    }
+
+    /// The peer list decides which servers we connect to and whose keys we expect,
+    /// so we only follow changes made through our own management interface, or
+    /// by a server whose key is pinned in our configuration. Otherwise any
+    /// server able to inject an event could redirect the sync network.
+    fn apply_sync_peer_change_to(
+        event_log: &ReplicatedEventLog,
+        my_id: ServerId,
+        name: &ServerName,
+        origin: ServerId,
+        peer: &Option<PeerConfig>,
+    ) {
+        if origin != my_id && !event_log.has_pinned_key(origin) {
+            tracing::warn!(
+                %name,
+                ?origin,
+                "Ignoring sync peer change from a server without a pinned key"
+            );
+            return;
+        }
+
+        match peer {
+            Some(conf) => event_log.add_peer(conf.clone()),
+            None => event_log.remove_peer(name),
+        }
+    }
+}
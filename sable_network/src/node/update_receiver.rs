@@ -225,7 +225,16 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
         Ok(Vec::new())
     }
 
-    fn report_audit_entry(&self, _detail: &update::NewAuditLogEntry) -> HandleResult {
+    #[tracing::instrument(skip(self))]
+    fn handle_sync_peer_update(&self, detail: &update::SyncPeerUpdate) -> HandleResult {
+        self.apply_sync_peer_change(&detail.name, detail.origin, &detail.peer);
+
+        Ok(Vec::new())
+    }
+
+    fn report_audit_entry(&self, detail: &update::NewAuditLogEntry) -> HandleResult {
+        self.publish_audit_entry(detail.entry);
+
         Ok(Vec::new())
     }
 
@@ -254,6 +263,10 @@ impl<Policy: crate::policy::PolicyService> NetworkNode<Policy> {
     fn handle_services_update(&self, _detail: &update::ServicesUpdate) -> HandleResult {
         Ok(Vec::new())
     }
+
+    fn handle_services_notice(&self, detail: &update::ServicesNotice) -> HandleResult {
+        Ok(vec![*detail.user.user()])
+    }
 }
 
 impl<Policy: crate::policy::PolicyService> NetworkUpdateReceiver for NetworkNode<Policy> {
@@ -286,10 +299,12 @@ impl<Policy: crate::policy::PolicyService> NetworkUpdateReceiver for NetworkNode
             NewMessage(detail) => self.handle_new_message(detail),
             NewServer(detail) => self.handle_new_server(detail),
             ServerQuit(detail) => self.handle_server_quit(detail),
+            SyncPeerUpdate(detail) => self.handle_sync_peer_update(detail),
             NewAuditLogEntry(detail) => self.report_audit_entry(detail),
             UserLoginChange(detail) => self.handle_user_login(detail),
             HistoryServerUpdate(detail) => self.handle_history_server_update(detail),
             ServicesUpdate(detail) => self.handle_services_update(detail),
+            ServicesNotice(detail) => self.handle_services_notice(detail),
             // We don't need to do anything with EventComplete, just pass it along to the subscriber
             EventComplete(_) => Ok(Vec::new()),
         };
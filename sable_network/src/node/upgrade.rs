@@ -39,7 +39,12 @@ impl<Policy: PolicyService + Saveable> NetworkNode<Policy> {
         rpc_receiver: UnboundedReceiver<NetworkMessage>,
         subscriber: UnboundedSender<NetworkHistoryUpdate>,
         remote_server_commands: Option<UnboundedSender<RemoteServerRequest>>,
+        storage: Option<DurableStorage>,
     ) -> std::io::Result<Self> {
+        Self::apply_sync_peer_changes(&event_log, state.id, &state.net);
+
+        let (quorum, _) = tokio::sync::watch::channel(Self::initial_quorum_status(&event_log));
+
         Ok(Self {
             my_id: state.id,
             name: state.name,
@@ -54,6 +59,10 @@ impl<Policy: PolicyService + Saveable> NetworkNode<Policy> {
             subscriber,
             policy_service: Policy::restore(state.policy_state),
             remote_server_commands,
+            quorum,
+            storage,
+            consistency: Default::default(),
+            audit_stream: audit_stream::new_audit_stream(),
         })
     }
 }
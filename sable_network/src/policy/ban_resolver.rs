@@ -2,8 +2,13 @@ use super::*;
 
 /// A `BanResolver` contains the policy to match ban list entries
 pub trait BanResolver {
+    /// Determine whether the given user is matched by the given pattern.
+    fn user_matches_pattern(&self, user: &User, pattern: &Pattern) -> bool;
+
     /// Determine whether the given user is matched by the given list entry.
-    fn user_matches_entry(&self, user: &User, entry: &ListModeEntry) -> bool;
+    fn user_matches_entry(&self, user: &User, entry: &ListModeEntry) -> bool {
+        self.user_matches_pattern(user, entry.pattern())
+    }
 
     /// Scan the provided list for an entry that matches the given user.
     fn user_matches_list<'a>(
@@ -26,8 +31,8 @@ impl StandardBanResolver {
 }
 
 impl BanResolver for StandardBanResolver {
-    fn user_matches_entry(&self, user: &User, ban: &ListModeEntry) -> bool {
+    fn user_matches_pattern(&self, user: &User, pattern: &Pattern) -> bool {
         let nuh = format!("{}!{}@{}", user.nick(), user.user(), user.visible_host());
-        ban.pattern().matches(&nuh)
+        pattern.matches(&nuh)
     }
 }
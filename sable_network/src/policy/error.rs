@@ -11,6 +11,8 @@ pub enum ChannelPermissionError {
     UserNotOp,
     /// User is banned from the channel
     UserIsBanned,
+    /// User matches an entry in the channel's auto-kick list; contains the entry's reason
+    AutoKicked(String),
     /// User cannot send for some other reason
     CannotSendToChannel,
     /// Channel is invite-only
@@ -28,6 +30,8 @@ pub enum ChannelPermissionError {
 pub enum UserPermissionError {
     /// User is not an oper
     NotOper,
+    /// User is an oper, but doesn't have the required privilege
+    MissingPrivilege(state::OperPrivilege),
     /// That user mode can't be set directly
     ReadOnlyUmode,
     /// User isn't logged in (and needs to be)
@@ -1,15 +1,32 @@
 #![allow(clippy::crate_in_macro_def)]
 
 use super::*;
+use std::net::IpAddr;
+
+/// The details a user presents when attempting to gain oper access
+#[derive(Debug)]
+pub struct OperCredentials<'a> {
+    /// The password given to `OPER`, or `None` when opering up automatically
+    /// on connection
+    pub password: Option<&'a str>,
+    /// The fingerprint of the user's TLS client certificate, if any
+    pub fingerprint: Option<&'a str>,
+    /// The account the user is logged in to, if any
+    pub account: Option<Nickname>,
+    pub username: &'a Username,
+    pub hostname: &'a Hostname,
+    pub ip: IpAddr,
+}
 
 /// Makes authentication decisions for users attempting to gain oper access
 #[delegatable_trait]
 pub trait OperAuthenticationService {
+    /// Determine whether the given credentials satisfy the named oper block
     fn authenticate(
         &self,
         oper_config: &crate::network::config::OperConfig,
         user: &str,
-        pass: &str,
+        credentials: &OperCredentials,
     ) -> bool;
 }
 
@@ -22,6 +39,13 @@ pub trait OperPolicyService {
     /// Utility function to determine whether the given user is opered (regardless of privileges)
     fn require_oper(&self, user: &wrapper::User) -> PermissionResult;
 
+    /// Determine whether the given user is opered with the given privilege
+    fn require_privilege(
+        &self,
+        user: &wrapper::User,
+        privilege: state::OperPrivilege,
+    ) -> PermissionResult;
+
     /// Determine whether the given oper can set a kline
     fn can_set_kline(
         &self,
@@ -31,9 +55,30 @@ pub trait OperPolicyService {
         duration: i64,
     ) -> PermissionResult;
 
+    /// Determine whether the given oper can add a network ban
+    fn can_set_network_ban(&self, oper: &wrapper::User) -> PermissionResult;
+
     /// Determine whether the given oper can disconnect the given target user
     fn can_kill(&self, oper: &wrapper::User, target: &wrapper::User) -> PermissionResult;
 
+    /// Determine whether the given oper can rename a channel without having access to it
+    fn can_override_rename(
+        &self,
+        oper: &wrapper::User,
+        channel: &wrapper::Channel,
+    ) -> PermissionResult;
+
+    /// Determine whether the given oper can change a channel's modes without having
+    /// access to it
+    fn can_override_channel_mode(
+        &self,
+        oper: &wrapper::User,
+        channel: &wrapper::Channel,
+    ) -> PermissionResult;
+
+    /// Determine whether the given oper can use server administration commands
+    fn can_administer(&self, oper: &wrapper::User) -> PermissionResult;
+
     /// Determine whether the given user can see detailed connection information about the target user
     fn can_see_connection_info(&self, source: &wrapper::User, target: &wrapper::User) -> bool;
 }
@@ -19,6 +19,20 @@ pub trait RegistrationPolicyService {
         channel: &wrapper::ChannelRegistration,
     ) -> PermissionResult;
 
+    /// Determine whether the given user can view the auto-kick list for a channel
+    fn can_view_auto_kicks(
+        &self,
+        source: &wrapper::User,
+        channel: &wrapper::ChannelRegistration,
+    ) -> PermissionResult;
+
+    /// Determine whether the given account can add or remove auto-kick entries for a channel
+    fn can_edit_auto_kicks(
+        &self,
+        source: &wrapper::Account,
+        channel: &wrapper::ChannelRegistration,
+    ) -> PermissionResult;
+
     /// Determine whether the given user can change access on a channel for a target user
     fn can_change_access_for(
         &self,
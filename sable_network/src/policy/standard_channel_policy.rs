@@ -75,6 +75,28 @@ impl ChannelPolicyService for StandardChannelPolicy {
             return Ok(());
         }
 
+        if let Some(registration) = channel.is_registered() {
+            let now = crate::utils::now();
+
+            for akick in registration.auto_kicks().filter(|a| !a.is_expired(now)) {
+                let matches = match &akick.target {
+                    state::ChannelAutoKickTarget::Mask(pattern) => {
+                        self.ban_resolver.user_matches_pattern(user, pattern)
+                    }
+                    state::ChannelAutoKickTarget::Account(account) => {
+                        user.account_name().as_ref() == Some(account)
+                    }
+                };
+
+                if matches {
+                    return Err(PermissionError::Channel(
+                        *channel.name(),
+                        AutoKicked(akick.reason.clone()),
+                    ));
+                }
+            }
+        }
+
         let chan_key = channel.mode().key();
         if chan_key.is_some() && key != chan_key {
             return Err(PermissionError::Channel(*channel.name(), BadChannelKey));
@@ -330,3 +352,114 @@ impl ChannelPolicyService for StandardChannelPolicy {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::tests::fixtures::NetworkBuilder;
+    use state::{ChannelAutoKick, ChannelAutoKickTarget};
+    use std::str::FromStr;
+
+    fn auto_kick(target: ChannelAutoKickTarget, expires: Option<i64>) -> ChannelAutoKick {
+        ChannelAutoKick {
+            target,
+            reason: "go away".to_owned(),
+            setter: Nickname::from_str("founder").unwrap(),
+            timestamp: 0,
+            expires,
+        }
+    }
+
+    fn mask(pattern: &str) -> ChannelAutoKickTarget {
+        ChannelAutoKickTarget::Mask(Pattern::new(pattern.to_owned()))
+    }
+
+    /// Build a network with a registered `#test` carrying the given auto-kicks, a
+    /// logged-in user `alice` and an anonymous user `bob`
+    fn network_with_auto_kicks(auto_kicks: Vec<ChannelAutoKick>) -> NetworkBuilder {
+        let mut builder = NetworkBuilder::new();
+        let channel = ChannelName::from_str("#test").unwrap();
+
+        builder.add_channel(channel);
+        builder.add_channel_registration(state::ChannelRegistration {
+            id: builder.next_id(),
+            channelname: channel,
+            last_used: None,
+            no_expire: false,
+            mode_lock_on: Default::default(),
+            mode_lock_off: Default::default(),
+            keep_topic: false,
+            last_topic: None,
+            entry_message: None,
+            guard: false,
+            auto_kicks,
+        });
+
+        let account = builder.add_account(Nickname::from_str("alice").unwrap());
+        builder.add_user_with_account(Nickname::from_str("alice").unwrap(), Some(account));
+        builder.add_user(Nickname::from_str("bob").unwrap());
+
+        builder
+    }
+
+    fn can_join(builder: &NetworkBuilder, nick: &str) -> PermissionResult {
+        let net = &builder.net;
+        let user = net
+            .user_by_nick(&Nickname::from_str(nick).unwrap())
+            .unwrap();
+        let channel = net
+            .channel_by_name(&ChannelName::from_str("#test").unwrap())
+            .unwrap();
+
+        StandardChannelPolicy::new().can_join(&user, &channel, None)
+    }
+
+    #[test]
+    fn mask_auto_kick_matches_case_insensitively() {
+        let builder = network_with_auto_kicks(vec![auto_kick(mask("BOB!*@*"), None)]);
+
+        assert!(matches!(
+            can_join(&builder, "bob"),
+            Err(PermissionError::Channel(_, AutoKicked(reason))) if reason == "go away"
+        ));
+        assert!(can_join(&builder, "alice").is_ok());
+    }
+
+    #[test]
+    fn account_auto_kick_matches_logged_in_user() {
+        let builder = network_with_auto_kicks(vec![auto_kick(
+            ChannelAutoKickTarget::Account(Nickname::from_str("Alice").unwrap()),
+            None,
+        )]);
+
+        assert!(can_join(&builder, "alice").is_err());
+        // A user merely using the account's name as a nick isn't matched
+        assert!(can_join(&builder, "bob").is_ok());
+    }
+
+    #[test]
+    fn expired_auto_kick_is_ignored() {
+        let now = crate::utils::now();
+        let builder = network_with_auto_kicks(vec![
+            auto_kick(mask("bob!*@*"), Some(now - 60)),
+            auto_kick(mask("alice!*@*"), Some(now + 3600)),
+        ]);
+
+        assert!(can_join(&builder, "bob").is_ok());
+        assert!(can_join(&builder, "alice").is_err());
+    }
+
+    #[test]
+    fn auto_kick_targets_compare_case_insensitively() {
+        assert_eq!(mask("*!*@Example.COM"), mask("*!*@example.com"));
+        assert_ne!(mask("*!*@example.com"), mask("*!*@example.org"));
+        assert_eq!(
+            ChannelAutoKickTarget::Account(Nickname::from_str("Alice").unwrap()),
+            ChannelAutoKickTarget::Account(Nickname::from_str("alice").unwrap())
+        );
+        assert_ne!(
+            mask("alice"),
+            ChannelAutoKickTarget::Account(Nickname::from_str("alice").unwrap())
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use super::*;
-use crate::network::config::OperConfig;
+use crate::network::{config::OperConfig, state::OperPrivilege};
 
 use UserPermissionError::*;
 
@@ -27,6 +27,19 @@ impl OperPolicyService for StandardOperPolicy {
         }
     }
 
+    fn require_privilege(
+        &self,
+        user: &wrapper::User,
+        privilege: OperPrivilege,
+    ) -> PermissionResult {
+        self.require_oper(user)?;
+        if user.has_oper_privilege(privilege) {
+            Ok(())
+        } else {
+            Err(PermissionError::User(MissingPrivilege(privilege)))
+        }
+    }
+
     fn can_set_kline(
         &self,
         oper: &wrapper::User,
@@ -34,20 +47,236 @@ impl OperPolicyService for StandardOperPolicy {
         _host: &Pattern,
         _duration: i64,
     ) -> PermissionResult {
-        self.require_oper(oper)
+        self.require_privilege(oper, OperPrivilege::NetworkBan)
+    }
+
+    fn can_set_network_ban(&self, oper: &wrapper::User) -> PermissionResult {
+        self.require_privilege(oper, OperPrivilege::NetworkBan)
     }
 
-    fn can_kill(&self, oper: &wrapper::User, _target: &wrapper::User) -> PermissionResult {
-        self.require_oper(oper)
+    fn can_kill(&self, oper: &wrapper::User, target: &wrapper::User) -> PermissionResult {
+        // A kill is local if every connection of the target is to a server that the oper
+        // is also connected to
+        let oper_servers: Vec<_> = oper.connections().map(|c| c.id().server()).collect();
+        let mut target_servers = target.connections().map(|c| c.id().server()).peekable();
+        let is_local = target_servers.peek().is_some()
+            && target_servers.all(|server| oper_servers.contains(&server));
+
+        if is_local {
+            self.require_privilege(oper, OperPrivilege::KillLocal)
+        } else {
+            self.require_privilege(oper, OperPrivilege::KillRemote)
+        }
+    }
+
+    fn can_override_rename(
+        &self,
+        oper: &wrapper::User,
+        _channel: &wrapper::Channel,
+    ) -> PermissionResult {
+        self.require_privilege(oper, OperPrivilege::RenameChannel)
+    }
+
+    fn can_override_channel_mode(
+        &self,
+        oper: &wrapper::User,
+        _channel: &wrapper::Channel,
+    ) -> PermissionResult {
+        self.require_privilege(oper, OperPrivilege::ChannelOverride)
+    }
+
+    fn can_administer(&self, oper: &wrapper::User) -> PermissionResult {
+        self.require_privilege(oper, OperPrivilege::Admin)
     }
 
     fn can_see_connection_info(&self, source: &wrapper::User, target: &wrapper::User) -> bool {
-        self.require_oper(source).is_ok() || source.id() == target.id()
+        source.has_oper_privilege(OperPrivilege::SeeIp) || source.id() == target.id()
     }
 }
 
 impl OperAuthenticationService for StandardOperPolicy {
-    fn authenticate(&self, oper_config: &OperConfig, user: &str, pass: &str) -> bool {
-        user == oper_config.name && unix::verify(pass, &oper_config.hash)
+    fn authenticate(
+        &self,
+        oper_config: &OperConfig,
+        user: &str,
+        credentials: &OperCredentials,
+    ) -> bool {
+        if user != oper_config.name {
+            return false;
+        }
+
+        let password_ok = match (credentials.password, &oper_config.hash) {
+            (Some(pass), Some(hash)) => unix::verify(pass, hash),
+            (Some(_), None) => false,
+            // Without a password, only a block bound to a certificate will do
+            (None, _) => oper_config.auto_oper && oper_config.fingerprint.is_some(),
+        };
+
+        let fingerprint_ok = match &oper_config.fingerprint {
+            Some(expected) => credentials
+                .fingerprint
+                .is_some_and(|fp| fp.eq_ignore_ascii_case(expected)),
+            None => true,
+        };
+
+        let account_ok = match &oper_config.account {
+            Some(expected) => credentials.account.as_ref() == Some(expected),
+            None => true,
+        };
+
+        let user_host = format!("{}@{}", credentials.username, credentials.hostname);
+        let user_ip = format!("{}@{}", credentials.username, credentials.ip);
+        let host_ok = oper_config.host_masks.is_empty()
+            || oper_config
+                .host_masks
+                .iter()
+                .any(|mask| mask.matches(&user_host) || mask.matches(&user_ip));
+
+        password_ok && fingerprint_ok && account_ok && host_ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::IpAddr, str::FromStr};
+
+    const FINGERPRINT: &str = "0123456789abcdef";
+
+    fn block() -> OperConfig {
+        OperConfig {
+            name: "admin".to_owned(),
+            hash: Some(pwhash::sha512_crypt::hash("secret").unwrap()),
+            class: None,
+            fingerprint: None,
+            account: None,
+            host_masks: Vec::new(),
+            auto_oper: false,
+        }
+    }
+
+    struct Client {
+        password: Option<&'static str>,
+        fingerprint: Option<&'static str>,
+        account: Option<Nickname>,
+        username: Username,
+        hostname: Hostname,
+        ip: IpAddr,
+    }
+
+    impl Client {
+        fn new(password: Option<&'static str>) -> Self {
+            Self {
+                password,
+                fingerprint: None,
+                account: None,
+                username: Username::from_str("oper").unwrap(),
+                hostname: Hostname::from_str("staff.example.org").unwrap(),
+                ip: "192.0.2.1".parse().unwrap(),
+            }
+        }
+
+        fn authenticate(&self, block: &OperConfig, name: &str) -> bool {
+            let credentials = OperCredentials {
+                password: self.password,
+                fingerprint: self.fingerprint,
+                account: self.account,
+                username: &self.username,
+                hostname: &self.hostname,
+                ip: self.ip,
+            };
+            StandardOperPolicy::new().authenticate(block, name, &credentials)
+        }
+    }
+
+    #[test]
+    fn password() {
+        let block = block();
+
+        assert!(Client::new(Some("secret")).authenticate(&block, "admin"));
+        assert!(!Client::new(Some("wrong")).authenticate(&block, "admin"));
+        assert!(!Client::new(Some("secret")).authenticate(&block, "other"));
+        assert!(!Client::new(None).authenticate(&block, "admin"));
+
+        let no_hash = OperConfig {
+            hash: None,
+            ..block
+        };
+        assert!(!Client::new(Some("secret")).authenticate(&no_hash, "admin"));
+    }
+
+    #[test]
+    fn fingerprint() {
+        let block = OperConfig {
+            fingerprint: Some(FINGERPRINT.to_owned()),
+            ..block()
+        };
+        let mut client = Client::new(Some("secret"));
+
+        assert!(!client.authenticate(&block, "admin"));
+        client.fingerprint = Some("fedcba9876543210");
+        assert!(!client.authenticate(&block, "admin"));
+        client.fingerprint = Some("0123456789ABCDEF");
+        assert!(client.authenticate(&block, "admin"));
+    }
+
+    #[test]
+    fn account() {
+        let block = OperConfig {
+            account: Some(Nickname::from_str("staff").unwrap()),
+            ..block()
+        };
+        let mut client = Client::new(Some("secret"));
+
+        assert!(!client.authenticate(&block, "admin"));
+        client.account = Some(Nickname::from_str("other").unwrap());
+        assert!(!client.authenticate(&block, "admin"));
+        client.account = Some(Nickname::from_str("staff").unwrap());
+        assert!(client.authenticate(&block, "admin"));
+    }
+
+    #[test]
+    fn host_mask() {
+        let by_host = OperConfig {
+            host_masks: vec![Pattern::new("oper@*.example.org".to_owned())],
+            ..block()
+        };
+        let by_ip = OperConfig {
+            host_masks: vec![Pattern::new("*@192.0.2.*".to_owned())],
+            ..block()
+        };
+        let elsewhere = OperConfig {
+            host_masks: vec![Pattern::new("*@*.example.net".to_owned())],
+            ..block()
+        };
+        let client = Client::new(Some("secret"));
+
+        assert!(client.authenticate(&by_host, "admin"));
+        assert!(client.authenticate(&by_ip, "admin"));
+        assert!(!client.authenticate(&elsewhere, "admin"));
+    }
+
+    #[test]
+    fn auto_oper_needs_fingerprint() {
+        let without_fingerprint = OperConfig {
+            auto_oper: true,
+            ..block()
+        };
+        let with_fingerprint = OperConfig {
+            fingerprint: Some(FINGERPRINT.to_owned()),
+            ..without_fingerprint.clone()
+        };
+        let mut client = Client::new(None);
+        client.fingerprint = Some(FINGERPRINT);
+
+        assert!(!client.authenticate(&without_fingerprint, "admin"));
+        assert!(client.authenticate(&with_fingerprint, "admin"));
+
+        // A password-less oper-up needs the block to be marked for it
+        let not_auto = OperConfig {
+            auto_oper: false,
+            ..with_fingerprint
+        };
+        assert!(!client.authenticate(&not_auto, "admin"));
     }
 }
@@ -11,6 +11,26 @@ impl StandardRegistrationPolicy {
     }
 }
 
+/// The auto-kick flags postdate many existing founder roles, so founders are always
+/// allowed to manage the auto-kick list regardless of their role's explicit flags
+fn has_auto_kick_flag(
+    source: &wrapper::Account,
+    channel: &wrapper::ChannelRegistration,
+    flag: ChannelAccessFlag,
+) -> PermissionResult {
+    let source_access = source
+        .has_access_in(channel.id())
+        .ok_or(RegistrationPermissionError::NoAccess)?;
+
+    let flags = source_access.role()?.flags();
+
+    if !flags.is_set(flag) && !flags.is_set(ChannelAccessFlag::Founder) {
+        return Err(RegistrationPermissionError::NoAccess.into());
+    }
+
+    Ok(())
+}
+
 impl RegistrationPolicyService for StandardRegistrationPolicy {
     fn can_view_access(
         &self,
@@ -60,6 +80,26 @@ impl RegistrationPolicyService for StandardRegistrationPolicy {
         Ok(())
     }
 
+    fn can_view_auto_kicks(
+        &self,
+        source: &wrapper::User,
+        channel: &wrapper::ChannelRegistration,
+    ) -> PermissionResult {
+        let source_account = source
+            .account()?
+            .ok_or(RegistrationPermissionError::NotLoggedIn)?;
+
+        has_auto_kick_flag(&source_account, channel, ChannelAccessFlag::AkickView)
+    }
+
+    fn can_edit_auto_kicks(
+        &self,
+        source: &wrapper::Account,
+        channel: &wrapper::ChannelRegistration,
+    ) -> PermissionResult {
+        has_auto_kick_flag(source, channel, ChannelAccessFlag::AkickEdit)
+    }
+
     fn can_change_access_for(
         &self,
         source: &wrapper::Account,
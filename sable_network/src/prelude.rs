@@ -6,7 +6,7 @@ pub use crate::{
     id::*,
     modes::*,
     network::errors::{LookupError, LookupResult},
-    network::event::{Event, EventClock, EventDetails},
+    network::event::{Event, EventClock, EventDetails, ServerKey},
     network::wrapper::{WrappedMessage, WrappedUser},
     network::*,
     node::NetworkNode,
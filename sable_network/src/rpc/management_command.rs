@@ -1,8 +1,16 @@
+use crate::{
+    audit::AuditLogFilter, id::NetworkBanId, network::ban::BanMatchType, sync::PeerConfig,
+    validated::ServerName,
+};
+use serde::{Deserialize, Serialize};
 use tokio::sync::oneshot::Sender;
 
 /// A management command
 pub struct ServerManagementCommand {
     pub cmd: ServerManagementCommandType,
+    /// The name of the management user who issued the command, as recorded in
+    /// the audit log
+    pub user: String,
     pub response: Sender<String>,
 }
 
@@ -14,4 +22,96 @@ pub enum ServerManagementCommandType {
     DumpNetwork,
     /// Dump event log (for debugging)
     DumpEvents,
+    /// Add or reconfigure a peer in the sync network
+    AddPeer(PeerConfig),
+    /// Remove a peer from the sync network
+    RemovePeer(ServerName),
+    /// Compare this server's network state with another's
+    StateDiff(ServerName),
+    /// List the active network bans
+    ListBans,
+    /// Add a network ban
+    AddBan(NewBanRequest),
+    /// Remove a network ban
+    RemoveBan(NetworkBanId),
+    /// Disconnect a user from the network
+    KillUser(KillRequest),
+    /// Find the users matching a ban pattern
+    FindUsers(UserSearchRequest),
+    /// Search the audit log
+    QueryAuditLog(AuditLogQuery),
+    /// Look up a single object and the objects related to it
+    Inspect(InspectTarget),
+}
+
+/// The action to be taken by a network ban added through the management
+/// interface
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NewBanAction {
+    RefuseConnection,
+    RequireSasl,
+}
+
+/// Details of a network ban to be added through the management interface.
+///
+/// These are the same fields accepted by the `BAN` command.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewBanRequest {
+    /// When the ban is matched; defaults to `pre_registration`
+    #[serde(rename = "type")]
+    pub match_type: Option<BanMatchType>,
+    /// Defaults to `refuse_connection`. Ignored for `pre_sasl` bans, which can
+    /// only deny SASL authentication.
+    pub action: Option<NewBanAction>,
+    /// Whether matching users who are already connected are affected; defaults
+    /// to true
+    pub apply_existing: Option<bool>,
+    /// The pattern expression to match against
+    pub pattern: String,
+    /// Duration of the ban, in minutes
+    pub duration: i64,
+    pub reason: String,
+    pub oper_reason: Option<String>,
+}
+
+/// A user to be disconnected through the management interface
+#[derive(Debug, Clone, Deserialize)]
+pub struct KillRequest {
+    /// The nickname or numeric ID of the user
+    pub user: String,
+    pub reason: String,
+}
+
+/// A search for users through the management interface
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserSearchRequest {
+    /// A pattern expression, using the fields available to a `pre_registration`
+    /// network ban
+    pub pattern: String,
+}
+
+/// A search of the audit log through the management interface
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuditLogQuery {
+    #[serde(flatten)]
+    pub filter: AuditLogFilter,
+    /// Return at most this many entries, the most recent that match
+    pub limit: Option<usize>,
+}
+
+/// An object to be looked up through the management interface. Each is given
+/// by numeric ID, or by name for those which have one.
+#[derive(Debug, Clone)]
+pub enum InspectTarget {
+    /// A user, by ID or nickname
+    User(String),
+    /// A channel, by ID or name
+    Channel(String),
+    /// An account, by ID or name
+    Account(String),
+    /// A server, by ID or name
+    Server(String),
+    /// An event, by ID
+    Event(String),
 }
@@ -1,9 +1,14 @@
-use std::num::NonZeroUsize;
+use std::{collections::BTreeMap, num::NonZeroUsize};
 
 use crate::{
     history::{HistoricalEvent, HistoryError, HistoryRequest},
     id::*,
-    network::{event::*, state::ChannelAccessSet, Network},
+    modes::ChannelModeSet,
+    network::{
+        event::*,
+        state::{ChannelAccessSet, ChannelAutoKickTarget},
+        Network, StateDigest, StateHash,
+    },
     validated::*,
 };
 use tokio::sync::{mpsc::Sender, oneshot};
@@ -45,6 +50,11 @@ pub enum RemoteServerRequestType {
     Services(RemoteServicesServerRequestType),
     /// A message to be handled by a history node
     History(RemoteHistoryServerRequestType),
+    /// Request for the target server's network state digest
+    StateDigest,
+    /// Request for the hash of each object in the named section of the target
+    /// server's network state
+    ObjectDigests(String),
 }
 
 impl From<RemoteServicesServerRequestType> for RemoteServerRequestType {
@@ -63,8 +73,9 @@ impl From<RemoteHistoryServerRequestType> for RemoteServerRequestType {
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum RemoteServicesServerRequestType {
     /// User attempting registration
-    /// Parameters: account name being registered, password provided
-    RegisterUser(Nickname, String),
+    /// Parameters: account name being registered, password provided, email address
+    /// provided (if any)
+    RegisterUser(Nickname, String, Option<String>),
     /// User attempting login
     /// Parameters: account id, password
     UserLogin(AccountId, String),
@@ -101,6 +112,58 @@ pub enum RemoteServicesServerRequestType {
     AddAccountFingerprint(AccountId, String),
     /// Remove an authorised fingerprint from an account
     RemoveAccountFingerprint(AccountId, String),
+    /// Set whether an account's last-seen information is hidden from other users
+    SetAccountHideInfo { account: AccountId, hide_info: bool },
+    /// Set or clear the no-expire flag on an account. Requires the source user to be an oper.
+    SetAccountNoExpire {
+        source: UserId,
+        account: AccountId,
+        no_expire: bool,
+    },
+    /// Set or clear the no-expire flag on a channel registration. Requires the source user
+    /// to be an oper.
+    SetChannelNoExpire {
+        source: UserId,
+        channel: ChannelRegistrationId,
+        no_expire: bool,
+    },
+    /// Change one of a channel registration's settings
+    SetChannelSetting {
+        source: AccountId,
+        channel: ChannelRegistrationId,
+        setting: ChannelSetting,
+    },
+    /// Add an entry to a channel's auto-kick list, replacing any existing entry for the
+    /// same target
+    AddAutoKick {
+        source: AccountId,
+        channel: ChannelRegistrationId,
+        target: ChannelAutoKickTarget,
+        reason: String,
+        expires: Option<i64>,
+    },
+    /// Remove an entry from a channel's auto-kick list
+    RemoveAutoKick {
+        source: AccountId,
+        channel: ChannelRegistrationId,
+        target: ChannelAutoKickTarget,
+    },
+}
+
+/// A channel registration setting, as changed by `CS SET`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum ChannelSetting {
+    /// Modes to keep set and unset on the channel
+    ModeLock {
+        on: ChannelModeSet,
+        off: ChannelModeSet,
+    },
+    /// Whether to restore the last topic when the channel is recreated
+    KeepTopic(bool),
+    /// Notice to send to joining users, or `None` to clear it
+    EntryMessage(Option<String>),
+    /// Whether to keep the channel in existence while it is empty
+    Guard(bool),
 }
 
 /// A message to be handled by a services node
@@ -146,6 +209,10 @@ pub enum RemoteServerResponse {
     Services(RemoteServicesServerResponse),
     /// Response type specific to history servers
     History(RemoteHistoryServerResponse),
+    /// The server's network state digest, and the event clock at which it was computed
+    StateDigest(EventClock, StateDigest),
+    /// Object hashes for the requested state section, if it exists
+    ObjectDigests(Option<BTreeMap<String, StateHash>>),
 }
 
 impl From<RemoteServicesServerResponse> for RemoteServerResponse {
@@ -0,0 +1,313 @@
+//! Wire encodings for the sync protocol
+//!
+//! Every frame is sent as a u32 length followed by that many bytes of payload. The
+//! encoding of the payload is chosen per connection: immediately after the TLS
+//! handshake each side sends a [`Hello`], always encoded as JSON, listing the codec
+//! versions it understands, and both then switch to the highest version they have
+//! in common. Codecs are identified by number rather than by name so that servers
+//! can skip over versions they don't know about, which lets new codecs be rolled
+//! out one server at a time.
+//!
+//! Servers from before this handshake existed open a new connection for each
+//! conversation, and send its messages as bare JSON [`Message`]s with the same
+//! length prefix. The side which accepts a connection reads before it writes, so
+//! it can tell such a server by its first frame; one which connects to such a
+//! server sees the connection closed in place of a [`Hello`]. Either way, the
+//! conversation is carried in the old framing instead, so that a network can be
+//! upgraded one server at a time.
+
+use super::connection::Frame;
+use super::message::{Message, MessageDetail, Request};
+use super::network::{NetworkError, NetworkResult};
+use crate::validated::ServerName;
+
+use std::convert::TryInto;
+use std::io::{ErrorKind, Read};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::mpsc::{channel, UnboundedSender},
+};
+
+/// Largest frame payload accepted from a peer, both on the wire and after
+/// decompression
+const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+/// Binary frames larger than this, carrying bulk data, are compressed
+const COMPRESSION_THRESHOLD: usize = 4096;
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Binary frame flag: the rest of the payload is zstd-compressed
+const FLAG_COMPRESSED: u8 = 0x01;
+
+/// A version of the encoding used for frames on a peer connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) enum WireCodec {
+    /// Each frame is a JSON document. Understood by all servers.
+    Json,
+    /// Each frame is a flag byte followed by a bincode-encoded frame, which is
+    /// zstd-compressed if the flag byte says so.
+    Bincode,
+}
+
+/// The first frame sent in each direction on a new connection
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub(super) struct Hello {
+    /// Codec versions understood by the sender
+    codecs: Vec<u16>,
+}
+
+/// What the peer which opened a connection sent first
+#[derive(Debug)]
+pub(super) enum Handshake {
+    /// A [`Hello`], after which both sides use the given codec
+    Negotiated(WireCodec),
+    /// The first message of a conversation, from a server which predates the
+    /// handshake
+    Legacy(Message),
+}
+
+impl WireCodec {
+    /// All codecs supported by this server
+    const SUPPORTED: &'static [WireCodec] = &[WireCodec::Json, WireCodec::Bincode];
+
+    fn version(self) -> u16 {
+        match self {
+            Self::Json => 0,
+            Self::Bincode => 1,
+        }
+    }
+
+    fn from_version(version: u16) -> Option<Self> {
+        Self::SUPPORTED
+            .iter()
+            .copied()
+            .find(|c| c.version() == version)
+    }
+
+    fn hello() -> Hello {
+        Hello {
+            codecs: Self::SUPPORTED.iter().map(|c| c.version()).collect(),
+        }
+    }
+
+    /// The best codec supported both by us and by the sender of `theirs`
+    fn choose(theirs: Hello) -> Self {
+        theirs
+            .codecs
+            .into_iter()
+            .filter_map(Self::from_version)
+            .max()
+            .unwrap_or(Self::Json)
+    }
+
+    /// Exchange [`Hello`] frames on a stream we have just opened, and choose the
+    /// best codec that both ends support.
+    ///
+    /// Fails with [`NetworkError::NoHandshake`] if the peer closes the connection
+    /// instead, as servers from before the handshake do when they can't parse our
+    /// [`Hello`].
+    pub(super) async fn negotiate(
+        stream: &mut (impl AsyncRead + AsyncWrite + Unpin),
+    ) -> Result<Self, NetworkError> {
+        write_raw(stream, &serde_json::to_vec(&Self::hello())?).await?;
+
+        let theirs = match read_raw(stream).await {
+            Ok(buf) => buf,
+            Err(NetworkError::Io(e))
+                if matches!(
+                    e.kind(),
+                    ErrorKind::UnexpectedEof | ErrorKind::ConnectionReset
+                ) =>
+            {
+                return Err(NetworkError::NoHandshake);
+            }
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self::choose(serde_json::from_slice(&theirs)?))
+    }
+
+    /// Wait for the peer which opened a stream to start the handshake, and answer
+    /// with our own [`Hello`]. If it instead starts a conversation in the framing
+    /// used before the handshake, return that conversation's first message.
+    pub(super) async fn accept(
+        stream: &mut (impl AsyncRead + AsyncWrite + Unpin),
+    ) -> Result<Handshake, NetworkError> {
+        let first = read_raw(stream).await?;
+
+        if let Ok(theirs) = serde_json::from_slice::<Hello>(&first) {
+            write_raw(stream, &serde_json::to_vec(&Self::hello())?).await?;
+            return Ok(Handshake::Negotiated(Self::choose(theirs)));
+        }
+
+        Ok(Handshake::Legacy(serde_json::from_slice(&first)?))
+    }
+
+    pub(super) fn encode(self, frame: &Frame) -> Result<Vec<u8>, NetworkError> {
+        match self {
+            Self::Json => Ok(serde_json::to_vec(frame)?),
+            Self::Bincode => {
+                let encoded = bincode::serialize(frame)?;
+                // Checked before compression, since the peer enforces the same limit
+                // after decompressing
+                if encoded.len() > MAX_FRAME_SIZE {
+                    return Err(frame_too_large(encoded.len()));
+                }
+
+                if encoded.len() > COMPRESSION_THRESHOLD && frame_is_bulk(frame) {
+                    let mut buf = vec![FLAG_COMPRESSED];
+                    zstd::stream::copy_encode(encoded.as_slice(), &mut buf, COMPRESSION_LEVEL)?;
+                    Ok(buf)
+                } else {
+                    let mut buf = Vec::with_capacity(encoded.len() + 1);
+                    buf.push(0);
+                    buf.extend_from_slice(&encoded);
+                    Ok(buf)
+                }
+            }
+        }
+    }
+
+    pub(super) fn decode(self, buf: &[u8]) -> Result<Frame, NetworkError> {
+        match self {
+            Self::Json => Ok(serde_json::from_slice(buf)?),
+            Self::Bincode => {
+                let (flags, body) = buf
+                    .split_first()
+                    .ok_or_else(|| NetworkError::InternalError("Empty frame".to_string()))?;
+
+                if flags & FLAG_COMPRESSED != 0 {
+                    // Read one byte more than allowed, to tell an oversized frame
+                    // from one that's exactly at the limit
+                    let mut decompressed = Vec::new();
+                    zstd::stream::read::Decoder::new(body)?
+                        .take(MAX_FRAME_SIZE as u64 + 1)
+                        .read_to_end(&mut decompressed)?;
+                    if decompressed.len() > MAX_FRAME_SIZE {
+                        return Err(frame_too_large(decompressed.len()));
+                    }
+                    Ok(bincode::deserialize(&decompressed)?)
+                } else {
+                    Ok(bincode::deserialize(body)?)
+                }
+            }
+        }
+    }
+
+    pub(super) async fn read_frame(
+        self,
+        stream: &mut (impl AsyncRead + Unpin),
+    ) -> Result<Frame, NetworkError> {
+        self.decode(&read_raw(stream).await?)
+    }
+
+    pub(super) async fn write_frame(
+        self,
+        stream: &mut (impl AsyncWrite + Unpin),
+        frame: &Frame,
+    ) -> Result<(), NetworkError> {
+        write_raw(stream, &self.encode(frame)?).await
+    }
+}
+
+/// Whether a frame carries one of the potentially very large messages which are
+/// worth compressing
+fn frame_is_bulk(frame: &Frame) -> bool {
+    matches!(
+        frame,
+        Frame::Message { message, .. }
+            if matches!(
+                message.content,
+                MessageDetail::BulkEvents(_)
+                    | MessageDetail::NetworkState(_)
+                    | MessageDetail::StateChunk(_)
+            )
+    )
+}
+
+fn frame_too_large(length: usize) -> NetworkError {
+    NetworkError::InternalError(format!(
+        "Frame of {length} bytes exceeds maximum size of {MAX_FRAME_SIZE}"
+    ))
+}
+
+async fn read_raw(stream: &mut (impl AsyncRead + Unpin)) -> Result<Vec<u8>, NetworkError> {
+    let length: usize = stream.read_u32().await?.try_into().unwrap();
+    if length > MAX_FRAME_SIZE {
+        return Err(frame_too_large(length));
+    }
+
+    let mut buf = vec![0; length];
+    stream.read_exact(&mut buf).await?;
+
+    Ok(buf)
+}
+
+async fn write_raw(stream: &mut (impl AsyncWrite + Unpin), buf: &[u8]) -> Result<(), NetworkError> {
+    // The peer would only reject it, so fail here where it's easier to diagnose
+    if buf.len() > MAX_FRAME_SIZE {
+        return Err(frame_too_large(buf.len()));
+    }
+    stream.write_u32(buf.len().try_into().unwrap()).await?;
+    stream.write_all(buf).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Read a message in the framing used before the handshake existed
+pub(super) async fn read_legacy_message(
+    stream: &mut (impl AsyncRead + Unpin),
+) -> Result<Message, NetworkError> {
+    Ok(serde_json::from_slice(&read_raw(stream).await?)?)
+}
+
+/// Write a message in the framing used before the handshake existed
+pub(super) async fn write_legacy_message(
+    stream: &mut (impl AsyncWrite + Unpin),
+    message: &Message,
+) -> NetworkResult {
+    write_raw(stream, &serde_json::to_vec(message)?).await
+}
+
+/// Carry one conversation with a server from before the handshake existed, on a
+/// connection of its own, as that server expects. Messages from the peer are
+/// passed to `handler`, starting with `received` if the peer's first message has
+/// already been read, and the conversation ends when either side sends
+/// [`MessageDetail::Done`].
+pub(super) async fn legacy_conversation(
+    mut stream: impl AsyncRead + AsyncWrite + Unpin,
+    peer_name: ServerName,
+    mut received: Option<Message>,
+    handler: UnboundedSender<Request>,
+) -> NetworkResult {
+    let result = async {
+        loop {
+            let message = match received.take() {
+                Some(message) => message,
+                None => read_legacy_message(&mut stream).await?,
+            };
+            if matches!(message.content, MessageDetail::Done) {
+                return Ok(());
+            }
+
+            let (response, mut responses) = channel(8);
+            handler.send(Request {
+                received_from: peer_name,
+                response,
+                message,
+            })?;
+
+            while let Some(response) = responses.recv().await {
+                let done = matches!(response.content, MessageDetail::Done);
+                write_legacy_message(&mut stream, &response).await?;
+                if done {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    .await;
+
+    stream.shutdown().await.ok();
+    result
+}
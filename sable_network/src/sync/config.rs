@@ -6,8 +6,10 @@ use thiserror::Error;
 
 use std::{fs::File, io::BufReader, io::Read, net::SocketAddr, path::Path, path::PathBuf};
 
+use ed25519_dalek::SigningKey;
 use rustls::{Certificate, PrivateKey};
 
+use crate::network::event::ServerKey;
 use crate::validated::ServerName;
 
 /// Configuration of a peer in the gossip network
@@ -16,15 +18,91 @@ pub struct PeerConfig {
     pub(crate) name: ServerName,
     pub(crate) address: String,
     pub(crate) fingerprint: String,
+    /// The public key with which the server signs its events. If set, events
+    /// claiming to come from this server must be signed with it.
+    #[serde(default)]
+    pub(crate) signing_key: Option<ServerKey>,
 }
 
 /// Configuration of the gossip network
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SyncConfig {
     pub(crate) peers: Vec<PeerConfig>,
+    /// The number of peers to which each new event is sent, or the initial number
+    /// if `adaptive_fanout` is set
     pub(crate) fanout: usize,
+    #[serde(default)]
+    pub(crate) adaptive_fanout: Option<AdaptiveFanoutConfig>,
 
     pub(crate) ca_file: PathBuf,
+
+    #[serde(default)]
+    pub(crate) quorum: QuorumConfig,
+
+    /// Reject events that aren't signed by their origin server's pinned key
+    #[serde(default)]
+    pub(crate) require_signatures: bool,
+}
+
+/// Configuration for netsplit detection
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuorumConfig {
+    /// Whether to enter degraded mode when fewer than a majority of the configured
+    /// peers (including this server) are reachable
+    #[serde(default)]
+    pub(crate) enabled: bool,
+    /// Seconds since a server's last ping after which it counts as unreachable
+    #[serde(default = "QuorumConfig::default_peer_timeout")]
+    pub(crate) peer_timeout: i64,
+    /// Whether a degraded server should restart itself, with a new epoch, once the
+    /// rest of the network can be reached again
+    #[serde(default)]
+    pub(crate) auto_restart: bool,
+}
+
+impl QuorumConfig {
+    fn default_peer_timeout() -> i64 {
+        150
+    }
+}
+
+impl Default for QuorumConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            peer_timeout: Self::default_peer_timeout(),
+            auto_restart: false,
+        }
+    }
+}
+
+/// Configuration for automatic adjustment of the gossip fanout
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdaptiveFanoutConfig {
+    /// The fewest peers to send each event to
+    #[serde(default = "AdaptiveFanoutConfig::default_min")]
+    pub(crate) min: usize,
+    /// The most peers to send each event to
+    #[serde(default = "AdaptiveFanoutConfig::default_max")]
+    pub(crate) max: usize,
+    /// The average number of copies of each event a server should receive. Fewer
+    /// than this raises the fanout; half as many again lowers it.
+    #[serde(default = "AdaptiveFanoutConfig::default_target_redundancy")]
+    pub(crate) target_redundancy: f64,
+}
+
+impl AdaptiveFanoutConfig {
+    fn default_min() -> usize {
+        1
+    }
+
+    fn default_max() -> usize {
+        8
+    }
+
+    fn default_target_redundancy() -> f64 {
+        2.0
+    }
 }
 
 /// Configuration for this server's node in the gossip network
@@ -33,6 +111,10 @@ pub struct NodeConfig {
     pub(crate) listen_addr: SocketAddr,
     pub(crate) cert_file: PathBuf,
     pub(crate) key_file: PathBuf,
+    /// File holding this server's event signing key. If it doesn't exist, a new
+    /// key is generated and saved there; if unset, a new key is used each run.
+    #[serde(default)]
+    pub(crate) signing_key_file: Option<PathBuf>,
 }
 
 /// Configuration for the server's event log
@@ -127,4 +209,34 @@ impl NodeConfig {
 
         Ok((cert_chain, PrivateKey(client_key)))
     }
+
+    /// Load this node's event signing key, generating one if needed
+    pub fn load_signing_key(&self) -> Result<SigningKey, ConfigError> {
+        let Some(path) = &self.signing_key_file else {
+            return Ok(SigningKey::generate(&mut rand::rngs::OsRng));
+        };
+
+        match std::fs::read_to_string(path) {
+            Ok(contents) => {
+                let mut seed = [0; 32];
+                hex::decode_to_slice(contents.trim(), &mut seed)
+                    .map_err(|e| ConfigError::FormatError(e.to_string(), path.clone()))?;
+                Ok(SigningKey::from_bytes(&seed))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                use std::{io::Write, os::unix::fs::OpenOptionsExt};
+
+                let key = SigningKey::generate(&mut rand::rngs::OsRng);
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(path)
+                    .and_then(|mut file| file.write_all(hex::encode(key.to_bytes()).as_bytes()))
+                    .map_err(|e| ConfigError::IoError(e, path.clone()))?;
+                Ok(key)
+            }
+            Err(e) => Err(ConfigError::IoError(e, path.clone())),
+        }
+    }
 }
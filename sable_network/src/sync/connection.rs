@@ -0,0 +1,543 @@
+//! Persistent, multiplexed connections to peers in the gossip network
+//!
+//! Each pair of peers shares one long-lived TLS connection, over which any number of
+//! conversations can be in progress at once. A conversation is started by sending a
+//! message, and continues in both directions until either side sends
+//! [`MessageDetail::Done`] - exactly as a single short-lived connection used to.
+//! Frames on the wire carry a conversation ID so that responses can be routed back
+//! to the task that is waiting for them.
+
+use super::codec::WireCodec;
+use super::message::Message;
+use super::network::{NetworkError, NetworkResult};
+use super::*;
+use crate::validated::ServerName;
+
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    sync::Arc,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf},
+    select,
+    sync::{
+        mpsc::{
+            channel, error::TrySendError, unbounded_channel, Receiver, Sender, UnboundedReceiver,
+            UnboundedSender,
+        },
+        watch,
+    },
+    task::JoinHandle,
+    time::{interval, timeout, MissedTickBehavior},
+};
+
+/// How often to send a keepalive on an otherwise idle connection
+pub(super) const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+/// How long to wait without hearing anything before deciding a connection is dead
+const READ_TIMEOUT: Duration = Duration::from_secs(90);
+/// How long a conversation may wait for the other side to say something
+const CONVERSATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Maximum number of frames waiting to be written to a peer. Once this many are
+/// queued, further sends fail and the peer is reported as backlogged until the queue
+/// has drained to half this size.
+pub(super) const MAX_QUEUED_FRAMES: usize = 4096;
+
+/// A unit of data sent over a peer connection
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub(super) enum Frame {
+    /// A message belonging to a conversation.
+    ///
+    /// Conversation IDs are allocated by the side which starts the conversation, so
+    /// `from_initiator` is needed to tell apart conversations started by each end.
+    Message {
+        conversation: u64,
+        from_initiator: bool,
+        message: Message,
+    },
+    Ping,
+    Pong,
+}
+
+/// Identifies a conversation from the local point of view: its ID, and whether it
+/// was started by this end of the connection
+type ConversationKey = (u64, bool);
+
+/// One end of a long-lived connection to a peer
+pub(super) struct PeerConnection {
+    peer_name: ServerName,
+    initiator: ServerName,
+    outgoing: Sender<Frame>,
+    /// Set while the outgoing queue is full and hasn't yet drained
+    backlogged: AtomicBool,
+    on_backlog: Box<dyn Fn(bool) + Send + Sync>,
+    conversations: Mutex<HashMap<ConversationKey, UnboundedSender<Message>>>,
+    next_conversation: AtomicU64,
+    inbound_handler: UnboundedSender<Request>,
+    retired: AtomicBool,
+    shutdown: watch::Sender<bool>,
+}
+
+impl PeerConnection {
+    /// Take ownership of an authenticated stream to `peer_name`, and spawn the tasks
+    /// which read from and write to it.
+    ///
+    /// `initiator` is the name of the server which opened the connection, and `codec`
+    /// the wire encoding negotiated for it. Conversations started by the remote end are passed to `inbound_handler`;
+    /// `on_backlog` is called with `true` when the outgoing queue fills up and with
+    /// `false` once it has drained; `on_close` is called once the connection has shut down.
+    pub(super) fn spawn<S: AsyncRead + AsyncWrite + Send + 'static>(
+        stream: S,
+        peer_name: ServerName,
+        initiator: ServerName,
+        codec: WireCodec,
+        inbound_handler: UnboundedSender<Request>,
+        on_backlog: impl Fn(bool) + Send + Sync + 'static,
+        on_close: impl FnOnce(&Arc<PeerConnection>) + Send + 'static,
+    ) -> Arc<Self> {
+        let (outgoing_send, outgoing_recv) = channel(MAX_QUEUED_FRAMES);
+        let (shutdown_send, shutdown_recv) = watch::channel(false);
+        let (read_half, write_half) = tokio::io::split(stream);
+
+        let conn = Arc::new(Self {
+            peer_name,
+            initiator,
+            outgoing: outgoing_send,
+            backlogged: AtomicBool::new(false),
+            on_backlog: Box::new(on_backlog),
+            conversations: Mutex::new(HashMap::new()),
+            next_conversation: AtomicU64::new(0),
+            inbound_handler,
+            retired: AtomicBool::new(false),
+            shutdown: shutdown_send,
+        });
+
+        tokio::spawn(Arc::clone(&conn).write_loop(
+            codec,
+            write_half,
+            outgoing_recv,
+            shutdown_recv.clone(),
+        ));
+
+        let reader = Arc::clone(&conn);
+        tokio::spawn(async move {
+            if let Err(e) = Arc::clone(&reader)
+                .read_loop(codec, read_half, shutdown_recv)
+                .await
+            {
+                tracing::warn!("Connection to {} failed: {}", reader.peer_name, e);
+            }
+            reader.close();
+
+            // Dropping the senders ends any conversations still waiting for input
+            reader.conversations.lock().unwrap().clear();
+            // Whatever was queued is gone, so the peer is no longer behind
+            if reader.backlogged.swap(false, Ordering::SeqCst) {
+                (reader.on_backlog)(false);
+            }
+            tracing::debug!("Connection to {} closed", reader.peer_name);
+            on_close(&reader);
+        });
+
+        conn
+    }
+
+    pub(super) fn peer_name(&self) -> &ServerName {
+        &self.peer_name
+    }
+
+    pub(super) fn initiator(&self) -> &ServerName {
+        &self.initiator
+    }
+
+    /// The number of frames waiting to be written to the peer
+    pub(super) fn queued_frames(&self) -> usize {
+        self.outgoing.max_capacity() - self.outgoing.capacity()
+    }
+
+    pub(super) fn is_open(&self) -> bool {
+        !*self.shutdown.borrow()
+    }
+
+    /// Shut down this connection. Any conversations in progress will fail.
+    pub(super) fn close(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// Mark this connection as superseded by another to the same peer. No new
+    /// conversations will be started on it, and it will be closed once those in
+    /// progress have finished.
+    pub(super) fn retire(&self) {
+        self.retired.store(true, Ordering::SeqCst);
+    }
+
+    /// Start a new conversation by sending `message`. Messages received in reply are
+    /// passed to `response_handler`, and the returned task completes when the
+    /// conversation ends.
+    pub(super) fn start_conversation(
+        self: &Arc<Self>,
+        message: Message,
+        response_handler: UnboundedSender<Request>,
+    ) -> Result<JoinHandle<NetworkResult>, NetworkError> {
+        let key = (self.next_conversation.fetch_add(1, Ordering::Relaxed), true);
+        let (send, recv) = unbounded_channel();
+
+        self.conversations.lock().unwrap().insert(key, send);
+
+        if let Err(e) = self.send_in_conversation(key, message) {
+            self.conversations.lock().unwrap().remove(&key);
+            return Err(e);
+        }
+
+        Ok(tokio::spawn(Arc::clone(self).run_conversation(
+            key,
+            recv,
+            response_handler,
+        )))
+    }
+
+    fn send_in_conversation(&self, key: ConversationKey, message: Message) -> NetworkResult {
+        self.queue(Frame::Message {
+            conversation: key.0,
+            from_initiator: key.1,
+            message,
+        })
+    }
+
+    fn queue(&self, frame: Frame) -> NetworkResult {
+        match self.outgoing.try_send(frame) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                if !self.backlogged.swap(true, Ordering::SeqCst) {
+                    (self.on_backlog)(true);
+                }
+                Err(NetworkError::QueueFull(self.peer_name))
+            }
+            Err(TrySendError::Closed(_)) => Err(NetworkError::Send(format!(
+                "Connection to {} closed",
+                self.peer_name
+            ))),
+        }
+    }
+
+    async fn run_conversation(
+        self: Arc<Self>,
+        key: ConversationKey,
+        incoming: UnboundedReceiver<Message>,
+        handler: UnboundedSender<Request>,
+    ) -> NetworkResult {
+        let result = self.do_run_conversation(key, incoming, handler).await;
+
+        self.conversations.lock().unwrap().remove(&key);
+
+        if let Err(e) = &result {
+            tracing::error!(
+                "Error in network sync conversation with {}: {}",
+                self.peer_name,
+                e
+            );
+        }
+        result
+    }
+
+    async fn do_run_conversation(
+        &self,
+        key: ConversationKey,
+        mut incoming: UnboundedReceiver<Message>,
+        handler: UnboundedSender<Request>,
+    ) -> NetworkResult {
+        loop {
+            let msg = match timeout(CONVERSATION_TIMEOUT, incoming.recv()).await {
+                Ok(Some(msg)) => msg,
+                Ok(None) => {
+                    return Err(NetworkError::Send(format!(
+                        "Connection to {} closed",
+                        self.peer_name
+                    )))
+                }
+                Err(_) => return Err(NetworkError::Timeout),
+            };
+
+            if matches!(msg.content, MessageDetail::Done) {
+                return Ok(());
+            }
+
+            tracing::trace!("Processing inbound message: {:?}", msg);
+
+            let (req_send, mut req_recv) = channel(8);
+            let req = Request {
+                received_from: self.peer_name,
+                response: req_send,
+                message: msg,
+            };
+
+            handler.send(req)?;
+
+            while let Some(response) = req_recv.recv().await {
+                tracing::trace!("Sending network response: {:?}", response);
+                let done = matches!(response.content, MessageDetail::Done);
+
+                self.send_in_conversation(key, response)?;
+
+                if done {
+                    tracing::trace!("Got done, ending conversation");
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Route a message received from the peer to the conversation it belongs to,
+    /// starting a new one if the peer has opened it
+    fn dispatch(self: &Arc<Self>, conversation: u64, from_initiator: bool, message: Message) {
+        let key = (conversation, !from_initiator);
+        let mut conversations = self.conversations.lock().unwrap();
+
+        if let Some(sender) = conversations.get(&key) {
+            // If this fails then the conversation has just ended; there's nobody left
+            // to care about the message
+            sender.send(message).ok();
+        } else if from_initiator {
+            let (send, recv) = unbounded_channel();
+            send.send(message).ok();
+            conversations.insert(key, send);
+
+            tokio::spawn(Arc::clone(self).run_conversation(
+                key,
+                recv,
+                self.inbound_handler.clone(),
+            ));
+        } else {
+            tracing::debug!(
+                "Dropping message from {} for finished conversation {}",
+                self.peer_name,
+                conversation
+            );
+        }
+    }
+
+    async fn read_loop<S: AsyncRead + Send + 'static>(
+        self: Arc<Self>,
+        codec: WireCodec,
+        mut stream: ReadHalf<S>,
+        mut shutdown: watch::Receiver<bool>,
+    ) -> NetworkResult {
+        // Reading a frame isn't cancel-safe, so it's done in its own task rather than
+        // inside the select below
+        let (frame_send, mut frame_recv) = channel(16);
+        let reader = tokio::spawn(async move {
+            loop {
+                let frame = codec.read_frame(&mut stream).await;
+                let failed = frame.is_err();
+                if frame_send.send(frame).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        let mut idle_check = interval(KEEPALIVE_INTERVAL);
+        idle_check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut last_heard = Instant::now();
+
+        let result = loop {
+            select! {
+                frame = frame_recv.recv() => {
+                    last_heard = Instant::now();
+
+                    let frame = match frame {
+                        Some(Ok(frame)) => frame,
+                        Some(Err(e)) => break Err(e),
+                        None => break Ok(()),
+                    };
+
+                    match frame {
+                        Frame::Message { conversation, from_initiator, message } => {
+                            self.dispatch(conversation, from_initiator, message);
+                        }
+                        Frame::Ping => {
+                            // A full queue means there's traffic on the way anyway
+                            if let Err(e @ NetworkError::Send(_)) = self.queue(Frame::Pong) {
+                                break Err(e);
+                            }
+                        }
+                        Frame::Pong => {}
+                    }
+                }
+                _ = idle_check.tick() => {
+                    if last_heard.elapsed() > READ_TIMEOUT {
+                        break Err(NetworkError::Timeout);
+                    }
+                    if self.retired.load(Ordering::SeqCst)
+                        && self.conversations.lock().unwrap().is_empty()
+                    {
+                        tracing::debug!("Closing superseded connection to {}", self.peer_name);
+                        break Ok(());
+                    }
+                }
+                _ = shutdown.changed() => {
+                    break Ok(());
+                }
+            }
+        };
+
+        reader.abort();
+        result
+    }
+
+    async fn write_loop<S: AsyncWrite>(
+        self: Arc<Self>,
+        codec: WireCodec,
+        mut stream: WriteHalf<S>,
+        mut outgoing: Receiver<Frame>,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        let mut keepalive = interval(KEEPALIVE_INTERVAL);
+        keepalive.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut sent_since_tick = false;
+
+        loop {
+            let frame = select! {
+                frame = outgoing.recv() => {
+                    match frame {
+                        Some(frame) => {
+                            if self.queued_frames() <= MAX_QUEUED_FRAMES / 2
+                                && self.backlogged.swap(false, Ordering::SeqCst)
+                            {
+                                (self.on_backlog)(false);
+                            }
+                            frame
+                        }
+                        None => break,
+                    }
+                }
+                _ = keepalive.tick() => {
+                    if std::mem::take(&mut sent_since_tick) {
+                        continue;
+                    }
+                    Frame::Ping
+                }
+                _ = shutdown.changed() => break,
+            };
+
+            if let Err(e) = codec.write_frame(&mut stream, &frame).await {
+                tracing::warn!("Error writing to {}: {}", self.peer_name, e);
+                // Nothing more can be sent, so the conversations waiting on this
+                // connection are ended, and the next one opens a new connection
+                self.close();
+                break;
+            }
+            sent_since_tick = true;
+        }
+
+        // Whatever is still queued will never be sent
+        outgoing.close();
+        while outgoing.try_recv().is_ok() {}
+
+        stream.shutdown().await.ok();
+    }
+}
+
+/// The connection in use to each peer
+pub(super) struct ConnectionTable {
+    my_name: ServerName,
+    connections: Mutex<HashMap<ServerName, Arc<PeerConnection>>>,
+}
+
+impl ConnectionTable {
+    pub(super) fn new(my_name: ServerName) -> Self {
+        Self {
+            my_name,
+            connections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The open connection to the given peer, if there is one
+    pub(super) fn live(&self, name: &ServerName) -> Option<Arc<PeerConnection>> {
+        self.connections
+            .lock()
+            .ok()?
+            .get(name)
+            .filter(|conn| conn.is_open())
+            .cloned()
+    }
+
+    /// Make a new connection available for sending to its peer, and return the
+    /// connection that should be used.
+    ///
+    /// If both sides connect to each other at once, each keeps the connection opened
+    /// by whichever server has the lower name, so that they settle on the same one.
+    pub(super) fn register(&self, conn: Arc<PeerConnection>) -> Arc<PeerConnection> {
+        let peer_name = *conn.peer_name();
+        let preferred = std::cmp::min(self.my_name, peer_name);
+        let mut connections = self.connections.lock().expect("Connection table poisoned");
+
+        match connections.get(&peer_name) {
+            Some(existing)
+                if existing.is_open()
+                    && existing.initiator() == &preferred
+                    && conn.initiator() != &preferred =>
+            {
+                conn.retire();
+                Arc::clone(existing)
+            }
+            _ => {
+                if let Some(old) = connections.insert(peer_name, Arc::clone(&conn)) {
+                    old.retire();
+                }
+                conn
+            }
+        }
+    }
+
+    /// Forget a connection which has shut down, unless it has already been replaced
+    pub(super) fn closed(&self, conn: &Arc<PeerConnection>) {
+        if let Ok(mut connections) = self.connections.lock() {
+            if connections
+                .get(conn.peer_name())
+                .is_some_and(|c| Arc::ptr_eq(c, conn))
+            {
+                connections.remove(conn.peer_name());
+            }
+        }
+    }
+
+    /// Close the connection to the given peer, if any, without waiting for it to be idle
+    pub(super) fn drop_connection(&self, name: &ServerName) {
+        if let Ok(mut connections) = self.connections.lock() {
+            if let Some(conn) = connections.remove(name) {
+                conn.close();
+            }
+        }
+    }
+
+    /// Stop using the connection to the given peer, if any, and close it once the
+    /// conversations in progress on it have finished
+    pub(super) fn retire_connection(&self, name: &ServerName) {
+        if let Ok(mut connections) = self.connections.lock() {
+            if let Some(conn) = connections.remove(name) {
+                conn.retire();
+            }
+        }
+    }
+
+    pub(super) fn close_all(&self) {
+        if let Ok(mut connections) = self.connections.lock() {
+            for (_, conn) in connections.drain() {
+                conn.close();
+            }
+        }
+    }
+
+    /// The number of frames waiting to be written to each peer
+    pub(super) fn queued_frames(&self) -> Vec<(ServerName, usize)> {
+        self.connections
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, conn)| (*name, conn.queued_frames()))
+            .collect()
+    }
+}
@@ -1,11 +1,12 @@
 //! Contains the event log
 
+use super::signing::EventKeys;
 use crate::network::event::*;
 use crate::prelude::*;
 
 use chrono::prelude::*;
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     ops::Bound::*,
 };
 use tokio::sync::mpsc::UnboundedSender;
@@ -21,6 +22,7 @@ pub struct EventLog {
     id_gen: ObjectIdGenerator,
     event_sender: Option<UnboundedSender<Event>>,
     last_event_clock: EventClock,
+    keys: Option<EventKeys>,
 }
 
 /// Saved state for an [EventLog], used to save and restore across an upgrade
@@ -28,10 +30,13 @@ pub struct EventLog {
 pub struct EventLogState {
     id_gen: ObjectIdGenerator,
     clock: EventClock,
+    #[serde(default)]
+    keys: Option<EventKeys>,
 }
 
 #[derive(Debug, serde::Serialize)]
 pub struct EventLogStats {
+    pub stored_events: usize,
     pub pending_events: usize,
     pub current_clock: EventClock,
 }
@@ -47,6 +52,7 @@ impl EventLog {
             id_gen: idgen,
             event_sender,
             last_event_clock: EventClock::new(),
+            keys: None,
         }
     }
 
@@ -58,6 +64,7 @@ impl EventLog {
             id_gen: state.id_gen,
             event_sender,
             last_event_clock: state.clock,
+            keys: state.keys,
         }
     }
 
@@ -68,9 +75,28 @@ impl EventLog {
         EventLogState {
             id_gen: self.id_gen,
             clock: self.last_event_clock,
+            keys: self.keys,
         }
     }
 
+    /// Sign events created by this log with the given keys, and only accept
+    /// events whose signatures can be verified with them.
+    ///
+    /// A log without keys neither signs nor checks events.
+    pub fn set_keys(&mut self, keys: EventKeys) {
+        self.keys = Some(keys);
+    }
+
+    /// Access the keys used to sign and check events, if any
+    pub fn keys(&self) -> Option<&EventKeys> {
+        self.keys.as_ref()
+    }
+
+    /// Mutable access to the keys used to sign and check events, if any
+    pub fn keys_mut(&mut self) -> Option<&mut EventKeys> {
+        self.keys.as_mut()
+    }
+
     /// Return a single event.
     pub fn get(&self, id: &EventId) -> Option<&Event> {
         self.history.get(&id.server()).and_then(|x| x.get(id))
@@ -101,11 +127,37 @@ impl EventLog {
     /// Return some statistics about the event log
     pub fn get_stats(&self) -> EventLogStats {
         EventLogStats {
+            stored_events: self.history.values().map(BTreeMap::len).sum(),
             pending_events: self.pending.len(),
             current_clock: self.last_event_clock.clone(),
         }
     }
 
+    /// Find the most recent event in the log targeting each of the given
+    /// objects. Objects with no such event still in the log are left out.
+    pub fn latest_events_for(&self, targets: &HashSet<ObjectId>) -> HashMap<ObjectId, EventId> {
+        let mut latest: HashMap<ObjectId, &Event> = HashMap::new();
+
+        for event in self.history.values().flat_map(BTreeMap::values) {
+            if !targets.contains(&event.target) {
+                continue;
+            }
+            latest
+                .entry(event.target)
+                .and_modify(|prev| {
+                    if (event.timestamp, event.id) > (prev.timestamp, prev.id) {
+                        *prev = event;
+                    }
+                })
+                .or_insert(event);
+        }
+
+        latest
+            .into_iter()
+            .map(|(target, event)| (target, event.id))
+            .collect()
+    }
+
     #[cfg(feature = "debug")]
     /// Access the full list of events stored in the log
     pub fn all_events(&self) -> impl Iterator<Item = &Event> {
@@ -130,6 +182,9 @@ impl EventLog {
     /// Add an event to the log.
     ///
     /// - If the event ID already exists within the log, do nothing.
+    /// - If the log has signing keys, the event is only accepted once its
+    ///   signature has been checked against its origin server's key, at the
+    ///   point its dependencies are satisfied.
     /// - If the event's dependencies (as denoted by the embedded event clock)
     ///   are all already present in the log, then immediately add it to the
     ///   log, update the log's event clock to reflect the newly added event,
@@ -166,14 +221,23 @@ impl EventLog {
     /// Create an [`Event`] with the provided details. The resulting ID,
     /// timestamp and dependency clock will be generated based on the current
     /// state of the log.
+    ///
+    /// If the log has signing keys, the event is signed with them.
     pub fn create(&self, target: impl Into<ObjectId>, details: impl Into<EventDetails>) -> Event {
-        Event {
+        let mut event = Event {
             id: self.id_gen.next(),
             timestamp: Utc::now().timestamp(),
             clock: self.last_event_clock.clone(),
             target: target.into(),
             details: details.into(),
+            signature: None,
+        };
+
+        if let Some(keys) = &self.keys {
+            keys.sign(&mut event);
         }
+
+        event
     }
 
     /// Remove events older than the provided timestamp
@@ -184,6 +248,15 @@ impl EventLog {
     }
 
     fn do_add(&mut self, e: Event) {
+        // Checked only once dependencies are satisfied, so that any `NewServer`
+        // event announcing the origin's current key has already been seen
+        if let Some(keys) = &mut self.keys {
+            if !keys.check(&e) {
+                tracing::warn!(?e, "Rejecting event with missing or invalid signature");
+                return;
+            }
+        }
+
         let s = e.id.server();
         let id = e.id;
 
@@ -223,6 +296,8 @@ impl EventLog {
                 ret.push(*v);
             }
         }
+        // The clock is unordered, but requests for these should be the same each time
+        ret.sort();
         ret
     }
 
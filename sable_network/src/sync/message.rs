@@ -1,5 +1,6 @@
 //! Contains the message types for the synchronisation protocol
 
+use super::state_transfer::{StateChunk, StateTransferHeader};
 use crate::prelude::*;
 
 use serde::{Deserialize, Serialize};
@@ -23,6 +24,16 @@ pub enum MessageDetail {
     GetNetworkState,
     /// Response containing the current network state
     NetworkState(Box<Network>),
+    /// Request to begin a chunked transfer of the current network state
+    StartStateTransfer,
+    /// Request for the remaining chunks of an interrupted state transfer
+    ResumeStateTransfer { transfer: u64, from: usize },
+    /// Describes the state transfer whose chunks are about to follow
+    StateTransferHeader(StateTransferHeader),
+    /// One chunk of a state transfer
+    StateChunk(StateChunk),
+    /// The requested state transfer is no longer available, and must be restarted
+    StateTransferUnavailable,
     /// A message targeted to a specific server
     TargetedMessage(TargetedMessage),
     /// A response to a targeted message
@@ -79,6 +90,8 @@ impl Message {
             MessageDetail::SyncRequest(_)
                 | MessageDetail::GetEvent(_)
                 | MessageDetail::GetNetworkState
+                | MessageDetail::StartStateTransfer
+                | MessageDetail::ResumeStateTransfer { .. }
         )
     }
 }
@@ -1,17 +1,25 @@
 //! This crate contains the code required to manage the ircd's event log and
 //! synchronise it with other servers.
 
+mod codec;
 mod config;
+mod connection;
 mod eventlog;
 mod message;
 mod network;
+mod propagation;
 
 mod replicated_log;
+mod signing;
+mod state_transfer;
+mod transport;
 
+pub use config::AdaptiveFanoutConfig;
 pub use config::ConfigError;
 pub use config::EventLogConfig;
 pub use config::NodeConfig;
 pub use config::PeerConfig;
+pub use config::QuorumConfig;
 pub use config::SyncConfig;
 pub use eventlog::EventLog;
 pub use eventlog::EventLogState;
@@ -22,10 +30,22 @@ pub use message::Request;
 pub use network::GossipNetwork;
 pub use network::GossipNetworkState;
 pub use network::NetworkError;
+pub use propagation::AdaptiveFanout;
+pub use propagation::PeerPropagationStats;
+pub use propagation::PropagationStats;
 
 pub use replicated_log::EventLogSaveError;
 pub use replicated_log::ReplicatedEventLog;
 pub use replicated_log::ReplicatedEventLogState;
 
+pub use signing::EventKeys;
+
+pub use state_transfer::StateChunk;
+pub use state_transfer::StateSectionInfo;
+pub use state_transfer::StateTransferError;
+pub use state_transfer::StateTransferHeader;
+
+#[cfg(test)]
+pub(crate) mod simulator;
 #[cfg(test)]
 mod tests;
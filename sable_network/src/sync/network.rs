@@ -1,66 +1,172 @@
 //! Networking code for the sync protocol
 
 use super::message::Message;
+use super::propagation::{AdaptiveFanout, PeerPropagationStats, PropagationStats};
+use super::transport::{TlsTransport, Transport};
 use super::*;
-use crate::validated::{ServerName, Validated};
+use crate::validated::ServerName;
 
 use futures::future;
 use std::{
-    convert::TryInto,
-    net::{IpAddr, SocketAddr, SocketAddrV6},
-    sync::atomic::{AtomicBool, Ordering},
-    sync::Arc,
-    sync::Mutex,
+    collections::{BTreeMap, HashMap},
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
 };
 use tokio::{
     io,
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::{lookup_host, TcpListener, TcpSocket, TcpStream},
-    select,
-    sync::{
-        mpsc::{channel, UnboundedSender},
-        oneshot,
-    },
+    sync::mpsc::UnboundedSender,
     task::{JoinError, JoinHandle},
 };
-use tokio_rustls::{TlsAcceptor, TlsConnector, TlsStream};
-
-use rustls::{server::AllowAnyAuthenticatedClient, ClientConfig, ServerConfig};
-use sha1::{Digest, Sha1};
-use x509_parser::prelude::*;
 
 use rand::prelude::*;
 use thiserror::Error;
 use tracing::instrument;
 
 /// An interface to the gossip network used to synchronise state.
+///
+/// This keeps track of the configured peers and decides which of them to talk
+/// to; the conversations themselves are carried by a [`Transport`].
 pub struct GossipNetwork {
-    fanout: usize,
-    tls_client_config: Arc<ClientConfig>,
-    shutdown_send: Mutex<Option<oneshot::Sender<()>>>,
-    task_state: Arc<NetworkTaskState>,
+    fanout: Mutex<AdaptiveFanout>,
+    quorum: QuorumConfig,
+    peers: Arc<PeerList>,
+    stats: Arc<PeerStats>,
+    transport: Arc<dyn Transport>,
+    message_sender: UnboundedSender<Request>,
+    /// Used to choose peers
+    rng: Mutex<StdRng>,
     me: PeerConfig,
 }
 
-/// State that's shared between the listener task and client code
-///
-/// Note that all additions to this struct must keep it `Send` and `Sync`.
-struct NetworkTaskState {
-    peers: Vec<Peer>,
-    listen_addr: SocketAddr,
-    tls_server_config: Arc<ServerConfig>,
-    message_sender: UnboundedSender<Request>,
+type PeerStats = Mutex<HashMap<ServerName, PeerPropagationStats>>;
+
+/// Initial and maximum delay between reconnection attempts to a failing peer
+pub(super) const RECONNECT_BACKOFF_MIN: Duration = Duration::from_millis(250);
+pub(super) const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// The configured peers, shared between the network and its transport
+pub(super) struct PeerList(RwLock<Vec<Arc<Peer>>>);
+
+impl PeerList {
+    fn new(peers: Vec<PeerConfig>) -> Self {
+        Self(RwLock::new(
+            peers.into_iter().map(|c| Arc::new(Peer::new(c))).collect(),
+        ))
+    }
+
+    /// A snapshot of the current peer list
+    pub(super) fn all(&self) -> Vec<Arc<Peer>> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub(super) fn get(&self, name: &ServerName) -> Option<Arc<Peer>> {
+        self.0
+            .read()
+            .unwrap()
+            .iter()
+            .find(|p| &p.conf.name == name)
+            .cloned()
+    }
 }
 
-struct Peer {
-    conf: PeerConfig,
+pub(super) struct Peer {
+    pub(super) conf: PeerConfig,
     enabled: AtomicBool,
+    /// Set if this peer was disabled because it couldn't be reached or wasn't keeping
+    /// up, rather than because it left the network, and should be re-enabled once it
+    /// recovers
+    pub(super) suspended: AtomicBool,
+    /// Consecutive failed connection attempts
+    failures: AtomicU32,
+    /// Earliest time at which another connection attempt should be made
+    retry_after: Mutex<Option<Instant>>,
+    /// Held while establishing a connection, so that concurrent senders share one
+    pub(super) connect_lock: tokio::sync::Mutex<()>,
+    /// Set while this peer is running a version from before the connection
+    /// handshake, and must be sent each conversation on a connection of its own
+    legacy: AtomicBool,
+}
+
+impl Peer {
+    pub(super) fn new(conf: PeerConfig) -> Self {
+        Self {
+            conf,
+            enabled: AtomicBool::new(false),
+            suspended: AtomicBool::new(false),
+            failures: AtomicU32::new(0),
+            retry_after: Mutex::new(None),
+            connect_lock: tokio::sync::Mutex::new(()),
+            legacy: AtomicBool::new(false),
+        }
+    }
+
+    pub(super) fn is_legacy(&self) -> bool {
+        self.legacy.load(Ordering::SeqCst)
+    }
+
+    /// Record whether this peer predates the connection handshake, returning
+    /// whether that has changed
+    pub(super) fn set_legacy(&self, legacy: bool) -> bool {
+        self.legacy.swap(legacy, Ordering::SeqCst) != legacy
+    }
+
+    pub(super) fn is_available(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    /// If a connection attempt shouldn't be made yet, how long until it should
+    pub(super) fn backoff_remaining(&self) -> Option<Duration> {
+        self.retry_after
+            .lock()
+            .unwrap()
+            .and_then(|t| t.checked_duration_since(Instant::now()))
+    }
+
+    pub(super) fn connection_succeeded(&self) {
+        *self.retry_after.lock().unwrap() = None;
+        self.failures.store(0, Ordering::SeqCst);
+    }
+
+    /// Record a failed connection attempt, returning the number of consecutive failures
+    pub(super) fn connection_failed(&self) -> u32 {
+        let failures = self.failures.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let delay = RECONNECT_BACKOFF_MIN
+            .saturating_mul(1u32 << (failures - 1).min(16))
+            .min(RECONNECT_BACKOFF_MAX);
+        *self.retry_after.lock().unwrap() = Some(Instant::now() + delay);
+
+        failures
+    }
+
+    /// Disable this peer because of a connection problem, returning whether it was
+    /// previously enabled
+    pub(super) fn suspend(&self) -> bool {
+        let was_enabled = self.enabled.swap(false, Ordering::SeqCst);
+        if was_enabled {
+            self.suspended.store(true, Ordering::SeqCst);
+        }
+        was_enabled
+    }
+
+    /// Re-enable this peer if it was suspended, returning whether it was
+    pub(super) fn resume(&self) -> bool {
+        let was_suspended = self.suspended.swap(false, Ordering::SeqCst);
+        if was_suspended {
+            self.enabled.store(true, Ordering::SeqCst);
+        }
+        was_suspended
+    }
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct GossipNetworkState {
     server_name: ServerName,
     peer_states: Vec<(ServerName, bool)>,
+    /// The full peer list at the time of saving, including any changed at runtime
+    #[serde(default)]
+    peers: Vec<PeerConfig>,
 }
 
 #[derive(Debug, Error)]
@@ -71,6 +177,8 @@ pub enum NetworkError {
     Send(String),
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("Encoding error: {0}")]
+    Bincode(#[from] bincode::Error),
     #[error("Error joining task: {0}")]
     Join(#[from] JoinError),
     #[error("Listen task already spawned")]
@@ -83,6 +191,14 @@ pub enum NetworkError {
     Timeout,
     #[error("No address found when resolving {0}")]
     NoAddress(String),
+    #[error("Unknown peer {0}")]
+    UnknownPeer(ServerName),
+    #[error("Waiting {0:?} before reconnecting to {1}")]
+    Backoff(Duration, ServerName),
+    #[error("Send queue to {0} is full")]
+    QueueFull(ServerName),
+    #[error("Peer closed the connection without a handshake")]
+    NoHandshake,
 }
 pub type NetworkResult = Result<(), NetworkError>;
 
@@ -101,51 +217,59 @@ impl GossipNetwork {
     ) -> Self {
         let ca_cert = net_config.load_ca_cert().expect("Error loading CA");
 
-        let (client_cert, client_key) = node_config
-            .load_cert_and_keys()
-            .expect("Error loading client cert");
-
-        let mut root_store = rustls::RootCertStore::empty();
-        root_store
-            .add(&ca_cert)
-            .expect("Error adding certificate to store");
-
-        let client_config = ClientConfig::builder()
-            .with_safe_defaults()
-            .with_root_certificates(root_store.clone())
-            .with_single_cert(client_cert.clone(), client_key.clone())
-            .expect("Bad TLS client config");
-
-        let server_config = ServerConfig::builder()
-            .with_safe_defaults()
-            .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(root_store.clone()))
-            .with_single_cert(client_cert, client_key)
-            .expect("Bad TLS server config");
+        Self::with_transport(
+            server_name,
+            net_config,
+            message_sender,
+            StdRng::from_entropy(),
+            |my_name, peers, message_sender| {
+                Arc::new(TlsTransport::new(
+                    my_name,
+                    ca_cert,
+                    node_config,
+                    peers,
+                    message_sender,
+                ))
+            },
+        )
+    }
 
+    /// Create an instance which reaches its peers through the transport returned
+    /// by `transport`. That is given this server's name, the peer list against
+    /// which to authorise peers, and the channel for conversations they start.
+    ///
+    /// Peers are chosen using `rng`.
+    pub(super) fn with_transport(
+        server_name: &ServerName,
+        net_config: SyncConfig,
+        message_sender: UnboundedSender<Request>,
+        rng: StdRng,
+        transport: impl FnOnce(
+            ServerName,
+            Arc<PeerList>,
+            UnboundedSender<Request>,
+        ) -> Arc<dyn Transport>,
+    ) -> Self {
         let mut peers = net_config.peers;
         let my_index = peers
             .iter()
             .position(|p| &p.name == server_name)
             .expect("Couldn't find myself in the network config");
         let me = peers.remove(my_index);
+        let peers = Arc::new(PeerList::new(peers));
 
         Self {
-            fanout: net_config.fanout,
-            tls_client_config: Arc::new(client_config),
-            shutdown_send: Mutex::new(None),
+            fanout: Mutex::new(AdaptiveFanout::new(
+                net_config.fanout,
+                net_config.adaptive_fanout,
+            )),
+            quorum: net_config.quorum,
+            transport: transport(me.name, Arc::clone(&peers), message_sender.clone()),
+            peers,
+            stats: Arc::new(Mutex::new(HashMap::new())),
+            message_sender,
+            rng: Mutex::new(rng),
             me,
-            task_state: Arc::new(NetworkTaskState {
-                listen_addr: node_config.listen_addr,
-                peers: peers
-                    .into_iter()
-                    .map(|c| Peer {
-                        conf: c,
-                        enabled: AtomicBool::new(false),
-                    })
-                    .collect(),
-                tls_server_config: Arc::new(server_config),
-                message_sender,
-            }),
         }
     }
 
@@ -157,6 +281,13 @@ impl GossipNetwork {
     ) -> Self {
         let ret = Self::new(&state.server_name, net_config, node_config, message_sender);
 
+        // Peers added at runtime aren't in the config file, but are still part of the network
+        for peer in state.peers {
+            if ret.peers.get(&peer.name).is_none() {
+                ret.add_peer(peer);
+            }
+        }
+
         for (peer, enabled) in state.peer_states {
             if enabled {
                 ret.enable_peer(&peer);
@@ -170,31 +301,82 @@ impl GossipNetwork {
         GossipNetworkState {
             server_name: self.me.name,
             peer_states: self
-                .task_state
                 .peers
+                .all()
                 .iter()
                 .map(|peer| (peer.conf.name, peer.enabled.load(Ordering::SeqCst)))
                 .collect(),
+            peers: self.peers.all().iter().map(|p| p.conf.clone()).collect(),
         }
     }
 
     pub fn shutdown(&self) {
-        if let Ok(mut shutdown_send) = self.shutdown_send.lock() {
-            if let Some(sender) = shutdown_send.take() {
-                sender.send(()).ok();
-            }
-        }
+        self.transport.shutdown();
     }
 
     pub fn me(&self) -> &PeerConfig {
         &self.me
     }
 
+    pub fn quorum_config(&self) -> &QuorumConfig {
+        &self.quorum
+    }
+
+    /// The names of all configured peers, not including this server
+    pub fn peer_names(&self) -> Vec<ServerName> {
+        self.peers.all().iter().map(|p| p.conf.name).collect()
+    }
+
+    /// Add a peer to the sync network, or replace the configuration of an existing
+    /// one. Connections to and from the peer are authorised against the new
+    /// configuration from now on.
+    pub fn add_peer(&self, conf: PeerConfig) {
+        if conf.name == self.me.name {
+            return;
+        }
+
+        tracing::info!("Updating sync peer {} ({})", conf.name, conf.address);
+
+        let name = conf.name;
+        let peer = Peer::new(conf);
+        {
+            let mut peers = self.peers.0.write().unwrap();
+            if let Some(existing) = peers.iter_mut().find(|p| p.conf.name == name) {
+                // A server that's already part of the network stays enabled
+                peer.enabled
+                    .store(existing.enabled.load(Ordering::SeqCst), Ordering::SeqCst);
+                *existing = Arc::new(peer);
+            } else {
+                peers.push(Arc::new(peer));
+            }
+        }
+
+        // Any existing connection was authorised against the old configuration
+        self.transport.drop_connection(&name);
+    }
+
+    /// Remove a peer from the sync network, closing any connection to it
+    pub fn remove_peer(&self, name: &ServerName) {
+        tracing::info!("Removing sync peer {}", name);
+
+        self.peers
+            .0
+            .write()
+            .unwrap()
+            .retain(|p| &p.conf.name != name);
+        self.stats.lock().unwrap().remove(name);
+
+        self.transport.drop_connection(name);
+    }
+
     pub fn enable_peer(&self, name: &ServerName) {
         tracing::debug!("enabling peer {}", name);
-        for p in self.task_state.peers.iter() {
+        for p in self.peers.all().iter() {
             if &p.conf.name == name {
                 p.enabled.store(true, Ordering::SeqCst);
+                p.suspended.store(false, Ordering::SeqCst);
+                // A server that's (re)joined the network gets a fresh start
+                p.connection_succeeded();
             }
         }
     }
@@ -202,22 +384,33 @@ impl GossipNetwork {
     pub fn disable_peer(&self, name: &ServerName) {
         tracing::debug!("disabling peer {}", name);
 
-        for p in self.task_state.peers.iter() {
+        for p in self.peers.all().iter() {
             if &p.conf.name == name {
                 p.enabled.store(false, Ordering::SeqCst);
+                // Explicitly disabled, so it mustn't come back when it recovers
+                p.suspended.store(false, Ordering::SeqCst);
             }
         }
+
+        // There's no further use for a connection to a server that's left the network
+        self.transport.retire_connection(name);
+    }
+
+    /// Whether the named peer is enabled, and hasn't been suspended for being
+    /// unreachable or backlogged
+    pub fn peer_is_healthy(&self, name: &ServerName) -> bool {
+        self.peers.get(name).is_some_and(|p| p.is_available())
     }
 
     #[instrument(skip_all)]
-    pub fn choose_peer(&self) -> Option<&PeerConfig> {
+    pub fn choose_peer(&self) -> Option<PeerConfig> {
         let ret = self
-            .task_state
             .peers
+            .all()
             .iter()
-            .filter(|p| p.enabled.load(Ordering::SeqCst))
-            .choose(&mut rand::thread_rng())
-            .map(|p| &p.conf);
+            .filter(|p| p.is_available())
+            .choose(&mut *self.rng.lock().unwrap())
+            .map(|p| p.conf.clone());
 
         if ret.is_none() {
             tracing::info!("No active peer available to choose");
@@ -226,13 +419,13 @@ impl GossipNetwork {
     }
 
     #[instrument(skip_all)]
-    pub fn choose_any_peer(&self) -> Option<&PeerConfig> {
+    pub fn choose_any_peer(&self) -> Option<PeerConfig> {
         let ret = self
-            .task_state
             .peers
+            .all()
             .iter()
-            .choose(&mut rand::thread_rng())
-            .map(|p| &p.conf);
+            .choose(&mut *self.rng.lock().unwrap())
+            .map(|p| p.conf.clone());
 
         if ret.is_none() {
             tracing::info!("No peer available to choose");
@@ -241,14 +434,14 @@ impl GossipNetwork {
     }
 
     /// Choose a peer at random that isn't in the provided list
-    pub fn choose_peer_except(&self, except: &[ServerName]) -> Option<&PeerConfig> {
+    pub fn choose_peer_except(&self, except: &[ServerName]) -> Option<PeerConfig> {
         let ret = self
-            .task_state
             .peers
+            .all()
             .iter()
-            .filter(|p| p.enabled.load(Ordering::SeqCst) && !except.contains(&p.conf.name))
-            .choose(&mut rand::thread_rng())
-            .map(|p| &p.conf);
+            .filter(|p| p.is_available() && !except.contains(&p.conf.name))
+            .choose(&mut *self.rng.lock().unwrap())
+            .map(|p| p.conf.clone());
 
         if ret.is_none() {
             tracing::info!("No active peer available to choose");
@@ -257,14 +450,14 @@ impl GossipNetwork {
     }
 
     /// Find a peer config with the given server name
-    pub fn find_peer(&self, name: &ServerName) -> Option<&PeerConfig> {
+    pub fn find_peer(&self, name: &ServerName) -> Option<PeerConfig> {
         let ret = self
-            .task_state
             .peers
+            .all()
             .iter()
-            .filter(|p| p.enabled.load(Ordering::Relaxed))
+            .filter(|p| p.is_available())
             .find(|p| &p.conf.name == name)
-            .map(|p| &p.conf);
+            .map(|p| p.conf.clone());
 
         if ret.is_none() {
             tracing::info!("No peer named {} available", name);
@@ -274,25 +467,92 @@ impl GossipNetwork {
 
     pub async fn propagate(&self, msg: &Message) {
         let mut tasks = Vec::new();
+        let fanout = self.fanout.lock().unwrap().current();
 
         let chosen_peers = self
-            .task_state
             .peers
+            .all()
             .iter()
-            .filter(|p| p.enabled.load(Ordering::SeqCst))
-            .choose_multiple(&mut rand::thread_rng(), self.fanout);
+            .filter(|p| p.is_available())
+            .choose_multiple(&mut *self.rng.lock().unwrap(), fanout)
+            .into_iter()
+            .map(|p| p.conf.clone())
+            .collect::<Vec<_>>();
 
         if chosen_peers.is_empty() {
             tracing::info!("No peers available to propagate message");
         }
 
-        for peer in chosen_peers {
-            tasks.push(self.send_to(&peer.conf, msg.clone()));
+        for peer in &chosen_peers {
+            tasks.push(self.send_measured(peer, msg.clone()));
         }
 
         future::join_all(tasks).await;
     }
 
+    /// Send a new event to a peer, recording how long it takes to be accepted
+    async fn send_measured(&self, peer: &PeerConfig, msg: Message) {
+        let started = Instant::now();
+        let name = peer.name;
+
+        match self.send_to(peer, msg).await {
+            Ok(handle) => {
+                let stats = Arc::clone(&self.stats);
+                tokio::spawn(async move {
+                    let delivered = matches!(handle.await, Ok(Ok(())));
+                    record_sent(&stats, &name, delivered.then(|| started.elapsed()));
+                });
+            }
+            Err(_) => record_sent(&self.stats, &name, None),
+        }
+    }
+
+    /// Record the arrival of a new event from a peer, and whether we'd already
+    /// received it from another
+    pub fn record_received(&self, from: &ServerName, duplicate: bool) {
+        self.stats
+            .lock()
+            .unwrap()
+            .entry(*from)
+            .or_default()
+            .record_received(duplicate);
+        self.fanout.lock().unwrap().record_received(duplicate);
+    }
+
+    /// Re-evaluate the fanout based on the current number of reachable peers and
+    /// recent delivery redundancy
+    pub fn adjust_fanout(&self) {
+        let available = self.peers.all().iter().filter(|p| p.is_available()).count();
+
+        let mut fanout = self.fanout.lock().unwrap();
+        let previous = fanout.current();
+        let current = fanout.adjust(available);
+        if current != previous {
+            tracing::debug!(previous, current, available, "Adjusted gossip fanout");
+        }
+    }
+
+    /// Statistics on event propagation to and from each peer
+    pub fn propagation_stats(&self) -> PropagationStats {
+        let mut peers: BTreeMap<_, _> = self
+            .stats
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, stats)| (*name, stats.clone()))
+            .collect();
+        for (name, queued) in self.transport.queued_messages() {
+            peers.entry(name).or_default().send_queue = queued;
+        }
+
+        let fanout = self.fanout.lock().unwrap();
+        PropagationStats {
+            fanout: fanout.current(),
+            adaptive: fanout.is_adaptive(),
+            peers,
+        }
+    }
+
     pub async fn send_to(
         &self,
         peer: &PeerConfig,
@@ -300,7 +560,7 @@ impl GossipNetwork {
     ) -> Result<JoinHandle<NetworkResult>, NetworkError> {
         tracing::trace!("Sending to {:?}: {:?}", peer.address, msg);
         let result = self
-            .do_send_to(peer, msg, self.task_state.message_sender.clone())
+            .do_send_to(peer, msg, self.message_sender.clone())
             .await;
 
         if let Err(e) = &result {
@@ -320,49 +580,6 @@ impl GossipNetwork {
         self.do_send_to(peer, msg, response_sender).await
     }
 
-    fn get_socket_for_addr(addr: &SocketAddr) -> std::io::Result<TcpSocket> {
-        match addr {
-            SocketAddr::V4(_) => TcpSocket::new_v4(),
-            SocketAddr::V6(_) => TcpSocket::new_v6(),
-        }
-    }
-
-    async fn connect(local_addr: &SocketAddr, host_addr: &str) -> Result<TcpStream, NetworkError> {
-        // Default error if the loop does not run at all
-        let mut last_err = NetworkError::NoAddress(host_addr.to_string());
-
-        for ip_addr in lookup_host(host_addr).await? {
-            // Ensure ip_addr is an IPv6 address if the socket binds an IPv6 address
-            let ip_addr = match (local_addr, ip_addr) {
-                (SocketAddr::V6(_), SocketAddr::V4(ipv4_addr)) => SocketAddr::V6(
-                    SocketAddrV6::new(ipv4_addr.ip().to_ipv6_mapped(), ipv4_addr.port(), 0, 0),
-                ),
-                (_, ip_addr) => ip_addr,
-            };
-
-            let socket = Self::get_socket_for_addr(local_addr)?;
-            socket.bind(*local_addr)?;
-            match socket.connect(ip_addr).await {
-                Ok(conn) => {
-                    tracing::info!("Connected to {} ({})", host_addr, ip_addr);
-                    return Ok(conn);
-                }
-                Err(err) => {
-                    tracing::trace!("Could not connect to {} ({}): {}", host_addr, ip_addr, err);
-                    last_err = err.into();
-                }
-            }
-        }
-
-        tracing::error!(
-            "Could not connect to {}. Last error: {}",
-            host_addr,
-            last_err
-        );
-
-        Err(last_err)
-    }
-
     #[instrument(err, skip(self, peer, response_sender), fields(peer=%peer.name))]
     async fn do_send_to(
         &self,
@@ -370,228 +587,22 @@ impl GossipNetwork {
         msg: Message,
         response_sender: UnboundedSender<Request>,
     ) -> Result<JoinHandle<NetworkResult>, NetworkError> {
-        let mut local_addr = self.task_state.listen_addr;
-        local_addr.set_port(0);
-        let connector = TlsConnector::from(Arc::clone(&self.tls_client_config));
-        let conn = Self::connect(&local_addr, &peer.address).await?;
-        let server_name = (peer.name.value() as &str)
-            .try_into()
-            .expect("Invalid server name");
-        let stream = connector.connect(server_name, conn).await?;
-
-        let task_state = Arc::clone(&self.task_state);
-        Ok(tokio::spawn(async move {
-            let result = task_state
-                .send_and_handle_response(stream.into(), msg, response_sender)
-                .await;
-
-            if let Err(e) = &result {
-                tracing::error!("Error in outbound network sync connection: {}", e);
-            }
-
-            result
-        }))
+        self.transport
+            .start_conversation(peer, msg, response_sender)
+            .await
     }
 
     pub async fn spawn_listen_task(&self) -> Result<JoinHandle<()>, NetworkError> {
-        let listener = TcpListener::bind(self.task_state.listen_addr).await?;
-        let task_state = Arc::clone(&self.task_state);
-
-        let (shutdown_send, shutdown_recv) = oneshot::channel();
-        {
-            let mut guard = self
-                .shutdown_send
-                .lock()
-                .map_err(|e| NetworkError::InternalError(e.to_string()))?;
-            if guard.is_some() {
-                Err(NetworkError::AlreadyListening)?;
-            }
-            let _ = guard.insert(shutdown_send);
-        }
-
-        Ok(tokio::spawn(async move {
-            if let Err(e) = task_state.listen_loop(listener, shutdown_recv).await {
-                tracing::error!("Error in network sync listener: {}", e);
-            }
-        }))
+        self.transport.listen().await
     }
 }
 
-impl NetworkTaskState {
-    #[instrument(skip(self))]
-    async fn listen_loop(
-        self: Arc<NetworkTaskState>,
-        listener: TcpListener,
-        mut shutdown: oneshot::Receiver<()>,
-    ) -> Result<(), io::Error> {
-        let tls_acceptor = TlsAcceptor::from(Arc::clone(&self.tls_server_config));
-
-        loop {
-            select! {
-                res = listener.accept() =>
-                {
-                    if let Ok((conn, _)) = res
-                    {
-                        let tls_acceptor = tls_acceptor.clone();
-                        let sender = self.message_sender.clone();
-                        let self_copy = Arc::clone(&self);
-                        tokio::spawn(async move {
-                            if let Err(e) = self_copy.handle_connection(tls_acceptor, conn, sender).await
-                            {
-                                tracing::error!("Error in network sync connection handler: {}", e);
-                            }
-                        });
-                    }
-                },
-                _ = &mut shutdown =>
-                {
-                    break
-                }
-            }
-        }
-
-        Ok(())
-    }
-
-    #[instrument(skip(tls_acceptor, self))]
-    async fn handle_connection(
-        self: Arc<NetworkTaskState>,
-        tls_acceptor: TlsAcceptor,
-        conn: TcpStream,
-        message_sender: UnboundedSender<Request>,
-    ) -> Result<(), NetworkError> {
-        let stream = tls_acceptor.accept(conn).await?;
-
-        if let Err(e) = self
-            .read_and_handle_message(stream.into(), message_sender)
-            .await
-        {
-            tracing::error!("Error handling message: {}", e);
-        }
-
-        Ok(())
-    }
-
-    #[instrument(skip(self))]
-    async fn send_and_handle_response(
-        self: Arc<NetworkTaskState>,
-        mut stream: TlsStream<TcpStream>,
-        message: Message,
-        response_sender: UnboundedSender<Request>,
-    ) -> Result<(), NetworkError> {
-        let buf = serde_json::to_vec(&message)?;
-        stream.write_u32(buf.len().try_into().unwrap()).await?;
-        stream.write_all(&buf).await?;
-
-        self.read_and_handle_message(stream, response_sender)
-            .await?;
-
-        Ok(())
-    }
-
-    #[instrument(skip(self))]
-    async fn read_and_handle_message(
-        self: Arc<NetworkTaskState>,
-        mut stream: TlsStream<TcpStream>,
-        message_sender: UnboundedSender<Request>,
-    ) -> Result<(), NetworkError> {
-        // Get the peer name we're talking to from the tls certificate
-        let (tcp_stream, state) = stream.get_ref();
-        let peer_certs = state
-            .peer_certificates()
-            .ok_or_else(|| NetworkError::InternalError("No peer certificates?".to_string()))?;
-
-        let (_, cert) = X509Certificate::from_der(&peer_certs[0].0)
-            .map_err(|e| NetworkError::InternalError(format!("Invalid peer certificate? {e}")))?;
-
-        let peer_name = cert
-            .subject()
-            .iter_common_name()
-            .next()
-            .and_then(|cn| cn.as_str().ok())
-            .ok_or_else(|| NetworkError::InternalError("Couldn't parse peer CN".to_string()))?
-            .to_string();
-        let peer_name = ServerName::convert(&peer_name).map_err(|_| {
-            NetworkError::InternalError(format!(
-                "Invalid server name in certificate: {}",
-                &peer_name
-            ))
-        })?;
-
-        let peer = self
-            .peers
-            .iter()
-            .find(|p| p.conf.name == peer_name)
-            .ok_or_else(|| {
-                NetworkError::AuthzError("Couldn't find peer configuration".to_string())
-            })?;
-        let peer_conf = &peer.conf;
-
-        let remote_addr = tcp_stream.peer_addr()?;
-        let allowed_ip_addresses: Vec<_> = lookup_host(&peer_conf.address)
-            .await?
-            .map(|addr| match (remote_addr.ip(), addr.ip()) {
-                (IpAddr::V6(_), IpAddr::V4(ipv4_addr)) => IpAddr::V6(ipv4_addr.to_ipv6_mapped()),
-                (IpAddr::V4(_), IpAddr::V6(ipv6_addr)) => ipv6_addr
-                    .to_ipv4_mapped()
-                    .map(IpAddr::V4)
-                    .unwrap_or(IpAddr::V6(ipv6_addr)),
-                (_, addr) => addr,
-            })
-            .collect();
-        if !allowed_ip_addresses.contains(&remote_addr.ip()) {
-            return Err(NetworkError::AuthzError(format!(
-                "IP address doesn't match peer configuration ({} not in {:?})",
-                remote_addr.ip(),
-                allowed_ip_addresses,
-            )));
-        }
-
-        let expected_fingerprint = &peer_conf.fingerprint;
-        let mut cert_hasher = Sha1::new();
-        cert_hasher.update(&peer_certs[0].0);
-        let remote_fingerprint = hex::encode(cert_hasher.finalize());
-
-        if &remote_fingerprint != expected_fingerprint {
-            return Err(NetworkError::AuthzError(format!(
-                "Certificate doesn't match ({remote_fingerprint}/{expected_fingerprint})"
-            )));
-        }
-
-        loop {
-            let length = stream.read_u32().await?;
-
-            let mut buf = vec![0; length.try_into().unwrap()];
-            stream.read_exact(&mut buf).await?;
-
-            let msg: Message = serde_json::from_slice(&buf)?;
-
-            if matches!(msg.content, MessageDetail::Done) {
-                return Ok(());
-            }
-
-            tracing::trace!("Processing inbound message: {:?}", msg);
-
-            let (req_send, mut req_recv) = channel(8);
-            let req = Request {
-                received_from: peer_name,
-                response: req_send,
-                message: msg,
-            };
-
-            message_sender.send(req)?;
-
-            while let Some(response) = req_recv.recv().await {
-                tracing::trace!("Sending network response: {:?}", response);
-                let buf = serde_json::to_vec(&response).expect("Failed to serialise response");
-                stream.write_u32(buf.len().try_into().unwrap()).await?;
-                stream.write_all(&buf).await?;
-
-                if matches!(response.content, MessageDetail::Done) {
-                    tracing::trace!("Got done, ending connection");
-                    return Ok(());
-                }
-            }
-        }
-    }
+/// Update propagation stats for an event sent to `name`; `None` records a failed send
+fn record_sent(stats: &PeerStats, name: &ServerName, latency: Option<Duration>) {
+    stats
+        .lock()
+        .unwrap()
+        .entry(*name)
+        .or_default()
+        .record_sent(latency);
 }
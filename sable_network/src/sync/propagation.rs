@@ -0,0 +1,148 @@
+//! Measurement of event propagation, and the adaptive gossip fanout based on it
+
+use super::config::AdaptiveFanoutConfig;
+use crate::validated::ServerName;
+
+use serde::Serialize;
+use std::{collections::BTreeMap, time::Duration};
+
+/// Weight given to each new latency sample in the moving average
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+/// Number of newly seen events needed in a window before its redundancy is used to
+/// adjust the fanout
+const MIN_SAMPLES: u64 = 20;
+
+/// Counters describing event propagation to and from one peer
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PeerPropagationStats {
+    /// New events sent to this peer
+    pub events_sent: u64,
+    /// New events which couldn't be delivered to this peer
+    pub send_failures: u64,
+    /// Moving average of the time taken for this peer to accept a new event
+    pub latency_ms: Option<f64>,
+    /// New events received from this peer
+    pub events_received: u64,
+    /// New events received from this peer which had already arrived from another
+    pub duplicates_received: u64,
+    /// Messages waiting to be written to our connection to this peer
+    pub send_queue: usize,
+}
+
+impl PeerPropagationStats {
+    pub(super) fn record_sent(&mut self, latency: Option<Duration>) {
+        self.events_sent += 1;
+
+        let Some(latency) = latency else {
+            self.send_failures += 1;
+            return;
+        };
+        let sample = latency.as_secs_f64() * 1000.0;
+        self.latency_ms = Some(match self.latency_ms {
+            Some(average) => average + LATENCY_SMOOTHING * (sample - average),
+            None => sample,
+        });
+    }
+
+    pub(super) fn record_received(&mut self, duplicate: bool) {
+        self.events_received += 1;
+        if duplicate {
+            self.duplicates_received += 1;
+        }
+    }
+
+    /// The proportion of events from this peer which we'd already seen
+    pub fn duplicate_rate(&self) -> f64 {
+        if self.events_received == 0 {
+            0.0
+        } else {
+            self.duplicates_received as f64 / self.events_received as f64
+        }
+    }
+}
+
+/// A snapshot of propagation statistics for this server
+#[derive(Debug, Clone, Serialize)]
+pub struct PropagationStats {
+    /// The number of peers to which each new event is currently sent
+    pub fanout: usize,
+    /// Whether the fanout is being adjusted automatically
+    pub adaptive: bool,
+    pub peers: BTreeMap<ServerName, PeerPropagationStats>,
+}
+
+/// Chooses how many peers to send each new event to.
+///
+/// The fanout never drops below what's needed for gossip to reach every server
+/// in a network of the current size, which grows with the logarithm of the
+/// number of peers. Within that, it's raised when events are seen too few
+/// times (so that some servers are probably relying on a single path to receive
+/// them), and lowered when they're seen many more times than needed.
+#[derive(Debug)]
+pub struct AdaptiveFanout {
+    config: Option<AdaptiveFanoutConfig>,
+    current: usize,
+    received: u64,
+    duplicates: u64,
+}
+
+impl AdaptiveFanout {
+    /// Create a controller starting at `initial`, which is fixed if `config` is
+    /// `None`
+    pub fn new(initial: usize, config: Option<AdaptiveFanoutConfig>) -> Self {
+        Self {
+            config,
+            current: initial,
+            received: 0,
+            duplicates: 0,
+        }
+    }
+
+    /// The number of peers to send each new event to
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn is_adaptive(&self) -> bool {
+        self.config.is_some()
+    }
+
+    /// Record the arrival of a new event from a peer
+    pub fn record_received(&mut self, duplicate: bool) {
+        self.received += 1;
+        if duplicate {
+            self.duplicates += 1;
+        }
+    }
+
+    /// Adjust the fanout for a network with the given number of reachable peers,
+    /// based on what's been received since the last adjustment, and return the
+    /// new value
+    pub fn adjust(&mut self, peers: usize) -> usize {
+        let Some(config) = &self.config else {
+            return self.current;
+        };
+
+        // Enough to reach everyone with high probability, and no more than there are
+        let floor = ((peers as f64 + 1.0).ln().ceil() as usize).max(config.min);
+        let ceiling = config.max.min(peers).max(1);
+
+        let unique = self.received - self.duplicates;
+        let mut fanout = self.current;
+        if unique >= MIN_SAMPLES {
+            // The average number of copies of each event that reached us
+            let redundancy = self.received as f64 / unique as f64;
+            if redundancy > config.target_redundancy * 1.5 {
+                fanout = fanout.saturating_sub(1);
+            } else if redundancy < config.target_redundancy {
+                fanout += 1;
+            }
+            self.received = 0;
+            self.duplicates = 0;
+        }
+
+        self.current = fanout.max(floor).min(ceiling);
+        self.current
+    }
+}
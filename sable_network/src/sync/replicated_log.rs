@@ -4,7 +4,13 @@ use crate::prelude::*;
 use crate::rpc::*;
 
 use backoff::ExponentialBackoff;
-use std::{cell::RefCell, collections::HashMap, sync::Arc, sync::RwLock, time::Duration};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    sync::RwLock,
+    time::Duration,
+};
 use tokio::sync::Notify;
 use tokio::{
     select,
@@ -20,6 +26,10 @@ use thiserror::Error;
 
 use super::message::TargetedMessage;
 use super::network::NetworkResult;
+use super::state_transfer::{PreparedTransfer, StateReceiver, MAX_RETAINED_TRANSFERS};
+
+/// How often the gossip fanout is re-evaluated
+const FANOUT_ADJUST_INTERVAL: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Error)]
 pub enum EventLogSaveError {
@@ -50,11 +60,14 @@ pub struct ReplicatedEventLog {
     task_state: Arc<Mutex<TaskState>>,
     new_event_send: UnboundedSender<EventLogMessage>,
     net: Arc<GossipNetwork>,
+    event_expiry: i64,
 }
 
 struct SharedState {
     server: (ServerId, EpochId),
     server_tombstones: RwLock<HashMap<ServerId, (ServerName, EpochId)>>,
+    /// Peers which have rejected our messages because they believe we've quit
+    rejected_by: RwLock<HashSet<ServerName>>,
     log: RwLock<EventLog>,
 }
 
@@ -66,6 +79,8 @@ struct TaskState {
     server_send: UnboundedSender<NetworkMessage>,
     shared_state: Arc<SharedState>,
     event_expiry: i64,
+    /// State transfers we've prepared for joining servers, kept for resumption
+    state_transfers: HashMap<u64, Arc<PreparedTransfer>>,
 }
 
 #[derive(Debug)]
@@ -103,25 +118,44 @@ impl ReplicatedEventLog {
         net_config: SyncConfig,
         node_config: NodeConfig,
         eventlog_config: EventLogConfig,
+    ) -> Self {
+        let keys = Self::load_keys(&net_config, &node_config);
+
+        Self::with_network(
+            server_id,
+            epoch,
+            server_send,
+            keys,
+            eventlog_config,
+            |net_send| GossipNetwork::new(server_name, net_config, node_config, net_send),
+        )
+    }
+
+    /// Create an instance which signs its events with `keys`, and syncs over the
+    /// gossip network returned by `make_net`. That is given the channel on which
+    /// to pass requests from peers. Other arguments are as for [`new`](Self::new).
+    pub(super) fn with_network(
+        server_id: ServerId,
+        epoch: EpochId,
+        server_send: UnboundedSender<NetworkMessage>,
+        keys: EventKeys,
+        eventlog_config: EventLogConfig,
+        make_net: impl FnOnce(UnboundedSender<Request>) -> GossipNetwork,
     ) -> Self {
         let (log_send, log_recv) = unbounded_channel();
         let (net_send, net_recv) = unbounded_channel();
         let (new_event_send, new_event_recv) = unbounded_channel();
 
-        let net = Arc::new(GossipNetwork::new(
-            server_name,
-            net_config,
-            node_config,
-            net_send,
-        ));
+        let mut log = EventLog::new(ObjectIdGenerator::new(server_id), Some(log_send));
+        log.set_keys(keys);
+
+        let net = Arc::new(make_net(net_send));
 
         let shared_state = Arc::new(SharedState {
             server: (server_id, epoch),
             server_tombstones: RwLock::new(HashMap::new()),
-            log: RwLock::new(EventLog::new(
-                ObjectIdGenerator::new(server_id),
-                Some(log_send),
-            )),
+            rejected_by: RwLock::new(HashSet::new()),
+            log: RwLock::new(log),
         });
 
         let task_state = Arc::new(Mutex::new(TaskState {
@@ -132,6 +166,7 @@ impl ReplicatedEventLog {
             server_send,
             shared_state: Arc::clone(&shared_state),
             event_expiry: eventlog_config.event_expiry,
+            state_transfers: HashMap::new(),
         }));
 
         Self {
@@ -139,6 +174,7 @@ impl ReplicatedEventLog {
             task_state,
             net,
             new_event_send,
+            event_expiry: eventlog_config.event_expiry,
         }
     }
 
@@ -158,6 +194,12 @@ impl ReplicatedEventLog {
         let (net_send, net_recv) = unbounded_channel();
         let (new_event_send, new_event_recv) = unbounded_channel();
 
+        let mut log = EventLog::restore(state.log_state, Some(log_send));
+        // Keep using the key we announced before the upgrade, unless it predates signing
+        if log.keys().is_none() {
+            log.set_keys(Self::load_keys(&net_config, &node_config));
+        }
+
         let net = Arc::new(GossipNetwork::restore(
             state.network_state,
             net_config,
@@ -168,7 +210,8 @@ impl ReplicatedEventLog {
         let shared_state = Arc::new(SharedState {
             server: state.server,
             server_tombstones: RwLock::new(state.server_tombstones),
-            log: RwLock::new(EventLog::restore(state.log_state, Some(log_send))),
+            rejected_by: RwLock::new(HashSet::new()),
+            log: RwLock::new(log),
         });
 
         let task_state = Arc::new(Mutex::new(TaskState {
@@ -179,6 +222,7 @@ impl ReplicatedEventLog {
             server_send,
             shared_state: Arc::clone(&shared_state),
             event_expiry: state.event_expiry,
+            state_transfers: HashMap::new(),
         }));
 
         Self {
@@ -186,9 +230,27 @@ impl ReplicatedEventLog {
             task_state,
             net,
             new_event_send,
+            event_expiry: state.event_expiry,
         }
     }
 
+    fn load_keys(net_config: &SyncConfig, node_config: &NodeConfig) -> EventKeys {
+        let signing_key = node_config
+            .load_signing_key()
+            .expect("Error loading event signing key");
+
+        let mut keys = EventKeys::new(signing_key, net_config.require_signatures);
+        for peer in &net_config.peers {
+            keys.pin(peer.name, peer.signing_key);
+        }
+        keys
+    }
+
+    /// The public key with which this server signs its events
+    pub fn public_key(&self) -> Option<ServerKey> {
+        self.event_log().keys().map(EventKeys::public_key)
+    }
+
     /// Create and propagate a new event.
     ///
     /// Arguments are the target object ID, and the event detail.
@@ -267,6 +329,64 @@ impl ReplicatedEventLog {
         self.net.enable_peer(&name);
     }
 
+    /// Configuration for netsplit detection
+    pub fn quorum_config(&self) -> &QuorumConfig {
+        self.net.quorum_config()
+    }
+
+    /// The names of all servers configured in the sync network, including this one
+    pub fn configured_servers(&self) -> Vec<ServerName> {
+        let mut servers = self.net.peer_names();
+        servers.insert(0, self.net.me().name);
+        servers
+    }
+
+    /// Add or replace a peer in the sync network configuration. A peer given
+    /// without a signing key keeps any key already pinned for it.
+    pub fn add_peer(&self, conf: PeerConfig) {
+        if let Some(key) = conf.signing_key {
+            if let Some(keys) = self.shared_state.log.write().unwrap().keys_mut() {
+                keys.pin(conf.name, Some(key));
+            }
+        }
+        self.net.add_peer(conf);
+    }
+
+    /// Remove a peer from the sync network configuration
+    pub fn remove_peer(&self, name: &ServerName) {
+        if let Some(keys) = self.shared_state.log.write().unwrap().keys_mut() {
+            keys.pin(*name, None);
+        }
+        self.net.remove_peer(name);
+    }
+
+    /// Whether the given server's signing key is one pinned in our configuration
+    pub fn has_pinned_key(&self, server: ServerId) -> bool {
+        self.shared_state
+            .log
+            .read()
+            .unwrap()
+            .keys()
+            .is_some_and(|keys| keys.has_pinned_key(server))
+    }
+
+    /// Statistics on event propagation to and from our peers
+    pub fn propagation_stats(&self) -> PropagationStats {
+        self.net.propagation_stats()
+    }
+
+    /// The peers which have rejected our sync messages since we started, because
+    /// they have seen this server (with its current epoch) leave the network
+    pub fn rejected_by(&self) -> Vec<ServerName> {
+        self.shared_state
+            .rejected_by
+            .read()
+            .unwrap()
+            .iter()
+            .copied()
+            .collect()
+    }
+
     /// Send a request to another server in the network, and wait for the response
     pub async fn send_remote_request(
         &self,
@@ -298,39 +418,133 @@ impl ReplicatedEventLog {
     /// request a copy of the current network state from that peer, return
     /// it, and update the log's event clock to the current value from
     /// the imported state.
+    ///
+    /// The state is transferred in chunks. If the connection to the donor is
+    /// lost, the transfer resumes from where it left off; if the donor can't be
+    /// reached, or the reassembled state doesn't match its hash, the transfer
+    /// starts again from any available peer.
     #[tracing::instrument(skip(self))]
     pub async fn sync_to_network(&self) -> Box<crate::network::Network> {
-        let net = 'outer: loop {
+        let mut transfer = StateReceiver::new();
+
+        let net = loop {
             let (send, mut recv) = unbounded_channel();
-            let handle = self.start_sync_to_network(send).await;
+            let (donor, handle) = self.start_sync_to_network(&transfer, send).await;
 
             while let Some(req) = recv.recv().await {
-                tracing::debug!("Bootstrap message: {:?}", req.message);
                 match req.message.content {
-                    MessageDetail::NetworkState(net) => {
-                        break 'outer net;
+                    MessageDetail::StateTransferHeader(header) => {
+                        tracing::debug!(
+                            "Receiving network state from {}: {:?}",
+                            donor,
+                            header.sections
+                        );
+                        transfer.begin(donor, header);
+                    }
+                    MessageDetail::StateChunk(chunk) => {
+                        tracing::trace!("Received state chunk {}", chunk.seq);
+                        if let Err(e) = transfer.add_chunk(chunk) {
+                            tracing::warn!("Error in state transfer from {}: {}", donor, e);
+                            transfer.reset();
+                            break;
+                        }
+                    }
+                    MessageDetail::StateTransferUnavailable => {
+                        tracing::info!("State transfer expired on {}; restarting", donor);
+                        transfer.reset();
                     }
                     _ => {
+                        tracing::debug!("Bootstrap message: {:?}", req.message);
                         continue;
                     }
                 }
             }
-            handle
-                .await
-                .expect("Error syncing to network")
-                .expect("Error syncing to network");
+            drop(recv);
+
+            if transfer.is_complete() {
+                match std::mem::take(&mut transfer).finish() {
+                    Ok(net) => {
+                        tracing::info!("Received network state from {}", donor);
+                        break Box::new(net);
+                    }
+                    Err(e) => {
+                        tracing::error!("Network state from {} is invalid: {}", donor, e);
+                    }
+                }
+            } else {
+                match handle.await {
+                    Ok(Ok(())) => (),
+                    Ok(Err(e)) => {
+                        tracing::warn!("State transfer from {} interrupted: {}", donor, e)
+                    }
+                    Err(e) => tracing::warn!("State transfer from {} failed: {}", donor, e),
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
         };
 
+        self.resume_from(&net);
+
+        net
+    }
+
+    /// How long, in seconds, events are kept for other servers to sync from
+    pub fn event_expiry(&self) -> i64 {
+        self.event_expiry
+    }
+
+    /// Prepare to continue from a network state obtained other than by
+    /// [`sync_to_network`](Self::sync_to_network), such as one loaded from disk.
+    ///
+    /// This enables the servers present in the state, and sets the log's event
+    /// clock from it.
+    pub fn resume_from(&self, net: &crate::network::Network) {
         for server in net.servers() {
             self.enable_server(*server.name(), server.id());
         }
-        self.shared_state
+
+        let mut log = self
+            .shared_state
             .log
             .write()
-            .expect("event log lock is poisoned?")
-            .set_clock(net.clock().clone());
+            .expect("event log lock is poisoned?");
 
-        net
+        // We won't see the events in which existing servers announced their keys
+        if let Some(keys) = log.keys_mut() {
+            for server in net.servers() {
+                if let Some(key) = server.signing_key() {
+                    keys.learn(server.id(), server.epoch(), server.name(), key);
+                }
+            }
+        }
+        log.set_clock(net.clock().clone());
+    }
+
+    /// Ask a peer for any events that aren't reflected in our current event clock.
+    ///
+    /// The events are processed by the sync task once it starts, in the same way
+    /// as those received during normal operation.
+    pub async fn request_missing_events(&self) {
+        let mut tried = Vec::new();
+
+        while let Some(peer) = self.net.choose_peer_except(&tried) {
+            if self.request_missing_events_from(&peer).await {
+                return;
+            }
+            tried.push(peer.name);
+        }
+
+        tracing::warn!("No peer available to fetch missed events from");
+    }
+
+    /// Ask the given peer for any events that aren't reflected in our current event
+    /// clock, returning whether the request could be sent
+    pub(super) async fn request_missing_events_from(&self, peer: &PeerConfig) -> bool {
+        let msg = Message {
+            source_server: self.shared_state.server,
+            content: MessageDetail::SyncRequest(self.event_log().clock().clone()),
+        };
+        self.net.send_to(peer, msg).await.is_ok()
     }
 
     #[allow(clippy::await_holding_refcell_ref)] // don't care about 'attempts' being borrowed too
@@ -338,8 +552,9 @@ impl ReplicatedEventLog {
                                                 // once at a time.
     async fn start_sync_to_network(
         &self,
+        transfer: &StateReceiver,
         sender: UnboundedSender<Request>,
-    ) -> JoinHandle<NetworkResult> {
+    ) -> (ServerName, JoinHandle<NetworkResult>) {
         let attempts = RefCell::new(0);
         let backoff = ExponentialBackoff {
             initial_interval: Duration::from_millis(100),
@@ -347,8 +562,14 @@ impl ReplicatedEventLog {
             ..ExponentialBackoff::default()
         };
         backoff::future::retry(backoff, || async {
-            let Some(peer) = self.net.choose_any_peer() else {
-                panic!("No peer available to sync. This probably means you are running a single-node sable_ircd and did not pass the --bootstrap-network option.");
+            // Resume an interrupted transfer from the same donor if it's still there
+            let resume_from = transfer.donor().and_then(|donor| self.net.find_peer(donor));
+            let (peer, content) = match resume_from {
+                Some(peer) => (peer, transfer.request()),
+                None => match self.net.choose_any_peer() {
+                    Some(peer) => (peer, MessageDetail::StartStateTransfer),
+                    None => panic!("No peer available to sync. This probably means you are running a single-node sable_ircd and did not pass the --bootstrap-network option."),
+                },
             };
             let mut attempts = attempts.borrow_mut();
             *attempts += 1;
@@ -366,9 +587,10 @@ impl ReplicatedEventLog {
             }
             let msg = Message {
                 source_server: self.shared_state.server,
-                content: MessageDetail::GetNetworkState,
+                content,
             };
-            Ok(self.net.send_and_process(peer, msg, sender.clone()).await?)
+            let handle = self.net.send_and_process(&peer, msg, sender.clone()).await?;
+            Ok((peer.name, handle))
         }).await.expect("start_sync_to_network returned an error")
     }
 
@@ -424,16 +646,28 @@ impl TaskState {
         let listen_task = self.net.spawn_listen_task().await?;
 
         let mut log_prune_timer = tokio::time::interval(Duration::from_secs(60));
+        let mut fanout_timer = tokio::time::interval(FANOUT_ADJUST_INTERVAL);
 
         loop {
             tracing::trace!("sync_task loop");
+            // Checked in a fixed order rather than at random, so that a simulated run
+            // can be reproduced from its seed
             select! {
+                biased;
+
+                _ = shutdown.recv() => {
+                    break
+                },
                 _ = log_prune_timer.tick() => {
                     tracing::trace!("...from log_prune_timer");
                     let threshold_timestamp = crate::utils::now() - self.event_expiry;
                     let mut log = self.shared_state.log.write().unwrap();
                     log.prune_events_before(threshold_timestamp);
                 },
+                _ = fanout_timer.tick() => {
+                    tracing::trace!("...from fanout_timer");
+                    self.net.adjust_fanout();
+                },
                 evt = self.log_recv.recv() => {
                     tracing::trace!("...from log_recv");
                     match evt {
@@ -509,9 +743,6 @@ impl TaskState {
                         None => break
                     }
                 },
-                _ = shutdown.recv() => {
-                    break
-                }
             }
         }
 
@@ -520,6 +751,90 @@ impl TaskState {
         Ok(())
     }
 
+    /// Ask the server task for a copy of the current network state
+    async fn export_network_state(&self) -> Option<Box<Network>> {
+        let (send, mut recv) = channel(1);
+        if let Err(e) = self
+            .server_send
+            .send(NetworkMessage::ExportNetworkState(send))
+        {
+            tracing::error!("Error sending network request to server: {}", e);
+        }
+        recv.recv().await
+    }
+
+    /// Find or create a prepared transfer of the given network state. Servers joining
+    /// at around the same time share one transfer if nothing has changed in between,
+    /// and only the most recent few are kept.
+    fn prepare_state_transfer(&mut self, net: &Network) -> Option<Arc<PreparedTransfer>> {
+        self.state_transfers.retain(|_, t| !t.expired());
+
+        if let Some(existing) = self
+            .state_transfers
+            .values()
+            .find(|t| t.clock() == net.clock())
+        {
+            return Some(Arc::clone(existing));
+        }
+
+        let transfer = match PreparedTransfer::new(rand::random(), net) {
+            Ok(transfer) => Arc::new(transfer),
+            Err(e) => {
+                tracing::error!("Error preparing network state for transfer: {}", e);
+                return None;
+            }
+        };
+
+        while self.state_transfers.len() >= MAX_RETAINED_TRANSFERS {
+            let Some(oldest) = self
+                .state_transfers
+                .iter()
+                .min_by_key(|(_, t)| t.created())
+                .map(|(id, _)| *id)
+            else {
+                break;
+            };
+            self.state_transfers.remove(&oldest);
+        }
+
+        self.state_transfers
+            .insert(transfer.header().transfer, Arc::clone(&transfer));
+        Some(transfer)
+    }
+
+    /// Stream a state transfer's chunks, starting at `from`, in the background so
+    /// that the sync task isn't held up by a large transfer
+    fn send_state_chunks(
+        &self,
+        transfer: Arc<PreparedTransfer>,
+        from: usize,
+        response: Sender<Message>,
+    ) {
+        let source_server = self.shared_state.server;
+        let message = move |content| Message {
+            source_server,
+            content,
+        };
+
+        tokio::spawn(async move {
+            let header = MessageDetail::StateTransferHeader(transfer.header().clone());
+            let chunks = transfer
+                .chunks_from(from)
+                .iter()
+                .map(|chunk| MessageDetail::StateChunk(chunk.clone()));
+
+            for content in std::iter::once(header)
+                .chain(chunks)
+                .chain([MessageDetail::Done])
+            {
+                if let Err(e) = response.send(message(content)).await {
+                    tracing::error!("Error sending state transfer: {}", e);
+                    return;
+                }
+            }
+        });
+    }
+
     /// Make a [`Message`] originating from this server, for submission to the network
     fn message(&self, content: MessageDetail) -> Message {
         Message {
@@ -603,7 +918,7 @@ impl TaskState {
             let send_result = self
                 .net
                 .send_and_process(
-                    target,
+                    &target,
                     self.message(MessageDetail::TargetedMessage(detail.clone())),
                     sender.clone(),
                 )
@@ -638,7 +953,7 @@ impl TaskState {
             if self
                 .net
                 .send_and_process(
-                    peer,
+                    &peer,
                     self.message(MessageDetail::TargetedMessage(detail.clone())),
                     sender.clone(),
                 )
@@ -677,6 +992,9 @@ impl TaskState {
 
         match req.message.content {
             MessageDetail::NewEvent(evt) => {
+                let duplicate = self.shared_state.log.read().unwrap().get(&evt.id).is_some();
+                self.net.record_received(&req.received_from, duplicate);
+
                 if self.handle_new_event(evt, true, &req.response).await {
                     if let Err(e) = req.response.send(self.message(MessageDetail::Done)).await {
                         tracing::error!("Error sending response to network message: {}", e);
@@ -737,14 +1055,7 @@ impl TaskState {
             }
             MessageDetail::GetNetworkState => {
                 tracing::trace!("Processing get network state request");
-                let (send, mut recv) = channel(1);
-                if let Err(e) = self
-                    .server_send
-                    .send(NetworkMessage::ExportNetworkState(send))
-                {
-                    tracing::error!("Error sending network request to server: {}", e);
-                }
-                if let Some(net) = recv.recv().await {
+                if let Some(net) = self.export_network_state().await {
                     if let Err(e) = req
                         .response
                         .send(self.message(MessageDetail::NetworkState(net)))
@@ -754,6 +1065,39 @@ impl TaskState {
                     }
                 }
             }
+            MessageDetail::StartStateTransfer => {
+                tracing::debug!("Starting state transfer to {}", req.received_from);
+                let Some(net) = self.export_network_state().await else {
+                    return;
+                };
+                let Some(transfer) = self.prepare_state_transfer(&net) else {
+                    return;
+                };
+
+                self.send_state_chunks(transfer, 0, req.response);
+            }
+            MessageDetail::ResumeStateTransfer { transfer, from } => {
+                tracing::debug!(
+                    "Resuming state transfer {} to {} from chunk {}",
+                    transfer,
+                    req.received_from,
+                    from
+                );
+                match self.state_transfers.get(&transfer) {
+                    Some(transfer) => {
+                        self.send_state_chunks(Arc::clone(transfer), from, req.response);
+                    }
+                    None => {
+                        for content in
+                            [MessageDetail::StateTransferUnavailable, MessageDetail::Done]
+                        {
+                            if let Err(e) = req.response.send(self.message(content)).await {
+                                tracing::error!("Error sending response to network message: {}", e);
+                            }
+                        }
+                    }
+                }
+            }
             MessageDetail::NetworkState(net) => {
                 tracing::debug!("Got new network state; applying");
                 tracing::debug!("New event clock is {:?}", net.clock());
@@ -830,6 +1174,11 @@ impl TaskState {
                     &req.received_from
                 );
                 self.net.disable_peer(&req.received_from);
+                self.shared_state
+                    .rejected_by
+                    .write()
+                    .unwrap()
+                    .insert(req.received_from);
             }
             MessageDetail::Done | MessageDetail::TargetedMessageResponse(_) => {
                 // These are only used in responses, so nothing to do here
@@ -0,0 +1,166 @@
+//! Keys used to sign events and check which server they came from
+
+use crate::network::event::*;
+use crate::prelude::*;
+
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::collections::HashMap;
+
+/// This server's event signing key, and what it knows of other servers' keys.
+///
+/// Events are relayed through other servers, so having arrived over an
+/// authenticated link says nothing about where an event came from. Each server
+/// instead signs the events it creates, and announces the key it uses in its
+/// `NewServer` event, which must itself be signed with that key.
+///
+/// Once a server's key is known, it can't be dropped, and can only be replaced by
+/// an announcement signed with the known key, or by the key pinned for it in the
+/// network configuration. Otherwise anyone able to inject events could turn off
+/// verification for a server, or take over its identity.
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EventKeys {
+    signing_key: SigningKey,
+    /// The current key of each server that has announced one, and the epoch in
+    /// which it was announced
+    #[serde_as(as = "Vec<(_,_)>")]
+    known: HashMap<ServerId, (EpochId, ServerKey)>,
+    /// Keys fixed in the network configuration, which announcements must match
+    #[serde_as(as = "Vec<(_,_)>")]
+    pinned: HashMap<ServerName, ServerKey>,
+    /// If set, every event must be signed, and every server's key pinned
+    require_signatures: bool,
+}
+
+impl EventKeys {
+    pub fn new(signing_key: SigningKey, require_signatures: bool) -> Self {
+        Self {
+            signing_key,
+            known: HashMap::new(),
+            pinned: HashMap::new(),
+            require_signatures,
+        }
+    }
+
+    /// The public half of this server's signing key
+    pub fn public_key(&self) -> ServerKey {
+        ServerKey::from_signing_key(&self.signing_key)
+    }
+
+    /// Set or clear the expected key for the named server
+    pub fn pin(&mut self, name: ServerName, key: Option<ServerKey>) {
+        match key {
+            Some(key) => self.pinned.insert(name, key),
+            None => self.pinned.remove(&name),
+        };
+    }
+
+    /// The key pinned for the named server, if any
+    pub fn pinned_key(&self, name: &ServerName) -> Option<&ServerKey> {
+        self.pinned.get(name)
+    }
+
+    /// Whether the given server's current key is one pinned in the network
+    /// configuration, so that its events are known to come from a server we trust
+    pub fn has_pinned_key(&self, server: ServerId) -> bool {
+        self.known
+            .get(&server)
+            .is_some_and(|(_, key)| self.pinned.values().any(|pinned| pinned == key))
+    }
+
+    /// Record a server's key from an imported network state, where we won't see
+    /// the event in which it was announced
+    pub fn learn(&mut self, server: ServerId, epoch: EpochId, name: &ServerName, key: ServerKey) {
+        let pinned = self.pinned.get(name);
+        if pinned.is_some_and(|pinned| pinned != &key) {
+            tracing::warn!(%name, "Network state has a signing key which doesn't match configuration");
+            return;
+        }
+        if pinned.is_none()
+            && self
+                .known
+                .get(&server)
+                .is_some_and(|(_, known)| known != &key)
+        {
+            tracing::warn!(%name, "Network state has a signing key which doesn't match the known one");
+            return;
+        }
+        self.known.insert(server, (epoch, key));
+    }
+
+    pub(crate) fn sign(&self, event: &mut Event) {
+        event.sign(&self.signing_key);
+    }
+
+    /// Decide whether an event may be accepted as coming from the server named in
+    /// its ID, and learn the key from it if it's a server announcing itself
+    pub(crate) fn check(&mut self, event: &Event) -> bool {
+        let origin = event.id.server();
+
+        if let EventDetails::NewServer(detail) = &event.details {
+            if event.target != ObjectId::Server(origin) {
+                return false;
+            }
+
+            let pinned = self.pinned.get(&detail.name);
+            let known = self.known.get(&origin);
+
+            let Some(key) = detail.signing_key else {
+                // A server that doesn't sign its events, which is only allowed if we
+                // weren't expecting it to
+                return known.is_none() && pinned.is_none() && !self.require_signatures;
+            };
+
+            if pinned.is_some_and(|pinned| pinned != &key) {
+                return false;
+            }
+            if self.require_signatures && pinned.is_none() {
+                return false;
+            }
+
+            let epoch = match known {
+                Some((known_epoch, known_key)) if known_key == &key => {
+                    if !event.verify(&key) {
+                        return false;
+                    }
+                    std::cmp::max(*known_epoch, detail.epoch)
+                }
+                // A new key has to be vouched for, either by the configuration or by
+                // the holder of the old one. A later epoch alone proves nothing, as
+                // anyone can announce one.
+                Some((_, known_key)) => {
+                    let vouched = if pinned.is_some() {
+                        event.verify(&key)
+                    } else {
+                        event.verify(known_key)
+                    };
+                    if !vouched {
+                        tracing::warn!(
+                            name = %detail.name,
+                            epoch = detail.epoch,
+                            "Rejecting new signing key for a server not signed by its known key"
+                        );
+                        return false;
+                    }
+                    detail.epoch
+                }
+                None => {
+                    if !event.verify(&key) {
+                        return false;
+                    }
+                    detail.epoch
+                }
+            };
+
+            self.known.insert(origin, (epoch, key));
+            return true;
+        }
+
+        match self.known.get(&origin) {
+            Some((_, key)) => event.verify(key),
+            None => !self.require_signatures,
+        }
+    }
+}
@@ -0,0 +1,655 @@
+//! A deterministic, in-process simulation of a sync network, for tests
+//!
+//! Each simulated node is a real [`NetworkNode`], whose [`ReplicatedEventLog`]
+//! reaches its peers through a [`SimTransport`] rather than TLS connections. It
+//! has no storage, no server type to handle remote requests, and nothing
+//! consuming its history updates, but otherwise pings, checks quorum and reports
+//! its state as a server would.
+//!
+//! Every message in a conversation takes its own simulated latency to arrive,
+//! and may be lost, which breaks the conversation as a dropped connection would.
+//! Messages within a conversation arrive in order, but because latencies are
+//! chosen independently, those in different conversations can overtake each
+//! other.
+//!
+//! Everything runs on a single-threaded tokio runtime whose clock only moves
+//! forward when every task is waiting. Latency, loss, and each node's choice of
+//! peers come from random number generators seeded from the simulation's seed, so
+//! a run can be reproduced exactly from it.
+
+use super::network::NetworkResult;
+use super::transport::Transport;
+use crate::network::config::NetworkConfig;
+use crate::policy::StandardPolicyService;
+use crate::prelude::*;
+use crate::rpc::*;
+
+use ed25519_dalek::SigningKey;
+use futures::future::BoxFuture;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    runtime::Runtime,
+    sync::{
+        broadcast,
+        mpsc::{channel, unbounded_channel, UnboundedReceiver, UnboundedSender},
+        Notify,
+    },
+    task::JoinHandle,
+    time::{sleep, sleep_until, Instant},
+};
+
+/// The length of one tick of simulated time
+const TICK: Duration = Duration::from_millis(1);
+
+/// Upper bound on the ticks [`Simulation::run`] waits for the network to settle,
+/// so that a protocol bug that never settles fails the test instead of hanging it
+const MAX_STEPS: usize = 1_000_000;
+
+/// Delivery conditions in a simulated network. Times are in ticks.
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    /// The number of peers to which each node propagates a new event
+    pub fanout: usize,
+    /// The shortest time taken to deliver a message
+    pub min_latency: u64,
+    /// The longest time taken to deliver a message
+    pub max_latency: u64,
+    /// The probability that any message is lost in transit
+    pub loss: f64,
+    /// How each node decides whether it can see enough of the network
+    pub quorum: QuorumConfig,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            fanout: 2,
+            min_latency: 1,
+            max_latency: 10,
+            loss: 0.0,
+            quorum: QuorumConfig::default(),
+        }
+    }
+}
+
+/// A record of one message reaching, or failing to reach, its destination
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    pub time: u64,
+    pub from: usize,
+    pub to: usize,
+    pub kind: &'static str,
+    /// Whether the message arrived, rather than being lost or blocked by a
+    /// partition
+    pub delivered: bool,
+}
+
+/// The links between simulated nodes, shared by each node's transport
+struct SimNetwork {
+    names: Vec<ServerName>,
+    /// Where each node receives conversations started by its peers
+    inbound: Mutex<Vec<Option<UnboundedSender<Request>>>>,
+    conditions: Mutex<(SimConfig, StdRng)>,
+    /// For each node, which side of the current partition it's on
+    sides: Mutex<Vec<usize>>,
+    trace: Mutex<Vec<Delivery>>,
+    in_flight: AtomicUsize,
+    /// When a message was last sent or delivered
+    last_activity: Mutex<Instant>,
+    start: Instant,
+}
+
+impl SimNetwork {
+    fn new(names: Vec<ServerName>, config: SimConfig, rng: StdRng) -> Self {
+        let nodes = names.len();
+        let now = Instant::now();
+
+        Self {
+            names,
+            inbound: Mutex::new(vec![None; nodes]),
+            conditions: Mutex::new((config, rng)),
+            sides: Mutex::new(vec![0; nodes]),
+            trace: Mutex::new(Vec::new()),
+            in_flight: AtomicUsize::new(0),
+            last_activity: Mutex::new(now),
+            start: now,
+        }
+    }
+
+    /// Make a transport for the given node, which passes conversations started by
+    /// its peers to `inbound`
+    fn connect(
+        self: &Arc<Self>,
+        node: usize,
+        inbound: UnboundedSender<Request>,
+    ) -> Arc<dyn Transport> {
+        self.inbound.lock().unwrap()[node] = Some(inbound);
+
+        Arc::new(SimTransport {
+            net: Arc::clone(self),
+            me: node,
+            shutdown: Arc::new(Notify::new()),
+        })
+    }
+
+    fn index(&self, name: &ServerName) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    fn reachable(&self, from: usize, to: usize) -> bool {
+        let sides = self.sides.lock().unwrap();
+        sides[from] == sides[to]
+    }
+
+    /// Choose the latency of a message, and whether it will be lost
+    fn conditions(&self) -> (Duration, bool) {
+        let mut conditions = self.conditions.lock().unwrap();
+        let (config, rng) = &mut *conditions;
+        let latency = rng.gen_range(config.min_latency..=config.max_latency);
+        let lost = rng.gen_bool(config.loss);
+
+        (TICK * latency as u32, lost)
+    }
+
+    fn sent(&self) {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    fn arrived(&self, from: usize, to: usize, message: &Message, delivered: bool) {
+        let now = Instant::now();
+
+        self.trace.lock().unwrap().push(Delivery {
+            time: ((now - self.start).as_millis() / TICK.as_millis()) as u64,
+            from,
+            to,
+            kind: message_kind(&message.content),
+            delivered,
+        });
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        *self.last_activity.lock().unwrap() = now;
+    }
+
+    /// Whether nothing is in transit, and everything delivered has been handled.
+    /// The clock only moves forward once every task is waiting, so anything
+    /// delivered before now has been.
+    fn is_idle(&self) -> bool {
+        self.in_flight.load(Ordering::SeqCst) == 0
+            && *self.last_activity.lock().unwrap() < Instant::now()
+    }
+}
+
+/// A [`Transport`] that carries conversations over simulated links
+struct SimTransport {
+    net: Arc<SimNetwork>,
+    me: usize,
+    shutdown: Arc<Notify>,
+}
+
+impl Transport for SimTransport {
+    fn start_conversation<'a>(
+        &'a self,
+        peer: &'a PeerConfig,
+        message: Message,
+        response_handler: UnboundedSender<Request>,
+    ) -> BoxFuture<'a, Result<JoinHandle<NetworkResult>, NetworkError>> {
+        Box::pin(async move {
+            let to = self
+                .net
+                .index(&peer.name)
+                .ok_or(NetworkError::UnknownPeer(peer.name))?;
+            if !self.net.reachable(self.me, to) {
+                return Err(NetworkError::Send(format!("{} is unreachable", peer.name)));
+            }
+            let inbound = self.net.inbound.lock().unwrap()[to]
+                .clone()
+                .ok_or(NetworkError::UnknownPeer(peer.name))?;
+
+            let (to_peer, peer_incoming) = Link::open(Arc::clone(&self.net), self.me, to);
+            let (from_peer, incoming) = Link::open(Arc::clone(&self.net), to, self.me);
+
+            tokio::spawn(converse(
+                self.net.names[self.me],
+                peer_incoming,
+                from_peer,
+                inbound,
+            ));
+            to_peer.send(message)?;
+
+            Ok(tokio::spawn(converse(
+                peer.name,
+                incoming,
+                to_peer,
+                response_handler,
+            )))
+        })
+    }
+
+    fn listen(&self) -> BoxFuture<'_, Result<JoinHandle<()>, NetworkError>> {
+        let shutdown = Arc::clone(&self.shutdown);
+        Box::pin(async move { Ok(tokio::spawn(async move { shutdown.notified().await })) })
+    }
+
+    fn shutdown(&self) {
+        self.shutdown.notify_one();
+    }
+
+    // Each conversation has its own links, so there are no connections to manage
+    fn drop_connection(&self, _name: &ServerName) {}
+
+    fn retire_connection(&self, _name: &ServerName) {}
+
+    fn queued_messages(&self) -> Vec<(ServerName, usize)> {
+        Vec::new()
+    }
+}
+
+/// One direction of a simulated conversation
+struct Link {
+    net: Arc<SimNetwork>,
+    queue: UnboundedSender<(Instant, bool, Message)>,
+}
+
+impl Link {
+    /// Open a link from one node to another, returning it and the receiving end
+    fn open(net: Arc<SimNetwork>, from: usize, to: usize) -> (Self, UnboundedReceiver<Message>) {
+        let (queue, mut pending) = unbounded_channel::<(Instant, bool, Message)>();
+        let (deliver, incoming) = unbounded_channel();
+
+        let link_net = Arc::clone(&net);
+        tokio::spawn(async move {
+            while let Some((due, lost, message)) = pending.recv().await {
+                sleep_until(due).await;
+
+                let delivered = !lost && net.reachable(from, to);
+                net.arrived(from, to, &message, delivered);
+                if !delivered {
+                    break;
+                }
+                // If the other end has finished the conversation, nobody is left to
+                // care about the message
+                deliver.send(message).ok();
+            }
+
+            // Anything still queued is lost along with the link
+            drop(deliver);
+            pending.close();
+            while let Ok((_, _, message)) = pending.try_recv() {
+                net.arrived(from, to, &message, false);
+            }
+        });
+
+        (
+            Self {
+                net: link_net,
+                queue,
+            },
+            incoming,
+        )
+    }
+
+    fn send(&self, message: Message) -> NetworkResult {
+        let (latency, lost) = self.net.conditions();
+
+        self.queue
+            .send((Instant::now() + latency, lost, message))
+            .map_err(|_| NetworkError::Send("Simulated link is broken".to_string()))?;
+        self.net.sent();
+        Ok(())
+    }
+}
+
+/// Carry one end of a conversation, as a peer connection does: pass each message
+/// from `other_end` to `handler`, and send back its responses
+async fn converse(
+    other_end: ServerName,
+    mut incoming: UnboundedReceiver<Message>,
+    outgoing: Link,
+    handler: UnboundedSender<Request>,
+) -> NetworkResult {
+    loop {
+        let Some(message) = incoming.recv().await else {
+            return Err(NetworkError::Send(format!(
+                "Connection to {other_end} closed"
+            )));
+        };
+        if matches!(message.content, MessageDetail::Done) {
+            return Ok(());
+        }
+
+        let (response, mut responses) = channel(8);
+        handler.send(Request {
+            received_from: other_end,
+            response,
+            message,
+        })?;
+
+        while let Some(response) = responses.recv().await {
+            let done = matches!(response.content, MessageDetail::Done);
+            outgoing.send(response)?;
+            if done {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// One server in a simulated network: a [`NetworkNode`] whose event log syncs
+/// through the simulated links
+pub struct SimNode {
+    name: ServerName,
+    node: Arc<NetworkNode>,
+    task: JoinHandle<ShutdownAction>,
+    /// Kept open so that the node can publish history updates, though nothing
+    /// reads them
+    _history: UnboundedReceiver<NetworkHistoryUpdate>,
+}
+
+impl SimNode {
+    fn start(
+        index: usize,
+        peers: &[PeerConfig],
+        config: &SimConfig,
+        net: &Arc<SimNetwork>,
+        seed: u64,
+        shutdown: &broadcast::Sender<ShutdownAction>,
+    ) -> Self {
+        let name = peers[index].name;
+        let id = ServerId::new(index as u16 + 1);
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let sync_config = SyncConfig {
+            peers: peers.to_vec(),
+            fanout: config.fanout,
+            adaptive_fanout: None,
+            ca_file: PathBuf::new(),
+            quorum: config.quorum.clone(),
+            require_signatures: false,
+        };
+        let keys = EventKeys::new(SigningKey::generate(&mut rng), false);
+        let (server_send, server_recv) = unbounded_channel();
+
+        let log = Arc::new(ReplicatedEventLog::with_network(
+            id,
+            1,
+            server_send,
+            keys,
+            EventLogConfig { event_expiry: 3600 },
+            |inbound| {
+                GossipNetwork::with_transport(&name, sync_config, inbound, rng, |_, _, inbound| {
+                    net.connect(index, inbound)
+                })
+            },
+        ));
+
+        let mut network_config = NetworkConfig::new();
+        network_config.debug_mode = cfg!(feature = "debug");
+        let (history_send, history) = unbounded_channel();
+
+        let node = Arc::new(NetworkNode::new(
+            id,
+            1,
+            name,
+            Network::new(network_config),
+            Arc::clone(&log),
+            server_recv,
+            history_send,
+            None,
+            StandardPolicyService::new(),
+            None,
+        ));
+
+        log.start_sync(shutdown.subscribe());
+        let task = tokio::spawn(Arc::clone(&node).run(shutdown.subscribe()));
+
+        Self {
+            name,
+            node,
+            task,
+            _history: history,
+        }
+    }
+
+    /// The server ID of this node
+    pub fn id(&self) -> ServerId {
+        self.node.id()
+    }
+
+    /// Generator for the IDs of objects created on this node
+    pub fn ids(&self) -> &ObjectIdGenerator {
+        self.node.ids()
+    }
+
+    pub fn network(&self) -> Arc<Network> {
+        self.node.network()
+    }
+
+    /// The server running on this node
+    pub fn node(&self) -> &NetworkNode {
+        &self.node
+    }
+
+    fn log(&self) -> &ReplicatedEventLog {
+        self.node.sync_log()
+    }
+
+    /// Whether the server is still running. It stops if an event fails to apply.
+    fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+}
+
+/// A network of simulated nodes, and the runtime in which they run
+pub struct Simulation {
+    nodes: Vec<SimNode>,
+    peers: Vec<PeerConfig>,
+    net: Arc<SimNetwork>,
+    _shutdown: broadcast::Sender<ShutdownAction>,
+    runtime: Runtime,
+}
+
+impl Simulation {
+    /// Create a fully connected network of `nodes` nodes, each starting with an
+    /// empty network state
+    pub fn new(nodes: usize, seed: u64, config: SimConfig) -> Self {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .start_paused(true)
+            .build()
+            .expect("Couldn't build simulation runtime");
+
+        let peers: Vec<_> = (0..nodes)
+            .map(|i| PeerConfig {
+                name: format!("node{}.test", i + 1).parse().unwrap(),
+                address: String::new(),
+                fingerprint: String::new(),
+                signing_key: None,
+            })
+            .collect();
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (shutdown, _) = broadcast::channel(1);
+
+        let (net, nodes) = {
+            let _guard = runtime.enter();
+
+            let net = Arc::new(SimNetwork::new(
+                peers.iter().map(|p| p.name).collect(),
+                config.clone(),
+                StdRng::seed_from_u64(rng.gen()),
+            ));
+            let nodes: Vec<_> = (0..nodes)
+                .map(|i| SimNode::start(i, &peers, &config, &net, rng.gen(), &shutdown))
+                .collect();
+
+            (net, nodes)
+        };
+
+        for node in &nodes {
+            for other in &nodes {
+                node.log().enable_server(other.name, other.id());
+            }
+        }
+
+        Self {
+            nodes,
+            peers,
+            net,
+            _shutdown: shutdown,
+            runtime,
+        }
+    }
+
+    pub fn node(&self, index: usize) -> &SimNode {
+        &self.nodes[index]
+    }
+
+    pub fn nodes(&self) -> &[SimNode] {
+        &self.nodes
+    }
+
+    /// Every message delivered or dropped so far, in order
+    pub fn trace(&self) -> Vec<Delivery> {
+        self.net.trace.lock().unwrap().clone()
+    }
+
+    /// Create an event on the given node, which applies it and propagates it to
+    /// the node's peers
+    pub fn create_event(
+        &mut self,
+        node: usize,
+        target: impl Into<ObjectId>,
+        details: impl Into<EventDetails>,
+    ) -> EventId {
+        let log = self.nodes[node].log();
+        self.runtime
+            .block_on(log.create_event_with_id(target.into(), details.into()))
+    }
+
+    /// Split the network in two: the given nodes on one side, and the rest on the
+    /// other. Conversations can't be started across the partition, and messages
+    /// that arrive across it while it's in place are lost, including any sent
+    /// before it began. Messages still in transit when it's healed are delivered.
+    pub fn partition(&mut self, side: &[usize]) {
+        for (index, node_side) in self.net.sides.lock().unwrap().iter_mut().enumerate() {
+            *node_side = usize::from(side.contains(&index));
+        }
+    }
+
+    /// Remove any partition
+    pub fn heal(&mut self) {
+        self.net.sides.lock().unwrap().fill(0);
+    }
+
+    /// Change the probability that a message is lost in transit, for example to
+    /// let a lossy network recover
+    pub fn set_loss(&mut self, loss: f64) {
+        self.net.conditions.lock().unwrap().0.loss = loss;
+    }
+
+    /// Ask a peer for any events the node has missed, as a server does on
+    /// restarting
+    pub fn request_missing_events(&mut self, node: usize) {
+        let log = self.nodes[node].log();
+        self.runtime.block_on(log.request_missing_events());
+    }
+
+    /// Have every node ask each of its peers for the events it has missed, and run
+    /// until they've all been delivered
+    pub fn sync_all(&mut self) {
+        for node in &self.nodes {
+            for peer in self.peers.iter().filter(|p| p.name != node.name) {
+                self.runtime
+                    .block_on(node.log().request_missing_events_from(peer));
+            }
+        }
+        self.run();
+    }
+
+    /// Run until no messages remain in transit
+    pub fn run(&mut self) {
+        let net = Arc::clone(&self.net);
+        self.runtime.block_on(async move {
+            for _ in 0..MAX_STEPS {
+                sleep(TICK).await;
+                if net.is_idle() {
+                    return;
+                }
+            }
+            panic!("Simulation did not settle after {MAX_STEPS} ticks");
+        });
+    }
+
+    /// Let the given length of simulated time pass, for example for servers on
+    /// either side of a partition to ping each other out
+    pub fn run_for(&mut self, time: Duration) {
+        self.runtime.block_on(sleep(time));
+    }
+
+    /// Let one tick of simulated time pass, returning false if there was nothing
+    /// left to happen
+    pub fn step(&mut self) -> bool {
+        let net = Arc::clone(&self.net);
+        self.runtime.block_on(async move {
+            sleep(TICK).await;
+            !net.is_idle()
+        })
+    }
+
+    /// Whether every node has seen the same events and holds the same state
+    pub fn is_converged(&self) -> bool {
+        let first = &self.nodes[0];
+        let clock = first.log().event_log().clock().clone();
+        let digest = first.network().state_digest();
+
+        self.nodes.iter().all(|node| {
+            node.is_running()
+                && node.log().event_log().clock() == &clock
+                && node.network().state_digest() == digest
+        })
+    }
+
+    /// Check that every node has converged on the same state as the first,
+    /// naming the sections which differ if not
+    pub fn assert_converged(&self) {
+        let first = &self.nodes[0];
+        let clock = first.log().event_log().clock().clone();
+        let digest = first.network().state_digest();
+
+        for node in &self.nodes {
+            assert!(node.is_running(), "{} failed to apply an event", node.name);
+            assert_eq!(
+                node.log().event_log().clock(),
+                &clock,
+                "{} has seen different events from {}",
+                node.name,
+                first.name
+            );
+            let differing = node.network().state_digest().differing_sections(&digest);
+            assert!(
+                differing.is_empty(),
+                "{} differs from {} in {:?}",
+                node.name,
+                first.name,
+                differing
+            );
+        }
+    }
+}
+
+fn message_kind(content: &MessageDetail) -> &'static str {
+    match content {
+        MessageDetail::NewEvent(_) => "NewEvent",
+        MessageDetail::BulkEvents(_) => "BulkEvents",
+        MessageDetail::SyncRequest(_) => "SyncRequest",
+        MessageDetail::GetEvent(_) => "GetEvent",
+        MessageDetail::Done => "Done",
+        _ => "Other",
+    }
+}
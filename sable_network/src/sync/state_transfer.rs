@@ -0,0 +1,283 @@
+//! Chunked, resumable transfer of the network state to a joining server
+//!
+//! Rather than sending the whole serialised [`Network`] as a single message, the
+//! donor splits it into one section per top-level field (users, channels, and so
+//! on), and the larger sections into chunks of a bounded number of objects. The
+//! prepared transfer is kept for a while so that a joining server whose connection
+//! drops can ask for the remaining chunks; only a few are kept at once, and joins
+//! against unchanged state share one. A hash of the whole state lets it
+//! confirm that what it reassembled matches what the donor had.
+
+use crate::prelude::*;
+
+use serde::{de::Error as _, Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Maximum number of objects sent in a single chunk
+const CHUNK_OBJECTS: usize = 1000;
+
+/// How long a donor keeps a prepared transfer available for resumption
+const TRANSFER_RETENTION: Duration = Duration::from_secs(600);
+
+/// Maximum number of prepared transfers a donor keeps at once. Each holds a full
+/// copy of the network state, so the oldest are discarded beyond this.
+pub(crate) const MAX_RETAINED_TRANSFERS: usize = 4;
+
+/// Describes one section of the network state in a transfer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateSectionInfo {
+    /// The name of the [`Network`] field held in this section
+    pub name: String,
+    /// Whether the section is a list of objects, split across its chunks, or a
+    /// single value sent in one chunk
+    pub list: bool,
+    /// The number of chunks in this section
+    pub chunks: usize,
+}
+
+/// Sent by the donor at the start of a state transfer, and again on resumption
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateTransferHeader {
+    /// Identifies this transfer, for resumption
+    pub transfer: u64,
+    /// Hash of the complete state being transferred
+    pub hash: StateHash,
+    /// The sections to expect, in the order their chunks will be sent
+    pub sections: Vec<StateSectionInfo>,
+}
+
+/// One piece of a state transfer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateChunk {
+    /// The transfer to which this chunk belongs
+    pub transfer: u64,
+    /// Position of this chunk in the whole transfer
+    pub seq: usize,
+    /// Index into the header's section list
+    pub section: usize,
+    /// JSON encoding of the chunk's objects, or of the section's value
+    pub data: String,
+}
+
+/// An error in receiving a state transfer
+#[derive(Debug, Error)]
+pub enum StateTransferError {
+    #[error("Received chunk {0} out of sequence")]
+    OutOfSequence(usize),
+    #[error("Received chunk for unknown transfer or section")]
+    UnknownChunk,
+    #[error("Malformed state data: {0}")]
+    Malformed(#[from] serde_json::Error),
+    #[error("Transferred state doesn't match donor (expected {expected}, got {actual})")]
+    HashMismatch {
+        expected: StateHash,
+        actual: StateHash,
+    },
+}
+
+/// A network state split up for sending, held by the donor
+pub(crate) struct PreparedTransfer {
+    header: StateTransferHeader,
+    chunks: Vec<StateChunk>,
+    /// The event clock of the state being transferred
+    clock: EventClock,
+    created: Instant,
+}
+
+impl PreparedTransfer {
+    pub fn new(transfer: u64, network: &Network) -> Result<Self, serde_json::Error> {
+        Self::with_chunk_size(transfer, network, CHUNK_OBJECTS)
+    }
+
+    pub(super) fn with_chunk_size(
+        transfer: u64,
+        network: &Network,
+        chunk_objects: usize,
+    ) -> Result<Self, serde_json::Error> {
+        let Value::Object(fields) = serde_json::to_value(network)? else {
+            return Err(serde_json::Error::custom("network state is not an object"));
+        };
+
+        let hash = StateHash::of_sections(&fields);
+        let mut sections = Vec::new();
+        let mut chunks = Vec::new();
+
+        for (name, value) in fields {
+            let first = chunks.len();
+            let list = value.is_array();
+
+            if let Value::Array(items) = value {
+                for objects in items.chunks(chunk_objects) {
+                    chunks.push((sections.len(), serde_json::to_string(objects)?));
+                }
+            } else {
+                chunks.push((sections.len(), serde_json::to_string(&value)?));
+            }
+
+            sections.push(StateSectionInfo {
+                name,
+                list,
+                chunks: chunks.len() - first,
+            });
+        }
+
+        let chunks = chunks
+            .into_iter()
+            .enumerate()
+            .map(|(seq, (section, data))| StateChunk {
+                transfer,
+                seq,
+                section,
+                data,
+            })
+            .collect();
+
+        Ok(Self {
+            header: StateTransferHeader {
+                transfer,
+                hash,
+                sections,
+            },
+            chunks,
+            clock: network.clock().clone(),
+            created: Instant::now(),
+        })
+    }
+
+    pub fn header(&self) -> &StateTransferHeader {
+        &self.header
+    }
+
+    pub fn clock(&self) -> &EventClock {
+        &self.clock
+    }
+
+    pub fn created(&self) -> Instant {
+        self.created
+    }
+
+    /// The chunks remaining to be sent, starting from the given position
+    pub fn chunks_from(&self, seq: usize) -> &[StateChunk] {
+        self.chunks.get(seq..).unwrap_or_default()
+    }
+
+    /// Whether this transfer has been kept long enough to be discarded
+    pub fn expired(&self) -> bool {
+        self.created.elapsed() > TRANSFER_RETENTION
+    }
+}
+
+/// The joining server's side of a state transfer, which survives interruptions
+/// so that the transfer can be resumed
+#[derive(Default)]
+pub(crate) struct StateReceiver {
+    donor: Option<ServerName>,
+    header: Option<StateTransferHeader>,
+    sections: Vec<Vec<String>>,
+    received: usize,
+}
+
+impl StateReceiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The server from which the transfer in progress is being received, if any
+    pub fn donor(&self) -> Option<&ServerName> {
+        self.donor.as_ref()
+    }
+
+    /// The request to send to the donor to start or continue this transfer
+    pub fn request(&self) -> MessageDetail {
+        match &self.header {
+            Some(header) => MessageDetail::ResumeStateTransfer {
+                transfer: header.transfer,
+                from: self.received,
+            },
+            None => MessageDetail::StartStateTransfer,
+        }
+    }
+
+    /// Discard any progress, so that the next request starts a new transfer
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Handle a transfer header. Progress is kept if this is the transfer
+    /// already in progress, and discarded otherwise.
+    pub fn begin(&mut self, donor: ServerName, header: StateTransferHeader) {
+        let same_transfer = self.donor == Some(donor)
+            && self
+                .header
+                .as_ref()
+                .is_some_and(|current| current.transfer == header.transfer);
+
+        if !same_transfer {
+            self.sections = vec![Vec::new(); header.sections.len()];
+            self.received = 0;
+            self.donor = Some(donor);
+            self.header = Some(header);
+        }
+    }
+
+    pub fn add_chunk(&mut self, chunk: StateChunk) -> Result<(), StateTransferError> {
+        let Some(header) = &self.header else {
+            return Err(StateTransferError::UnknownChunk);
+        };
+        if chunk.transfer != header.transfer || chunk.section >= self.sections.len() {
+            return Err(StateTransferError::UnknownChunk);
+        }
+        if chunk.seq != self.received {
+            return Err(StateTransferError::OutOfSequence(chunk.seq));
+        }
+
+        self.sections[chunk.section].push(chunk.data);
+        self.received += 1;
+        Ok(())
+    }
+
+    /// Whether every chunk has been received
+    pub fn is_complete(&self) -> bool {
+        self.header.as_ref().is_some_and(|header| {
+            self.received == header.sections.iter().map(|s| s.chunks).sum::<usize>()
+        })
+    }
+
+    /// Reassemble the transferred state, and check that it matches the donor's
+    pub fn finish(self) -> Result<Network, StateTransferError> {
+        let header = self.header.ok_or(StateTransferError::UnknownChunk)?;
+
+        let mut fields = Map::new();
+        for (info, chunks) in header.sections.into_iter().zip(self.sections) {
+            let value = if info.list {
+                let mut objects = Vec::new();
+                for chunk in chunks {
+                    objects.extend(serde_json::from_str::<Vec<Value>>(&chunk)?);
+                }
+                Value::Array(objects)
+            } else {
+                match chunks.first() {
+                    Some(chunk) => serde_json::from_str(chunk)?,
+                    None => Value::Null,
+                }
+            };
+            fields.insert(info.name, value);
+        }
+
+        let network: Network = serde_json::from_value(Value::Object(fields))?;
+
+        // Hash the state as deserialised, not as received, so that anything lost or
+        // altered in reconstruction is caught too
+        let actual = StateHash::of(&network)?;
+        if actual != header.hash {
+            return Err(StateTransferError::HashMismatch {
+                expected: header.hash,
+                actual,
+            });
+        }
+
+        Ok(network)
+    }
+}
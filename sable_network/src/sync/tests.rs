@@ -157,3 +157,1024 @@ fn get_since() {
     assert_eq!(entries[0].id, e2.id);
     assert_eq!(entries[1].id, e3.id);
 }
+
+#[test]
+fn latest_events_for() {
+    let server_id = ServerId::new(1);
+    let idgen = ObjectIdGenerator::new(server_id);
+    let mut log = EventLog::new(idgen, None);
+
+    let uid1 = UserId::new(Snowflake::from_parts(server_id, 0, 1));
+    let uid2 = UserId::new(Snowflake::from_parts(server_id, 0, 2));
+    let uid3 = UserId::new(Snowflake::from_parts(server_id, 0, 3));
+
+    let mut ids = Vec::new();
+    for (target, message) in [(uid1, "aaa"), (uid1, "bbb"), (uid2, "ccc")] {
+        let event = log.create(
+            target,
+            details::UserQuit {
+                message: message.to_string(),
+            },
+        );
+        ids.push(event.id);
+        log.add(event);
+    }
+
+    let targets: std::collections::HashSet<ObjectId> = [uid1.into(), uid3.into()].into();
+    let found = log.latest_events_for(&targets);
+
+    assert_eq!(found.len(), 1);
+    assert_eq!(found.get(&uid1.into()), Some(&ids[1]));
+}
+
+#[test]
+fn codec_round_trip() {
+    use super::codec::WireCodec;
+    use super::connection::Frame;
+
+    let server_id = ServerId::new(1);
+    let idgen = ObjectIdGenerator::new(server_id);
+    let mut log = EventLog::new(idgen, None);
+
+    let uid = UserId::new(Snowflake::from_parts(server_id, 0, 1));
+
+    // Enough events to take the frame over the compression threshold
+    let events: Vec<_> = (0..200)
+        .map(|i| {
+            log.create(
+                uid,
+                details::UserQuit {
+                    message: format!("quit message {i}"),
+                },
+            )
+        })
+        .collect();
+
+    for codec in [WireCodec::Json, WireCodec::Bincode] {
+        let frame = Frame::Message {
+            conversation: 3,
+            from_initiator: true,
+            message: Message {
+                source_server: (server_id, 1),
+                content: MessageDetail::BulkEvents(events.clone()),
+            },
+        };
+
+        let encoded = codec.encode(&frame).unwrap();
+        let Frame::Message {
+            conversation,
+            from_initiator,
+            message,
+        } = codec.decode(&encoded).unwrap()
+        else {
+            panic!("Decoded wrong frame type");
+        };
+
+        assert_eq!(conversation, 3);
+        assert!(from_initiator);
+        let MessageDetail::BulkEvents(decoded) = message.content else {
+            panic!("Decoded wrong message type");
+        };
+        let ids: Vec<_> = decoded.iter().map(|e| e.id).collect();
+        let expected: Vec<_> = events.iter().map(|e| e.id).collect();
+        assert_eq!(ids, expected);
+    }
+}
+
+#[test]
+fn oversized_frames_are_rejected() {
+    use super::codec::WireCodec;
+    use std::io::Read;
+
+    // A length prefix beyond the limit is refused before anything is allocated
+    let mut header: &[u8] = &u32::MAX.to_be_bytes();
+    let result = futures::executor::block_on(WireCodec::Bincode.read_frame(&mut header));
+    assert!(result.is_err());
+
+    // As is a small frame that decompresses to more than the limit
+    let mut frame = vec![0x01];
+    zstd::stream::copy_encode(std::io::repeat(0).take(65 * 1024 * 1024), &mut frame, 3).unwrap();
+    assert!(frame.len() < 1024 * 1024);
+    assert!(WireCodec::Bincode.decode(&frame).is_err());
+}
+
+fn legacy_message(content: MessageDetail) -> Message {
+    Message {
+        source_server: (ServerId::new(2), 1),
+        content,
+    }
+}
+
+#[test]
+fn handshake_with_current_and_legacy_peers() {
+    use super::codec::{self, Handshake, WireCodec};
+
+    futures::executor::block_on(async {
+        // Two current servers agree on the best codec
+        let (mut ours, mut theirs) = tokio::io::duplex(64 * 1024);
+        let (connected, accepted) = futures::join!(
+            WireCodec::negotiate(&mut ours),
+            WireCodec::accept(&mut theirs)
+        );
+        assert_eq!(connected.unwrap(), WireCodec::Bincode);
+        assert!(matches!(
+            accepted.unwrap(),
+            Handshake::Negotiated(WireCodec::Bincode)
+        ));
+
+        // A legacy server connecting to us starts a conversation straight away
+        let (mut ours, mut theirs) = tokio::io::duplex(64 * 1024);
+        codec::write_legacy_message(&mut theirs, &legacy_message(MessageDetail::GetNetworkState))
+            .await
+            .unwrap();
+        let Handshake::Legacy(first) = WireCodec::accept(&mut ours).await.unwrap() else {
+            panic!("Legacy peer wasn't recognised");
+        };
+        assert!(matches!(first.content, MessageDetail::GetNetworkState));
+
+        // One we connect to can't parse our hello, and hangs up
+        let (mut ours, mut theirs) = tokio::io::duplex(64 * 1024);
+        let legacy_peer = async move {
+            assert!(codec::read_legacy_message(&mut theirs).await.is_err());
+        };
+        let (connected, ()) = futures::join!(WireCodec::negotiate(&mut ours), legacy_peer);
+        assert!(matches!(connected, Err(NetworkError::NoHandshake)));
+    });
+}
+
+#[test]
+fn legacy_conversation() {
+    use super::codec;
+
+    let (ours, mut theirs) = tokio::io::duplex(64 * 1024);
+    let peer_name: ServerName = "legacy.test".parse().unwrap();
+    let (handler, mut requests) = unbounded_channel();
+
+    let local_server = async move {
+        let request: Request = requests.recv().await.unwrap();
+        assert_eq!(request.received_from, peer_name);
+        assert!(matches!(
+            request.message.content,
+            MessageDetail::GetNetworkState
+        ));
+
+        let response = legacy_message(MessageDetail::GetEvent(Vec::new()));
+        request.response.send(response).await.unwrap();
+        let done = legacy_message(MessageDetail::Done);
+        request.response.send(done).await.unwrap();
+    };
+    let legacy_peer = async move {
+        let first = codec::read_legacy_message(&mut theirs).await.unwrap();
+        let second = codec::read_legacy_message(&mut theirs).await.unwrap();
+        (first, second)
+    };
+    let conversation = codec::legacy_conversation(
+        ours,
+        peer_name,
+        Some(legacy_message(MessageDetail::GetNetworkState)),
+        handler,
+    );
+
+    let (result, (), (first, second)) = futures::executor::block_on(async {
+        futures::join!(conversation, local_server, legacy_peer)
+    });
+    result.unwrap();
+    assert!(matches!(first.content, MessageDetail::GetEvent(_)));
+    assert!(matches!(second.content, MessageDetail::Done));
+}
+
+/// Run a test on a runtime whose clock only moves forward when every task is
+/// waiting, as the simulator does
+fn run_paused<F: std::future::Future>(test: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .start_paused(true)
+        .build()
+        .unwrap()
+        .block_on(test)
+}
+
+/// Run one end of a peer connection over an in-memory stream, returning the
+/// connection and channels which report its backlog and its closing
+fn spawn_connection(
+    stream: impl tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+    peer: &str,
+    initiator: &str,
+    inbound: UnboundedSender<Request>,
+) -> (
+    std::sync::Arc<super::connection::PeerConnection>,
+    UnboundedReceiver<bool>,
+    tokio::sync::oneshot::Receiver<()>,
+) {
+    let (backlog_send, backlog) = unbounded_channel();
+    let (closed_send, closed) = tokio::sync::oneshot::channel();
+
+    let conn = super::connection::PeerConnection::spawn(
+        stream,
+        peer.parse().unwrap(),
+        initiator.parse().unwrap(),
+        super::codec::WireCodec::Bincode,
+        inbound,
+        move |backlogged| {
+            backlog_send.send(backlogged).ok();
+        },
+        move |_| {
+            closed_send.send(()).ok();
+        },
+    );
+    (conn, backlog, closed)
+}
+
+/// A stream which reads as normal, but fails every write
+struct FailingWrites(tokio::io::DuplexStream);
+
+impl tokio::io::AsyncRead for FailingWrites {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl tokio::io::AsyncWrite for FailingWrites {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+        _buf: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        std::task::Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into()))
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[test]
+fn simultaneous_connections_settle_on_lower_named_initiator() {
+    use super::connection::{ConnectionTable, KEEPALIVE_INTERVAL};
+    use std::sync::Arc;
+
+    run_paused(async {
+        let (a, b): (ServerName, ServerName) =
+            ("a.test".parse().unwrap(), "b.test".parse().unwrap());
+        let (a_inbound, _a_requests) = unbounded_channel();
+        let (b_inbound, mut b_requests) = unbounded_channel();
+
+        // Each server connects to the other at the same time...
+        let (a_end, b_end) = tokio::io::duplex(64 * 1024);
+        let (a_opened, ..) = spawn_connection(a_end, "b.test", "a.test", a_inbound.clone());
+        let (b_accepted, ..) = spawn_connection(b_end, "a.test", "a.test", b_inbound.clone());
+        let (a_end, b_end) = tokio::io::duplex(64 * 1024);
+        let (a_accepted, ..) = spawn_connection(a_end, "b.test", "b.test", a_inbound);
+        let (b_opened, ..) = spawn_connection(b_end, "a.test", "b.test", b_inbound);
+
+        // ...and each registers the two connections in the opposite order
+        let a_table = ConnectionTable::new(a);
+        a_table.register(Arc::clone(&a_opened));
+        a_table.register(Arc::clone(&a_accepted));
+        let b_table = ConnectionTable::new(b);
+        b_table.register(Arc::clone(&b_opened));
+        b_table.register(Arc::clone(&b_accepted));
+
+        // Both use the one opened by a.test
+        assert!(Arc::ptr_eq(&a_table.live(&b).unwrap(), &a_opened));
+        assert!(Arc::ptr_eq(&b_table.live(&a).unwrap(), &b_accepted));
+
+        let (responses, _) = unbounded_channel();
+        a_table
+            .live(&b)
+            .unwrap()
+            .start_conversation(legacy_message(MessageDetail::GetNetworkState), responses)
+            .unwrap();
+        let request = b_requests.recv().await.unwrap();
+        assert_eq!(request.received_from, a);
+
+        // The other connection has nothing in progress, so it's closed
+        tokio::time::sleep(KEEPALIVE_INTERVAL * 2).await;
+        assert!(!a_accepted.is_open());
+        assert!(!b_opened.is_open());
+        assert!(a_opened.is_open());
+        assert!(b_accepted.is_open());
+    });
+}
+
+#[test]
+fn write_error_closes_connection() {
+    use super::connection::ConnectionTable;
+    use std::time::Duration;
+
+    run_paused(async {
+        let (ours, _theirs) = tokio::io::duplex(64 * 1024);
+        let (inbound, _requests) = unbounded_channel();
+        let (conn, _backlog, closed) =
+            spawn_connection(FailingWrites(ours), "b.test", "a.test", inbound);
+        let table = ConnectionTable::new("a.test".parse().unwrap());
+        table.register(std::sync::Arc::clone(&conn));
+
+        let (responses, _) = unbounded_channel();
+        let conversation = conn
+            .start_conversation(legacy_message(MessageDetail::GetNetworkState), responses)
+            .unwrap();
+
+        // The connection gives up as soon as the write fails, rather than waiting
+        // for the conversation or the read side to time out
+        tokio::time::timeout(Duration::from_secs(1), closed)
+            .await
+            .expect("Connection wasn't closed")
+            .unwrap();
+        assert!(!conn.is_open());
+        assert!(conversation.await.unwrap().is_err());
+
+        // Nothing more is queued on it, and the next conversation needs a new one
+        let (responses, _) = unbounded_channel();
+        assert!(conn
+            .start_conversation(legacy_message(MessageDetail::GetNetworkState), responses)
+            .is_err());
+        assert!(table.live(&"b.test".parse().unwrap()).is_none());
+    });
+}
+
+#[test]
+fn full_send_queue_reports_backlog_until_drained() {
+    use super::connection::MAX_QUEUED_FRAMES;
+
+    run_paused(async {
+        // Nothing reads from the other end yet, so frames stay queued
+        let (ours, mut theirs) = tokio::io::duplex(1024);
+        let (inbound, _requests) = unbounded_channel();
+        let (conn, mut backlog, _closed) = spawn_connection(ours, "b.test", "a.test", inbound);
+
+        let mut conversations = Vec::new();
+        let error = loop {
+            let (responses, _) = unbounded_channel();
+            match conn.start_conversation(legacy_message(MessageDetail::GetNetworkState), responses)
+            {
+                Ok(conversation) => conversations.push(conversation),
+                Err(e) => break e,
+            }
+            assert!(
+                conversations.len() <= MAX_QUEUED_FRAMES,
+                "Send queue never filled"
+            );
+        };
+
+        assert!(matches!(error, NetworkError::QueueFull(_)));
+        assert_eq!(conn.queued_frames(), MAX_QUEUED_FRAMES);
+        assert_eq!(backlog.recv().await, Some(true));
+
+        // A backlogged peer is only reported once
+        let (responses, _) = unbounded_channel();
+        assert!(conn
+            .start_conversation(legacy_message(MessageDetail::GetNetworkState), responses)
+            .is_err());
+        assert!(backlog.try_recv().is_err());
+
+        // Once the peer reads again, the queue drains and the backlog is cleared
+        tokio::spawn(async move { tokio::io::copy(&mut theirs, &mut tokio::io::sink()).await });
+        assert_eq!(backlog.recv().await, Some(false));
+        assert!(conn.queued_frames() <= MAX_QUEUED_FRAMES / 2);
+        assert!(conn.is_open());
+    });
+}
+
+#[test]
+fn reconnection_backs_off_exponentially() {
+    use super::network::{Peer, RECONNECT_BACKOFF_MAX, RECONNECT_BACKOFF_MIN};
+    use std::time::Duration;
+
+    let peer = Peer::new(PeerConfig {
+        name: "b.test".parse().unwrap(),
+        address: String::new(),
+        fingerprint: String::new(),
+        signing_key: None,
+    });
+    assert!(peer.backoff_remaining().is_none());
+
+    let mut expected = RECONNECT_BACKOFF_MIN;
+    for failures in 1..=12 {
+        assert_eq!(peer.connection_failed(), failures);
+
+        // Allowing for the time taken since the failure was recorded
+        let remaining = peer.backoff_remaining().unwrap();
+        assert!(
+            remaining <= expected && remaining + Duration::from_millis(100) > expected,
+            "After {failures} failures, waiting {remaining:?} rather than {expected:?}"
+        );
+        expected = (expected * 2).min(RECONNECT_BACKOFF_MAX);
+    }
+    assert_eq!(expected, RECONNECT_BACKOFF_MAX);
+
+    // Success starts the backoff afresh
+    peer.connection_succeeded();
+    assert!(peer.backoff_remaining().is_none());
+    assert_eq!(peer.connection_failed(), 1);
+    assert!(peer.backoff_remaining().unwrap() <= RECONNECT_BACKOFF_MIN);
+}
+
+#[test]
+fn signed_events() {
+    use ed25519_dalek::SigningKey;
+
+    let server_id = ServerId::new(1);
+    let server_id2 = ServerId::new(2);
+    let mut log = EventLog::new(ObjectIdGenerator::new(server_id), None);
+    let mut log2 = EventLog::new(ObjectIdGenerator::new(server_id2), None);
+    log.set_keys(EventKeys::new(SigningKey::from_bytes(&[1; 32]), false));
+    log2.set_keys(EventKeys::new(SigningKey::from_bytes(&[2; 32]), false));
+
+    let new_server = log2.create(
+        server_id2,
+        details::NewServer {
+            epoch: 1,
+            name: "server2.test".parse().unwrap(),
+            ts: 0,
+            flags: crate::network::state::ServerFlags::empty(),
+            version: String::new(),
+            signing_key: log2.keys().map(EventKeys::public_key),
+        },
+    );
+    log.add(new_server.clone());
+    assert!(log.get(&new_server.id).is_some());
+
+    let uid = UserId::new(Snowflake::from_parts(server_id2, 0, 1));
+    let quit = log2.create(
+        uid,
+        details::UserQuit {
+            message: "aaa".to_string(),
+        },
+    );
+
+    // Tampering with the content invalidates the signature
+    let mut tampered = quit.clone();
+    tampered.details = details::UserQuit {
+        message: "bbb".to_string(),
+    }
+    .into();
+    log.add(tampered.clone());
+    assert!(log.get(&tampered.id).is_none());
+
+    // As does signing with another server's key
+    let mut forged = quit.clone();
+    forged.sign(&SigningKey::from_bytes(&[3; 32]));
+    log.add(forged.clone());
+    assert!(log.get(&forged.id).is_none());
+
+    log.add(quit.clone());
+    assert!(log.get(&quit.id).is_some());
+}
+
+/// Create a `NewServer` event for server 2, signed with the given key
+fn announce_server2(
+    log: &EventLog,
+    epoch: EpochId,
+    key: Option<&ed25519_dalek::SigningKey>,
+) -> Event {
+    let mut event = log.create(
+        ServerId::new(2),
+        details::NewServer {
+            epoch,
+            name: "server2.test".parse().unwrap(),
+            ts: 0,
+            flags: crate::network::state::ServerFlags::empty(),
+            version: String::new(),
+            signing_key: key.map(ServerKey::from_signing_key),
+        },
+    );
+    if let Some(key) = key {
+        event.sign(key);
+    }
+    event
+}
+
+#[test]
+fn signing_key_cannot_be_downgraded() {
+    use ed25519_dalek::SigningKey;
+
+    let key2 = SigningKey::from_bytes(&[2; 32]);
+    let mut keys = EventKeys::new(SigningKey::from_bytes(&[1; 32]), false);
+    let log2 = EventLog::new(ObjectIdGenerator::new(ServerId::new(2)), None);
+
+    assert!(keys.check(&announce_server2(&log2, 1, Some(&key2))));
+
+    // An unsigned announcement would turn off verification for the server
+    let unsigned = announce_server2(&log2, 2, None);
+    assert!(!keys.check(&unsigned));
+
+    // So unsigned events from it are still refused afterwards
+    let uid = UserId::new(Snowflake::from_parts(ServerId::new(2), 0, 1));
+    let mut quit = log2.create(
+        uid,
+        details::UserQuit {
+            message: "aaa".to_string(),
+        },
+    );
+    quit.signature = None;
+    assert!(!keys.check(&quit));
+    quit.sign(&key2);
+    assert!(keys.check(&quit));
+}
+
+#[test]
+fn signing_key_replacement() {
+    use ed25519_dalek::SigningKey;
+
+    let key2 = SigningKey::from_bytes(&[2; 32]);
+    let forged_key = SigningKey::from_bytes(&[3; 32]);
+    let mut keys = EventKeys::new(SigningKey::from_bytes(&[1; 32]), false);
+    let log2 = EventLog::new(ObjectIdGenerator::new(ServerId::new(2)), None);
+
+    assert!(keys.check(&announce_server2(&log2, 5, Some(&key2))));
+    // Seeing the same announcement again is fine
+    assert!(keys.check(&announce_server2(&log2, 5, Some(&key2))));
+
+    // A different key for the same or an earlier epoch is a forgery
+    assert!(!keys.check(&announce_server2(&log2, 5, Some(&forged_key))));
+    assert!(!keys.check(&announce_server2(&log2, 4, Some(&forged_key))));
+
+    let uid = UserId::new(Snowflake::from_parts(ServerId::new(2), 0, 1));
+    let mut quit = log2.create(
+        uid,
+        details::UserQuit {
+            message: "aaa".to_string(),
+        },
+    );
+    quit.sign(&forged_key);
+    assert!(!keys.check(&quit));
+
+    // A new key for a new epoch is only accepted if the old key signed for it
+    let new_key = SigningKey::from_bytes(&[4; 32]);
+    assert!(!keys.check(&announce_server2(&log2, 6, Some(&new_key))));
+    let mut rotation = announce_server2(&log2, 6, Some(&new_key));
+    rotation.sign(&key2);
+    assert!(keys.check(&rotation));
+    quit.sign(&new_key);
+    assert!(keys.check(&quit));
+
+    // A pinned key is accepted whatever the epoch, and nothing else is
+    let pinned_key = SigningKey::from_bytes(&[5; 32]);
+    keys.pin(
+        "server2.test".parse().unwrap(),
+        Some(ServerKey::from_signing_key(&pinned_key)),
+    );
+    assert!(keys.check(&announce_server2(&log2, 6, Some(&pinned_key))));
+    assert!(!keys.check(&announce_server2(&log2, 7, Some(&new_key))));
+}
+
+#[test]
+fn forged_key_for_later_epoch_is_rejected() {
+    use ed25519_dalek::SigningKey;
+
+    let key2 = SigningKey::from_bytes(&[2; 32]);
+    let relay_key = SigningKey::from_bytes(&[3; 32]);
+    let mut keys = EventKeys::new(SigningKey::from_bytes(&[1; 32]), false);
+    let log2 = EventLog::new(ObjectIdGenerator::new(ServerId::new(2)), None);
+
+    assert!(keys.check(&announce_server2(&log2, 1, Some(&key2))));
+
+    // A relay announces a later epoch of server 2 with a key of its own, correctly
+    // signed with that key
+    let forged = announce_server2(&log2, 100, Some(&relay_key));
+    assert!(forged.verify(&ServerKey::from_signing_key(&relay_key)));
+    assert!(!keys.check(&forged));
+
+    // So it can't sign events as server 2, and the real server's are still accepted
+    let uid = UserId::new(Snowflake::from_parts(ServerId::new(2), 0, 1));
+    let mut quit = log2.create(
+        uid,
+        details::UserQuit {
+            message: "aaa".to_string(),
+        },
+    );
+    quit.sign(&relay_key);
+    assert!(!keys.check(&quit));
+    quit.sign(&key2);
+    assert!(keys.check(&quit));
+
+    // Nor can it slip the key in through an imported network state
+    keys.learn(
+        ServerId::new(2),
+        100,
+        &"server2.test".parse().unwrap(),
+        ServerKey::from_signing_key(&relay_key),
+    );
+    assert!(keys.check(&quit));
+}
+
+#[test]
+fn state_transfer() {
+    use super::state_transfer::{PreparedTransfer, StateReceiver};
+    use crate::network::tests::fixtures::NetworkBuilder;
+
+    let mut builder = NetworkBuilder::new();
+    for i in 0..10 {
+        builder.add_user(format!("user{i}").parse().unwrap());
+    }
+    let expected = StateHash::of(&builder.net).unwrap();
+
+    let transfer = PreparedTransfer::with_chunk_size(1, &builder.net, 3).unwrap();
+    assert_eq!(transfer.header().hash, expected);
+    let donor: ServerName = "donor.test".parse().unwrap();
+
+    // Receive some chunks, then resume from where the first attempt stopped
+    let mut receiver = StateReceiver::new();
+    receiver.begin(donor, transfer.header().clone());
+    for chunk in transfer.chunks_from(0).iter().take(4) {
+        receiver.add_chunk(chunk.clone()).unwrap();
+    }
+    assert!(!receiver.is_complete());
+
+    let MessageDetail::ResumeStateTransfer { transfer: id, from } = receiver.request() else {
+        panic!("Interrupted transfer should be resumed");
+    };
+    assert_eq!((id, from), (1, 4));
+
+    receiver.begin(donor, transfer.header().clone());
+    for chunk in transfer.chunks_from(from) {
+        receiver.add_chunk(chunk.clone()).unwrap();
+    }
+    assert!(receiver.is_complete());
+
+    let net = receiver.finish().unwrap();
+    assert_eq!(StateHash::of(&net).unwrap(), expected);
+
+    // A chunk that doesn't match the donor's state is detected
+    let mut receiver = StateReceiver::new();
+    receiver.begin(donor, transfer.header().clone());
+    for chunk in transfer.chunks_from(0) {
+        let mut chunk = chunk.clone();
+        chunk.data = chunk.data.replace("user3", "user9");
+        receiver.add_chunk(chunk).unwrap();
+    }
+    assert!(matches!(
+        receiver.finish(),
+        Err(StateTransferError::HashMismatch { .. })
+    ));
+}
+
+#[test]
+fn fixed_fanout() {
+    let mut fanout = AdaptiveFanout::new(5, None);
+    for _ in 0..100 {
+        fanout.record_received(true);
+    }
+    assert_eq!(fanout.adjust(2), 5);
+    assert_eq!(fanout.adjust(50), 5);
+    assert!(!fanout.is_adaptive());
+}
+
+#[test]
+fn adaptive_fanout() {
+    let config = AdaptiveFanoutConfig {
+        min: 1,
+        max: 8,
+        target_redundancy: 2.0,
+    };
+    let mut fanout = AdaptiveFanout::new(3, Some(config));
+
+    let receive = |fanout: &mut AdaptiveFanout, unique, duplicates| {
+        for _ in 0..unique {
+            fanout.record_received(false);
+        }
+        for _ in 0..duplicates {
+            fanout.record_received(true);
+        }
+    };
+
+    // Too few events seen to judge redundancy
+    receive(&mut fanout, 19, 0);
+    assert_eq!(fanout.adjust(10), 3);
+
+    // Each event arriving about once is too little redundancy
+    receive(&mut fanout, 1, 0);
+    assert_eq!(fanout.adjust(10), 4);
+
+    // Within the target band, nothing changes
+    receive(&mut fanout, 20, 30);
+    assert_eq!(fanout.adjust(10), 4);
+
+    // Four copies of each event is too many
+    receive(&mut fanout, 20, 60);
+    assert_eq!(fanout.adjust(10), 3);
+
+    // ...but not below what's needed to reach ten peers
+    receive(&mut fanout, 20, 60);
+    assert_eq!(fanout.adjust(10), 3);
+
+    // Nor above the number of peers there are
+    receive(&mut fanout, 20, 0);
+    assert_eq!(fanout.adjust(2), 2);
+    assert_eq!(fanout.adjust(0), 1);
+
+    // A larger network raises the floor
+    assert_eq!(fanout.adjust(100), 5);
+}
+
+#[test]
+fn propagation_stats() {
+    use std::time::Duration;
+
+    let mut stats = PeerPropagationStats::default();
+    assert_eq!(stats.duplicate_rate(), 0.0);
+
+    stats.record_sent(Some(Duration::from_millis(100)));
+    stats.record_sent(None);
+    stats.record_sent(Some(Duration::from_millis(200)));
+    assert_eq!(stats.events_sent, 3);
+    assert_eq!(stats.send_failures, 1);
+    assert!((stats.latency_ms.unwrap() - 120.0).abs() < 1e-9);
+
+    stats.record_received(false);
+    stats.record_received(true);
+    stats.record_received(false);
+    stats.record_received(true);
+    assert_eq!(stats.events_received, 4);
+    assert_eq!(stats.duplicates_received, 2);
+    assert_eq!(stats.duplicate_rate(), 0.5);
+}
+
+mod simulation {
+    use super::super::simulator::{SimConfig, Simulation};
+    use crate::prelude::*;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    fn new_user(sim: &mut Simulation, node: usize, nick: &str) -> UserId {
+        let user = sim.node(node).ids().next();
+        let server = sim.node(node).id();
+        sim.create_event(
+            node,
+            user,
+            details::NewUser {
+                mode: state::UserMode::new(UserModeSet::default()),
+                nickname: Nickname::from_str(nick).unwrap(),
+                username: Username::from_str("a").unwrap(),
+                realname: Realname::from_str("user").unwrap(),
+                visible_hostname: Hostname::from_str("host.name").unwrap(),
+                server,
+                account: None,
+                initial_connection: None,
+            },
+        );
+        user
+    }
+
+    fn new_channel(sim: &mut Simulation, node: usize, name: &str) -> ChannelId {
+        let channel = sim.node(node).ids().next();
+        sim.create_event(
+            node,
+            channel,
+            details::NewChannel {
+                mode: state::ChannelMode::new(ChannelModeSet::default()),
+                name: ChannelName::from_str(name).unwrap(),
+            },
+        );
+        channel
+    }
+
+    fn join(sim: &mut Simulation, node: usize, user: UserId, channel: ChannelId) {
+        sim.create_event(
+            node,
+            MembershipId::new(user, channel),
+            details::ChannelJoin {
+                channel,
+                user,
+                permissions: MembershipFlagSet::new(),
+            },
+        );
+    }
+
+    fn user_count(sim: &Simulation, node: usize) -> usize {
+        sim.node(node).network().users().count()
+    }
+
+    fn peer(name: &str, signing_key: Option<ServerKey>) -> PeerConfig {
+        PeerConfig {
+            name: name.parse().unwrap(),
+            address: String::new(),
+            fingerprint: String::new(),
+            signing_key,
+        }
+    }
+
+    fn pinned_key(sim: &Simulation, node: usize, name: &str) -> Option<ServerKey> {
+        let log = sim.node(node).node().sync_log().event_log();
+        log.keys()
+            .and_then(|keys| keys.pinned_key(&name.parse().unwrap()).copied())
+    }
+
+    fn has_peer(sim: &Simulation, node: usize, name: &str) -> bool {
+        let name = name.parse().unwrap();
+        sim.node(node)
+            .node()
+            .sync_log()
+            .configured_servers()
+            .contains(&name)
+    }
+
+    #[test]
+    fn converges_despite_reordering() {
+        // Send every event to every peer, so that nothing depends on later
+        // events to fill gaps in gossip coverage
+        let config = SimConfig {
+            fanout: 4,
+            min_latency: 1,
+            max_latency: 50,
+            ..SimConfig::default()
+        };
+        let mut sim = Simulation::new(5, 1, config);
+
+        let users: Vec<_> = (0..5)
+            .map(|node| new_user(&mut sim, node, &format!("user{node}")))
+            .collect();
+        sim.run();
+        sim.assert_converged();
+        assert_eq!(user_count(&sim, 4), 5);
+
+        // Join a channel from another node as soon as it arrives there, so that
+        // the join overtakes the channel's creation on the way to some nodes
+        let channel = new_channel(&mut sim, 0, "#test");
+        while sim.node(3).network().channel(channel).is_err() {
+            assert!(sim.step(), "Channel never reached node 3");
+        }
+        join(&mut sim, 3, users[3], channel);
+        join(&mut sim, 3, users[1], channel);
+        sim.run();
+
+        sim.assert_converged();
+        for node in sim.nodes() {
+            assert_eq!(node.network().memberships().count(), 2);
+        }
+    }
+
+    #[test]
+    fn heals_after_netsplit() {
+        let config = SimConfig {
+            fanout: 5,
+            ..SimConfig::default()
+        };
+        let mut sim = Simulation::new(6, 2, config);
+        new_user(&mut sim, 0, "before");
+        sim.run();
+
+        // Split while this user's creation is still in transit
+        let split_user = new_user(&mut sim, 4, "late");
+        sim.partition(&[0, 1, 2]);
+        for node in 0..6 {
+            new_user(&mut sim, node, &format!("split{node}"));
+        }
+        sim.run();
+
+        assert!(!sim.is_converged());
+        assert_eq!(user_count(&sim, 0), 4);
+        assert_eq!(user_count(&sim, 3), 5);
+        assert!(sim.node(1).network().user(split_user).is_err());
+        assert!(sim.trace().iter().any(|d| !d.delivered));
+
+        sim.heal();
+        sim.sync_all();
+
+        sim.assert_converged();
+        assert_eq!(user_count(&sim, 0), 8);
+        assert!(sim.node(1).network().user(split_user).is_ok());
+    }
+
+    #[test]
+    fn recovers_from_message_loss() {
+        let config = SimConfig {
+            loss: 0.3,
+            ..SimConfig::default()
+        };
+        let mut sim = Simulation::new(4, 3, config);
+
+        for i in 0..20 {
+            new_user(&mut sim, i % 4, &format!("user{i}"));
+        }
+        sim.run();
+        assert!(sim.trace().iter().any(|d| !d.delivered));
+
+        // A later event depends on everything its origin has seen, so any node
+        // that receives it asks for what it's missing
+        sim.set_loss(0.0);
+        new_user(&mut sim, 0, "last");
+        sim.run();
+        sim.sync_all();
+
+        sim.assert_converged();
+        assert_eq!(user_count(&sim, 3), 21);
+    }
+
+    #[test]
+    fn same_seed_same_schedule() {
+        let run = |seed| {
+            let config = SimConfig {
+                loss: 0.1,
+                ..SimConfig::default()
+            };
+            let mut sim = Simulation::new(4, seed, config);
+            for i in 0..10 {
+                new_user(&mut sim, i % 4, &format!("user{i}"));
+            }
+            sim.request_missing_events(2);
+            sim.run();
+            sim.trace()
+        };
+
+        assert_eq!(run(7), run(7));
+    }
+
+    #[test]
+    fn minority_side_of_partition_is_degraded_and_pinged_out() {
+        let config = SimConfig {
+            quorum: QuorumConfig {
+                enabled: true,
+                ..QuorumConfig::default()
+            },
+            ..SimConfig::default()
+        };
+        let mut sim = Simulation::new(5, 4, config);
+        sim.run();
+        for node in sim.nodes() {
+            assert_eq!(node.network().servers().count(), 5);
+            assert!(!node.node().is_degraded());
+        }
+
+        sim.partition(&[0, 1]);
+        // Longer than both the quorum timeout and the ping-out duration
+        sim.run_for(Duration::from_secs(400));
+
+        for minority in 0..2 {
+            let status = sim.node(minority).node().quorum_status();
+            assert!(status.degraded, "node {minority} isn't degraded");
+            assert_eq!(status.reachable, 2);
+        }
+        for majority in 2..5 {
+            let node = sim.node(majority);
+            assert!(!node.node().is_degraded());
+            assert_eq!(node.node().quorum_status().reachable, 3);
+            for minority in 0..2 {
+                let pinged_out = sim.node(minority).id();
+                assert!(node.network().server(pinged_out).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn re_adding_peer_without_key_keeps_pin() {
+        let sim = Simulation::new(2, 5, SimConfig::default());
+        let key = sim.node(1).node().sync_log().public_key();
+        assert!(key.is_some());
+
+        let log = sim.node(0).node().sync_log();
+        log.add_peer(peer("node2.test", key));
+        assert_eq!(pinned_key(&sim, 0, "node2.test"), key);
+
+        log.add_peer(peer("node2.test", None));
+        assert_eq!(pinned_key(&sim, 0, "node2.test"), key);
+
+        log.remove_peer(&"node2.test".parse().unwrap());
+        assert_eq!(pinned_key(&sim, 0, "node2.test"), None);
+    }
+
+    #[test]
+    fn peer_changes_followed_only_from_trusted_servers() {
+        let mut sim = Simulation::new(3, 6, SimConfig::default());
+        sim.run();
+
+        // Nobody has pinned node 2's key, so only node 2 follows its own change
+        sim.node(1).node().add_sync_peer(peer("node9.test", None));
+        sim.run();
+        assert!(has_peer(&sim, 1, "node9.test"));
+        assert!(!has_peer(&sim, 0, "node9.test"));
+        assert!(!has_peer(&sim, 2, "node9.test"));
+
+        // Once node 1 has pinned it, node 1 follows node 2's changes too
+        let key = sim.node(1).node().sync_log().public_key();
+        sim.node(0)
+            .node()
+            .sync_log()
+            .add_peer(peer("node2.test", key));
+        sim.node(1).node().add_sync_peer(peer("node8.test", None));
+        sim.run();
+        assert!(has_peer(&sim, 0, "node8.test"));
+        assert!(!has_peer(&sim, 2, "node8.test"));
+
+        // Every node still records the changes in its network state
+        sim.assert_converged();
+        for node in sim.nodes() {
+            assert_eq!(node.network().sync_peer_changes().count(), 2);
+        }
+    }
+}
@@ -0,0 +1,605 @@
+//! The connections over which the gossip network talks to its peers
+
+use super::codec::{self, Handshake, WireCodec};
+use super::connection::{ConnectionTable, PeerConnection};
+use super::message::Message;
+use super::network::{NetworkError, NetworkResult, Peer, PeerList};
+use super::*;
+use crate::validated::{ServerName, Validated};
+
+use futures::future::BoxFuture;
+use std::{
+    convert::TryInto,
+    net::{IpAddr, SocketAddr, SocketAddrV6},
+    sync::atomic::Ordering,
+    sync::{Arc, Mutex, Weak},
+};
+use tokio::{
+    io,
+    net::{lookup_host, TcpListener, TcpSocket, TcpStream},
+    select,
+    sync::{mpsc::UnboundedSender, oneshot},
+    task::JoinHandle,
+};
+use tokio_rustls::{TlsAcceptor, TlsConnector, TlsStream};
+
+use rustls::{server::AllowAnyAuthenticatedClient, Certificate, ClientConfig, ServerConfig};
+use sha1::{Digest, Sha1};
+use x509_parser::prelude::*;
+
+use tracing::instrument;
+
+/// Number of consecutive connection failures after which a peer is suspended
+const UNREACHABLE_THRESHOLD: u32 = 3;
+
+/// Carries conversations between this server and its peers, on behalf of a
+/// [`GossipNetwork`].
+///
+/// The gossip network decides which peers to talk to, and when; the transport
+/// delivers the messages. Real servers use a [`TlsTransport`], and tests can
+/// substitute one that simulates the network in memory.
+pub(super) trait Transport: Send + Sync {
+    /// Start a conversation with `peer` by sending `message`. Messages received in
+    /// reply are passed to `response_handler`, and the returned task completes when
+    /// the conversation ends.
+    fn start_conversation<'a>(
+        &'a self,
+        peer: &'a PeerConfig,
+        message: Message,
+        response_handler: UnboundedSender<Request>,
+    ) -> BoxFuture<'a, Result<JoinHandle<NetworkResult>, NetworkError>>;
+
+    /// Start accepting conversations from peers. The returned task runs until
+    /// [`shutdown`](Self::shutdown) is called.
+    fn listen(&self) -> BoxFuture<'_, Result<JoinHandle<()>, NetworkError>>;
+
+    /// Stop listening, and close any open connections
+    fn shutdown(&self);
+
+    /// Close the connection to the given peer, if any, without waiting for it to be idle
+    fn drop_connection(&self, name: &ServerName);
+
+    /// Stop using the connection to the given peer, if any, and close it once the
+    /// conversations in progress on it have finished
+    fn retire_connection(&self, name: &ServerName);
+
+    /// The number of messages waiting to be sent to each peer
+    fn queued_messages(&self) -> Vec<(ServerName, usize)>;
+}
+
+/// A transport over mutually authenticated TLS connections, keeping one
+/// long-lived connection to each peer
+pub(super) struct TlsTransport {
+    shutdown_send: Mutex<Option<oneshot::Sender<()>>>,
+    task_state: Arc<NetworkTaskState>,
+}
+
+/// State that's shared between the listener task and client code
+///
+/// Note that all additions to this struct must keep it `Send` and `Sync`.
+struct NetworkTaskState {
+    my_name: ServerName,
+    peers: Arc<PeerList>,
+    listen_addr: SocketAddr,
+    tls_client_config: Arc<ClientConfig>,
+    tls_server_config: Arc<ServerConfig>,
+    message_sender: UnboundedSender<Request>,
+    connections: ConnectionTable,
+}
+
+impl TlsTransport {
+    /// Create a transport for the server named `my_name`, which authorises
+    /// connections against `peers` and passes conversations started by them to
+    /// `message_sender`
+    pub(super) fn new(
+        my_name: ServerName,
+        ca_cert: Certificate,
+        node_config: NodeConfig,
+        peers: Arc<PeerList>,
+        message_sender: UnboundedSender<Request>,
+    ) -> Self {
+        let (client_cert, client_key) = node_config
+            .load_cert_and_keys()
+            .expect("Error loading client cert");
+
+        let mut root_store = rustls::RootCertStore::empty();
+        root_store
+            .add(&ca_cert)
+            .expect("Error adding certificate to store");
+
+        let client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(root_store.clone())
+            .with_single_cert(client_cert.clone(), client_key.clone())
+            .expect("Bad TLS client config");
+
+        let server_config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(root_store.clone()))
+            .with_single_cert(client_cert, client_key)
+            .expect("Bad TLS server config");
+
+        Self {
+            shutdown_send: Mutex::new(None),
+            task_state: Arc::new(NetworkTaskState {
+                my_name,
+                connections: ConnectionTable::new(my_name),
+                peers,
+                listen_addr: node_config.listen_addr,
+                tls_client_config: Arc::new(client_config),
+                tls_server_config: Arc::new(server_config),
+                message_sender,
+            }),
+        }
+    }
+}
+
+impl Transport for TlsTransport {
+    fn start_conversation<'a>(
+        &'a self,
+        peer: &'a PeerConfig,
+        message: Message,
+        response_handler: UnboundedSender<Request>,
+    ) -> BoxFuture<'a, Result<JoinHandle<NetworkResult>, NetworkError>> {
+        Box::pin(async move {
+            match self.task_state.connection_to(&peer.name).await {
+                Ok(conn) => conn.start_conversation(message, response_handler),
+                Err(NetworkError::NoHandshake) => {
+                    self.task_state
+                        .legacy_conversation(peer, message, response_handler)
+                        .await
+                }
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    fn listen(&self) -> BoxFuture<'_, Result<JoinHandle<()>, NetworkError>> {
+        Box::pin(async move {
+            let listener = TcpListener::bind(self.task_state.listen_addr).await?;
+            let task_state = Arc::clone(&self.task_state);
+
+            let (shutdown_send, shutdown_recv) = oneshot::channel();
+            {
+                let mut guard = self
+                    .shutdown_send
+                    .lock()
+                    .map_err(|e| NetworkError::InternalError(e.to_string()))?;
+                if guard.is_some() {
+                    Err(NetworkError::AlreadyListening)?;
+                }
+                let _ = guard.insert(shutdown_send);
+            }
+
+            Ok(tokio::spawn(async move {
+                if let Err(e) = task_state.listen_loop(listener, shutdown_recv).await {
+                    tracing::error!("Error in network sync listener: {}", e);
+                }
+            }))
+        })
+    }
+
+    fn shutdown(&self) {
+        if let Ok(mut shutdown_send) = self.shutdown_send.lock() {
+            if let Some(sender) = shutdown_send.take() {
+                sender.send(()).ok();
+            }
+        }
+
+        self.task_state.connections.close_all();
+    }
+
+    fn drop_connection(&self, name: &ServerName) {
+        self.task_state.connections.drop_connection(name);
+    }
+
+    fn retire_connection(&self, name: &ServerName) {
+        self.task_state.connections.retire_connection(name);
+    }
+
+    fn queued_messages(&self) -> Vec<(ServerName, usize)> {
+        self.task_state.connections.queued_frames()
+    }
+}
+
+impl NetworkTaskState {
+    #[instrument(skip(self))]
+    async fn listen_loop(
+        self: Arc<NetworkTaskState>,
+        listener: TcpListener,
+        mut shutdown: oneshot::Receiver<()>,
+    ) -> Result<(), io::Error> {
+        let tls_acceptor = TlsAcceptor::from(Arc::clone(&self.tls_server_config));
+
+        loop {
+            select! {
+                res = listener.accept() =>
+                {
+                    if let Ok((conn, _)) = res
+                    {
+                        let tls_acceptor = tls_acceptor.clone();
+                        let self_copy = Arc::clone(&self);
+                        tokio::spawn(async move {
+                            if let Err(e) = self_copy.handle_connection(tls_acceptor, conn).await
+                            {
+                                tracing::error!("Error in network sync connection handler: {}", e);
+                            }
+                        });
+                    }
+                },
+                _ = &mut shutdown =>
+                {
+                    break
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip(tls_acceptor, self))]
+    async fn handle_connection(
+        self: Arc<NetworkTaskState>,
+        tls_acceptor: TlsAcceptor,
+        conn: TcpStream,
+    ) -> Result<(), NetworkError> {
+        let mut stream: TlsStream<TcpStream> = tls_acceptor.accept(conn).await?.into();
+        let peer_name = self.authorise_peer(&stream).await?;
+        let peer = self.peers.get(&peer_name);
+
+        match WireCodec::accept(&mut stream).await? {
+            Handshake::Negotiated(codec) => {
+                if peer.is_some_and(|peer| peer.set_legacy(false)) {
+                    tracing::info!("Peer {} now supports the connection handshake", peer_name);
+                }
+
+                tracing::debug!("Accepted connection from {} using {:?}", peer_name, codec);
+                self.register_connection(stream, peer_name, peer_name, codec);
+            }
+            Handshake::Legacy(message) => {
+                if peer.is_some_and(|peer| peer.set_legacy(true)) {
+                    tracing::info!(
+                        "Peer {} predates the connection handshake; using a connection per conversation",
+                        peer_name
+                    );
+                }
+
+                codec::legacy_conversation(
+                    stream,
+                    peer_name,
+                    Some(message),
+                    self.message_sender.clone(),
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Find the current connection to the given peer, establishing one if needed
+    async fn connection_to(
+        self: &Arc<Self>,
+        name: &ServerName,
+    ) -> Result<Arc<PeerConnection>, NetworkError> {
+        if let Some(conn) = self.connections.live(name) {
+            return Ok(conn);
+        }
+
+        let peer = self
+            .peers
+            .get(name)
+            .ok_or(NetworkError::UnknownPeer(*name))?;
+        // Until it's upgraded, each conversation needs a connection of its own
+        if peer.is_legacy() {
+            return Err(NetworkError::NoHandshake);
+        }
+        let _guard = peer.connect_lock.lock().await;
+
+        // Someone else may have connected while we were waiting for the lock
+        if let Some(conn) = self.connections.live(name) {
+            return Ok(conn);
+        }
+
+        if let Some(remaining) = peer.backoff_remaining() {
+            return Err(NetworkError::Backoff(remaining, *name));
+        }
+
+        self.attempt_connection(&peer).await
+    }
+
+    /// Try to connect to a peer, backing off after failures. If it has just failed
+    /// too many times in a row, suspend it and keep trying in the background until
+    /// it recovers.
+    async fn attempt_connection(
+        self: &Arc<Self>,
+        peer: &Peer,
+    ) -> Result<Arc<PeerConnection>, NetworkError> {
+        match self.open_connection(peer).await {
+            // A peer which predates the handshake is still reachable
+            result @ (Ok(_) | Err(NetworkError::NoHandshake)) => {
+                peer.connection_succeeded();
+                if peer.resume() {
+                    tracing::info!("Peer {} is reachable again", peer.conf.name);
+                }
+                result
+            }
+            Err(e) => {
+                if peer.connection_failed() == UNREACHABLE_THRESHOLD && peer.suspend() {
+                    tracing::warn!(
+                        "Peer {} is unreachable; no longer choosing it",
+                        peer.conf.name
+                    );
+                    tokio::spawn(Arc::clone(self).reconnect_loop(peer.conf.name));
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn reconnect_loop(self: Arc<Self>, name: ServerName) {
+        let Some(peer) = self.peers.get(&name) else {
+            return;
+        };
+
+        // Stop if the peer is re-enabled or disabled by someone else, or is removed or
+        // reconfigured, since its replacement starts afresh
+        while peer.suspended.load(Ordering::SeqCst)
+            && self
+                .peers
+                .get(&name)
+                .is_some_and(|p| Arc::ptr_eq(&p, &peer))
+        {
+            if let Some(remaining) = peer.backoff_remaining() {
+                tokio::time::sleep(remaining).await;
+            }
+
+            let _guard = peer.connect_lock.lock().await;
+            if self.connections.live(&name).is_some() {
+                peer.connection_succeeded();
+                peer.resume();
+                break;
+            }
+            match self.open_connection(&peer).await {
+                Ok(_) | Err(NetworkError::NoHandshake) => {
+                    peer.connection_succeeded();
+                    if peer.resume() {
+                        tracing::info!("Peer {} is reachable again", name);
+                    }
+                }
+                Err(e) => {
+                    tracing::debug!("Reconnection to {} failed: {}", name, e);
+                    peer.connection_failed();
+                }
+            }
+        }
+    }
+
+    async fn open_connection(
+        self: &Arc<Self>,
+        peer: &Peer,
+    ) -> Result<Arc<PeerConnection>, NetworkError> {
+        let mut stream = self.open_stream(&peer.conf).await?;
+
+        let codec = match WireCodec::negotiate(&mut stream).await {
+            Ok(codec) => codec,
+            Err(NetworkError::NoHandshake) => {
+                if peer.set_legacy(true) {
+                    tracing::info!(
+                        "Peer {} predates the connection handshake; using a connection per conversation",
+                        peer.conf.name
+                    );
+                }
+                return Err(NetworkError::NoHandshake);
+            }
+            Err(e) => return Err(e),
+        };
+        tracing::debug!("Connected to {} using {:?}", peer.conf.name, codec);
+
+        Ok(self.register_connection(stream, peer.conf.name, self.my_name, codec))
+    }
+
+    /// Start a conversation with a peer from before the connection handshake, on a
+    /// connection of its own
+    async fn legacy_conversation(
+        &self,
+        peer: &PeerConfig,
+        message: Message,
+        response_handler: UnboundedSender<Request>,
+    ) -> Result<JoinHandle<NetworkResult>, NetworkError> {
+        let mut stream = self.open_stream(peer).await?;
+        codec::write_legacy_message(&mut stream, &message).await?;
+
+        let peer_name = peer.name;
+        Ok(tokio::spawn(async move {
+            let result =
+                codec::legacy_conversation(stream, peer_name, None, response_handler).await;
+            if let Err(e) = &result {
+                tracing::error!(
+                    "Error in legacy sync conversation with {}: {}",
+                    peer_name,
+                    e
+                );
+            }
+            result
+        }))
+    }
+
+    /// Open an authorised TLS stream to a peer
+    async fn open_stream(&self, peer: &PeerConfig) -> Result<TlsStream<TcpStream>, NetworkError> {
+        let mut local_addr = self.listen_addr;
+        local_addr.set_port(0);
+        let connector = TlsConnector::from(Arc::clone(&self.tls_client_config));
+        let conn = Self::connect(&local_addr, &peer.address).await?;
+        let server_name = (peer.name.value() as &str)
+            .try_into()
+            .expect("Invalid server name");
+        let stream: TlsStream<TcpStream> = connector.connect(server_name, conn).await?.into();
+
+        let peer_name = self.authorise_peer(&stream).await?;
+        if peer_name != peer.name {
+            return Err(NetworkError::AuthzError(format!(
+                "Connected to {} but found {}",
+                peer.name, peer_name
+            )));
+        }
+
+        Ok(stream)
+    }
+
+    /// Take an authorised stream and make it available for sending to the given peer,
+    /// returning the connection that should be used for it
+    fn register_connection(
+        self: &Arc<Self>,
+        stream: TlsStream<TcpStream>,
+        peer_name: ServerName,
+        initiator: ServerName,
+        codec: WireCodec,
+    ) -> Arc<PeerConnection> {
+        let weak_self: Weak<Self> = Arc::downgrade(self);
+        let backlog_state = Weak::clone(&weak_self);
+        let conn = PeerConnection::spawn(
+            stream,
+            peer_name,
+            initiator,
+            codec,
+            self.message_sender.clone(),
+            move |backlogged| {
+                let Some(peer) = backlog_state
+                    .upgrade()
+                    .and_then(|s| s.peers.get(&peer_name))
+                else {
+                    return;
+                };
+                if backlogged {
+                    if peer.suspend() {
+                        tracing::warn!(
+                            "Send queue to {} is full; no longer choosing it",
+                            peer_name
+                        );
+                    }
+                } else if peer.resume() {
+                    tracing::info!("Send queue to {} has drained", peer_name);
+                }
+            },
+            move |closed| {
+                if let Some(state) = weak_self.upgrade() {
+                    state.connections.closed(closed);
+                }
+            },
+        );
+
+        self.connections.register(conn)
+    }
+
+    fn get_socket_for_addr(addr: &SocketAddr) -> std::io::Result<TcpSocket> {
+        match addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4(),
+            SocketAddr::V6(_) => TcpSocket::new_v6(),
+        }
+    }
+
+    async fn connect(local_addr: &SocketAddr, host_addr: &str) -> Result<TcpStream, NetworkError> {
+        // Default error if the loop does not run at all
+        let mut last_err = NetworkError::NoAddress(host_addr.to_string());
+
+        for ip_addr in lookup_host(host_addr).await? {
+            // Ensure ip_addr is an IPv6 address if the socket binds an IPv6 address
+            let ip_addr = match (local_addr, ip_addr) {
+                (SocketAddr::V6(_), SocketAddr::V4(ipv4_addr)) => SocketAddr::V6(
+                    SocketAddrV6::new(ipv4_addr.ip().to_ipv6_mapped(), ipv4_addr.port(), 0, 0),
+                ),
+                (_, ip_addr) => ip_addr,
+            };
+
+            let socket = Self::get_socket_for_addr(local_addr)?;
+            socket.bind(*local_addr)?;
+            match socket.connect(ip_addr).await {
+                Ok(conn) => {
+                    tracing::info!("Connected to {} ({})", host_addr, ip_addr);
+                    return Ok(conn);
+                }
+                Err(err) => {
+                    tracing::trace!("Could not connect to {} ({}): {}", host_addr, ip_addr, err);
+                    last_err = err.into();
+                }
+            }
+        }
+
+        tracing::error!(
+            "Could not connect to {}. Last error: {}",
+            host_addr,
+            last_err
+        );
+
+        Err(last_err)
+    }
+
+    /// Check that the other end of a stream is a configured peer, connecting from its
+    /// configured address with its configured certificate, and return its name
+    async fn authorise_peer(
+        &self,
+        stream: &TlsStream<TcpStream>,
+    ) -> Result<ServerName, NetworkError> {
+        // Get the peer name we're talking to from the tls certificate
+        let (tcp_stream, state) = stream.get_ref();
+        let peer_certs = state
+            .peer_certificates()
+            .ok_or_else(|| NetworkError::InternalError("No peer certificates?".to_string()))?;
+
+        let (_, cert) = X509Certificate::from_der(&peer_certs[0].0)
+            .map_err(|e| NetworkError::InternalError(format!("Invalid peer certificate? {e}")))?;
+
+        let peer_name = cert
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .ok_or_else(|| NetworkError::InternalError("Couldn't parse peer CN".to_string()))?
+            .to_string();
+        let peer_name = ServerName::convert(&peer_name).map_err(|_| {
+            NetworkError::InternalError(format!(
+                "Invalid server name in certificate: {}",
+                &peer_name
+            ))
+        })?;
+
+        let peer = self.peers.get(&peer_name).ok_or_else(|| {
+            NetworkError::AuthzError("Couldn't find peer configuration".to_string())
+        })?;
+        let peer_conf = &peer.conf;
+
+        let remote_addr = tcp_stream.peer_addr()?;
+        let allowed_ip_addresses: Vec<_> = lookup_host(&peer_conf.address)
+            .await?
+            .map(|addr| match (remote_addr.ip(), addr.ip()) {
+                (IpAddr::V6(_), IpAddr::V4(ipv4_addr)) => IpAddr::V6(ipv4_addr.to_ipv6_mapped()),
+                (IpAddr::V4(_), IpAddr::V6(ipv6_addr)) => ipv6_addr
+                    .to_ipv4_mapped()
+                    .map(IpAddr::V4)
+                    .unwrap_or(IpAddr::V6(ipv6_addr)),
+                (_, addr) => addr,
+            })
+            .collect();
+        if !allowed_ip_addresses.contains(&remote_addr.ip()) {
+            return Err(NetworkError::AuthzError(format!(
+                "IP address doesn't match peer configuration ({} not in {:?})",
+                remote_addr.ip(),
+                allowed_ip_addresses,
+            )));
+        }
+
+        let expected_fingerprint = &peer_conf.fingerprint;
+        let mut cert_hasher = Sha1::new();
+        cert_hasher.update(&peer_certs[0].0);
+        let remote_fingerprint = hex::encode(cert_hasher.finalize());
+
+        if &remote_fingerprint != expected_fingerprint {
+            return Err(NetworkError::AuthzError(format!(
+                "Certificate doesn't match ({remote_fingerprint}/{expected_fingerprint})"
+            )));
+        }
+
+        Ok(peer_name)
+    }
+}
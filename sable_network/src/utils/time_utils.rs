@@ -1,5 +1,22 @@
 use chrono::prelude::*;
 
+#[cfg(not(test))]
 pub fn now() -> i64 {
     Utc::now().timestamp()
 }
+
+/// In tests, time follows tokio's clock, so that code run on a paused runtime (such
+/// as the sync simulator) sees simulated time pass. Outside such a runtime this is
+/// the same as the wall clock.
+#[cfg(test)]
+pub fn now() -> i64 {
+    use std::sync::OnceLock;
+    static START: OnceLock<(DateTime<Utc>, std::time::Instant)> = OnceLock::new();
+
+    let (wall, instant) = START.get_or_init(|| (Utc::now(), std::time::Instant::now()));
+    let elapsed = tokio::time::Instant::now()
+        .into_std()
+        .saturating_duration_since(*instant);
+
+    (wall.timestamp_millis() + elapsed.as_millis() as i64) / 1000
+}
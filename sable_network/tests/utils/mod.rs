@@ -7,11 +7,13 @@ pub mod receiver;
 pub fn empty_network_config() -> NetworkConfig {
     NetworkConfig {
         opers: Vec::new(),
+        oper_classes: Vec::new(),
         debug_mode: false,
         default_roles: HashMap::new(),
         alias_users: Vec::new(),
         object_expiry: 0,
         pingout_duration: 240,
+        audit_log_retention: Default::default(),
     }
 }
 
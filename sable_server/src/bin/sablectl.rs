@@ -0,0 +1,740 @@
+//! Command-line client for the management interface of a sable server.
+
+use anyhow::{bail, Context};
+use clap::{Args, Parser, Subcommand};
+use hyper::{
+    header::{CONTENT_TYPE, HOST},
+    Body, Method, Request,
+};
+use sable_network::{
+    config::TlsConfig,
+    network::ban::BanMatchType,
+    rpc::{NewBanAction, NewBanRequest},
+};
+use sable_server::config::LogLevel;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+use std::{
+    fs::File,
+    io::{BufReader, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Opts {
+    /// Address of the server's management interface, as `host:port`
+    #[arg(short, long)]
+    address: String,
+
+    /// Name to expect in the server's certificate. Defaults to the host part of
+    /// `address`
+    #[arg(short, long)]
+    server_name: Option<String>,
+
+    /// Client certificate file, authorised in the server's management config
+    #[arg(short, long)]
+    cert: PathBuf,
+
+    /// Private key for the client certificate
+    #[arg(short, long)]
+    key: PathBuf,
+
+    /// CA certificate used to verify the server's certificate
+    #[arg(long)]
+    ca: PathBuf,
+
+    /// Print responses as JSON instead of tables
+    #[arg(short, long)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Show event log and propagation statistics
+    Stats,
+    /// Dump the server's view of the network state
+    DumpNetwork(DumpArgs),
+    /// Dump the server's event log
+    DumpEvents(DumpArgs),
+    /// Shut the server down
+    Shutdown(ConfirmArgs),
+    /// Restart the server, resyncing to the network
+    Restart(ConfirmArgs),
+    /// Upgrade the server in place to the executable on disk
+    Upgrade(ConfirmArgs),
+    /// Show a single object, the objects related to it, and the events that
+    /// last changed them
+    #[command(subcommand)]
+    Inspect(InspectCommand),
+    /// Manage network bans
+    #[command(subcommand)]
+    Bans(BanCommand),
+    /// Show or change the server's log filters
+    #[command(subcommand)]
+    LogFilters(LogFilterCommand),
+}
+
+#[derive(Debug, Args)]
+struct DumpArgs {
+    /// Select part of the output with a path such as `.users[][1].user`. Supports
+    /// `.field`, `[index]`, `[]` to iterate over an array or object's values,
+    /// and `[field=value]` to keep only the objects where `field` has that value.
+    #[arg(short, long)]
+    filter: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct ConfirmArgs {
+    /// Don't ask for confirmation
+    #[arg(short, long)]
+    yes: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum InspectCommand {
+    /// A user, by ID or nickname
+    User { name: String },
+    /// A channel, by ID or name
+    Channel { name: String },
+    /// An account, by ID or name
+    Account { name: String },
+    /// A server, by ID or name
+    Server { name: String },
+    /// An event, by ID
+    Event { id: u64 },
+}
+
+impl InspectCommand {
+    fn path(&self) -> String {
+        match self {
+            Self::User { name } => format!("/users/{}", percent_encode(name)),
+            Self::Channel { name } => format!("/channels/{}", percent_encode(name)),
+            Self::Account { name } => format!("/accounts/{}", percent_encode(name)),
+            Self::Server { name } => format!("/servers/{}", percent_encode(name)),
+            Self::Event { id } => format!("/events/{id}"),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum BanCommand {
+    /// List the active network bans
+    List,
+    /// Add a network ban
+    Add(AddBanArgs),
+    /// Remove the network ban with the given ID
+    Remove { id: u64 },
+}
+
+#[derive(Debug, Args)]
+struct AddBanArgs {
+    /// The pattern expression to match against
+    pattern: String,
+
+    /// The reason shown to affected users
+    #[arg(short, long)]
+    reason: String,
+
+    /// How long the ban lasts, in minutes
+    #[arg(short, long)]
+    duration: i64,
+
+    /// When the ban is matched: `pre_registration`, `new_connection` or `pre_sasl`
+    #[arg(long = "type", value_parser = parse_snake_case::<BanMatchType>)]
+    match_type: Option<BanMatchType>,
+
+    /// `refuse_connection` or `require_sasl`
+    #[arg(long, value_parser = parse_snake_case::<NewBanAction>)]
+    action: Option<NewBanAction>,
+
+    /// A reason shown only to operators
+    #[arg(long)]
+    oper_reason: Option<String>,
+
+    /// Don't affect matching users who are already connected
+    #[arg(long)]
+    new_only: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum LogFilterCommand {
+    /// Show the filters in effect
+    Show,
+    /// Change the filters
+    Set(SetLogFilterArgs),
+    /// Return to the configured filters
+    Reset,
+}
+
+#[derive(Debug, Args)]
+struct SetLogFilterArgs {
+    /// Level for modules without their own level
+    #[arg(long, value_parser = parse_level)]
+    default: Option<LogLevel>,
+
+    /// Level for a module, as `module=level`. May be repeated
+    #[arg(long = "module", value_parser = parse_module_level)]
+    modules: Vec<(String, LogLevel)>,
+
+    /// Level for a log target, as `index=level`. May be repeated
+    #[arg(long = "target", value_parser = parse_target_level)]
+    targets: Vec<(usize, LogLevel)>,
+
+    /// Return to the configured filters after this many seconds
+    #[arg(long)]
+    revert_after: Option<u64>,
+}
+
+fn parse_snake_case<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_value(Value::String(value.to_owned())).map_err(|e| e.to_string())
+}
+
+fn parse_level(value: &str) -> Result<LogLevel, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid log level {value}"))
+}
+
+fn parse_module_level(value: &str) -> Result<(String, LogLevel), String> {
+    let (module, level) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected module=level, got {value}"))?;
+    Ok((module.to_owned(), parse_level(level)?))
+}
+
+fn parse_target_level(value: &str) -> Result<(usize, LogLevel), String> {
+    let (target, level) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected index=level, got {value}"))?;
+    let target = target
+        .parse()
+        .map_err(|_| format!("invalid log target index {target}"))?;
+    Ok((target, parse_level(level)?))
+}
+
+/// A connection to a management interface
+struct ManagementClient {
+    address: String,
+    server_name: rustls::ServerName,
+    connector: TlsConnector,
+}
+
+impl ManagementClient {
+    fn new(opts: &Opts) -> anyhow::Result<Self> {
+        let ca_file = File::open(&opts.ca)
+            .with_context(|| format!("Could not open {}", opts.ca.display()))?;
+        let ca_cert = rustls_pemfile::certs(&mut BufReader::new(ca_file))
+            .with_context(|| format!("Could not read {}", opts.ca.display()))?
+            .pop()
+            .with_context(|| format!("No certificate in {}", opts.ca.display()))?;
+        let mut root_store = rustls::RootCertStore::empty();
+        root_store
+            .add(&rustls::Certificate(ca_cert))
+            .context("Invalid CA certificate")?;
+
+        let client_cert = TlsConfig {
+            key_file: opts.key.clone(),
+            cert_file: opts.cert.clone(),
+        }
+        .load_from_disk()?;
+
+        let config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(root_store)
+            .with_single_cert(
+                client_cert
+                    .cert_chain
+                    .into_iter()
+                    .map(rustls::Certificate)
+                    .collect(),
+                rustls::PrivateKey(client_cert.key),
+            )
+            .context("Invalid client certificate")?;
+
+        let server_name = match &opts.server_name {
+            Some(name) => name.as_str(),
+            None => opts
+                .address
+                .rsplit_once(':')
+                .map_or(opts.address.as_str(), |(host, _)| host),
+        };
+
+        Ok(Self {
+            address: opts.address.clone(),
+            server_name: server_name
+                .try_into()
+                .with_context(|| format!("Invalid server name {server_name}"))?,
+            connector: TlsConnector::from(Arc::new(config)),
+        })
+    }
+
+    /// Make a request, returning the parsed JSON response. Empty responses are
+    /// returned as `null`, and error responses are returned as errors.
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> anyhow::Result<Value> {
+        let stream = TcpStream::connect(&self.address)
+            .await
+            .with_context(|| format!("Could not connect to {}", self.address))?;
+        let stream = self
+            .connector
+            .connect(self.server_name.clone(), stream)
+            .await
+            .context("TLS handshake failed")?;
+
+        let (mut sender, connection) = hyper::client::conn::handshake(stream).await?;
+        tokio::spawn(connection);
+
+        let body = match body {
+            Some(body) => Body::from(body.to_string()),
+            None => Body::empty(),
+        };
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(HOST, &self.address)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)?;
+
+        let response = sender.send_request(request).await?;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+
+        if !status.is_success() {
+            bail!("Request failed: {status}");
+        }
+        if body.is_empty() {
+            return Ok(Value::Null);
+        }
+
+        let value: Value = serde_json::from_slice(&body).context("Invalid response")?;
+        if let Some(error) = value.get("error") {
+            bail!("{}", cell_text(error));
+        }
+        Ok(value)
+    }
+}
+
+/// One step of a `--filter` path
+#[derive(Debug, PartialEq)]
+enum FilterStep {
+    Field(String),
+    Index(usize),
+    Iterate,
+    Select(String, String),
+}
+
+fn parse_filter(filter: &str) -> anyhow::Result<Vec<FilterStep>> {
+    let mut steps = Vec::new();
+    let mut rest = filter.trim();
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let Some((inner, after)) = after.split_once(']') else {
+                bail!("Unclosed [ in filter");
+            };
+            steps.push(if inner.is_empty() {
+                FilterStep::Iterate
+            } else if let Some((field, value)) = inner.split_once('=') {
+                FilterStep::Select(field.to_owned(), value.trim_matches('"').to_owned())
+            } else {
+                FilterStep::Index(
+                    inner
+                        .parse()
+                        .with_context(|| format!("Invalid index {inner} in filter"))?,
+                )
+            });
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(&['.', '['][..]).unwrap_or(after.len());
+            if end > 0 {
+                steps.push(FilterStep::Field(after[..end].to_owned()));
+            }
+            rest = &after[end..];
+        } else {
+            bail!("Invalid filter at {rest}");
+        }
+    }
+
+    Ok(steps)
+}
+
+/// Apply a filter path. If it iterates, the results are collected into an array.
+fn apply_filter(value: Value, steps: &[FilterStep]) -> Value {
+    let mut current = vec![value];
+    let mut iterated = false;
+
+    for step in steps {
+        current = match step {
+            FilterStep::Field(field) => current
+                .into_iter()
+                .map(|v| v.get(field).cloned().unwrap_or(Value::Null))
+                .collect(),
+            FilterStep::Index(index) => current
+                .into_iter()
+                .map(|v| v.get(index).cloned().unwrap_or(Value::Null))
+                .collect(),
+            FilterStep::Iterate => {
+                iterated = true;
+                current
+                    .into_iter()
+                    .flat_map(|v| match v {
+                        Value::Array(items) => items,
+                        Value::Object(map) => map.into_iter().map(|(_, v)| v).collect(),
+                        _ => Vec::new(),
+                    })
+                    .collect()
+            }
+            FilterStep::Select(field, expected) => current
+                .into_iter()
+                .filter(|v| v.get(field).is_some_and(|f| cell_text(f) == *expected))
+                .collect(),
+        };
+    }
+
+    if iterated {
+        Value::Array(current)
+    } else {
+        current.pop().unwrap_or(Value::Null)
+    }
+}
+
+/// Text for a single value in a table: strings without quotes, and anything
+/// other than a scalar as compact JSON
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Flatten nested objects and arrays into rows of dotted paths and values
+fn flatten(prefix: &str, value: &Value, rows: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten(&path, value, rows);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (index, value) in items.iter().enumerate() {
+                flatten(&format!("{prefix}[{index}]"), value, rows);
+            }
+        }
+        other => rows.push((prefix.to_owned(), cell_text(other))),
+    }
+}
+
+fn print_rows(header: &[String], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = header.iter().map(String::len).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let print_row = |row: &[String]| {
+        let line: Vec<_> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+
+    if !header.is_empty() {
+        print_row(header);
+    }
+    for row in rows {
+        print_row(row);
+    }
+}
+
+/// Print a value as a table. An array of objects gets a column for each of
+/// `columns`, or for every field if none are given; anything else is flattened
+/// into path and value columns.
+fn print_table(value: &Value, columns: &[&str]) {
+    match value {
+        Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_object) => {
+            let header: Vec<String> = if columns.is_empty() {
+                let mut fields = Map::new();
+                for item in items.iter().filter_map(Value::as_object) {
+                    for key in item.keys() {
+                        fields.insert(key.clone(), Value::Null);
+                    }
+                }
+                fields.keys().cloned().collect()
+            } else {
+                columns.iter().map(|c| c.to_string()).collect()
+            };
+            let rows: Vec<Vec<String>> = items
+                .iter()
+                .map(|item| {
+                    header
+                        .iter()
+                        .map(|c| item.get(c).map(cell_text).unwrap_or_default())
+                        .collect()
+                })
+                .collect();
+            print_rows(&header, &rows);
+        }
+        Value::Null => {}
+        other => {
+            let mut rows = Vec::new();
+            flatten("", other, &mut rows);
+            let rows: Vec<Vec<String>> = rows.into_iter().map(|(k, v)| vec![k, v]).collect();
+            print_rows(&[], &rows);
+        }
+    }
+}
+
+/// Encode a name for use as a path segment
+fn percent_encode(name: &str) -> String {
+    name.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn confirm(action: &str, address: &str) -> anyhow::Result<bool> {
+    print!("{action} the server at {address}? [y/N] ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+async fn dump(client: &ManagementClient, path: &str, args: DumpArgs) -> anyhow::Result<Value> {
+    let response = client.request(Method::GET, path, None).await?;
+    match &args.filter {
+        Some(filter) => Ok(apply_filter(response, &parse_filter(filter)?)),
+        None => Ok(response),
+    }
+}
+
+async fn shutdown(
+    client: &ManagementClient,
+    address: &str,
+    action: &str,
+    path: &str,
+    args: ConfirmArgs,
+) -> anyhow::Result<()> {
+    if !args.yes && !confirm(action, address)? {
+        println!("Cancelled");
+        return Ok(());
+    }
+    client.request(Method::POST, path, None).await?;
+    println!("{action} requested");
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let opts = Opts::parse();
+    let client = ManagementClient::new(&opts)?;
+
+    let (response, columns): (Value, &[&str]) = match opts.command {
+        Command::Stats => (client.request(Method::GET, "/statistics", None).await?, &[]),
+        Command::DumpNetwork(args) => (dump(&client, "/dump-network", args).await?, &[]),
+        Command::DumpEvents(args) => (dump(&client, "/dump-events", args).await?, &[]),
+        Command::Shutdown(args) => {
+            return shutdown(&client, &opts.address, "Shut down", "/shutdown", args).await
+        }
+        Command::Restart(args) => {
+            return shutdown(&client, &opts.address, "Restart", "/restart", args).await
+        }
+        Command::Upgrade(args) => {
+            return shutdown(&client, &opts.address, "Upgrade", "/upgrade", args).await
+        }
+        Command::Inspect(target) => (
+            client.request(Method::GET, &target.path(), None).await?,
+            &[],
+        ),
+        Command::Bans(BanCommand::List) => (
+            client.request(Method::GET, "/bans", None).await?,
+            &[
+                "id",
+                "match_type",
+                "action",
+                "expires",
+                "setter_info",
+                "reason",
+            ],
+        ),
+        Command::Bans(BanCommand::Add(args)) => {
+            let request = NewBanRequest {
+                match_type: args.match_type,
+                action: args.action,
+                apply_existing: Some(!args.new_only),
+                pattern: args.pattern,
+                duration: args.duration,
+                reason: args.reason,
+                oper_reason: args.oper_reason,
+            };
+            let body = serde_json::to_value(request)?;
+            (
+                client.request(Method::POST, "/bans", Some(body)).await?,
+                &[],
+            )
+        }
+        Command::Bans(BanCommand::Remove { id }) => {
+            client
+                .request(Method::DELETE, &format!("/bans/{id}"), None)
+                .await?;
+            println!("Removed ban {id}");
+            return Ok(());
+        }
+        Command::LogFilters(LogFilterCommand::Show) => (
+            client.request(Method::GET, "/log-filters", None).await?,
+            &[],
+        ),
+        Command::LogFilters(LogFilterCommand::Set(args)) => {
+            let change = json!({
+                "default_level": args.default,
+                "module_levels": args
+                    .modules
+                    .into_iter()
+                    .map(|(module, level)| (module, json!(level)))
+                    .collect::<Map<_, _>>(),
+                "targets": args
+                    .targets
+                    .into_iter()
+                    .map(|(target, level)| json!({ "target": target, "level": level }))
+                    .collect::<Vec<_>>(),
+                "revert_after": args.revert_after,
+            });
+            (
+                client
+                    .request(Method::POST, "/log-filters", Some(change))
+                    .await?,
+                &[],
+            )
+        }
+        Command::LogFilters(LogFilterCommand::Reset) => (
+            client
+                .request(Method::POST, "/log-filters/reset", None)
+                .await?,
+            &[],
+        ),
+    };
+
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&response)?);
+    } else {
+        print_table(&response, columns);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(value: Value, filter: &str) -> Value {
+        apply_filter(value, &parse_filter(filter).unwrap())
+    }
+
+    #[test]
+    fn parse_steps() {
+        use FilterStep::*;
+
+        assert_eq!(
+            parse_filter(r#".servers[].peers[2][name="a.test"]"#).unwrap(),
+            vec![
+                Field("servers".to_owned()),
+                Iterate,
+                Field("peers".to_owned()),
+                Index(2),
+                Select("name".to_owned(), "a.test".to_owned()),
+            ]
+        );
+        assert_eq!(parse_filter(" . ").unwrap(), vec![]);
+        assert_eq!(parse_filter("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn parse_malformed() {
+        assert!(parse_filter(".servers[").is_err());
+        assert!(parse_filter(".servers[x]").is_err());
+        assert!(parse_filter(".servers[-1]").is_err());
+        assert!(parse_filter("servers").is_err());
+        assert!(parse_filter(".servers[0]x").is_err());
+    }
+
+    #[test]
+    fn field_and_index() {
+        let value = json!({ "a": { "b": [10, 20, 30] } });
+
+        assert_eq!(filter(value.clone(), ".a.b[1]"), json!(20));
+        assert_eq!(filter(value.clone(), ".a.b"), json!([10, 20, 30]));
+        assert_eq!(filter(value.clone(), ""), value);
+        assert_eq!(filter(value.clone(), ".a.b[5]"), Value::Null);
+        assert_eq!(filter(value.clone(), ".missing.b"), Value::Null);
+        assert_eq!(filter(value, ".a[0]"), Value::Null);
+    }
+
+    #[test]
+    fn iterate() {
+        let value = json!({
+            "servers": [
+                { "name": "a.test", "up": true },
+                { "name": "b.test", "up": false },
+            ],
+            "counts": { "x": 1, "y": 2 },
+        });
+
+        assert_eq!(
+            filter(value.clone(), ".servers[].name"),
+            json!(["a.test", "b.test"])
+        );
+        assert_eq!(filter(value.clone(), ".counts[]"), json!([1, 2]));
+        // Iterating over something that isn't a collection yields nothing, but
+        // still gives an array
+        assert_eq!(filter(value, ".servers[0].name[]"), json!([]));
+    }
+
+    #[test]
+    fn select() {
+        let value = json!([
+            { "name": "a.test", "port": 6667, "tls": false },
+            { "name": "b.test", "port": 6697, "tls": true },
+            { "port": 6697 },
+        ]);
+
+        assert_eq!(
+            filter(value.clone(), r#"[][name="b.test"].port"#),
+            json!([6697])
+        );
+        // Values are compared as they'd be shown in a table
+        assert_eq!(
+            filter(value.clone(), "[][port=6697].name"),
+            json!(["b.test", null])
+        );
+        assert_eq!(
+            filter(value.clone(), "[][tls=true].name"),
+            json!(["b.test"])
+        );
+        assert_eq!(filter(value, "[][name=c.test]"), json!([]));
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use std::{
     fs::File,
@@ -13,6 +13,37 @@ use tracing_core::LevelFilter;
 pub struct AuthorisedFingerprint {
     pub name: String,
     pub fingerprint: String,
+    /// The operations this client may perform; all of them if not configured
+    #[serde(default = "ManagementPermission::all")]
+    pub permissions: HashSet<ManagementPermission>,
+}
+
+/// A group of management operations which a client can be permitted to perform
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManagementPermission {
+    /// Read statistics and metrics
+    Stats,
+    /// Read network state, the event log and the audit log, including dumps
+    Inspect,
+    /// Add and remove network bans, and kill users
+    Bans,
+    /// Change sync peers and log filters, and reload configuration
+    Configure,
+    /// Shut down, restart or upgrade the server
+    Shutdown,
+}
+
+impl ManagementPermission {
+    pub fn all() -> HashSet<Self> {
+        HashSet::from([
+            Self::Stats,
+            Self::Inspect,
+            Self::Bans,
+            Self::Configure,
+            Self::Shutdown,
+        ])
+    }
 }
 
 /// One of the special built-in log targets
@@ -128,3 +159,24 @@ impl From<LogLevel> for LevelFilter {
         }
     }
 }
+
+/// Error returned when parsing an unknown log level name
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown log level {0}")]
+pub struct InvalidLogLevel(String);
+
+impl std::str::FromStr for LogLevel {
+    type Err = InvalidLogLevel;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "trace" => Ok(LogLevel::Trace),
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            "off" => Ok(LogLevel::Off),
+            _ => Err(InvalidLogLevel(s.to_string())),
+        }
+    }
+}
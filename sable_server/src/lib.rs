@@ -22,6 +22,12 @@ pub mod run;
 
 mod management;
 
+mod reload;
+pub use reload::{reload_config, ConfigReloadReport, ConfigReloadResult};
+
 mod tracing_config;
 
-pub use tracing_config::build_subscriber;
+pub use tracing_config::{
+    build_subscriber, log_filters, LogFilterChange, LogFilterError, LogFilterSettings, LogFilters,
+    TargetFilterChange, TargetFilterSettings,
+};
@@ -1,12 +1,21 @@
 use crate::config::*;
+use crate::reload::ConfigReloadResult;
+use crate::tracing_config::{log_filters, LogFilterChange, LogFilterError, LogFilterSettings};
 use sable_network::{
+    audit::AuditLogFilter,
     config::TlsData,
-    rpc::{ServerManagementCommand, ServerManagementCommandType, ShutdownAction},
+    id::{NetworkBanId, Snowflake},
+    network::state::AuditLogEntry,
+    rpc::{InspectTarget, ServerManagementCommand, ServerManagementCommandType, ShutdownAction},
+    sync::PeerConfig,
+    validated::{ServerName, Validated},
 };
 
-use hyper::{Body, Method, Request, Response, StatusCode};
+use hyper::{body::Bytes, header::CONTENT_TYPE, Body, Method, Request, Response, StatusCode};
+use serde::de::DeserializeOwned;
 use sha1::{Digest, Sha1};
 use std::{
+    collections::HashSet,
     future::Future,
     pin::Pin,
     sync::Arc,
@@ -17,6 +26,7 @@ use tokio::{
     net::{TcpListener, TcpStream},
     select,
     sync::{
+        broadcast,
         mpsc::{channel, Receiver, Sender},
         oneshot,
     },
@@ -27,6 +37,23 @@ use tracing::Instrument;
 
 pub enum ManagementCommand {
     ServerCommand(ServerManagementCommand),
+    /// Collect metrics in the Prometheus text format
+    Metrics(oneshot::Sender<String>),
+    /// Subscribe to new audit log entries, also returning the existing entries
+    /// that match the filter
+    StreamAuditLog(
+        AuditLogFilter,
+        oneshot::Sender<(Vec<AuditLogEntry>, broadcast::Receiver<AuditLogEntry>)>,
+    ),
+    /// Reload the server and network configuration files, on behalf of the
+    /// named management user
+    ReloadConfig(String, oneshot::Sender<ConfigReloadResult>),
+    /// Record a request made by the named management user in the audit log
+    AuditRequest {
+        user: String,
+        request: String,
+        denied: bool,
+    },
     Shutdown(ShutdownAction),
 }
 
@@ -60,14 +87,110 @@ fn internal_error() -> hyper::Result<Response<Body>> {
     Ok(response)
 }
 
+fn bad_request() -> hyper::Result<Response<Body>> {
+    let mut response = Response::default();
+    *response.status_mut() = StatusCode::BAD_REQUEST;
+    Ok(response)
+}
+
+fn not_found() -> hyper::Result<Response<Body>> {
+    let mut response = Response::default();
+    *response.status_mut() = StatusCode::NOT_FOUND;
+    Ok(response)
+}
+
+fn forbidden() -> hyper::Result<Response<Body>> {
+    let body = serde_json::json!({ "error": "Permission denied" }).to_string();
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = StatusCode::FORBIDDEN;
+    Ok(response)
+}
+
+/// Paths under which single objects are inspected
+const INSPECT_PREFIXES: [&str; 5] = [
+    "/users/",
+    "/channels/",
+    "/accounts/",
+    "/servers/",
+    "/events/",
+];
+
+/// The permission needed for a request, or `None` if there is no such endpoint.
+/// Every endpoint handled by [`ManagementService::call`] must be listed here.
+fn required_permission(method: &Method, path: &str) -> Option<ManagementPermission> {
+    use ManagementPermission::*;
+
+    match (method, path) {
+        (&Method::GET, "/statistics" | "/metrics") => Some(Stats),
+        (&Method::GET, "/dump-network" | "/dump-events") => Some(Inspect),
+        (&Method::GET, _) if path.starts_with("/state-diff/") => Some(Inspect),
+        (&Method::GET, _) if INSPECT_PREFIXES.iter().any(|p| path.starts_with(p)) => Some(Inspect),
+        (&Method::POST, "/users/search" | "/audit-log/search" | "/audit-log/stream") => {
+            Some(Inspect)
+        }
+        (&Method::GET | &Method::POST, "/bans") | (&Method::POST, "/kill") => Some(Bans),
+        (&Method::DELETE, _) if path.starts_with("/bans/") => Some(Bans),
+        (&Method::POST, "/peers") => Some(Configure),
+        (&Method::DELETE, _) if path.starts_with("/peers/") => Some(Configure),
+        (&Method::GET | &Method::POST, "/log-filters") => Some(Configure),
+        (&Method::POST, "/log-filters/reset" | "/reload") => Some(Configure),
+        (&Method::POST, "/shutdown" | "/restart" | "/upgrade") => Some(Shutdown),
+        _ => None,
+    }
+}
+
+fn json_line(entry: &AuditLogEntry) -> Bytes {
+    let mut line = serde_json::to_vec(entry).expect("Failed to serialise audit log entry");
+    line.push(b'\n');
+    Bytes::from(line)
+}
+
+/// The line sent in place of entries that a stream fell too far behind to send
+fn missed_line(missed: u64) -> Bytes {
+    let mut line = serde_json::json!({ "missed": missed }).to_string();
+    line.push('\n');
+    Bytes::from(line)
+}
+
+fn log_filters_json(result: Result<LogFilterSettings, LogFilterError>) -> String {
+    match result {
+        Ok(settings) => serde_json::to_string(&settings).expect("Failed to serialise log filters"),
+        Err(error) => serde_json::json!({ "error": error.to_string() }).to_string(),
+    }
+}
+
+/// Decode a percent-encoded path segment, returning `None` if it isn't valid
+fn percent_decode(segment: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(segment.len());
+    let mut rest = segment.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            decoded.extend(hex::decode(tail.get(..2)?).ok()?);
+            rest = &tail[2..];
+        } else {
+            decoded.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Read a JSON request body, returning `None` if it isn't valid
+async fn parse_body<T: DeserializeOwned>(body: Body) -> Result<Option<T>, hyper::Error> {
+    let body = hyper::body::to_bytes(body).await?;
+    Ok(serde_json::from_slice(&body).ok())
+}
+
 impl ManagementService {
     async fn server_management_command(
         command_sender: Sender<ManagementCommand>,
+        user: String,
         cmd: ServerManagementCommandType,
     ) -> Result<Response<Body>, hyper::Error> {
         let (send, recv) = oneshot::channel();
         let cmd = ServerManagementCommand {
             cmd,
+            user,
             response: send,
         };
         if command_sender
@@ -83,6 +206,300 @@ impl ManagementService {
         }
     }
 
+    async fn add_peer_command(
+        command_sender: Sender<ManagementCommand>,
+        user: String,
+        body: Body,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let Some(peer) = parse_body::<PeerConfig>(body).await? else {
+            return bad_request();
+        };
+
+        Self::server_management_command(
+            command_sender,
+            user,
+            ServerManagementCommandType::AddPeer(peer),
+        )
+        .await
+    }
+
+    async fn remove_peer_command(
+        command_sender: Sender<ManagementCommand>,
+        user: String,
+        name: &str,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let Ok(name) = ServerName::convert(name) else {
+            return bad_request();
+        };
+
+        Self::server_management_command(
+            command_sender,
+            user,
+            ServerManagementCommandType::RemovePeer(name),
+        )
+        .await
+    }
+
+    async fn state_diff_command(
+        command_sender: Sender<ManagementCommand>,
+        user: String,
+        name: &str,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let Ok(name) = ServerName::convert(name) else {
+            return bad_request();
+        };
+
+        Self::server_management_command(
+            command_sender,
+            user,
+            ServerManagementCommandType::StateDiff(name),
+        )
+        .await
+    }
+
+    async fn add_ban_command(
+        command_sender: Sender<ManagementCommand>,
+        user: String,
+        body: Body,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let Some(request) = parse_body(body).await? else {
+            return bad_request();
+        };
+
+        Self::server_management_command(
+            command_sender,
+            user,
+            ServerManagementCommandType::AddBan(request),
+        )
+        .await
+    }
+
+    async fn remove_ban_command(
+        command_sender: Sender<ManagementCommand>,
+        user: String,
+        id: &str,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let Ok(id) = id.parse::<u64>() else {
+            return bad_request();
+        };
+
+        Self::server_management_command(
+            command_sender,
+            user,
+            ServerManagementCommandType::RemoveBan(NetworkBanId::from(Snowflake::from(id))),
+        )
+        .await
+    }
+
+    async fn kill_command(
+        command_sender: Sender<ManagementCommand>,
+        user: String,
+        body: Body,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let Some(request) = parse_body(body).await? else {
+            return bad_request();
+        };
+
+        Self::server_management_command(
+            command_sender,
+            user,
+            ServerManagementCommandType::KillUser(request),
+        )
+        .await
+    }
+
+    async fn find_users_command(
+        command_sender: Sender<ManagementCommand>,
+        user: String,
+        body: Body,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let Some(request) = parse_body(body).await? else {
+            return bad_request();
+        };
+
+        Self::server_management_command(
+            command_sender,
+            user,
+            ServerManagementCommandType::FindUsers(request),
+        )
+        .await
+    }
+
+    async fn inspect_command(
+        command_sender: Sender<ManagementCommand>,
+        user: String,
+        target: fn(String) -> InspectTarget,
+        name: &str,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let Some(name) = percent_decode(name) else {
+            return bad_request();
+        };
+
+        Self::server_management_command(
+            command_sender,
+            user,
+            ServerManagementCommandType::Inspect(target(name)),
+        )
+        .await
+    }
+
+    async fn metrics_command(
+        command_sender: Sender<ManagementCommand>,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let (send, recv) = oneshot::channel();
+        if command_sender
+            .send(ManagementCommand::Metrics(send))
+            .await
+            .is_err()
+        {
+            return internal_error();
+        }
+
+        match recv.await {
+            Ok(metrics) => Ok(Response::builder()
+                .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+                .body(Body::from(metrics))
+                .expect("Failed to build metrics response")),
+            Err(_) => internal_error(),
+        }
+    }
+
+    async fn query_audit_log_command(
+        command_sender: Sender<ManagementCommand>,
+        user: String,
+        body: Body,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let Some(query) = parse_body(body).await? else {
+            return bad_request();
+        };
+
+        Self::server_management_command(
+            command_sender,
+            user,
+            ServerManagementCommandType::QueryAuditLog(query),
+        )
+        .await
+    }
+
+    /// Stream audit log entries matching the filter in the request body as JSON
+    /// lines, for as long as the client stays connected. If the filter has a
+    /// `since` time, existing entries from then on are sent first.
+    async fn stream_audit_log_command(
+        command_sender: Sender<ManagementCommand>,
+        body: Body,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let Some(filter) = parse_body::<AuditLogFilter>(body).await? else {
+            return bad_request();
+        };
+
+        let (send, recv) = oneshot::channel();
+        if command_sender
+            .send(ManagementCommand::StreamAuditLog(filter.clone(), send))
+            .await
+            .is_err()
+        {
+            return internal_error();
+        }
+        let Ok((existing, mut new_entries)) = recv.await else {
+            return internal_error();
+        };
+
+        let (mut sender, body) = Body::channel();
+
+        task::spawn(async move {
+            let mut sent = HashSet::new();
+
+            for entry in existing {
+                sent.insert(entry.id);
+                if sender.send_data(json_line(&entry)).await.is_err() {
+                    return;
+                }
+            }
+
+            loop {
+                let entry = match new_entries.recv().await {
+                    Ok(entry) => entry,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!(missed, "Audit log stream fell behind");
+                        if sender.send_data(missed_line(missed)).await.is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+
+                // Entries added while the existing ones were being read can
+                // arrive both ways
+                if sent.contains(&entry.id) || !filter.matches(&entry) {
+                    continue;
+                }
+                if sender.send_data(json_line(&entry)).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::builder()
+            .header(CONTENT_TYPE, "application/x-ndjson")
+            .body(body)
+            .expect("Failed to build audit log stream response"))
+    }
+
+    async fn log_filters_command(
+        user: String,
+        change: Option<Body>,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let Some(filters) = log_filters() else {
+            return internal_error();
+        };
+
+        let result = match change {
+            None => Ok(filters.current()),
+            Some(body) => {
+                let Some(change) = parse_body::<LogFilterChange>(body).await? else {
+                    return bad_request();
+                };
+                tracing::warn!(?change, %user, "Changing log filters");
+                filters.change(change)
+            }
+        };
+
+        Ok(Response::new(Body::from(log_filters_json(result))))
+    }
+
+    async fn reset_log_filters_command(user: String) -> Result<Response<Body>, hyper::Error> {
+        let Some(filters) = log_filters() else {
+            return internal_error();
+        };
+
+        tracing::warn!(%user, "Resetting log filters");
+        Ok(Response::new(Body::from(log_filters_json(filters.reset()))))
+    }
+
+    async fn reload_command(
+        command_sender: Sender<ManagementCommand>,
+        user: String,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let (send, recv) = oneshot::channel();
+        if command_sender
+            .send(ManagementCommand::ReloadConfig(user, send))
+            .await
+            .is_err()
+        {
+            return internal_error();
+        }
+
+        let body = match recv.await {
+            Ok(Ok(report)) => {
+                serde_json::to_string(&report).expect("Failed to serialise reload report")
+            }
+            Ok(Err(error)) => serde_json::json!({ "error": error }).to_string(),
+            Err(_) => return internal_error(),
+        };
+        Ok(Response::new(Body::from(body)))
+    }
+
     async fn shutdown_command(
         command_sender: Sender<ManagementCommand>,
         cmd: ShutdownAction,
@@ -111,21 +528,48 @@ impl hyper::service::Service<Request<Body>> for ManagementService {
 
     fn call(&mut self, req: Request<Body>) -> Self::Future {
         let command_sender = self.data.command_sender.clone();
+        let user = self.authorised_fingerprint.name.clone();
 
         tracing::debug!(method=?req.method(), path=?req.uri().path(), user=?self.authorised_fingerprint.name, "Got management request");
 
+        let permissions = &self.authorised_fingerprint.permissions;
+        let permitted = required_permission(req.method(), req.uri().path())
+            .map(|permission| permissions.contains(&permission));
+
         Box::pin(async move {
-            match (req.method(), req.uri().path()) {
+            let (parts, body) = req.into_parts();
+
+            let audit = ManagementCommand::AuditRequest {
+                user: user.clone(),
+                request: format!("{} {}", parts.method, parts.uri.path()),
+                denied: permitted == Some(false),
+            };
+            if command_sender.send(audit).await.is_err() {
+                return internal_error();
+            }
+            match permitted {
+                Some(true) => (),
+                Some(false) => {
+                    tracing::warn!(method=?parts.method, path=?parts.uri.path(), %user, "Management request not permitted");
+                    return forbidden();
+                }
+                None => return not_found(),
+            }
+
+            match (&parts.method, parts.uri.path()) {
                 (&Method::GET, "/statistics") => {
                     Self::server_management_command(
                         command_sender,
+                        user,
                         ServerManagementCommandType::ServerStatistics,
                     )
                     .await
                 }
+                (&Method::GET, "/metrics") => Self::metrics_command(command_sender).await,
                 (&Method::GET, "/dump-network") => {
                     Self::server_management_command(
                         command_sender,
+                        user,
                         ServerManagementCommandType::DumpNetwork,
                     )
                     .await
@@ -133,10 +577,71 @@ impl hyper::service::Service<Request<Body>> for ManagementService {
                 (&Method::GET, "/dump-events") => {
                     Self::server_management_command(
                         command_sender,
+                        user,
                         ServerManagementCommandType::DumpEvents,
                     )
                     .await
                 }
+                (&Method::POST, "/peers") => {
+                    Self::add_peer_command(command_sender, user, body).await
+                }
+                (&Method::DELETE, path) if path.starts_with("/peers/") => {
+                    Self::remove_peer_command(command_sender, user, &path["/peers/".len()..]).await
+                }
+                (&Method::GET, path) if path.starts_with("/state-diff/") => {
+                    Self::state_diff_command(command_sender, user, &path["/state-diff/".len()..])
+                        .await
+                }
+                (&Method::GET, "/bans") => {
+                    Self::server_management_command(
+                        command_sender,
+                        user,
+                        ServerManagementCommandType::ListBans,
+                    )
+                    .await
+                }
+                (&Method::POST, "/bans") => Self::add_ban_command(command_sender, user, body).await,
+                (&Method::DELETE, path) if path.starts_with("/bans/") => {
+                    Self::remove_ban_command(command_sender, user, &path["/bans/".len()..]).await
+                }
+                (&Method::POST, "/kill") => Self::kill_command(command_sender, user, body).await,
+                (&Method::POST, "/users/search") => {
+                    Self::find_users_command(command_sender, user, body).await
+                }
+                (&Method::GET, path) if path.starts_with("/users/") => {
+                    let name = &path["/users/".len()..];
+                    Self::inspect_command(command_sender, user, InspectTarget::User, name).await
+                }
+                (&Method::GET, path) if path.starts_with("/channels/") => {
+                    let name = &path["/channels/".len()..];
+                    Self::inspect_command(command_sender, user, InspectTarget::Channel, name).await
+                }
+                (&Method::GET, path) if path.starts_with("/accounts/") => {
+                    let name = &path["/accounts/".len()..];
+                    Self::inspect_command(command_sender, user, InspectTarget::Account, name).await
+                }
+                (&Method::GET, path) if path.starts_with("/servers/") => {
+                    let name = &path["/servers/".len()..];
+                    Self::inspect_command(command_sender, user, InspectTarget::Server, name).await
+                }
+                (&Method::GET, path) if path.starts_with("/events/") => {
+                    let id = &path["/events/".len()..];
+                    Self::inspect_command(command_sender, user, InspectTarget::Event, id).await
+                }
+                (&Method::POST, "/audit-log/search") => {
+                    Self::query_audit_log_command(command_sender, user, body).await
+                }
+                (&Method::POST, "/audit-log/stream") => {
+                    Self::stream_audit_log_command(command_sender, body).await
+                }
+                (&Method::GET, "/log-filters") => Self::log_filters_command(user, None).await,
+                (&Method::POST, "/log-filters") => {
+                    Self::log_filters_command(user, Some(body)).await
+                }
+                (&Method::POST, "/log-filters/reset") => {
+                    Self::reset_log_filters_command(user).await
+                }
+                (&Method::POST, "/reload") => Self::reload_command(command_sender, user).await,
                 (&Method::POST, "/shutdown") => {
                     Self::shutdown_command(command_sender, ShutdownAction::Shutdown).await
                 }
@@ -146,11 +651,7 @@ impl hyper::service::Service<Request<Body>> for ManagementService {
                 (&Method::POST, "/upgrade") => {
                     Self::shutdown_command(command_sender, ShutdownAction::Upgrade).await
                 }
-                _ => {
-                    let mut response = Response::default();
-                    *response.status_mut() = StatusCode::NOT_FOUND;
-                    Ok(response)
-                }
+                _ => not_found(),
             }
         })
     }
@@ -243,10 +744,16 @@ impl ManagementServer {
                     {
                         if let Ok((conn, _)) = res
                         {
-                            if let Err(e) = Self::handle_connection(conn, Arc::clone(&acceptor), Arc::clone(&data)).await
-                            {
-                                tracing::warn!("Error handling management connection: {}", e);
-                            }
+                            // Handle each connection in its own task, so that a
+                            // long-lived request such as a stream doesn't block others
+                            let acceptor = Arc::clone(&acceptor);
+                            let data = Arc::clone(&data);
+                            task::spawn(async move {
+                                if let Err(e) = Self::handle_connection(conn, acceptor, data).await
+                                {
+                                    tracing::warn!("Error handling management connection: {}", e);
+                                }
+                            }.in_current_span());
                         }
                     }
                     _ = &mut shutdown =>
@@ -273,3 +780,70 @@ impl ManagementServer {
         Ok(self.server_task.await??)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permission_for_each_endpoint() {
+        use ManagementPermission::*;
+
+        let cases = [
+            (Method::GET, "/statistics", Some(Stats)),
+            (Method::GET, "/metrics", Some(Stats)),
+            (Method::GET, "/dump-network", Some(Inspect)),
+            (Method::GET, "/dump-events", Some(Inspect)),
+            (Method::GET, "/state-diff/server1.test", Some(Inspect)),
+            (Method::GET, "/users/user1", Some(Inspect)),
+            (Method::GET, "/channels/%23test", Some(Inspect)),
+            (Method::GET, "/accounts/account1", Some(Inspect)),
+            (Method::GET, "/servers/server1.test", Some(Inspect)),
+            (Method::GET, "/events/1", Some(Inspect)),
+            (Method::POST, "/users/search", Some(Inspect)),
+            (Method::POST, "/audit-log/search", Some(Inspect)),
+            (Method::POST, "/audit-log/stream", Some(Inspect)),
+            (Method::GET, "/bans", Some(Bans)),
+            (Method::POST, "/bans", Some(Bans)),
+            (Method::DELETE, "/bans/1", Some(Bans)),
+            (Method::POST, "/kill", Some(Bans)),
+            (Method::POST, "/peers", Some(Configure)),
+            (Method::DELETE, "/peers/server2.test", Some(Configure)),
+            (Method::GET, "/log-filters", Some(Configure)),
+            (Method::POST, "/log-filters", Some(Configure)),
+            (Method::POST, "/log-filters/reset", Some(Configure)),
+            (Method::POST, "/reload", Some(Configure)),
+            (Method::POST, "/shutdown", Some(Shutdown)),
+            (Method::POST, "/restart", Some(Shutdown)),
+            (Method::POST, "/upgrade", Some(Shutdown)),
+        ];
+
+        for (method, path, expected) in cases {
+            assert_eq!(
+                required_permission(&method, path),
+                expected,
+                "{method} {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_endpoints_need_no_permission() {
+        let cases = [
+            (Method::GET, "/"),
+            (Method::GET, "/unknown"),
+            (Method::POST, "/statistics"),
+            (Method::DELETE, "/users/user1"),
+            (Method::DELETE, "/bans"),
+            (Method::GET, "/kill"),
+            (Method::GET, "/peers"),
+            (Method::GET, "/reload"),
+            (Method::GET, "/shutdown"),
+            (Method::PUT, "/bans"),
+        ];
+
+        for (method, path) in cases {
+            assert_eq!(required_permission(&method, path), None, "{method} {path}");
+        }
+    }
+}
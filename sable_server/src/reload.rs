@@ -0,0 +1,44 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    oneshot,
+};
+
+/// What changed when a server's configuration was reloaded
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ConfigReloadReport {
+    /// Changes which have taken effect
+    pub applied: Vec<String>,
+    /// Changes which have been started, but not yet confirmed to have taken
+    /// effect. Any that fail are logged, and retried by the next reload.
+    pub pending: Vec<String>,
+    /// Changes which will only take effect once the server restarts
+    pub restart_required: Vec<String>,
+}
+
+/// The outcome of a configuration reload. If the new configuration couldn't be
+/// loaded, nothing is changed and the error describes why.
+pub type ConfigReloadResult = Result<ConfigReloadReport, String>;
+
+pub(crate) type ConfigReloadRequest = oneshot::Sender<ConfigReloadResult>;
+
+static RELOAD_REQUESTS: Mutex<Option<UnboundedSender<ConfigReloadRequest>>> = Mutex::new(None);
+
+/// Ask the server running in this process to reload its configuration files, as
+/// for the management interface's `/reload` endpoint.
+///
+/// Returns `None` if no server is running to handle the request.
+pub async fn reload_config() -> Option<ConfigReloadResult> {
+    let (send, recv) = oneshot::channel();
+    RELOAD_REQUESTS.lock().unwrap().as_ref()?.send(send).ok()?;
+    recv.await.ok()
+}
+
+/// Register the channel through which [`reload_config`] requests are received,
+/// replacing any previous one
+pub(crate) fn reload_requests() -> UnboundedReceiver<ConfigReloadRequest> {
+    let (send, recv) = unbounded_channel();
+    *RELOAD_REQUESTS.lock().unwrap() = Some(send);
+    recv
+}
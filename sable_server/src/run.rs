@@ -70,6 +70,25 @@ where
     );
 }
 
+/// Arguments with which to re-execute ourselves on restart.
+///
+/// A restarted server always syncs to the existing network with a new epoch, so any
+/// request to bootstrap a new network is dropped.
+fn restart_args() -> Vec<String> {
+    let mut args = Vec::new();
+    let mut iter = env::args().skip(1);
+
+    while let Some(arg) = iter.next() {
+        if arg == "--bootstrap-network" {
+            iter.next();
+        } else if !arg.starts_with("--bootstrap-network=") {
+            args.push(arg);
+        }
+    }
+
+    args
+}
+
 // The async entry point for the application. Because `run_server` can fork into the background
 // depending on options, it needs to initialise the tokio runtime after doing so
 #[allow(clippy::too_many_arguments)]
@@ -125,9 +144,7 @@ where
             server.shutdown().await;
             let current_exe = env::current_exe().context("Could not get current executable")?;
 
-            let err = Command::new(&current_exe)
-                .args(env::args().skip(1).collect::<Vec<_>>())
-                .exec();
+            let err = Command::new(&current_exe).args(restart_args()).exec();
 
             panic!("Couldn't re-execute {}: {}", current_exe.display(), err);
         }
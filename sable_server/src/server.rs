@@ -4,8 +4,9 @@ use anyhow::Context;
 use parking_lot::Mutex;
 use sable_network::{
     config::*,
+    metrics::MetricsWriter,
     network::config::NetworkConfig,
-    node::NetworkNodeState,
+    node::{DurableStorage, NetworkNodeState, StorageConfig},
     policy::StandardPolicyService,
     prelude::*,
     rpc::{RemoteServerRequest, ShutdownAction},
@@ -17,7 +18,12 @@ use tokio::sync::{
     oneshot,
 };
 
-use std::{fs::File, io::Read, path::Path, sync::Arc};
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 /// Configuration for a network server
 #[derive(Debug, Deserialize)]
@@ -35,10 +41,31 @@ where
     pub tls_config: TlsConfig,
     pub node_config: NodeConfig,
     pub event_log: EventLogConfig,
+    #[serde(default)]
+    pub storage: Option<StorageConfig>,
 
     pub log: LoggingConfig,
+
+    /// Network-wide configuration file to apply when the configuration is reloaded
+    #[serde(default)]
+    pub network_config: Option<PathBuf>,
+
+    #[serde(skip)]
+    source: Option<ConfigSource>,
 }
 
+/// The file a [`ServerConfig`] was loaded from, and its raw contents, to compare
+/// against when reloading
+#[derive(Debug)]
+struct ConfigSource {
+    path: PathBuf,
+    raw: serde_json::Value,
+    tls_data: Option<TlsData>,
+}
+
+/// Top-level config sections which can be changed without a restart
+const RELOADABLE_SECTIONS: &[&str] = &["server", "tls_config", "network_config"];
+
 impl<ST> ServerConfig<ST>
 where
     ST: ServerType,
@@ -46,10 +73,17 @@ where
 {
     /// Load configuration from a file
     pub fn load_file<P: AsRef<Path>>(filename: P) -> Result<Self, anyhow::Error> {
-        let mut file = File::open(filename)?;
+        let mut file = File::open(&filename)?;
         let mut config = String::new();
         file.read_to_string(&mut config)?;
-        Ok(json5::from_str(&config)?)
+        let raw: serde_json::Value = json5::from_str(&config)?;
+        let mut ret = Self::deserialize(&raw)?;
+        ret.source = Some(ConfigSource {
+            path: filename.as_ref().to_owned(),
+            raw,
+            tls_data: None,
+        });
+        Ok(ret)
     }
 }
 
@@ -65,6 +99,7 @@ pub struct Server<ST> {
     management_config: ManagementConfig,
     tls_data: TlsData,
     remote_command_recv: Mutex<Option<UnboundedReceiver<RemoteServerRequest>>>,
+    config_source: Mutex<Option<ConfigSource>>,
 }
 
 /// Saved state of a network server
@@ -84,8 +119,9 @@ where
 {
     /// Construct a server.
     ///
-    /// If `bootstrap_config` is `None`, then this function will call out to one of the defined
-    /// network peers to retrieve the current network state.
+    /// If `bootstrap_config` is `None`, then this function will restore the network state from
+    /// durable storage if it is configured and recent enough, or otherwise call out to one of
+    /// the defined network peers to retrieve the current network state.
     pub async fn new(
         conf: ServerConfig<ST>,
         server_conf: ST::ProcessedConfig,
@@ -110,9 +146,24 @@ where
             conf.event_log,
         ));
 
+        let storage = conf
+            .storage
+            .map(DurableStorage::open)
+            .transpose()
+            .context("Could not open durable storage")?;
+
         let network = match bootstrap_config {
             Some(conf) => Network::new(conf),
-            None => *log.sync_to_network().await,
+            None => {
+                let recovered = match &storage {
+                    Some(storage) => NetworkNode::recover_network(storage, &log).await,
+                    None => None,
+                };
+                match recovered {
+                    Some(network) => network,
+                    None => *log.sync_to_network().await,
+                }
+            }
         };
 
         let node = Arc::new(NetworkNode::new(
@@ -125,6 +176,7 @@ where
             history_send,
             Some(remote_send),
             policy,
+            storage,
         ));
 
         let server = Arc::new(
@@ -133,6 +185,11 @@ where
                 .context("Could not initialize server")?,
         );
 
+        let config_source = conf.source.map(|source| ConfigSource {
+            tls_data: Some(tls_data),
+            ..source
+        });
+
         Ok(Self {
             node,
             log,
@@ -143,6 +200,7 @@ where
                 .load_from_disk()
                 .expect("Couldn't load TLS files"),
             remote_command_recv: Mutex::new(Some(remote_recv)),
+            config_source: Mutex::new(config_source),
         })
     }
 
@@ -260,22 +318,75 @@ where
             server_shutdown_recv,
         );
 
+        let mut quorum = self.node.subscribe_quorum();
+        let mut reload_requests = reload::reload_requests();
+
         let shutdown_action = loop {
-            if let Some(cmd) = server.recv().await {
-                tracing::debug!("Received from management server");
-                match cmd {
-                    management::ManagementCommand::ServerCommand(scmd) => {
-                        let command = &scmd.cmd;
-                        tracing::debug!(?command, "Management server command");
-                        self.node.handle_management_command(scmd).await;
+            tokio::select! {
+                cmd = server.recv() => {
+                    if let Some(cmd) = cmd {
+                        tracing::debug!("Received from management server");
+                        match cmd {
+                            management::ManagementCommand::ServerCommand(scmd) => {
+                                let command = &scmd.cmd;
+                                tracing::debug!(?command, "Management server command");
+                                self.node.handle_management_command(scmd).await;
+                            }
+                            management::ManagementCommand::Metrics(response) => {
+                                let mut metrics = MetricsWriter::new();
+                                self.node.export_metrics(&mut metrics);
+                                self.server.export_metrics(&mut metrics);
+                                let _ = response.send(metrics.finish());
+                            }
+                            management::ManagementCommand::StreamAuditLog(filter, response) => {
+                                let _ = response.send(self.node.stream_audit_log(&filter));
+                            }
+                            management::ManagementCommand::ReloadConfig(user, response) => {
+                                let result = self.reload_config();
+                                if result.is_ok() {
+                                    AuditLoggerEntry::management(
+                                        &self.node,
+                                        state::AuditLogCategory::General,
+                                        &user,
+                                        "REHASH",
+                                    )
+                                    .log();
+                                }
+                                let _ = response.send(result);
+                            }
+                            management::ManagementCommand::AuditRequest {
+                                user,
+                                request,
+                                denied,
+                            } => {
+                                let action = if denied { "DENIED" } else { "REQUEST" };
+                                AuditLoggerEntry::management(
+                                    &self.node,
+                                    state::AuditLogCategory::Management,
+                                    &user,
+                                    action,
+                                )
+                                .target_str(request)
+                                .log();
+                            }
+                            management::ManagementCommand::Shutdown(action) => {
+                                break action;
+                            }
+                        }
+                    } else {
+                        tracing::error!("Lost management server, shutting down");
+                        break ShutdownAction::Shutdown;
                     }
-                    management::ManagementCommand::Shutdown(action) => {
-                        break action;
+                }
+                Some(response) = reload_requests.recv() => {
+                    let _ = response.send(self.reload_config());
+                }
+                Ok(()) = quorum.changed() => {
+                    if quorum.borrow_and_update().restart_requested {
+                        tracing::warn!("Restarting to rejoin the network after a netsplit");
+                        break ShutdownAction::Restart;
                     }
                 }
-            } else {
-                tracing::error!("Lost management server, shutting down");
-                break ShutdownAction::Shutdown;
             }
         };
 
@@ -287,6 +398,126 @@ where
         shutdown_action
     }
 
+    /// Reload the server's config file, and the network config file it names, applying
+    /// whatever can be changed while running.
+    fn reload_config(&self) -> ConfigReloadResult {
+        match self.try_reload_config() {
+            Ok(report) => {
+                tracing::info!(?report, "Reloaded configuration");
+                Ok(report)
+            }
+            Err(e) => {
+                tracing::error!("Couldn't reload configuration: {:#}", e);
+                Err(format!("{e:#}"))
+            }
+        }
+    }
+
+    fn try_reload_config(&self) -> anyhow::Result<ConfigReloadReport> {
+        let mut source = self.config_source.lock();
+        let Some(source) = source.as_mut() else {
+            anyhow::bail!("Server configuration wasn't loaded from a file");
+        };
+
+        // Load and validate everything before changing anything
+        let config = ServerConfig::<ST>::load_file(&source.path).with_context(|| {
+            format!(
+                "Failed to read server config from {}",
+                source.path.display()
+            )
+        })?;
+        let processed_config = ST::validate_config(&config.server)
+            .context("Failed to validate server configuration")?;
+        let tls_data = config
+            .tls_config
+            .load_from_disk()
+            .context("Failed to read TLS config")?;
+        let network_config = config
+            .network_config
+            .as_ref()
+            .map(load_network_config)
+            .transpose()
+            .context("Failed to read network config")?;
+        let new_raw = config.source.map(|s| s.raw).unwrap_or_default();
+
+        let mut report = ConfigReloadReport::default();
+
+        let sections = |raw: &serde_json::Value| {
+            raw.as_object()
+                .map(|o| o.keys().cloned().collect::<Vec<_>>())
+                .unwrap_or_default()
+        };
+        let mut changed_sections: Vec<_> = sections(&source.raw)
+            .into_iter()
+            .chain(sections(&new_raw))
+            .filter(|section| source.raw.get(section) != new_raw.get(section))
+            .collect();
+        changed_sections.sort();
+        changed_sections.dedup();
+
+        for section in changed_sections {
+            if !RELOADABLE_SECTIONS.contains(&section.as_str()) {
+                report.restart_required.push(section);
+            }
+        }
+
+        let tls_changed = source.tls_data.as_ref() != Some(&tls_data);
+        if tls_changed {
+            report
+                .restart_required
+                .push("tls_config (management interface)".to_owned());
+        }
+
+        let network_config = match network_config {
+            Some(network_config) => {
+                let current = serde_json::to_value(self.node.network().config())?;
+                (serde_json::to_value(&network_config)? != current).then_some(network_config)
+            }
+            None => None,
+        };
+
+        // The server type checks its part of the configuration before applying any
+        // of it, and nothing after this can fail
+        self.server.reload_config(
+            processed_config,
+            source.raw.get("server") != new_raw.get("server"),
+            tls_changed.then_some(&tls_data),
+            &mut report,
+        )?;
+
+        if let Some(network_config) = network_config {
+            self.node.load_network_config(network_config);
+            report.applied.push("network_config".to_owned());
+        }
+
+        // Anything which needs a restart keeps its old value, so that it's still
+        // reported as changed until then. The server type decides this for its own
+        // section, and the management interface always needs a restart for new
+        // certificates.
+        let needs_restart = |section: &str| {
+            report
+                .restart_required
+                .iter()
+                .any(|r| r.split(' ').next() == Some(section))
+        };
+        if let Some(raw) = source.raw.as_object_mut() {
+            for section in RELOADABLE_SECTIONS {
+                if needs_restart(*section) {
+                    continue;
+                }
+                match new_raw.get(*section) {
+                    Some(value) => raw.insert(section.to_string(), value.clone()),
+                    None => raw.remove(*section),
+                };
+            }
+        }
+        if !needs_restart("tls_config") {
+            source.tls_data = Some(tls_data);
+        }
+
+        Ok(report)
+    }
+
     /// Save the state of the server, including all its component parts, for resumption after a code upgrade.
     pub async fn save(self) -> Result<ServerState<ST>, ServerSaveError> {
         // Order matters here.
@@ -327,6 +558,12 @@ where
         let processed_server_config = ST::validate_config(&server_config.server)
             .context("Could not load server configuration")?;
 
+        let storage = server_config
+            .storage
+            .map(DurableStorage::open)
+            .transpose()
+            .context("Could not open durable storage")?;
+
         let log = Arc::new(ReplicatedEventLog::restore(
             state.log_state,
             server_send,
@@ -340,6 +577,7 @@ where
             server_recv,
             history_send,
             Some(remote_send),
+            storage,
         )?);
 
         let server = Arc::new(ST::restore(
@@ -359,6 +597,9 @@ where
                 .load_from_disk()
                 .expect("Couldn't load TLS data files"),
             remote_command_recv: Mutex::new(Some(remote_recv)),
+            // The TLS certificates in use were loaded before the upgrade, so may not
+            // match those on disk
+            config_source: Mutex::new(server_config.source),
         })
     }
 }
@@ -1,4 +1,5 @@
-use sable_network::{config::TlsData, node::*, rpc::*};
+use crate::ConfigReloadReport;
+use sable_network::{config::TlsData, metrics::MetricsWriter, node::*, rpc::*};
 
 use serde::{de::DeserializeOwned, Serialize};
 use std::{future::Future, sync::Arc};
@@ -68,4 +69,28 @@ pub trait ServerType: Send + Sync + Sized + 'static {
         &self,
         request: RemoteServerRequestType,
     ) -> impl Future<Output = RemoteServerResponse> + Send;
+
+    /// Write any metrics specific to this server type, to be exported alongside
+    /// those of the network node
+    fn export_metrics(&self, _out: &mut MetricsWriter) {}
+
+    /// Apply a reloaded configuration to the running server, recording in `report` which
+    /// changes took effect and which need a restart. `changed` is true if the server
+    /// type's section of the config file differs from the one in effect, and `tls_data`
+    /// is only provided if the TLS certificates have changed.
+    ///
+    /// If this returns an error, no changes should have been made. The default
+    /// implementation applies nothing, and reports any change as needing a restart.
+    fn reload_config(
+        &self,
+        _config: Self::ProcessedConfig,
+        changed: bool,
+        _tls_data: Option<&TlsData>,
+        report: &mut ConfigReloadReport,
+    ) -> anyhow::Result<()> {
+        if changed {
+            report.restart_required.push("server".to_owned());
+        }
+        Ok(())
+    }
 }
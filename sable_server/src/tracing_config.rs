@@ -1,14 +1,235 @@
 use crate::config::*;
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashMap;
 use std::convert::Into;
-use tracing::Subscriber;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{Metadata, Subscriber};
 use tracing_core::LevelFilter;
-use tracing_subscriber::{filter::filter_fn, prelude::*, registry::LookupSpan, Layer};
+use tracing_subscriber::{
+    filter::Targets,
+    layer::{Context, Filter},
+    prelude::*,
+    registry::LookupSpan,
+    reload, Layer, Registry,
+};
 
 use std::{io::Error as IoError, path::Path};
 
+/// The filters in effect for one log target
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct TargetFilterSettings {
+    pub level: LogLevel,
+    pub modules: Vec<String>,
+    pub category: Option<String>,
+}
+
+impl TargetFilterSettings {
+    fn from_config(conf: &LogEntry) -> Self {
+        Self {
+            level: conf.level.unwrap_or(LogLevel::Trace),
+            modules: conf.modules.clone(),
+            category: conf.category.clone(),
+        }
+    }
+
+    fn matches(&self, metadata: &Metadata<'_>) -> bool {
+        let level: LevelFilter = self.level.into();
+
+        metadata.level() <= &level
+            && (self.modules.is_empty()
+                || if let Some(module) = metadata.module_path() {
+                    self.modules.iter().any(|m| module.starts_with(m))
+                } else {
+                    true
+                })
+            && (if let Some(category) = &self.category {
+                category == metadata.target()
+            } else {
+                true
+            })
+    }
+}
+
+/// The complete set of log filters in effect
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct LogFilterSettings {
+    /// Maximum level logged from modules not listed in `module_levels`
+    pub default_level: LogLevel,
+    /// Per-module maximum levels
+    pub module_levels: HashMap<String, LogLevel>,
+    /// Filters for each log target, in the order they're configured
+    pub targets: Vec<TargetFilterSettings>,
+}
+
+impl LogFilterSettings {
+    fn from_config(conf: &LoggingConfig) -> Self {
+        Self {
+            default_level: conf.default_level.unwrap_or(LogLevel::Trace),
+            module_levels: conf.module_levels.clone(),
+            targets: conf
+                .targets
+                .iter()
+                .map(TargetFilterSettings::from_config)
+                .collect(),
+        }
+    }
+
+    // The global filter is for excluding overly verbose messages from external modules - its default
+    // needs to be permissive so that individual log targets can filter as they need to
+    fn global_filter(&self) -> Targets {
+        Targets::new()
+            .with_default(self.default_level)
+            .with_targets(self.module_levels.clone())
+    }
+}
+
+/// A change to the log filters in effect
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct LogFilterChange {
+    /// New maximum level for modules not listed in `module_levels`
+    pub default_level: Option<LogLevel>,
+    /// Per-module maximum levels to add or replace
+    pub module_levels: HashMap<String, LogLevel>,
+    /// Changes to the filters of individual log targets
+    pub targets: Vec<TargetFilterChange>,
+    /// If set, return to the configured filters after this many seconds
+    pub revert_after: Option<u64>,
+}
+
+/// A change to the filters of one log target. Fields which are not set are left
+/// unchanged.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct TargetFilterChange {
+    /// The index of the target in the configured list of targets
+    pub target: usize,
+    pub level: Option<LogLevel>,
+    pub modules: Option<Vec<String>>,
+    pub category: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum LogFilterError {
+    #[error("No log target with index {0}")]
+    NoSuchTarget(usize),
+    #[error("Couldn't update global log filter: {0}")]
+    Reload(#[from] reload::Error),
+}
+
+/// Per-target filter which reads its settings from the shared [`LogFilters`]
+/// state, so that changes take effect immediately
+struct TargetFilter {
+    settings: Arc<RwLock<LogFilterSettings>>,
+    index: usize,
+}
+
+impl<S> Filter<S> for TargetFilter {
+    fn enabled(&self, metadata: &Metadata<'_>, _cx: &Context<'_, S>) -> bool {
+        match self.settings.read().targets.get(self.index) {
+            Some(target) => target.matches(metadata),
+            None => true,
+        }
+    }
+}
+
+/// Handle to change the log filters of the running process
+pub struct LogFilters {
+    configured: LogFilterSettings,
+    current: Arc<RwLock<LogFilterSettings>>,
+    global: reload::Handle<Targets, Registry>,
+    revert_task: Mutex<Option<tokio::task::JoinHandle<()>>>,
+}
+
+static LOG_FILTERS: OnceLock<LogFilters> = OnceLock::new();
+
+/// The log filters of this process, if a subscriber has been built with
+/// [`build_subscriber`]
+pub fn log_filters() -> Option<&'static LogFilters> {
+    LOG_FILTERS.get()
+}
+
+impl LogFilters {
+    /// The filters currently in effect
+    pub fn current(&self) -> LogFilterSettings {
+        self.current.read().clone()
+    }
+
+    /// Apply a change to the filters, returning the new filters in effect.
+    ///
+    /// If the change has a `revert_after` time, the configured filters are
+    /// restored once it expires, replacing any revert scheduled by an earlier
+    /// change. Otherwise any earlier scheduled revert still happens.
+    pub fn change(
+        &'static self,
+        change: LogFilterChange,
+    ) -> Result<LogFilterSettings, LogFilterError> {
+        let mut settings = self.current();
+
+        if let Some(level) = change.default_level {
+            settings.default_level = level;
+        }
+        settings.module_levels.extend(change.module_levels);
+
+        for target_change in change.targets {
+            let target = settings
+                .targets
+                .get_mut(target_change.target)
+                .ok_or(LogFilterError::NoSuchTarget(target_change.target))?;
+
+            if let Some(level) = target_change.level {
+                target.level = level;
+            }
+            if let Some(modules) = target_change.modules {
+                target.modules = modules;
+            }
+            if let Some(category) = target_change.category {
+                target.category = Some(category);
+            }
+        }
+
+        self.apply(settings.clone())?;
+
+        if let Some(seconds) = change.revert_after {
+            let task = tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(seconds)).await;
+                tracing::info!("Reverting log filters to configured settings");
+                if let Err(e) = self.apply(self.configured.clone()) {
+                    tracing::error!("Couldn't revert log filters: {}", e);
+                }
+            });
+            if let Some(previous) = self.revert_task.lock().replace(task) {
+                previous.abort();
+            }
+        }
+
+        Ok(settings)
+    }
+
+    /// Restore the configured filters, cancelling any scheduled revert
+    pub fn reset(&self) -> Result<LogFilterSettings, LogFilterError> {
+        if let Some(task) = self.revert_task.lock().take() {
+            task.abort();
+        }
+        self.apply(self.configured.clone())?;
+        Ok(self.configured.clone())
+    }
+
+    fn apply(&self, settings: LogFilterSettings) -> Result<(), LogFilterError> {
+        let global = settings.global_filter();
+        *self.current.write() = settings;
+        // Reloading also rebuilds the cached interest of every callsite, which
+        // takes the new per-target settings into account
+        self.global.reload(global)?;
+        Ok(())
+    }
+}
+
 fn build_target<S>(
-    conf: LogEntry,
+    conf: &LogEntry,
     dir: impl AsRef<Path>,
+    filter: TargetFilter,
 ) -> Result<Box<dyn Layer<S> + Send + Sync + 'static>, IoError>
 where
     S: Subscriber + Send + Sync,
@@ -27,42 +248,27 @@ where
             .boxed(),
     };
 
-    let filter = filter_fn(move |metadata| {
-        let level: tracing_core::LevelFilter = if let Some(level) = conf.level {
-            level.into()
-        } else {
-            LevelFilter::TRACE
-        };
-        metadata.level() <= &level
-            && (conf.modules.is_empty()
-                || if let Some(module) = metadata.module_path() {
-                    conf.modules.iter().any(|m| module.starts_with(m))
-                } else {
-                    true
-                })
-            && (if let Some(category) = &conf.category {
-                category == metadata.target()
-            } else {
-                true
-            })
-    });
-
     Ok(layer.with_filter(filter).boxed())
 }
 
-/// Build a [`Subscriber`] from a logging configuration
+/// Build a [`Subscriber`] from a logging configuration.
+///
+/// The subscriber's filters can be changed later through [`log_filters`].
 pub fn build_subscriber(conf: LoggingConfig) -> Result<impl Subscriber, IoError> {
+    let settings = LogFilterSettings::from_config(&conf);
+    let current = Arc::new(RwLock::new(settings.clone()));
+
     let mut layers = Vec::new();
 
-    for target in conf.targets {
-        layers.push(build_target(target, &conf.dir)?);
+    for (index, target) in conf.targets.iter().enumerate() {
+        let filter = TargetFilter {
+            settings: Arc::clone(&current),
+            index,
+        };
+        layers.push(build_target(target, &conf.dir, filter)?);
     }
 
-    // The global filter is for excluding overly verbose messages from external modules - its default
-    // needs to be permissive so that individual log targets can filter as they need to
-    let filter = tracing_subscriber::filter::Targets::new()
-        .with_default(conf.default_level.unwrap_or(LogLevel::Trace))
-        .with_targets(conf.module_levels);
+    let (filter, global) = reload::Layer::new(settings.global_filter());
 
     let console = conf.console_address.map(|addr| {
         console_subscriber::ConsoleLayer::builder()
@@ -70,8 +276,17 @@ pub fn build_subscriber(conf: LoggingConfig) -> Result<impl Subscriber, IoError>
             .spawn()
     });
 
+    // Only one subscriber is installed per process, so there's nothing to do if
+    // filters have already been registered
+    let _ = LOG_FILTERS.set(LogFilters {
+        configured: settings,
+        current,
+        global,
+        revert_task: Mutex::new(None),
+    });
+
     Ok(tracing_subscriber::registry()
-        .with(console)
         .with(filter)
+        .with(console)
         .with(layers))
 }
@@ -1,6 +1,26 @@
+use sable_services::database::{jsonfile::JsonDatabase, sqlite::SqliteDatabase};
+use sable_services::ServicesServer;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
+#[derive(Debug)]
+enum DatabaseType {
+    Json,
+    Sqlite,
+}
+
+impl std::str::FromStr for DatabaseType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "sqlite" => Ok(Self::Sqlite),
+            _ => Err(format!("Unknown database type {s}; expected json or sqlite")),
+        }
+    }
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(rename_all = "kebab")]
 struct Opts {
@@ -15,6 +35,11 @@ struct Opts {
     /// Run in foreground without daemonising
     #[structopt(short, long)]
     foreground: bool,
+
+    /// Database provider to use for the `database` location in the server config:
+    /// `json` or `sqlite`
+    #[structopt(long, default_value = "json")]
+    database_type: DatabaseType,
 }
 
 /// Main entry point.
@@ -25,13 +50,20 @@ struct Opts {
 pub fn main() -> Result<(), anyhow::Error> {
     let opts = Opts::from_args();
 
-    sable_server::run::run_server::<
-        sable_services::ServicesServer<sable_services::database::jsonfile::JsonDatabase>,
-    >(
-        opts.server_conf,
-        opts.network_conf,
-        opts.foreground,
-        None,
-        None::<PathBuf>,
-    )
+    match opts.database_type {
+        DatabaseType::Json => sable_server::run::run_server::<ServicesServer<JsonDatabase>>(
+            opts.server_conf,
+            opts.network_conf,
+            opts.foreground,
+            None,
+            None::<PathBuf>,
+        ),
+        DatabaseType::Sqlite => sable_server::run::run_server::<ServicesServer<SqliteDatabase>>(
+            opts.server_conf,
+            opts.network_conf,
+            opts.foreground,
+            None,
+            None::<PathBuf>,
+        ),
+    }
 }
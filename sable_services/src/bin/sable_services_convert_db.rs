@@ -0,0 +1,45 @@
+//! One-shot conversion of a JSON services database to SQLite
+
+use anyhow::Context;
+use sable_services::database::{
+    jsonfile::JsonDatabase, sqlite::SqliteDatabase, DatabaseConnection,
+};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab")]
+struct Opts {
+    /// Existing JSON database file
+    #[structopt(long)]
+    json: PathBuf,
+
+    /// SQLite database to create. If it already exists, it must not contain any of the
+    /// objects being imported.
+    #[structopt(long)]
+    sqlite: PathBuf,
+}
+
+pub fn main() -> Result<(), anyhow::Error> {
+    let opts = Opts::from_args();
+
+    if !opts.json.exists() {
+        anyhow::bail!("{} does not exist", opts.json.display());
+    }
+
+    let source = JsonDatabase::connect(opts.json.to_string_lossy())
+        .context("Could not read JSON database")?;
+    let dest = SqliteDatabase::connect(opts.sqlite.to_string_lossy())
+        .context("Could not open SQLite database")?;
+
+    dest.import_from(&source)
+        .context("Could not import database contents")?;
+
+    println!(
+        "Converted {} to {}",
+        opts.json.display(),
+        opts.sqlite.display()
+    );
+
+    Ok(())
+}
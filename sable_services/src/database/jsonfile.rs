@@ -133,6 +133,20 @@ impl DatabaseConnection for JsonDatabase {
             .cloned()
     }
 
+    fn account_with_fingerprint(&self, fingerprint: &str) -> Result<state::Account> {
+        self.state
+            .read()
+            .accounts
+            .values()
+            .find(|a| {
+                a.authorised_fingerprints
+                    .iter()
+                    .any(|f| f.eq_ignore_ascii_case(fingerprint))
+            })
+            .ok_or(DatabaseError::NoSuchId)
+            .cloned()
+    }
+
     fn update_account(&self, new_data: &state::Account) -> Result<()> {
         let ret = match self.state.write().accounts.entry(new_data.id) {
             Entry::Occupied(mut entry) => {
@@ -153,6 +167,15 @@ impl DatabaseConnection for JsonDatabase {
         ))
     }
 
+    fn remove_account(&self, id: AccountId) -> Result<()> {
+        let mut state = self.state.write();
+        state.accounts.remove(&id);
+        state.account_auth.remove(&id);
+        drop(state);
+
+        self.save()
+    }
+
     fn auth_for_account(&self, id: AccountId) -> Result<AccountAuth> {
         self.state
             .read()
@@ -204,6 +227,11 @@ impl DatabaseConnection for JsonDatabase {
         ))
     }
 
+    fn remove_nick_registration(&self, id: NickRegistrationId) -> Result<()> {
+        self.state.write().nick_registrations.remove(&id);
+        self.save()
+    }
+
     fn new_channel_registration(
         &self,
         data: state::ChannelRegistration,
@@ -257,6 +285,11 @@ impl DatabaseConnection for JsonDatabase {
         ))
     }
 
+    fn remove_channel_registration(&self, id: ChannelRegistrationId) -> Result<()> {
+        self.state.write().channel_registrations.remove(&id);
+        self.save()
+    }
+
     fn channel_access(&self, id: ChannelAccessId) -> Result<state::ChannelAccess> {
         self.state
             .read()
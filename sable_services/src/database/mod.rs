@@ -41,10 +41,14 @@ pub trait DatabaseConnection: Sized + Send + Sync + 'static {
     fn account(&self, id: AccountId) -> Result<state::Account>;
     /// Retrieve an account by name
     fn account_named(&self, name: &Nickname) -> Result<state::Account>;
+    /// Retrieve the account, if any, which has the given certificate fingerprint authorised
+    fn account_with_fingerprint(&self, fingerprint: &str) -> Result<state::Account>;
     /// Update an account's details
     fn update_account(&self, new_data: &state::Account) -> Result<()>;
     /// Retrieve all accounts in the database
     fn all_accounts(&self) -> Result<impl Iterator<Item = state::Account> + '_>;
+    /// Remove an account, along with its authentication data
+    fn remove_account(&self, id: AccountId) -> Result<()>;
 
     /// Retrieve the authentication data for a given account
     fn auth_for_account(&self, id: AccountId) -> Result<AccountAuth>;
@@ -60,6 +64,8 @@ pub trait DatabaseConnection: Sized + Send + Sync + 'static {
     fn update_nick_registration(&self, new_data: &state::NickRegistration) -> Result<()>;
     /// Retrieve all nick registrations in the database
     fn all_nick_registrations(&self) -> Result<impl Iterator<Item = state::NickRegistration> + '_>;
+    /// Remove a nick registration
+    fn remove_nick_registration(&self, id: NickRegistrationId) -> Result<()>;
 
     /// Create a new channel registration, store it in the database, and return it
     fn new_channel_registration(
@@ -75,6 +81,8 @@ pub trait DatabaseConnection: Sized + Send + Sync + 'static {
     fn all_channel_registrations(
         &self,
     ) -> Result<impl Iterator<Item = state::ChannelRegistration> + '_>;
+    /// Remove a channel registration
+    fn remove_channel_registration(&self, id: ChannelRegistrationId) -> Result<()>;
 
     /// Create a new channel role
     fn new_channel_role(&self, data: state::ChannelRole) -> Result<state::ChannelRole>;
@@ -100,3 +108,4 @@ pub trait DatabaseConnection: Sized + Send + Sync + 'static {
 }
 
 pub mod jsonfile;
+pub mod sqlite;
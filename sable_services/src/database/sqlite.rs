@@ -0,0 +1,799 @@
+use parking_lot::Mutex;
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::*;
+
+/// An SQLite-backed database
+///
+/// Each object is stored as a serialised JSON blob, alongside those of its fields which
+/// need to be indexed for lookups. This means that adding fields to the state types
+/// doesn't require a schema change, as long as they deserialise with a default.
+///
+/// Indexed names are stored casefolded, as the name types compare them, rather than
+/// relying on SQLite's collation, and fingerprints in lower case.
+pub struct SqliteDatabase {
+    conn: Mutex<Connection>,
+}
+
+/// Schema migrations, applied in order. The number of migrations applied to a given
+/// database is stored in its `user_version` pragma; new migrations must only ever be
+/// appended to this list.
+const MIGRATIONS: &[&str] = &[r#"
+    CREATE TABLE accounts (
+        id TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL UNIQUE,
+        data TEXT NOT NULL
+    );
+
+    CREATE TABLE account_fingerprints (
+        fingerprint TEXT PRIMARY KEY NOT NULL,
+        account TEXT NOT NULL
+    );
+    CREATE INDEX account_fingerprints_account ON account_fingerprints (account);
+
+    CREATE TABLE account_auth (
+        account TEXT PRIMARY KEY NOT NULL,
+        data TEXT NOT NULL
+    );
+
+    CREATE TABLE nick_registrations (
+        id TEXT PRIMARY KEY NOT NULL,
+        nick TEXT NOT NULL,
+        account TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX nick_registrations_nick ON nick_registrations (nick);
+    CREATE INDEX nick_registrations_account ON nick_registrations (account);
+
+    CREATE TABLE channel_registrations (
+        id TEXT PRIMARY KEY NOT NULL,
+        channelname TEXT NOT NULL UNIQUE,
+        data TEXT NOT NULL
+    );
+
+    CREATE TABLE channel_roles (
+        id TEXT PRIMARY KEY NOT NULL,
+        channel TEXT,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX channel_roles_channel ON channel_roles (channel);
+
+    CREATE TABLE channel_accesses (
+        id TEXT PRIMARY KEY NOT NULL,
+        account TEXT NOT NULL,
+        channel TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX channel_accesses_account ON channel_accesses (account);
+    CREATE INDEX channel_accesses_channel ON channel_accesses (channel);
+"#];
+
+impl From<rusqlite::Error> for DatabaseError {
+    fn from(error: rusqlite::Error) -> Self {
+        match &error {
+            rusqlite::Error::SqliteFailure(e, _) if e.code == ErrorCode::ConstraintViolation => {
+                match e.extended_code {
+                    rusqlite::ffi::SQLITE_CONSTRAINT_PRIMARYKEY => Self::DuplicateId,
+                    rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE => Self::DuplicateName,
+                    _ => Self::from_inner(error),
+                }
+            }
+            _ => Self::from_inner(error),
+        }
+    }
+}
+
+/// Serialise an ID or object for storage
+fn encode<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value).map_err(DatabaseError::from_inner)
+}
+
+/// Deserialise a stored object
+fn decode<T: DeserializeOwned>(data: &str) -> Result<T> {
+    serde_json::from_str(data).map_err(|_| DatabaseError::InvalidData)
+}
+
+fn insert_account(conn: &Connection, data: &state::Account, auth: &AccountAuth) -> Result<()> {
+    let id = encode(&data.id)?;
+
+    conn.prepare_cached("INSERT INTO accounts (id, name, data) VALUES (?1, ?2, ?3)")?
+        .execute(params![id, data.name.casefolded(), encode(data)?])?;
+    conn.prepare_cached("INSERT INTO account_auth (account, data) VALUES (?1, ?2)")?
+        .execute(params![id, encode(auth)?])?;
+
+    insert_fingerprints(conn, &id, &data.authorised_fingerprints)
+}
+
+/// Index an account's fingerprints. Each fingerprint can only belong to one account.
+fn insert_fingerprints(conn: &Connection, account: &str, fingerprints: &[String]) -> Result<()> {
+    let mut fingerprints: Vec<_> = fingerprints
+        .iter()
+        .map(|fp| fp.to_ascii_lowercase())
+        .collect();
+    fingerprints.sort();
+    fingerprints.dedup();
+
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO account_fingerprints (fingerprint, account) VALUES (?1, ?2)",
+    )?;
+    for fp in fingerprints {
+        stmt.execute(params![fp, account])?;
+    }
+    Ok(())
+}
+
+fn insert_nick_registration(conn: &Connection, data: &state::NickRegistration) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO nick_registrations (id, nick, account, data) VALUES (?1, ?2, ?3, ?4)",
+    )?
+    .execute(params![
+        encode(&data.id)?,
+        data.nick.casefolded(),
+        encode(&data.account)?,
+        encode(data)?
+    ])?;
+    Ok(())
+}
+
+fn insert_channel_registration(conn: &Connection, data: &state::ChannelRegistration) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO channel_registrations (id, channelname, data) VALUES (?1, ?2, ?3)",
+    )?
+    .execute(params![
+        encode(&data.id)?,
+        data.channelname.casefolded(),
+        encode(data)?
+    ])?;
+    Ok(())
+}
+
+fn insert_channel_role(conn: &Connection, data: &state::ChannelRole) -> Result<()> {
+    let channel = data.channel.as_ref().map(encode).transpose()?;
+
+    conn.prepare_cached("INSERT INTO channel_roles (id, channel, data) VALUES (?1, ?2, ?3)")?
+        .execute(params![encode(&data.id)?, channel, encode(data)?])?;
+    Ok(())
+}
+
+fn upsert_channel_access(conn: &Connection, data: &state::ChannelAccess) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO channel_accesses (id, account, channel, data) VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (id) DO UPDATE SET data = excluded.data",
+    )?
+    .execute(params![
+        encode(&data.id)?,
+        encode(&data.id.account())?,
+        encode(&data.id.channel())?,
+        encode(data)?
+    ])?;
+    Ok(())
+}
+
+impl SqliteDatabase {
+    /// Bring the schema up to date, applying each outstanding migration in its own
+    /// transaction
+    fn migrate(conn: &mut Connection) -> Result<()> {
+        let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        if version > MIGRATIONS.len() {
+            return Err(DatabaseError::from_inner(anyhow::anyhow!(
+                "Database schema version {} is newer than this server supports ({})",
+                version,
+                MIGRATIONS.len()
+            )));
+        }
+
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            tracing::info!("Applying database migration {}", i + 1);
+
+            let tx = conn.transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", i + 1)?;
+            tx.commit()?;
+        }
+
+        Ok(())
+    }
+
+    /// Copy the entire contents of another database into this one, in a single
+    /// transaction. Intended for one-shot conversion from another provider; fails
+    /// without making any changes if any of the objects already exist here.
+    pub fn import_from(&self, source: &impl DatabaseConnection) -> Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+
+        for account in source.all_accounts()? {
+            let auth = source.auth_for_account(account.id)?;
+            insert_account(&tx, &account, &auth)?;
+        }
+        for nick in source.all_nick_registrations()? {
+            insert_nick_registration(&tx, &nick)?;
+        }
+        for channel in source.all_channel_registrations()? {
+            insert_channel_registration(&tx, &channel)?;
+        }
+        for role in source.all_channel_roles()? {
+            insert_channel_role(&tx, &role)?;
+        }
+        for access in source.all_channel_accesses()? {
+            upsert_channel_access(&tx, &access)?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Fetch a single object given a query taking one key parameter and returning
+    /// the serialised data
+    fn fetch_one<T: DeserializeOwned>(&self, sql: &str, key: &str) -> Result<T> {
+        let conn = self.conn.lock();
+        let data: Option<String> = conn
+            .prepare_cached(sql)?
+            .query_row([key], |row| row.get(0))
+            .optional()?;
+
+        decode(&data.ok_or(DatabaseError::NoSuchId)?)
+    }
+
+    /// Fetch every object returned by a query with no parameters
+    fn fetch_all<T: DeserializeOwned>(&self, sql: &str) -> Result<std::vec::IntoIter<T>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare_cached(sql)?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+
+        let mut ret = Vec::new();
+        for row in rows {
+            ret.push(decode(&row?)?);
+        }
+        Ok(ret.into_iter())
+    }
+
+    /// Run a statement which is expected to modify exactly one existing row
+    fn update_one(&self, sql: &str, params: impl rusqlite::Params) -> Result<()> {
+        let conn = self.conn.lock();
+        match conn.prepare_cached(sql)?.execute(params)? {
+            0 => Err(DatabaseError::NoSuchId),
+            _ => Ok(()),
+        }
+    }
+
+    /// Run a deletion statement taking one key parameter
+    fn remove_one(&self, sql: &str, key: &str) -> Result<()> {
+        self.conn.lock().prepare_cached(sql)?.execute([key])?;
+        Ok(())
+    }
+}
+
+impl DatabaseConnection for SqliteDatabase {
+    fn connect(conn: impl AsRef<str>) -> Result<Self> {
+        let mut conn = Connection::open(conn.as_ref())?;
+
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+
+        Self::migrate(&mut conn)?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn new_account(&self, data: state::Account, mut auth: AccountAuth) -> Result<state::Account> {
+        // Just in case
+        auth.account = data.id;
+
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        insert_account(&tx, &data, &auth)?;
+        tx.commit()?;
+
+        Ok(data)
+    }
+
+    fn account(&self, id: AccountId) -> Result<state::Account> {
+        self.fetch_one("SELECT data FROM accounts WHERE id = ?1", &encode(&id)?)
+    }
+
+    fn account_named(&self, name: &Nickname) -> Result<state::Account> {
+        self.fetch_one(
+            "SELECT data FROM accounts WHERE name = ?1",
+            &name.casefolded(),
+        )
+    }
+
+    fn account_with_fingerprint(&self, fingerprint: &str) -> Result<state::Account> {
+        self.fetch_one(
+            "SELECT accounts.data FROM account_fingerprints
+                JOIN accounts ON accounts.id = account_fingerprints.account
+                WHERE account_fingerprints.fingerprint = ?1",
+            &fingerprint.to_ascii_lowercase(),
+        )
+    }
+
+    fn update_account(&self, new_data: &state::Account) -> Result<()> {
+        let id = encode(&new_data.id)?;
+
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+
+        let updated = tx
+            .prepare_cached("UPDATE accounts SET name = ?2, data = ?3 WHERE id = ?1")?
+            .execute(params![id, new_data.name.casefolded(), encode(new_data)?])?;
+        if updated == 0 {
+            return Err(DatabaseError::NoSuchId);
+        }
+
+        tx.prepare_cached("DELETE FROM account_fingerprints WHERE account = ?1")?
+            .execute([&id])?;
+        insert_fingerprints(&tx, &id, &new_data.authorised_fingerprints)?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn all_accounts(&self) -> Result<impl Iterator<Item = state::Account> + '_> {
+        self.fetch_all("SELECT data FROM accounts")
+    }
+
+    fn remove_account(&self, id: AccountId) -> Result<()> {
+        let id = encode(&id)?;
+
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM accounts WHERE id = ?1", [&id])?;
+        tx.execute("DELETE FROM account_auth WHERE account = ?1", [&id])?;
+        tx.execute("DELETE FROM account_fingerprints WHERE account = ?1", [&id])?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn auth_for_account(&self, id: AccountId) -> Result<AccountAuth> {
+        self.fetch_one(
+            "SELECT data FROM account_auth WHERE account = ?1",
+            &encode(&id)?,
+        )
+    }
+
+    fn new_nick_registration(
+        &self,
+        data: state::NickRegistration,
+    ) -> Result<state::NickRegistration> {
+        insert_nick_registration(&self.conn.lock(), &data)?;
+        Ok(data)
+    }
+
+    fn nick_registration(&self, id: NickRegistrationId) -> Result<state::NickRegistration> {
+        self.fetch_one(
+            "SELECT data FROM nick_registrations WHERE id = ?1",
+            &encode(&id)?,
+        )
+    }
+
+    fn update_nick_registration(&self, new_data: &state::NickRegistration) -> Result<()> {
+        self.update_one(
+            "UPDATE nick_registrations SET nick = ?2, account = ?3, data = ?4 WHERE id = ?1",
+            params![
+                encode(&new_data.id)?,
+                new_data.nick.casefolded(),
+                encode(&new_data.account)?,
+                encode(new_data)?
+            ],
+        )
+    }
+
+    fn all_nick_registrations(&self) -> Result<impl Iterator<Item = state::NickRegistration> + '_> {
+        self.fetch_all("SELECT data FROM nick_registrations")
+    }
+
+    fn remove_nick_registration(&self, id: NickRegistrationId) -> Result<()> {
+        self.remove_one(
+            "DELETE FROM nick_registrations WHERE id = ?1",
+            &encode(&id)?,
+        )
+    }
+
+    fn new_channel_registration(
+        &self,
+        data: state::ChannelRegistration,
+    ) -> Result<state::ChannelRegistration> {
+        insert_channel_registration(&self.conn.lock(), &data)?;
+        Ok(data)
+    }
+
+    fn channel_registration(
+        &self,
+        id: ChannelRegistrationId,
+    ) -> Result<state::ChannelRegistration> {
+        self.fetch_one(
+            "SELECT data FROM channel_registrations WHERE id = ?1",
+            &encode(&id)?,
+        )
+    }
+
+    fn update_channel_registration(&self, new_data: &state::ChannelRegistration) -> Result<()> {
+        self.update_one(
+            "UPDATE channel_registrations SET channelname = ?2, data = ?3 WHERE id = ?1",
+            params![
+                encode(&new_data.id)?,
+                new_data.channelname.casefolded(),
+                encode(new_data)?
+            ],
+        )
+    }
+
+    fn all_channel_registrations(
+        &self,
+    ) -> Result<impl Iterator<Item = state::ChannelRegistration> + '_> {
+        self.fetch_all("SELECT data FROM channel_registrations")
+    }
+
+    fn remove_channel_registration(&self, id: ChannelRegistrationId) -> Result<()> {
+        self.remove_one(
+            "DELETE FROM channel_registrations WHERE id = ?1",
+            &encode(&id)?,
+        )
+    }
+
+    fn new_channel_role(&self, data: state::ChannelRole) -> Result<state::ChannelRole> {
+        insert_channel_role(&self.conn.lock(), &data)?;
+        Ok(data)
+    }
+
+    fn channel_role(&self, id: ChannelRoleId) -> Result<state::ChannelRole> {
+        self.fetch_one(
+            "SELECT data FROM channel_roles WHERE id = ?1",
+            &encode(&id)?,
+        )
+    }
+
+    fn update_channel_role(&self, data: &state::ChannelRole) -> Result<()> {
+        let channel = data.channel.as_ref().map(encode).transpose()?;
+
+        self.update_one(
+            "UPDATE channel_roles SET channel = ?2, data = ?3 WHERE id = ?1",
+            params![encode(&data.id)?, channel, encode(data)?],
+        )
+    }
+
+    fn all_channel_roles(&self) -> Result<impl Iterator<Item = state::ChannelRole> + '_> {
+        self.fetch_all("SELECT data FROM channel_roles")
+    }
+
+    fn remove_channel_role(&self, id: ChannelRoleId) -> Result<()> {
+        self.remove_one("DELETE FROM channel_roles WHERE id = ?1", &encode(&id)?)
+    }
+
+    fn update_channel_access(&self, data: &state::ChannelAccess) -> Result<()> {
+        upsert_channel_access(&self.conn.lock(), data)
+    }
+
+    fn channel_access(&self, id: ChannelAccessId) -> Result<state::ChannelAccess> {
+        self.fetch_one(
+            "SELECT data FROM channel_accesses WHERE id = ?1",
+            &encode(&id)?,
+        )
+    }
+
+    fn all_channel_accesses(&self) -> Result<impl Iterator<Item = state::ChannelAccess> + '_> {
+        self.fetch_all("SELECT data FROM channel_accesses")
+    }
+
+    fn remove_channel_access(&self, id: ChannelAccessId) -> Result<()> {
+        self.remove_one("DELETE FROM channel_accesses WHERE id = ?1", &encode(&id)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::jsonfile::JsonDatabase;
+    use std::str::FromStr;
+
+    fn ids() -> ObjectIdGenerator {
+        ObjectIdGenerator::new(ServerId::new(1))
+    }
+
+    fn account(ids: &ObjectIdGenerator, name: &str) -> (state::Account, AccountAuth) {
+        let account = state::Account {
+            id: ids.next(),
+            name: Nickname::from_str(name).unwrap(),
+            authorised_fingerprints: Vec::new(),
+            registered: Some(1000),
+            last_seen: None,
+            last_quit: None,
+            hide_info: false,
+            no_expire: false,
+        };
+        let auth = AccountAuth {
+            account: account.id,
+            password_hash: "hash".to_owned(),
+            email: Some("user@example.com".to_owned()),
+        };
+        (account, auth)
+    }
+
+    fn channel(ids: &ObjectIdGenerator, name: &str) -> state::ChannelRegistration {
+        state::ChannelRegistration {
+            id: ids.next(),
+            channelname: ChannelName::from_str(name).unwrap(),
+            last_used: None,
+            no_expire: false,
+            mode_lock_on: Default::default(),
+            mode_lock_off: Default::default(),
+            keep_topic: false,
+            last_topic: None,
+            entry_message: None,
+            guard: false,
+            auto_kicks: Vec::new(),
+        }
+    }
+
+    /// Populate a database with one of each object type, returning the account
+    /// and channel so that callers can look them up again
+    fn populate(
+        db: &impl DatabaseConnection,
+        ids: &ObjectIdGenerator,
+    ) -> (
+        state::Account,
+        state::ChannelRegistration,
+        state::ChannelRole,
+    ) {
+        let (acc, auth) = account(ids, "alice");
+        let acc = db.new_account(acc, auth).unwrap();
+
+        db.new_nick_registration(state::NickRegistration {
+            id: ids.next(),
+            nick: Nickname::from_str("alice_").unwrap(),
+            account: acc.id,
+        })
+        .unwrap();
+
+        let chan = db.new_channel_registration(channel(ids, "#test")).unwrap();
+        let role = db
+            .new_channel_role(state::ChannelRole {
+                id: ids.next(),
+                channel: Some(chan.id),
+                name: state::ChannelRoleName::BuiltinFounder,
+                flags: state::ChannelAccessSet::new(),
+            })
+            .unwrap();
+        db.update_channel_access(&state::ChannelAccess {
+            id: ChannelAccessId::new(acc.id, chan.id),
+            role: role.id,
+        })
+        .unwrap();
+
+        (acc, chan, role)
+    }
+
+    #[test]
+    fn migrations_set_schema_version() {
+        let db = SqliteDatabase::connect(":memory:").unwrap();
+        let version: usize = db
+            .conn
+            .lock()
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+
+        // Re-running against an up-to-date schema is a no-op
+        SqliteDatabase::migrate(&mut db.conn.lock()).unwrap();
+    }
+
+    #[test]
+    fn newer_schema_is_refused() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+        assert!(SqliteDatabase::migrate(&mut conn).is_err());
+    }
+
+    #[test]
+    fn account_round_trip() {
+        let ids = ids();
+        let db = SqliteDatabase::connect(":memory:").unwrap();
+
+        let (acc, auth) = account(&ids, "alice");
+        let acc = db.new_account(acc, auth).unwrap();
+
+        assert_eq!(db.account(acc.id).unwrap(), acc);
+        assert_eq!(
+            db.account_named(&Nickname::from_str("ALICE").unwrap())
+                .unwrap(),
+            acc
+        );
+        let stored_auth = db.auth_for_account(acc.id).unwrap();
+        assert_eq!(stored_auth.password_hash, "hash");
+        assert_eq!(stored_auth.email.as_deref(), Some("user@example.com"));
+
+        let mut updated = acc.clone();
+        updated.hide_info = true;
+        updated.authorised_fingerprints.push("abcd".to_owned());
+        db.update_account(&updated).unwrap();
+        assert_eq!(db.account(acc.id).unwrap(), updated);
+        assert_eq!(db.all_accounts().unwrap().count(), 1);
+
+        db.remove_account(acc.id).unwrap();
+        assert!(matches!(db.account(acc.id), Err(DatabaseError::NoSuchId)));
+        assert!(matches!(
+            db.auth_for_account(acc.id),
+            Err(DatabaseError::NoSuchId)
+        ));
+        assert!(matches!(
+            db.update_account(&updated),
+            Err(DatabaseError::NoSuchId)
+        ));
+    }
+
+    #[test]
+    fn fingerprint_lookup() {
+        let ids = ids();
+        let db = SqliteDatabase::connect(":memory:").unwrap();
+
+        let (acc, auth) = account(&ids, "alice");
+        let mut acc = db.new_account(acc, auth).unwrap();
+        let (other, other_auth) = account(&ids, "bob");
+        let mut other = db.new_account(other, other_auth).unwrap();
+
+        assert!(matches!(
+            db.account_with_fingerprint("abcd"),
+            Err(DatabaseError::NoSuchId)
+        ));
+
+        acc.authorised_fingerprints = vec!["ABCD".to_owned(), "1234".to_owned()];
+        db.update_account(&acc).unwrap();
+        assert_eq!(db.account_with_fingerprint("abcd").unwrap(), acc);
+        assert_eq!(db.account_with_fingerprint("1234").unwrap(), acc);
+
+        // A fingerprint can't be authorised for two accounts
+        other.authorised_fingerprints = vec!["abcd".to_owned()];
+        assert!(db.update_account(&other).is_err());
+        assert_eq!(db.account_with_fingerprint("ABCD").unwrap(), acc);
+
+        // Removing a fingerprint from an account removes it from the index
+        acc.authorised_fingerprints = vec!["1234".to_owned()];
+        db.update_account(&acc).unwrap();
+        assert!(db.account_with_fingerprint("abcd").is_err());
+        db.update_account(&other).unwrap();
+        assert_eq!(db.account_with_fingerprint("abcd").unwrap(), other);
+
+        db.remove_account(acc.id).unwrap();
+        assert!(db.account_with_fingerprint("1234").is_err());
+    }
+
+    #[test]
+    fn names_are_stored_casefolded() {
+        let ids = ids();
+        let db = SqliteDatabase::connect(":memory:").unwrap();
+        let (acc, _, _) = populate(&db, &ids);
+
+        let mut renamed = acc.clone();
+        renamed.name = Nickname::from_str("ALICE[]").unwrap();
+        db.update_account(&renamed).unwrap();
+
+        let stored: String = db
+            .conn
+            .lock()
+            .query_row("SELECT name FROM accounts", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, renamed.name.casefolded());
+        assert_eq!(
+            db.account_named(&Nickname::from_str("alice[]").unwrap())
+                .unwrap(),
+            renamed
+        );
+    }
+
+    #[test]
+    fn duplicates_are_rejected() {
+        let ids = ids();
+        let db = SqliteDatabase::connect(":memory:").unwrap();
+
+        let (acc, auth) = account(&ids, "alice");
+        db.new_account(acc.clone(), auth.clone()).unwrap();
+        assert!(matches!(
+            db.new_account(acc, auth),
+            Err(DatabaseError::DuplicateId)
+        ));
+
+        let (other, other_auth) = account(&ids, "Alice");
+        assert!(matches!(
+            db.new_account(other, other_auth),
+            Err(DatabaseError::DuplicateName)
+        ));
+
+        db.new_channel_registration(channel(&ids, "#test")).unwrap();
+        assert!(matches!(
+            db.new_channel_registration(channel(&ids, "#TEST")),
+            Err(DatabaseError::DuplicateName)
+        ));
+    }
+
+    #[test]
+    fn registration_round_trip() {
+        let ids = ids();
+        let db = SqliteDatabase::connect(":memory:").unwrap();
+        let (acc, chan, role) = populate(&db, &ids);
+
+        let nick = db.all_nick_registrations().unwrap().next().unwrap();
+        assert_eq!(db.nick_registration(nick.id).unwrap(), nick);
+        assert_eq!(nick.account, acc.id);
+
+        let mut updated_chan = chan.clone();
+        updated_chan.keep_topic = true;
+        updated_chan.last_topic = Some("topic".to_owned());
+        db.update_channel_registration(&updated_chan).unwrap();
+        assert_eq!(db.channel_registration(chan.id).unwrap(), updated_chan);
+
+        assert_eq!(db.channel_role(role.id).unwrap(), role);
+
+        let access_id = ChannelAccessId::new(acc.id, chan.id);
+        assert_eq!(db.channel_access(access_id).unwrap().role, role.id);
+
+        // Updating an access replaces it rather than failing as a duplicate
+        let other_role = db
+            .new_channel_role(state::ChannelRole {
+                id: ids.next(),
+                channel: Some(chan.id),
+                name: state::ChannelRoleName::BuiltinOp,
+                flags: state::ChannelAccessSet::new(),
+            })
+            .unwrap();
+        db.update_channel_access(&state::ChannelAccess {
+            id: access_id,
+            role: other_role.id,
+        })
+        .unwrap();
+        assert_eq!(db.channel_access(access_id).unwrap().role, other_role.id);
+        assert_eq!(db.all_channel_accesses().unwrap().count(), 1);
+
+        db.remove_channel_access(access_id).unwrap();
+        db.remove_channel_role(role.id).unwrap();
+        db.remove_channel_registration(chan.id).unwrap();
+        db.remove_nick_registration(nick.id).unwrap();
+        assert_eq!(db.all_channel_accesses().unwrap().count(), 0);
+        assert_eq!(db.all_channel_roles().unwrap().count(), 1);
+        assert_eq!(db.all_channel_registrations().unwrap().count(), 0);
+        assert_eq!(db.all_nick_registrations().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn import_from_json() {
+        let path = std::env::temp_dir().join(format!(
+            "sable-services-import-test-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let ids = ids();
+        let json = JsonDatabase::connect(path.to_string_lossy()).unwrap();
+        let (acc, chan, role) = populate(&json, &ids);
+
+        let sqlite = SqliteDatabase::connect(":memory:").unwrap();
+        sqlite.import_from(&json).unwrap();
+
+        assert_eq!(sqlite.account(acc.id).unwrap(), acc);
+        assert_eq!(
+            sqlite.auth_for_account(acc.id).unwrap().password_hash,
+            "hash"
+        );
+        assert_eq!(sqlite.channel_registration(chan.id).unwrap(), chan);
+        assert_eq!(sqlite.channel_role(role.id).unwrap(), role);
+        assert_eq!(sqlite.all_nick_registrations().unwrap().count(), 1);
+        assert_eq!(
+            sqlite
+                .channel_access(ChannelAccessId::new(acc.id, chan.id))
+                .unwrap()
+                .role,
+            role.id
+        );
+
+        // A second import conflicts with the existing objects and changes nothing
+        assert!(sqlite.import_from(&json).is_err());
+        assert_eq!(sqlite.all_accounts().unwrap().count(), 1);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -5,6 +5,9 @@ use serde::{Deserialize, Serialize};
 pub struct AccountAuth {
     pub account: AccountId,
     pub password_hash: String,
+    /// The address given at registration, used to warn of upcoming expiry
+    #[serde(default)]
+    pub email: Option<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -0,0 +1,159 @@
+use super::*;
+
+use sable_network::{
+    network::update,
+    prelude::{ChannelModeSet, ChannelName, OptionChange},
+    rpc::NetworkHistoryUpdate,
+};
+
+impl<DB: DatabaseConnection> ServicesServer<DB> {
+    /// Apply registered channels' mode lock, topic retention and entry message settings
+    /// in response to network activity
+    pub(super) fn enforce_channel_settings(&self, update: &NetworkHistoryUpdate) {
+        match &update.change {
+            NetworkStateChange::ChannelJoin(detail) => {
+                self.channel_joined(detail);
+            }
+            NetworkStateChange::ChannelModeChange(detail) => {
+                let net = self.node.network();
+                let Ok(channel) = net.channel(detail.channel) else {
+                    return;
+                };
+                let name = *channel.name();
+                drop(net);
+
+                self.enforce_mode_lock(&name);
+            }
+            NetworkStateChange::ChannelTopicChange(detail) => {
+                self.record_topic(detail);
+            }
+            _ => {}
+        }
+    }
+
+    fn channel_joined(&self, detail: &update::ChannelJoin) {
+        let net = self.node.network();
+
+        let Ok(channel) = net.channel(detail.membership.channel()) else {
+            return;
+        };
+        let Some(registration) = channel.is_registered() else {
+            return;
+        };
+
+        if let Some(message) = registration.entry_message() {
+            self.node.submit_event(
+                *detail.user.user(),
+                ServicesNotice {
+                    source_alias: "CS".to_owned(),
+                    text: format!("[{}] {}", channel.name(), message),
+                },
+            );
+        }
+
+        // Channels get a new ID each time they're created, so a change of ID for the
+        // same registration means the channel has been recreated since we last saw it.
+        // This also fires on the first join seen after services start, which only
+        // re-applies settings that should already be in effect.
+        let recreated = self
+            .channel_instances
+            .insert(registration.id(), channel.id())
+            != Some(channel.id());
+
+        if recreated && registration.keep_topic() && channel.topic().is_none() {
+            if let Some(topic) = registration.last_topic() {
+                self.node.submit_event(
+                    self.node.ids().next::<ChannelTopicId>(),
+                    NewChannelTopic {
+                        channel: channel.id(),
+                        text: topic.to_owned(),
+                        setter: self.node.id().into(),
+                    },
+                );
+            }
+        }
+
+        let name = *channel.name();
+        drop(net);
+
+        if recreated {
+            self.enforce_mode_lock(&name);
+        }
+    }
+
+    /// Check a channel's current modes against its registration's mode lock, and revert
+    /// any locked modes which have been changed
+    pub(super) fn enforce_mode_lock(&self, channel_name: &ChannelName) {
+        let net = self.node.network();
+
+        let Ok(channel) = net.channel_by_name(channel_name) else {
+            return;
+        };
+        let Some(registration) = channel.is_registered() else {
+            return;
+        };
+
+        let current = channel.mode();
+        let mut added = ChannelModeSet::new();
+        let mut removed = ChannelModeSet::new();
+
+        for mode in ChannelModeSet::all() {
+            if registration.mode_lock_on().is_set(mode) && !current.has_mode(mode) {
+                added |= mode;
+            }
+            if registration.mode_lock_off().is_set(mode) && current.has_mode(mode) {
+                removed |= mode;
+            }
+        }
+
+        if added.is_empty() && removed.is_empty() {
+            return;
+        }
+
+        self.node.submit_event(
+            channel.id(),
+            ChannelModeChange {
+                changed_by: self.node.id().into(),
+                added,
+                removed,
+                key_change: OptionChange::NoChange,
+            },
+        );
+    }
+
+    /// Remember a channel's new topic, if its registration asks for topic retention
+    fn record_topic(&self, detail: &update::ChannelTopicChange) {
+        let net = self.node.network();
+
+        let Ok(channel) = net.channel(detail.channel) else {
+            return;
+        };
+        let Some(registration) = channel.is_registered() else {
+            return;
+        };
+        if !registration.keep_topic() || registration.last_topic() == Some(detail.new_text.as_str())
+        {
+            return;
+        }
+
+        let id = registration.id();
+        drop(net);
+
+        let Ok(mut registration) = self.db.channel_registration(id) else {
+            return;
+        };
+        registration.last_topic = Some(detail.new_text.clone());
+
+        if let Err(error) = self.db.update_channel_registration(&registration) {
+            tracing::error!(?id, ?error, "Failed to record channel topic");
+            return;
+        }
+
+        self.node.submit_event(
+            id,
+            ChannelRegistrationUpdate {
+                data: Some(registration),
+            },
+        );
+    }
+}
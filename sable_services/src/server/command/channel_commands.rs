@@ -1,4 +1,7 @@
-use sable_network::prelude::state::ChannelAccessSet;
+use sable_network::{
+    policy::{OperPolicyService, RegistrationPolicyService},
+    prelude::state::{ChannelAccessSet, ChannelAutoKick, ChannelAutoKickTarget},
+};
 
 use super::*;
 
@@ -15,6 +18,15 @@ impl<DB: DatabaseConnection> ServicesServer<DB> {
         let new_channel_registration = state::ChannelRegistration {
             id: self.node.ids().next(),
             channelname: *channel.name(),
+            last_used: Some(sable_network::utils::now()),
+            no_expire: false,
+            mode_lock_on: Default::default(),
+            mode_lock_off: Default::default(),
+            keep_topic: false,
+            last_topic: None,
+            entry_message: None,
+            guard: false,
+            auto_kicks: Vec::new(),
         };
 
         let new_channel_registration =
@@ -233,4 +245,181 @@ impl<DB: DatabaseConnection> ServicesServer<DB> {
 
         Ok(RemoteServerResponse::Success)
     }
+
+    pub(crate) fn set_channel_no_expire(
+        &self,
+        source: UserId,
+        channel_id: ChannelRegistrationId,
+        no_expire: bool,
+    ) -> CommandResult {
+        let net = self.node.network();
+        let source = net.user(source)?;
+
+        if self.node.policy().require_oper(&source).is_err() {
+            return Ok(RemoteServicesServerResponse::AccessDenied.into());
+        }
+
+        let mut registration = self.db.channel_registration(channel_id)?;
+        registration.no_expire = no_expire;
+
+        self.db.update_channel_registration(&registration)?;
+        self.node.submit_event(
+            registration.id,
+            ChannelRegistrationUpdate {
+                data: Some(registration),
+            },
+        );
+
+        Ok(RemoteServerResponse::Success)
+    }
+
+    pub(crate) fn set_channel_setting(
+        &self,
+        source: AccountId,
+        channel_id: ChannelRegistrationId,
+        setting: ChannelSetting,
+    ) -> CommandResult {
+        let net = self.node.network();
+        let source = net.account(source)?;
+
+        let is_founder = source
+            .has_access_in(channel_id)
+            .is_some_and(|access| access.has(ChannelAccessFlag::Founder));
+
+        if !is_founder {
+            return Ok(RemoteServicesServerResponse::AccessDenied.into());
+        }
+
+        let mut registration = self.db.channel_registration(channel_id)?;
+
+        match setting {
+            ChannelSetting::ModeLock { on, off } => {
+                registration.mode_lock_on = on;
+                registration.mode_lock_off = off;
+            }
+            ChannelSetting::KeepTopic(keep_topic) => {
+                registration.keep_topic = keep_topic;
+                // Start from the current topic, rather than waiting for it to change
+                registration.last_topic = if keep_topic {
+                    net.channel_by_name(&registration.channelname)
+                        .ok()
+                        .and_then(|c| c.topic().map(|t| t.text().to_owned()))
+                } else {
+                    None
+                };
+            }
+            ChannelSetting::EntryMessage(message) => {
+                registration.entry_message = message;
+            }
+            ChannelSetting::Guard(guard) => {
+                registration.guard = guard;
+            }
+        }
+
+        self.db.update_channel_registration(&registration)?;
+
+        let channelname = registration.channelname;
+        self.node.submit_event(
+            registration.id,
+            ChannelRegistrationUpdate {
+                data: Some(registration),
+            },
+        );
+
+        drop(net);
+        self.enforce_mode_lock(&channelname);
+
+        Ok(RemoteServerResponse::Success)
+    }
+
+    pub(crate) fn add_auto_kick(
+        &self,
+        source: AccountId,
+        channel_id: ChannelRegistrationId,
+        target: ChannelAutoKickTarget,
+        reason: String,
+        expires: Option<i64>,
+    ) -> CommandResult {
+        let now = sable_network::utils::now();
+
+        let setter = {
+            let net = self.node.network();
+            let source = net.account(source)?;
+            let channel = net.channel_registration(channel_id)?;
+
+            if self
+                .node
+                .policy()
+                .can_edit_auto_kicks(&source, &channel)
+                .is_err()
+            {
+                return Ok(RemoteServicesServerResponse::AccessDenied.into());
+            }
+
+            source.name()
+        };
+
+        let mut registration = self.db.channel_registration(channel_id)?;
+
+        registration
+            .auto_kicks
+            .retain(|a| a.target != target && !a.is_expired(now));
+        registration.auto_kicks.push(ChannelAutoKick {
+            target,
+            reason,
+            setter,
+            timestamp: now,
+            expires,
+        });
+
+        self.db.update_channel_registration(&registration)?;
+        self.node.submit_event(
+            registration.id,
+            ChannelRegistrationUpdate {
+                data: Some(registration),
+            },
+        );
+
+        Ok(RemoteServerResponse::Success)
+    }
+
+    pub(crate) fn remove_auto_kick(
+        &self,
+        source: AccountId,
+        channel_id: ChannelRegistrationId,
+        target: ChannelAutoKickTarget,
+    ) -> CommandResult {
+        let now = sable_network::utils::now();
+
+        {
+            let net = self.node.network();
+            let source = net.account(source)?;
+            let channel = net.channel_registration(channel_id)?;
+
+            if self
+                .node
+                .policy()
+                .can_edit_auto_kicks(&source, &channel)
+                .is_err()
+            {
+                return Ok(RemoteServicesServerResponse::AccessDenied.into());
+            }
+        }
+
+        let mut registration = self.db.channel_registration(channel_id)?;
+
+        registration
+            .auto_kicks
+            .retain(|a| a.target != target && !a.is_expired(now));
+
+        self.db.update_channel_registration(&registration)?;
+        self.node.submit_event(
+            registration.id,
+            ChannelRegistrationUpdate {
+                data: Some(registration),
+            },
+        );
+
+        Ok(RemoteServerResponse::Success)
+    }
 }
@@ -1,7 +1,14 @@
 use super::*;
 
+use sable_network::policy::OperPolicyService;
+
 impl<DB: DatabaseConnection> ServicesServer<DB> {
-    pub(crate) fn register_user(&self, account_name: Nickname, password: String) -> CommandResult {
+    pub(crate) fn register_user(
+        &self,
+        account_name: Nickname,
+        password: String,
+        email: Option<String>,
+    ) -> CommandResult {
         let new_account_id = self.node.ids().next();
 
         let password_hash = match self.config.password_hash.hash(&password) {
@@ -21,10 +28,16 @@ impl<DB: DatabaseConnection> ServicesServer<DB> {
             id: new_account_id,
             name: account_name,
             authorised_fingerprints: Vec::new(),
+            registered: Some(sable_network::utils::now()),
+            last_seen: Some(sable_network::utils::now()),
+            last_quit: None,
+            hide_info: false,
+            no_expire: false,
         };
         let auth_data = AccountAuth {
             account: new_account_id,
             password_hash,
+            email,
         };
 
         match self.db.new_account(account_data, auth_data) {
@@ -70,7 +83,7 @@ impl<DB: DatabaseConnection> ServicesServer<DB> {
     }
 
     pub(crate) fn user_add_fp(&self, account_id: AccountId, fp: String) -> CommandResult {
-        if self.node.network().account_with_fingerprint(&fp).is_some() {
+        if self.db.account_with_fingerprint(&fp).is_ok() {
             return Err("Duplicate fingerprint".into());
         }
 
@@ -110,4 +123,50 @@ impl<DB: DatabaseConnection> ServicesServer<DB> {
 
         Ok(RemoteServerResponse::Success)
     }
+
+    pub(crate) fn set_account_no_expire(
+        &self,
+        source: UserId,
+        account_id: AccountId,
+        no_expire: bool,
+    ) -> CommandResult {
+        let net = self.node.network();
+        let source = net.user(source)?;
+
+        if self.node.policy().require_oper(&source).is_err() {
+            return Ok(RemoteServicesServerResponse::AccessDenied.into());
+        }
+
+        let mut account = self.db.account(account_id)?;
+        account.no_expire = no_expire;
+
+        self.db.update_account(&account)?;
+        self.node.submit_event(
+            account.id,
+            event::AccountUpdate {
+                data: Some(account),
+            },
+        );
+
+        Ok(RemoteServerResponse::Success)
+    }
+
+    pub(crate) fn set_account_hide_info(
+        &self,
+        account_id: AccountId,
+        hide_info: bool,
+    ) -> CommandResult {
+        let mut account = self.db.account(account_id)?;
+        account.hide_info = hide_info;
+
+        self.db.update_account(&account)?;
+        self.node.submit_event(
+            account.id,
+            event::AccountUpdate {
+                data: Some(account),
+            },
+        );
+
+        Ok(RemoteServerResponse::Success)
+    }
 }
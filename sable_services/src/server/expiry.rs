@@ -0,0 +1,566 @@
+use super::*;
+
+use sable_network::{network::update, rpc::NetworkHistoryUpdate, utils::now};
+
+use dashmap::DashSet;
+
+const fn default_warning_period() -> i64 {
+    7 * 24 * 60 * 60
+}
+
+const fn default_check_interval() -> u64 {
+    60 * 60
+}
+
+const fn default_seen_resolution() -> i64 {
+    60 * 60
+}
+
+/// Configuration for expiry of inactive registrations
+#[derive(Deserialize, Clone)]
+pub struct ExpiryConfig {
+    /// Number of seconds after the last login before an account is dropped.
+    /// If not set, accounts never expire.
+    #[serde(default)]
+    pub account_expiry: Option<i64>,
+    /// Number of seconds after a user with access was last present in a registered
+    /// channel before the registration is dropped. If not set, channels never expire.
+    #[serde(default)]
+    pub channel_expiry: Option<i64>,
+    /// How long before expiry to begin warning users
+    #[serde(default = "default_warning_period")]
+    pub warning_period: i64,
+    /// How often, in seconds, to check for expired registrations
+    #[serde(default = "default_check_interval")]
+    pub check_interval: u64,
+    /// Last-seen and last-used times are only propagated to the network if the stored
+    /// value is at least this many seconds old, to avoid an event for every login or join
+    #[serde(default = "default_seen_resolution")]
+    pub seen_resolution: i64,
+}
+
+impl Default for ExpiryConfig {
+    fn default() -> Self {
+        Self {
+            account_expiry: None,
+            channel_expiry: None,
+            warning_period: default_warning_period(),
+            check_interval: default_check_interval(),
+            seen_resolution: default_seen_resolution(),
+        }
+    }
+}
+
+/// Tracks which registrations have already been warned about their upcoming expiry,
+/// so that each is only warned once per period of inactivity
+#[derive(Default)]
+pub(super) struct ExpiryWarnings {
+    accounts: DashSet<AccountId>,
+    channels: DashSet<ChannelRegistrationId>,
+}
+
+/// Format a number of seconds remaining as a whole number of days, rounding up
+fn days_remaining(seconds: i64) -> i64 {
+    (seconds + 86399) / 86400
+}
+
+/// What an expiry check should do with a registration
+#[derive(Debug, PartialEq)]
+enum ExpiryAction {
+    /// The registration is held, so never expires
+    Exempt,
+    /// The registration is in use, or its last use isn't known, so its inactivity
+    /// period starts now
+    Touch,
+    /// The registration isn't close to expiring
+    Keep,
+    /// The registration expires in the given number of seconds
+    Warn(i64),
+    /// The registration has expired
+    Expire,
+}
+
+/// Decide what to do with a registration last used at `last_used` (or `None` if it's
+/// in use or not yet tracked)
+fn expiry_action(
+    held: bool,
+    last_used: Option<i64>,
+    now: i64,
+    expiry: i64,
+    warning_period: i64,
+) -> ExpiryAction {
+    if held {
+        return ExpiryAction::Exempt;
+    }
+    let Some(last_used) = last_used else {
+        return ExpiryAction::Touch;
+    };
+
+    let expires_at = last_used + expiry;
+
+    if now >= expires_at {
+        ExpiryAction::Expire
+    } else if now >= expires_at - warning_period {
+        ExpiryAction::Warn(expires_at - now)
+    } else {
+        ExpiryAction::Keep
+    }
+}
+
+impl<DB: DatabaseConnection> ServicesServer<DB> {
+    /// Update stored last-seen and last-used timestamps in response to network activity
+    pub(super) fn track_activity(&self, update: &NetworkHistoryUpdate) {
+        let net = self.node.network();
+
+        match &update.change {
+            NetworkStateChange::UserLoginChange(detail) => {
+                for account in [detail.old_account, detail.new_account]
+                    .into_iter()
+                    .flatten()
+                {
+                    self.touch_account(account, update.timestamp);
+                }
+            }
+            NetworkStateChange::UserQuit(detail) => {
+                let account = net
+                    .historic_user(detail.user)
+                    .ok()
+                    .and_then(|user| user.account)
+                    .and_then(|name| net.account_by_name(&name).ok());
+
+                if let Some(account) = account {
+                    self.record_quit(account.id(), update.timestamp, &detail.message);
+                }
+            }
+            NetworkStateChange::ChannelJoin(detail) => {
+                self.touch_channel_for_join(&net, detail, update.timestamp);
+            }
+            _ => {}
+        }
+    }
+
+    fn touch_channel_for_join(
+        &self,
+        net: &sable_network::network::Network,
+        detail: &update::ChannelJoin,
+        timestamp: i64,
+    ) {
+        let Ok(user) = net.user(*detail.user.user()) else {
+            return;
+        };
+        let Ok(Some(account)) = user.account() else {
+            return;
+        };
+        let Some(registration) = net
+            .channel(detail.membership.channel())
+            .ok()
+            .and_then(|c| c.is_registered())
+        else {
+            return;
+        };
+
+        if account.has_access_in(registration.id()).is_some() {
+            self.touch_channel(registration.id(), timestamp);
+        }
+    }
+
+    /// Record that an account was in use at the given time
+    fn touch_account(&self, id: AccountId, timestamp: i64) {
+        self.expiry_warnings.accounts.remove(&id);
+
+        let Ok(mut account) = self.db.account(id) else {
+            return;
+        };
+
+        if account
+            .last_seen
+            .is_some_and(|seen| timestamp - seen < self.config.expiry.seen_resolution)
+        {
+            return;
+        }
+
+        account.last_seen = Some(timestamp);
+
+        if let Err(error) = self.db.update_account(&account) {
+            tracing::error!(?id, ?error, "Failed to update account last-seen time");
+            return;
+        }
+
+        self.node.submit_event(
+            id,
+            AccountUpdate {
+                data: Some(account),
+            },
+        );
+    }
+
+    /// Record the last quit message for an account. Unlike other activity, every
+    /// quit is recorded, so that the message shown is always the latest.
+    fn record_quit(&self, id: AccountId, timestamp: i64, message: &str) {
+        self.expiry_warnings.accounts.remove(&id);
+
+        let Ok(mut account) = self.db.account(id) else {
+            return;
+        };
+
+        account.last_seen = Some(timestamp);
+        account.last_quit = Some(message.to_owned());
+
+        if let Err(error) = self.db.update_account(&account) {
+            tracing::error!(?id, ?error, "Failed to update account last quit message");
+            return;
+        }
+
+        self.node.submit_event(
+            id,
+            AccountUpdate {
+                data: Some(account),
+            },
+        );
+    }
+
+    /// Record that a channel registration was in use at the given time
+    fn touch_channel(&self, id: ChannelRegistrationId, timestamp: i64) {
+        self.expiry_warnings.channels.remove(&id);
+
+        let Ok(mut registration) = self.db.channel_registration(id) else {
+            return;
+        };
+
+        if registration
+            .last_used
+            .is_some_and(|used| timestamp - used < self.config.expiry.seen_resolution)
+        {
+            return;
+        }
+
+        registration.last_used = Some(timestamp);
+
+        if let Err(error) = self.db.update_channel_registration(&registration) {
+            tracing::error!(?id, ?error, "Failed to update channel last-used time");
+            return;
+        }
+
+        self.node.submit_event(
+            id,
+            ChannelRegistrationUpdate {
+                data: Some(registration),
+            },
+        );
+    }
+
+    /// Find and drop any registrations which have passed their expiry time, and warn
+    /// about those which are about to
+    pub(super) fn check_expiry(&self) {
+        let now = now();
+
+        if let Some(account_expiry) = self.config.expiry.account_expiry {
+            self.check_account_expiry(now, account_expiry);
+        }
+
+        if let Some(channel_expiry) = self.config.expiry.channel_expiry {
+            self.check_channel_expiry(now, channel_expiry);
+        }
+    }
+
+    fn check_account_expiry(&self, now: i64, expiry: i64) {
+        let accounts: Vec<_> = match self.db.all_accounts() {
+            Ok(accounts) => accounts.collect(),
+            Err(error) => {
+                tracing::error!(?error, "Couldn't read accounts for expiry check");
+                return;
+            }
+        };
+
+        for account in accounts {
+            let in_use = self
+                .node
+                .network()
+                .account(account.id)
+                .is_ok_and(|a| a.users().next().is_some());
+
+            // Accounts that are logged in, or which predate last-seen tracking,
+            // start their inactivity period now
+            let last_seen = account.last_seen.filter(|_| !in_use);
+
+            match expiry_action(
+                account.no_expire,
+                last_seen,
+                now,
+                expiry,
+                self.config.expiry.warning_period,
+            ) {
+                ExpiryAction::Exempt | ExpiryAction::Keep => {}
+                ExpiryAction::Touch => self.touch_account(account.id, now),
+                ExpiryAction::Warn(remaining) => {
+                    if self.expiry_warnings.accounts.insert(account.id) {
+                        self.warn_account_expiry(&account, remaining);
+                    }
+                }
+                ExpiryAction::Expire => {
+                    tracing::info!(account = ?account.name, "Dropping expired account");
+
+                    if let Err(error) = self.expire_account(account.id) {
+                        tracing::error!(?error, account = ?account.name, "Failed to drop account");
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_channel_expiry(&self, now: i64, expiry: i64) {
+        let registrations: Vec<_> = match self.db.all_channel_registrations() {
+            Ok(registrations) => registrations.collect(),
+            Err(error) => {
+                tracing::error!(?error, "Couldn't read channels for expiry check");
+                return;
+            }
+        };
+
+        for registration in registrations {
+            let in_use = self.channel_in_use(&registration);
+
+            let last_used = registration.last_used.filter(|_| !in_use);
+
+            match expiry_action(
+                registration.no_expire,
+                last_used,
+                now,
+                expiry,
+                self.config.expiry.warning_period,
+            ) {
+                ExpiryAction::Exempt | ExpiryAction::Keep => {}
+                ExpiryAction::Touch => self.touch_channel(registration.id, now),
+                ExpiryAction::Warn(remaining) => {
+                    if self.expiry_warnings.channels.insert(registration.id) {
+                        self.warn_channel_expiry(&registration, remaining);
+                    }
+                }
+                ExpiryAction::Expire => {
+                    tracing::info!(channel = ?registration.channelname, "Dropping expired channel");
+
+                    if let Err(error) = self.expire_channel(registration.id) {
+                        tracing::error!(?error, channel = ?registration.channelname, "Failed to drop channel");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Determine whether any user with access to the given channel is currently present in it
+    fn channel_in_use(&self, registration: &state::ChannelRegistration) -> bool {
+        let net = self.node.network();
+
+        let Ok(channel) = net.channel_by_name(&registration.channelname) else {
+            return false;
+        };
+
+        channel.members().any(|m| {
+            m.user()
+                .ok()
+                .and_then(|u| u.account().ok().flatten())
+                .is_some_and(|a| a.has_access_in(registration.id).is_some())
+        })
+    }
+
+    fn warn_account_expiry(&self, account: &state::Account, remaining: i64) {
+        let text = format!(
+            "The account {} will expire in {} day(s) unless you log in to it",
+            account.name,
+            days_remaining(remaining)
+        );
+
+        // Warn anyone logged in to the account, and anyone online using one of its
+        // registered nicks, since the owner may be connected without logging in
+        let net = self.node.network();
+        let mut users: Vec<_> = net
+            .account(account.id)
+            .map(|a| a.users().map(|u| u.id()).collect())
+            .unwrap_or_default();
+        if let Ok(registrations) = self.db.all_nick_registrations() {
+            users.extend(
+                registrations
+                    .filter(|r| r.account == account.id)
+                    .filter_map(|r| net.user_by_nick(&r.nick).ok())
+                    .map(|u| u.id()),
+            );
+        }
+        users.sort();
+        users.dedup();
+
+        for user in users {
+            self.node.submit_event(
+                user,
+                ServicesNotice {
+                    source_alias: "NS".to_owned(),
+                    text: text.clone(),
+                },
+            );
+        }
+
+        self.mail_account_owner(account.id, "Your account is about to expire", &text);
+    }
+
+    fn warn_channel_expiry(&self, registration: &state::ChannelRegistration, remaining: i64) {
+        let net = self.node.network();
+        let Ok(channel) = net.channel_registration(registration.id) else {
+            return;
+        };
+
+        let text = format!(
+            "The registration for {} will expire in {} day(s) unless someone with access uses the channel",
+            registration.channelname,
+            days_remaining(remaining)
+        );
+
+        for access in channel.access_entries() {
+            if !access.has(ChannelAccessFlag::Founder) {
+                continue;
+            }
+            let Ok(account) = access.user() else {
+                continue;
+            };
+
+            self.mail_account_owner(account.id(), "Your channel is about to expire", &text);
+
+            for user in account.users() {
+                self.node.submit_event(
+                    user.id(),
+                    ServicesNotice {
+                        source_alias: "CS".to_owned(),
+                        text: text.clone(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Drop an account along with its nick registrations and channel access entries
+    fn expire_account(&self, id: AccountId) -> Result<(), DatabaseError> {
+        let nicks: Vec<_> = self
+            .db
+            .all_nick_registrations()?
+            .filter(|n| n.account == id)
+            .map(|n| n.id)
+            .collect();
+
+        for nick in nicks {
+            self.db.remove_nick_registration(nick)?;
+            self.node
+                .submit_event(nick, NickRegistrationUpdate { data: None });
+        }
+
+        let accesses: Vec<_> = self
+            .db
+            .all_channel_accesses()?
+            .filter(|a| a.id.account() == id)
+            .map(|a| a.id)
+            .collect();
+
+        for access in accesses {
+            self.db.remove_channel_access(access)?;
+            self.node
+                .submit_event(access, ChannelAccessUpdate { data: None });
+        }
+
+        self.db.remove_account(id)?;
+        self.node.submit_event(id, AccountUpdate { data: None });
+        self.expiry_warnings.accounts.remove(&id);
+
+        Ok(())
+    }
+
+    /// Drop a channel registration along with its roles and access entries
+    fn expire_channel(&self, id: ChannelRegistrationId) -> Result<(), DatabaseError> {
+        let accesses: Vec<_> = self
+            .db
+            .all_channel_accesses()?
+            .filter(|a| a.id.channel() == id)
+            .map(|a| a.id)
+            .collect();
+
+        for access in accesses {
+            self.db.remove_channel_access(access)?;
+            self.node
+                .submit_event(access, ChannelAccessUpdate { data: None });
+        }
+
+        let roles: Vec<_> = self
+            .db
+            .all_channel_roles()?
+            .filter(|r| r.channel == Some(id))
+            .map(|r| r.id)
+            .collect();
+
+        for role in roles {
+            self.db.remove_channel_role(role)?;
+            self.node
+                .submit_event(role, ChannelRoleUpdate { data: None });
+        }
+
+        self.db.remove_channel_registration(id)?;
+        self.node
+            .submit_event(id, ChannelRegistrationUpdate { data: None });
+        self.expiry_warnings.channels.remove(&id);
+        self.channel_instances.remove(&id);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    #[test]
+    fn held_registrations_never_expire() {
+        assert_eq!(
+            expiry_action(true, Some(0), 1000 * DAY, 30 * DAY, 7 * DAY),
+            ExpiryAction::Exempt
+        );
+        assert_eq!(
+            expiry_action(true, None, 1000 * DAY, 30 * DAY, 7 * DAY),
+            ExpiryAction::Exempt
+        );
+    }
+
+    #[test]
+    fn untracked_registrations_are_touched() {
+        assert_eq!(
+            expiry_action(false, None, 1000 * DAY, 30 * DAY, 7 * DAY),
+            ExpiryAction::Touch
+        );
+    }
+
+    #[test]
+    fn recently_used_registrations_are_kept() {
+        assert_eq!(
+            expiry_action(false, Some(100 * DAY), 110 * DAY, 30 * DAY, 7 * DAY),
+            ExpiryAction::Keep
+        );
+    }
+
+    #[test]
+    fn registrations_are_warned_before_expiry() {
+        assert_eq!(
+            expiry_action(false, Some(100 * DAY), 123 * DAY, 30 * DAY, 7 * DAY),
+            ExpiryAction::Warn(7 * DAY)
+        );
+        assert_eq!(
+            expiry_action(false, Some(100 * DAY), 130 * DAY - 1, 30 * DAY, 7 * DAY),
+            ExpiryAction::Warn(1)
+        );
+        assert_eq!(days_remaining(1), 1);
+        assert_eq!(days_remaining(7 * DAY), 7);
+    }
+
+    #[test]
+    fn registrations_expire_after_the_expiry_period() {
+        assert_eq!(
+            expiry_action(false, Some(100 * DAY), 130 * DAY, 30 * DAY, 7 * DAY),
+            ExpiryAction::Expire
+        );
+    }
+}
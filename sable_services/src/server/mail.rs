@@ -0,0 +1,74 @@
+use super::*;
+
+use std::{path::PathBuf, process::Stdio};
+use tokio::io::AsyncWriteExt;
+
+fn default_sendmail() -> PathBuf {
+    PathBuf::from("/usr/sbin/sendmail")
+}
+
+/// Configuration for mailing account owners
+#[derive(Deserialize, Clone)]
+pub struct MailConfig {
+    /// A sendmail-compatible program, which is given each message on standard input
+    #[serde(default = "default_sendmail")]
+    pub sendmail: PathBuf,
+    /// The address from which mail is sent
+    pub from: String,
+}
+
+/// Determine whether the given string is usable as a mail recipient. This only
+/// rules out addresses which couldn't be written safely into a message header.
+fn valid_email_address(address: &str) -> bool {
+    address.contains('@')
+        && !address
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == ',')
+}
+
+impl<DB: DatabaseConnection> ServicesServer<DB> {
+    /// Mail the owner of the given account, if mail is configured and they gave an
+    /// address when registering
+    pub(super) fn mail_account_owner(&self, account: AccountId, subject: &str, body: &str) {
+        let Some(config) = self.config.mail.clone() else {
+            return;
+        };
+        let Some(to) = self.db.auth_for_account(account).ok().and_then(|a| a.email) else {
+            return;
+        };
+        if !valid_email_address(&to) {
+            tracing::warn!(?account, "Not mailing invalid address");
+            return;
+        }
+
+        let message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\n\r\n{}\r\n",
+            config.from, to, subject, body
+        );
+
+        tokio::spawn(async move {
+            if let Err(error) = send_mail(&config.sendmail, &message).await {
+                tracing::error!(?error, "Failed to send mail");
+            }
+        });
+    }
+}
+
+async fn send_mail(sendmail: &PathBuf, message: &str) -> std::io::Result<()> {
+    let mut child = tokio::process::Command::new(sendmail)
+        .args(["-t", "-oi"])
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(message.as_bytes()).await?;
+    }
+
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(std::io::Error::other(format!(
+            "sendmail exited with {status}"
+        )));
+    }
+    Ok(())
+}
@@ -19,7 +19,7 @@ use sable_network::{
 use sable_server::ServerSaveError;
 use sable_server::ServerType;
 
-use std::{collections::HashMap, convert::Infallible, sync::Arc};
+use std::{collections::HashMap, convert::Infallible, sync::Arc, time::Duration};
 
 use anyhow::Context;
 use dashmap::DashMap;
@@ -27,17 +27,27 @@ use serde::Deserialize;
 use tokio::sync::{broadcast, mpsc::UnboundedReceiver, Mutex};
 use tracing::instrument;
 
+mod channel_settings;
 mod command;
+mod expiry;
+mod mail;
 mod roles;
 mod sasl;
 mod sync;
 
+pub use expiry::ExpiryConfig;
+pub use mail::MailConfig;
+
 #[derive(Deserialize, Clone)]
 pub struct ServicesConfig {
     pub database: String,
     pub default_roles: HashMap<ChannelRoleName, Vec<ChannelAccessFlag>>,
     #[serde(default)]
     pub password_hash: HashConfig,
+    #[serde(default)]
+    pub expiry: ExpiryConfig,
+    #[serde(default)]
+    pub mail: Option<MailConfig>,
 }
 
 pub struct ServicesServer<DB> {
@@ -47,6 +57,10 @@ pub struct ServicesServer<DB> {
     config: ServicesConfig,
     sasl_sessions: DashMap<SaslSessionId, SaslSession>,
     sasl_mechanisms: HashMap<String, Box<dyn sasl::SaslMechanism<DB>>>,
+    expiry_warnings: expiry::ExpiryWarnings,
+    /// The channel last seen for each registration, used to notice when a registered
+    /// channel has been recreated
+    channel_instances: DashMap<ChannelRegistrationId, ChannelId>,
 }
 
 impl<DB> ServerType for ServicesServer<DB>
@@ -93,6 +107,8 @@ where
             config,
             sasl_sessions: DashMap::new(),
             sasl_mechanisms: sasl::build_mechanisms(),
+            expiry_warnings: Default::default(),
+            channel_instances: DashMap::new(),
         })
     }
 
@@ -100,6 +116,10 @@ where
 
     async fn run(self: Arc<Self>, mut shutdown_channel: broadcast::Receiver<ShutdownAction>) {
         let mut history_receiver = self.history_receiver.lock().await;
+        // Don't check for expiry immediately on startup, before we've synced with the network
+        let expiry_period = Duration::from_secs(self.config.expiry.check_interval);
+        let mut expiry_timer =
+            tokio::time::interval_at(tokio::time::Instant::now() + expiry_period, expiry_period);
 
         loop {
             tokio::select! {
@@ -116,8 +136,16 @@ where
                                 self.burst_to_network().await;
                             }
                         }
+
+                        self.track_activity(&update);
+                        self.enforce_channel_settings(&update);
                     }
                 }
+
+                _ = expiry_timer.tick() =>
+                {
+                    self.check_expiry();
+                }
             }
         }
     }
@@ -144,10 +172,10 @@ where
 
         let result = match req {
             Services(req) => match req {
-                RegisterUser(account_name, password) => {
+                RegisterUser(account_name, password, email) => {
                     tracing::debug!(?account_name, "Got register request");
 
-                    self.register_user(account_name, password)
+                    self.register_user(account_name, password, email)
                 }
                 UserLogin(account_id, password) => {
                     tracing::debug!(?account_id, "Got login request");
@@ -209,6 +237,58 @@ where
 
                     self.user_del_fp(acc, fp)
                 }
+                SetAccountHideInfo { account, hide_info } => {
+                    tracing::debug!(?account, ?hide_info, "Got set hide info");
+
+                    self.set_account_hide_info(account, hide_info)
+                }
+                SetAccountNoExpire {
+                    source,
+                    account,
+                    no_expire,
+                } => {
+                    tracing::debug!(?source, ?account, ?no_expire, "Got account hold");
+
+                    self.set_account_no_expire(source, account, no_expire)
+                }
+                SetChannelNoExpire {
+                    source,
+                    channel,
+                    no_expire,
+                } => {
+                    tracing::debug!(?source, ?channel, ?no_expire, "Got channel hold");
+
+                    self.set_channel_no_expire(source, channel, no_expire)
+                }
+                SetChannelSetting {
+                    source,
+                    channel,
+                    setting,
+                } => {
+                    tracing::debug!(?source, ?channel, ?setting, "Got channel setting");
+
+                    self.set_channel_setting(source, channel, setting)
+                }
+                AddAutoKick {
+                    source,
+                    channel,
+                    target,
+                    reason,
+                    expires,
+                } => {
+                    tracing::debug!(?source, ?channel, ?target, "Got auto-kick addition");
+
+                    self.add_auto_kick(source, channel, target, reason, expires)
+                }
+                RemoveAutoKick {
+                    source,
+                    channel,
+                    target,
+                } => {
+                    tracing::debug!(?source, ?channel, ?target, "Got auto-kick removal");
+
+                    self.remove_auto_kick(source, channel, target)
+                }
             },
             History(_) => {
                 tracing::warn!(?req, "Got unsupported request (history)");
@@ -218,6 +298,11 @@ where
                 tracing::warn!(?req, "Got unsupported request (ping)");
                 Ok(RemoteServerResponse::NotSupported)
             }
+            // Answered by the network node before reaching here
+            StateDigest | ObjectDigests(_) => {
+                tracing::warn!(?req, "Got unsupported request (state digest)");
+                Ok(RemoteServerResponse::NotSupported)
+            }
         };
 
         match result {